@@ -0,0 +1,135 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Source of the current time, in seconds since the unix epoch.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs())
+    }
+}
+
+/// A clock that only moves when told to, for reproducible runs.
+pub struct FixedClock(AtomicU64);
+
+impl FixedClock {
+    pub fn new(now: u64) -> Self {
+        FixedClock(AtomicU64::new(now))
+    }
+
+    pub fn advance(&self, secs: u64) {
+        self.0.fetch_add(secs, Ordering::SeqCst);
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// A point in time split into its UTC calendar fields.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct UtcDateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl UtcDateTime {
+    pub fn from_unix(secs: u64) -> Self {
+        let days = (secs / 86400) as i64;
+        let secs_of_day = (secs % 86400) as u32;
+
+        // civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        UtcDateTime {
+            year,
+            month,
+            day,
+            hour: secs_of_day / 3600,
+            minute: secs_of_day % 3600 / 60,
+            second: secs_of_day % 60,
+        }
+    }
+
+    /// Parses `2024-01-31T23:59` as sent by `datetime-local` inputs, also
+    /// with a space instead of the `T` and with seconds.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (date, time) = text.trim().split_once(['T', ' '])?;
+        let mut date = date.splitn(3, '-').map(str::parse::<u32>);
+        let mut time = time.splitn(3, ':').map(str::parse::<u32>);
+        let parsed = UtcDateTime {
+            year: date.next()?.ok()? as i64,
+            month: date.next()?.ok()?,
+            day: date.next()?.ok()?,
+            hour: time.next()?.ok()?,
+            minute: time.next()?.ok()?,
+            second: time.next().unwrap_or(Ok(0)).ok()?,
+        };
+        // normalising invalid fields would change the date, e.g. 02-30
+        match UtcDateTime::from_unix(parsed.to_unix()?) == parsed {
+            true => Some(parsed),
+            false => None,
+        }
+    }
+
+    /// Seconds since the unix epoch, `None` before it.
+    pub fn to_unix(&self) -> Option<u64> {
+        // days-from-civil, the inverse of the conversion in `from_unix`
+        let year = self.year - if self.month <= 2 { 1 } else { 0 };
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let mp = (self.month as i64 + 9) % 12;
+        let doy = (153 * mp + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146097 + doe - 719468;
+        let secs =
+            days * 86400 + self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64;
+        u64::try_from(secs).ok()
+    }
+
+    /// The value of a `datetime-local` input, e.g. `2024-01-31T23:59`.
+    pub fn input_value(&self) -> String {
+        format!("{}T{:02}:{:02}", self.date(), self.hour, self.minute)
+    }
+
+    /// The basic ISO 8601 format, e.g. `20240131T235959Z`.
+    pub fn basic_format(&self) -> String {
+        format!(
+            "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+
+    /// e.g. `2024-01-31`
+    pub fn date(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+
+    /// e.g. `2024-01-31 23:59`
+    pub fn date_time(&self) -> String {
+        format!("{} {:02}:{:02}", self.date(), self.hour, self.minute)
+    }
+}
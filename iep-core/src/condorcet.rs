@@ -0,0 +1,119 @@
+//! Pairwise comparison of the nominees on ranked ballots: a nominee is
+//! preferred over another on a ballot ranking them higher, or ranking only
+//! them. The Condorcet winner is preferred over every other nominee by more
+//! ballots than the other way round.
+
+use crate::{Ballot, ElectionProcess};
+use itertools::*;
+use std::collections::HashMap;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct PairwiseMatrix {
+    /// The nominees on the ballot, ordered by name.
+    pub nominees: Vec<u64>,
+    /// How many ballots prefer the first nominee over the second.
+    pub preferred: HashMap<(u64, u64), usize>,
+}
+
+impl PairwiseMatrix {
+    pub fn preferred(&self, nominee: u64, over: u64) -> usize {
+        self.preferred.get(&(nominee, over)).copied().unwrap_or(0)
+    }
+
+    /// Whether more ballots prefer `nominee` over `other` than the reverse.
+    pub fn beats(&self, nominee: u64, other: u64) -> bool {
+        self.preferred(nominee, other) > self.preferred(other, nominee)
+    }
+
+    /// The nominee who beats every other one, if there is one.
+    pub fn condorcet_winner(&self) -> Option<u64> {
+        self.nominees.iter().copied().find(|nominee| {
+            self.nominees
+                .iter()
+                .all(|other| other == nominee || self.beats(*nominee, *other))
+        })
+    }
+}
+
+impl ElectionProcess {
+    pub fn pairwise_matrix(&self, round: &HashMap<String, Ballot>) -> PairwiseMatrix {
+        let nominees = self
+            .nominees
+            .keys()
+            .copied()
+            .filter(|id| self.on_ballot(*id))
+            .sorted_by(|a, b| {
+                self.nominee_name(*a)
+                    .cmp(self.nominee_name(*b))
+                    .then(a.cmp(b))
+            })
+            .collect::<Vec<_>>();
+        let mut preferred = HashMap::new();
+        for ballot in round.values() {
+            let preferences = ballot.preferences();
+            let rank = |nominee: &u64| preferences.iter().position(|id| id == nominee);
+            for (nominee, other) in nominees.iter().tuple_combinations() {
+                let winner = match (rank(nominee), rank(other)) {
+                    (Some(a), Some(b)) if a < b => (*nominee, *other),
+                    (Some(_), None) => (*nominee, *other),
+                    (Some(_), Some(_)) | (None, Some(_)) => (*other, *nominee),
+                    (None, None) => continue,
+                };
+                *preferred.entry(winner).or_insert(0) += 1;
+            }
+        }
+        PairwiseMatrix {
+            nominees,
+            preferred,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SeededRandom;
+
+    /// Ann, Bob and Cid have the ids 0, 1 and 2.
+    fn matrix(rankings: &[&[u64]]) -> PairwiseMatrix {
+        let random = SeededRandom::new(1);
+        let election = ElectionProcess::new_and_cleaned(
+            "e".to_string(),
+            "Lead".to_string(),
+            "Ann\nBob\nCid",
+            0,
+        );
+        let round = rankings
+            .iter()
+            .enumerate()
+            .map(|(i, r)| (format!("v{}", i), Ballot::ranked(r.to_vec(), &random)))
+            .collect();
+        election.pairwise_matrix(&round)
+    }
+
+    #[test]
+    fn finds_the_condorcet_winner() {
+        let matrix = matrix(&[&[0, 1, 2], &[0, 2, 1], &[1, 2, 0]]);
+        assert_eq!(matrix.nominees, [0, 1, 2]);
+        assert_eq!((matrix.preferred(0, 1), matrix.preferred(1, 0)), (2, 1));
+        assert_eq!((matrix.preferred(1, 2), matrix.preferred(2, 1)), (2, 1));
+        assert!(matrix.beats(0, 2));
+        assert_eq!(matrix.condorcet_winner(), Some(0));
+    }
+
+    #[test]
+    fn prefers_ranked_nominees_over_unranked_ones() {
+        let matrix = matrix(&[&[2], &[2], &[0, 1]]);
+        assert_eq!((matrix.preferred(2, 0), matrix.preferred(0, 2)), (2, 1));
+        assert_eq!(matrix.preferred(0, 1), 1);
+        // the first two ballots rank only Cid, the last only Bob of the two
+        assert_eq!((matrix.preferred(1, 2), matrix.preferred(2, 1)), (1, 2));
+        assert_eq!(matrix.condorcet_winner(), Some(2));
+    }
+
+    #[test]
+    fn has_no_winner_in_a_cycle() {
+        let matrix = matrix(&[&[0, 1, 2], &[1, 2, 0], &[2, 0, 1]]);
+        assert_eq!(matrix.condorcet_winner(), None);
+    }
+}
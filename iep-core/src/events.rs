@@ -0,0 +1,654 @@
+//! Every change to an election is recorded as an event. The state of an
+//! election is what applying its events in order gives, so the events are a
+//! full audit trail and changes can be undone by replaying all but the last.
+
+use crate::{
+    Ballot, Elected, ElectionPhase, ElectionProcess, Feedback, NominationStatus, Objection,
+    ObjectionStatus, PhaseTransition, ProcessError, Runoff, SafetyResponse, TieBreak,
+    TieBreakMethod, VotingWindow,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+pub enum Round {
+    First,
+    Second,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub enum ElectionEvent {
+    VoteCast {
+        round: Round,
+        voter_name: String,
+        ballot: Ballot,
+    },
+    VotesReset {
+        round: Round,
+    },
+    /// Replaces the tokens of the voting links.
+    TokensIssued {
+        tokens: HashMap<String, String>,
+    },
+    TokenUsed {
+        token: String,
+    },
+    /// Removes the ballot of `voter_name` at their request.
+    VoteWithdrawn {
+        round: Round,
+        voter_name: String,
+    },
+    PhaseChanged {
+        phase: ElectionPhase,
+    },
+    /// Keeps the ballots of the current later round and opens the next one.
+    RoundStarted,
+    /// Discards the ballots of the current later round and reopens the tally
+    /// of the one before.
+    RoundReverted,
+    /// Records how a tie for the most votes of the final round was broken.
+    TieBroken {
+        nominee: u64,
+        method: TieBreakMethod,
+    },
+    /// Adds a round between the tied `nominees` and opens it.
+    RunoffStarted {
+        nominees: Vec<u64>,
+    },
+    /// Moves into [`ElectionPhase::Completed`] with `nominee` elected.
+    Completed {
+        nominee: u64,
+    },
+    /// Confirms `nominee` for a seat of a multi-seat election while more
+    /// seats are left, starting the safety round for the next one.
+    SeatFilled {
+        nominee: u64,
+    },
+    FeedbackOpened,
+    FeedbackClosed,
+    FeedbackGiven {
+        rating: u8,
+        comment: Option<String>,
+    },
+    Closed,
+    SafetyResponseGiven {
+        participant: String,
+        consent: bool,
+        reason: Option<String>,
+    },
+    /// A nominee added by a participant, in the nomination phase or as a
+    /// write-in during the first vote.
+    NomineeWrittenIn {
+        id: u64,
+        name: String,
+        /// Whether the nominee still has to accept.
+        #[serde(default)]
+        pending: bool,
+    },
+    NominationAnswered {
+        id: u64,
+        accepted: bool,
+    },
+    /// A nominee removed in the nomination phase, e.g. as a duplicate.
+    NomineeRemoved {
+        id: u64,
+        name: String,
+        group: Option<String>,
+    },
+    /// The facilitator's choice of who the safety round asks about.
+    Proposed {
+        nominee: u64,
+    },
+    ObjectionRaised {
+        by: Option<String>,
+        text: String,
+    },
+    ObjectionResolved {
+        index: usize,
+        status: ObjectionStatus,
+    },
+    /// Ballots and nominees taken over from a merged election.
+    Merged {
+        nominees: Vec<MergedNominee>,
+        ballots: Vec<(Round, String, Ballot)>,
+    },
+    CoFacilitatorInvited {
+        key: String,
+        name: String,
+    },
+    CoFacilitatorRevoked {
+        key: String,
+        name: String,
+    },
+    /// `secret` signs the payloads from now on, if it was created with this
+    /// first webhook.
+    WebhookAdded {
+        url: String,
+        secret: Option<String>,
+    },
+    WebhookRemoved {
+        index: usize,
+        url: String,
+    },
+    TelegramChatJoined {
+        chat: i64,
+    },
+    /// Replaces the scheduled voting windows.
+    VotingWindowsChanged {
+        windows: Vec<VotingWindow>,
+        previous: Vec<VotingWindow>,
+    },
+    /// Replaces the elections this one follows and is followed by.
+    LinksChanged {
+        follows: Option<String>,
+        follow_ups: Vec<String>,
+        previous_follows: Option<String>,
+        previous_follow_ups: Vec<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct MergedNominee {
+    pub id: u64,
+    pub name: String,
+    pub group: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct RecordedEvent {
+    pub at: u64,
+    pub event: ElectionEvent,
+    /// The facilitator who made the change, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub by: Option<String>,
+}
+
+impl ElectionProcess {
+    pub fn round_mut(&mut self, round: Round) -> &mut HashMap<String, Ballot> {
+        match round {
+            Round::First => &mut self.first_round_id,
+            Round::Second => &mut self.second_round_id,
+        }
+    }
+
+    /// The round ballots are currently cast into.
+    pub fn voting_round(&self) -> Result<Round, ProcessError> {
+        match self.phase {
+            ElectionPhase::FirstVote => Ok(Round::First),
+            ElectionPhase::SecondVote => Ok(Round::Second),
+            _ => Err(ProcessError::NotInVotingPhase),
+        }
+    }
+
+    /// Records `event` and changes the state accordingly. Events are not
+    /// checked; the methods creating them make sure they are valid.
+    pub fn apply(&mut self, event: ElectionEvent, at: u64) {
+        self.apply_recorded(RecordedEvent {
+            at,
+            event,
+            by: None,
+        });
+    }
+
+    /// Like `apply`, keeping who made the change.
+    pub fn apply_recorded(&mut self, recorded: RecordedEvent) {
+        self.change(&recorded.event, recorded.at);
+        self.events.push(recorded);
+    }
+
+    /// Records that the events from the index `since` on were made by the
+    /// facilitator `by`.
+    pub fn attribute_events(&mut self, since: usize, by: &str) {
+        for event in self.events.iter_mut().skip(since) {
+            event.by = Some(by.to_string());
+        }
+    }
+
+    fn change(&mut self, event: &ElectionEvent, at: u64) {
+        match event {
+            ElectionEvent::VoteCast {
+                round,
+                voter_name,
+                ballot,
+            } => {
+                self.round_mut(*round)
+                    .insert(voter_name.clone(), ballot.clone());
+                self.ballots_submitted += 1;
+            }
+            ElectionEvent::VotesReset { round } => {
+                self.round_mut(*round).clear();
+                self.voting_tokens.clear();
+            }
+            ElectionEvent::TokensIssued { tokens } => self.voting_tokens = tokens.clone(),
+            ElectionEvent::TokenUsed { token } => {
+                self.voting_tokens.remove(token);
+            }
+            ElectionEvent::VoteWithdrawn { round, voter_name } => {
+                self.round_mut(*round).remove(voter_name);
+            }
+            ElectionEvent::PhaseChanged { phase } => self.change_phase(*phase, at),
+            ElectionEvent::RoundStarted => {
+                let ballots = std::mem::take(&mut self.second_round_id);
+                self.earlier_rounds.push(ballots);
+                self.change_phase(ElectionPhase::SecondVote, at);
+            }
+            ElectionEvent::RoundReverted => {
+                let round = self.round_number();
+                self.second_round_id = self.earlier_rounds.pop().unwrap_or_default();
+                if self.runoffs.last().is_some_and(|r| r.round == round) {
+                    self.runoffs.pop();
+                    self.rounds -= 1;
+                }
+                self.change_phase(ElectionPhase::SecondTally, at);
+            }
+            ElectionEvent::TieBroken { nominee, method } => {
+                self.tie_break = Some(TieBreak {
+                    nominee: *nominee,
+                    method: *method,
+                    at,
+                })
+            }
+            ElectionEvent::RunoffStarted { nominees } => {
+                let ballots = std::mem::take(&mut self.second_round_id);
+                self.earlier_rounds.push(ballots);
+                self.rounds += 1;
+                self.runoffs.push(Runoff {
+                    round: self.earlier_rounds.len() + 2,
+                    nominees: nominees.clone(),
+                });
+                self.tie_break = None;
+                self.change_phase(ElectionPhase::SecondVote, at);
+            }
+            ElectionEvent::Completed { nominee } => {
+                self.change_phase(ElectionPhase::Completed, at);
+                self.elected = Some(Elected {
+                    nominee_id: *nominee,
+                    name: self.nominees.get(nominee).cloned().unwrap_or_default(),
+                    at,
+                });
+            }
+            ElectionEvent::SeatFilled { nominee } => {
+                self.seated.push(Elected {
+                    nominee_id: *nominee,
+                    name: self.nominees.get(nominee).cloned().unwrap_or_default(),
+                    at,
+                });
+                self.safety_responses.clear();
+                self.objections.clear();
+                self.proposed = None;
+            }
+            ElectionEvent::FeedbackOpened => self.feedback_open = true,
+            ElectionEvent::FeedbackClosed => self.feedback_open = false,
+            ElectionEvent::NomineeWrittenIn { id, name, pending } => {
+                self.nominees.insert(*id, name.clone());
+                if *pending {
+                    self.nomination_status
+                        .insert(*id, NominationStatus::Pending);
+                }
+            }
+            ElectionEvent::NominationAnswered { id, accepted } => {
+                let status = match accepted {
+                    true => NominationStatus::Accepted,
+                    false => NominationStatus::Declined,
+                };
+                self.nomination_status.insert(*id, status);
+            }
+            ElectionEvent::NomineeRemoved { id, .. } => {
+                self.nominees.remove(id);
+                self.nominee_groups.remove(id);
+                self.nominee_photos.remove(id);
+                self.nomination_status.remove(id);
+            }
+            ElectionEvent::Proposed { nominee } => {
+                // consents and objections were about someone else
+                if self.proposed != Some(*nominee) {
+                    self.safety_responses.clear();
+                    self.objections.clear();
+                }
+                self.proposed = Some(*nominee);
+            }
+            ElectionEvent::ObjectionRaised { by, text } => self.objections.push(Objection {
+                by: by.clone(),
+                text: text.clone(),
+                status: ObjectionStatus::Open,
+                raised_at: at,
+            }),
+            ElectionEvent::ObjectionResolved { index, status } => {
+                if let Some(objection) = self.objections.get_mut(*index) {
+                    objection.status = *status;
+                }
+            }
+            ElectionEvent::Closed => {
+                self.closed_at = Some(at);
+                self.feedback_open = false;
+            }
+            ElectionEvent::SafetyResponseGiven {
+                participant,
+                consent,
+                reason,
+            } => {
+                let objection = reason.as_deref().filter(|_| !*consent);
+                self.track_objection(participant, objection, at);
+                self.safety_responses.insert(
+                    participant.clone(),
+                    SafetyResponse {
+                        consent: *consent,
+                        reason: reason.clone(),
+                        at,
+                    },
+                );
+            }
+            ElectionEvent::FeedbackGiven { rating, comment } => self.feedback.push(Feedback {
+                rating: *rating,
+                comment: comment.clone(),
+                at,
+            }),
+            ElectionEvent::Merged { nominees, ballots } => {
+                for nominee in nominees {
+                    self.nominees.insert(nominee.id, nominee.name.clone());
+                    if let Some(group) = &nominee.group {
+                        self.nominee_groups.insert(nominee.id, group.clone());
+                    }
+                }
+                for (round, voter_name, ballot) in ballots {
+                    self.round_mut(*round)
+                        .insert(voter_name.clone(), ballot.clone());
+                    self.ballots_submitted += 1;
+                }
+            }
+            ElectionEvent::CoFacilitatorInvited { key, name } => {
+                self.co_facilitators.insert(key.clone(), name.clone());
+            }
+            ElectionEvent::CoFacilitatorRevoked { key, .. } => {
+                self.co_facilitators.remove(key);
+            }
+            ElectionEvent::WebhookAdded { url, secret } => {
+                self.webhooks.push(url.clone());
+                if secret.is_some() {
+                    self.webhook_secret = secret.clone();
+                }
+            }
+            ElectionEvent::WebhookRemoved { index, .. } => {
+                if *index < self.webhooks.len() {
+                    self.webhooks.remove(*index);
+                }
+            }
+            ElectionEvent::TelegramChatJoined { chat } => self.telegram_chats.push(*chat),
+            ElectionEvent::VotingWindowsChanged { windows, .. } => {
+                self.voting_windows = windows.clone()
+            }
+            ElectionEvent::LinksChanged {
+                follows,
+                follow_ups,
+                ..
+            } => {
+                self.follows = follows.clone();
+                self.follow_ups = follow_ups.clone();
+            }
+        }
+    }
+
+    fn change_phase(&mut self, phase: ElectionPhase, at: u64) {
+        if phase != self.phase {
+            self.phase = phase;
+            self.phase_history
+                .push(crate::PhaseTransition { phase, at });
+        }
+        // Going back to an earlier phase ends the feedback round.
+        if !self.is_last_phase() {
+            self.feedback_open = false;
+        }
+        if phase != ElectionPhase::Completed {
+            self.elected = None;
+        }
+        // Going back before the safety round asks again once it is reached.
+        if phase != ElectionPhase::SafetyRound && phase != ElectionPhase::Completed {
+            self.safety_responses.clear();
+            self.objections.clear();
+            self.proposed = None;
+            self.seated.clear();
+        }
+    }
+
+    /// The election as it was created, before any of its events: what the
+    /// constructor gives for its id, role, nominees and start, with the
+    /// settings as they were before the events changing them.
+    fn initial_state(&self) -> ElectionProcess {
+        // no `..`, so a new field has to be sorted into settings or state here
+        let ElectionProcess {
+            id,
+            kind,
+            phase: _,
+            elected_role,
+            nominees: _,
+            nominee_groups: _,
+            nominee_photos,
+            first_round_id: _,
+            second_round_id: _,
+            post_vote_message,
+            phase_history,
+            ballots_submitted: _,
+            feedback_open: _,
+            feedback: _,
+            meeting,
+            follows,
+            follow_ups,
+            circle_role,
+            events: _,
+            complete_history,
+            last_activity,
+            keep_for,
+            closed_at: _,
+            elected: _,
+            voting_windows,
+            safety_responses: _,
+            objections: _,
+            nomination_status: _,
+            proposed: _,
+            method,
+            seats,
+            seated: _,
+            rounds,
+            earlier_rounds: _,
+            tie_break: _,
+            runoffs,
+            expected_voters,
+            require_quorum,
+            auto_advance,
+            roster,
+            voter_emails,
+            voter_phones,
+            voting_tokens: _,
+            facilitator_key,
+            passcode,
+            created_by,
+            co_facilitators,
+            webhooks,
+            webhook_secret,
+            slack_webhook,
+            discord_webhook,
+            report_recipients,
+            report_to_roster,
+            telegram_chats,
+        } = self.clone();
+        let start = phase_history.first().cloned().unwrap_or(PhaseTransition {
+            phase: ElectionPhase::FirstVote,
+            at: 0,
+        });
+        let (nominees, nominee_groups) = self.created_nominees();
+        let mut created = ElectionProcess {
+            kind,
+            phase: start.phase,
+            nominees,
+            nominee_groups,
+            nominee_photos,
+            post_vote_message,
+            phase_history: vec![start.clone()],
+            meeting,
+            follows,
+            follow_ups,
+            circle_role,
+            complete_history,
+            last_activity,
+            keep_for,
+            voting_windows,
+            method,
+            seats,
+            // each runoff added a round
+            rounds: rounds - runoffs.len(),
+            expected_voters,
+            require_quorum,
+            auto_advance,
+            roster,
+            voter_emails,
+            voter_phones,
+            facilitator_key,
+            passcode,
+            created_by,
+            co_facilitators,
+            webhooks,
+            webhook_secret,
+            slack_webhook,
+            discord_webhook,
+            report_recipients,
+            report_to_roster,
+            telegram_chats,
+            ..ElectionProcess::new_and_cleaned(id, elected_role, "", start.at)
+        };
+        for event in self.events.iter().rev() {
+            created.revert_setting(&event.event);
+        }
+        created
+    }
+
+    /// The nominees and their groups on creation, from the current ones and
+    /// the events changing them.
+    fn created_nominees(&self) -> (HashMap<u64, String>, HashMap<u64, String>) {
+        let mut nominees = self.nominees.clone();
+        let mut groups = self.nominee_groups.clone();
+        // undo the changes to the nominees, latest first
+        for event in self.events.iter().rev() {
+            match &event.event {
+                ElectionEvent::Merged {
+                    nominees: merged, ..
+                } => {
+                    for nominee in merged {
+                        nominees.remove(&nominee.id);
+                        groups.remove(&nominee.id);
+                    }
+                }
+                ElectionEvent::NomineeWrittenIn { id, .. } => {
+                    nominees.remove(id);
+                }
+                ElectionEvent::NomineeRemoved { id, name, group } => {
+                    nominees.insert(*id, name.clone());
+                    if let Some(group) = group {
+                        groups.insert(*id, group.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+        (nominees, groups)
+    }
+
+    /// Rebuilds the election from its events. Fails for elections stored
+    /// before events were recorded, as their earlier changes are unknown.
+    pub fn replay(&self) -> Result<ElectionProcess, ProcessError> {
+        self.replay_first(self.events.len())
+    }
+
+    /// Rebuilds the election from its first `count` events.
+    fn replay_first(&self, count: usize) -> Result<ElectionProcess, ProcessError> {
+        if !self.complete_history {
+            return Err(ProcessError::IncompleteHistory);
+        }
+        // from all events, as the later ones may have added nominees
+        let mut election = self.initial_state();
+        for event in &self.events[..count] {
+            election.apply_recorded(event.clone());
+        }
+        Ok(election)
+    }
+
+    /// Reverts the last change and returns its event.
+    pub fn undo(&mut self) -> Result<ElectionEvent, ProcessError> {
+        let undone = self.events.last().ok_or(ProcessError::NothingToUndo)?;
+        let undone = undone.event.clone();
+        *self = self.replay_first(self.events.len() - 1)?;
+        Ok(undone)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SeededRandom;
+
+    /// Ann and Bob tie in the second round of an election with a write-in,
+    /// and a runoff between them is open.
+    fn in_runoff() -> ElectionProcess {
+        let random = SeededRandom::new(1);
+        let mut election =
+            ElectionProcess::new_and_cleaned("e".to_string(), "Lead".to_string(), "Ann\nBob", 10);
+        let (cid, _) = election.write_in("Cid", 11).unwrap();
+        for (voter, vote) in [("v1", 0), ("v2", cid)] {
+            let ballot = Ballot::new(vote, &random);
+            election
+                .add_vote(voter.to_string(), ballot, None, 12)
+                .unwrap();
+        }
+        election.step_next(13);
+        election.step_next(14);
+        for (voter, vote) in [("v1", 0), ("v2", 1)] {
+            let ballot = Ballot::new(vote, &random);
+            election
+                .add_vote(voter.to_string(), ballot, None, 15)
+                .unwrap();
+        }
+        election.step_next(16);
+        election.start_runoff(17).unwrap();
+        election
+    }
+
+    #[test]
+    fn replays_to_the_same_state() {
+        let election = in_runoff();
+        assert_eq!(election.rounds, 3);
+        assert_eq!(election.replay().unwrap(), election);
+    }
+
+    #[test]
+    fn undoes_the_last_change() {
+        let mut election = in_runoff();
+        let before = election.clone();
+        assert_eq!(
+            election.undo(),
+            Ok(ElectionEvent::RunoffStarted {
+                nominees: vec![0, 1]
+            })
+        );
+        assert_eq!(election.rounds, 2);
+        assert_eq!(election.phase, ElectionPhase::SecondTally);
+        assert_eq!(election.events.len(), before.events.len() - 1);
+        assert_eq!(election.second_round_id, before.earlier_rounds[0]);
+
+        while !election.events.is_empty() {
+            election.undo().unwrap();
+        }
+        // back to the nominees the election was created with
+        assert_eq!(election.nominees.len(), 2);
+        assert_eq!(election.phase_history.len(), 1);
+        assert_eq!(election.ballots_submitted, 0);
+        assert_eq!(election.undo(), Err(ProcessError::NothingToUndo));
+    }
+
+    #[test]
+    fn replays_only_complete_histories() {
+        let election = ElectionProcess {
+            complete_history: false,
+            ..in_runoff()
+        };
+        assert_eq!(election.replay(), Err(ProcessError::IncompleteHistory));
+    }
+}
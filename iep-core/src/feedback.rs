@@ -0,0 +1,60 @@
+use crate::{ElectionEvent, ElectionProcess, ProcessError};
+use serde::{Deserialize, Serialize};
+
+pub const MAX_RATING: u8 = 5;
+
+/// Anonymous feedback on how the election went.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Feedback {
+    /// From 1 to [`MAX_RATING`].
+    pub rating: u8,
+    pub comment: Option<String>,
+    pub at: u64,
+}
+
+impl ElectionProcess {
+    /// Feedback can only be opened once the election reached its last phase.
+    pub fn open_feedback(&mut self, now: u64) -> Result<(), ProcessError> {
+        if self.closed_at.is_some() {
+            return Err(ProcessError::Closed);
+        }
+        if !self.is_last_phase() {
+            return Err(ProcessError::NotFinished);
+        }
+        self.apply(ElectionEvent::FeedbackOpened, now);
+        Ok(())
+    }
+
+    pub fn close_feedback(&mut self, now: u64) {
+        if self.feedback_open {
+            self.apply(ElectionEvent::FeedbackClosed, now);
+        }
+    }
+
+    pub fn add_feedback(
+        &mut self,
+        rating: u8,
+        comment: Option<String>,
+        now: u64,
+    ) -> Result<(), ProcessError> {
+        if !self.feedback_open {
+            return Err(ProcessError::FeedbackClosed);
+        }
+        if !(1..=MAX_RATING).contains(&rating) {
+            return Err(ProcessError::InvalidRating);
+        }
+        let comment = comment
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty());
+        self.apply(ElectionEvent::FeedbackGiven { rating, comment }, now);
+        Ok(())
+    }
+
+    pub fn average_rating(&self) -> Option<f64> {
+        if self.feedback.is_empty() {
+            return None;
+        }
+        let sum: u32 = self.feedback.iter().map(|f| f.rating as u32).sum();
+        Some(sum as f64 / self.feedback.len() as f64)
+    }
+}
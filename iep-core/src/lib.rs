@@ -0,0 +1,40 @@
+//! Election logic of the Integrative Election Process, independent of any
+//! web framework: phases, rounds of ballots and their tallies.
+
+mod clock;
+mod condorcet;
+mod events;
+mod feedback;
+mod merge;
+mod nomination;
+mod process;
+mod quorum;
+mod random;
+mod ranked;
+mod registry;
+mod roster;
+mod rounds;
+mod safety;
+mod schedule;
+mod score;
+mod seats;
+mod settings;
+mod stats;
+mod tie_break;
+mod tokens;
+
+pub use clock::*;
+pub use condorcet::*;
+pub use events::*;
+pub use feedback::*;
+pub use merge::*;
+pub use nomination::*;
+pub use process::*;
+pub use random::*;
+pub use ranked::*;
+pub use registry::*;
+pub use safety::*;
+pub use schedule::*;
+pub use score::*;
+pub use stats::*;
+pub use tie_break::*;
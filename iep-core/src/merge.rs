@@ -0,0 +1,159 @@
+use crate::{ElectionEvent, ElectionProcess, MergedNominee, Round};
+use itertools::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Which ballot survives when a voter cast one in both merged elections.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeConflict {
+    #[default]
+    KeepTarget,
+    KeepSource,
+    /// The ballot that was submitted last.
+    Latest,
+}
+
+#[derive(Serialize, PartialEq, Debug, Default)]
+pub struct MergeReport {
+    pub ballots_added: usize,
+    pub ballots_replaced: usize,
+    /// Voters who had ballots in the same round of both elections.
+    pub conflicting_voters: Vec<String>,
+    /// Nominees of the source election the target did not know yet.
+    pub nominees_added: Vec<String>,
+}
+
+impl ElectionProcess {
+    /// Moves the ballots of both rounds of `source` into this election.
+    ///
+    /// Nominees are matched by name; unknown ones are added. Merged ballots
+    /// keep their submission time and are numbered after the existing ones.
+    pub fn merge_from(
+        &mut self,
+        source: ElectionProcess,
+        conflict: MergeConflict,
+        now: u64,
+    ) -> MergeReport {
+        let mut report = MergeReport::default();
+
+        let mut nominee_ids = HashMap::new();
+        let mut nominees = Vec::<MergedNominee>::new();
+        let mut next_id = self.nominees.keys().max().map_or(0, |max| max + 1);
+        for (source_id, name) in source.nominees.iter().sorted_by_key(|(id, _)| **id) {
+            let id = match self.nominees.iter().find(|(_, n)| *n == name) {
+                Some((id, _)) => *id,
+                None => {
+                    let id = next_id;
+                    next_id += 1;
+                    nominees.push(MergedNominee {
+                        id,
+                        name: name.clone(),
+                        group: source.nominee_groups.get(source_id).cloned(),
+                    });
+                    report.nominees_added.push(name.clone());
+                    id
+                }
+            };
+            nominee_ids.insert(*source_id, id);
+        }
+
+        let rounds = [
+            (Round::First, source.first_round_id, &self.first_round_id),
+            (Round::Second, source.second_round_id, &self.second_round_id),
+        ];
+        let mut merged = Vec::new();
+        for (round, source_round, target_round) in rounds {
+            for (voter_name, mut ballot) in source_round {
+                let Some(vote) = nominee_ids.get(&ballot.vote) else {
+                    continue;
+                };
+                ballot.vote = *vote;
+                let replace = match target_round.get(&voter_name) {
+                    None => {
+                        report.ballots_added += 1;
+                        true
+                    }
+                    Some(existing) => {
+                        report.conflicting_voters.push(voter_name.clone());
+                        let replace = match conflict {
+                            MergeConflict::KeepTarget => false,
+                            MergeConflict::KeepSource => true,
+                            MergeConflict::Latest => ballot.submitted_at > existing.submitted_at,
+                        };
+                        report.ballots_replaced += replace as usize;
+                        replace
+                    }
+                };
+                if replace {
+                    merged.push((round, voter_name, ballot));
+                }
+            }
+        }
+        report.conflicting_voters.sort();
+        report.conflicting_voters.dedup();
+
+        // Merged ballots are numbered after the existing ones, keeping their
+        // relative order.
+        merged.sort_by_key(|(_, _, ballot)| ballot.sequence);
+        for (sequence, (_, _, ballot)) in (self.ballots_submitted + 1..).zip(merged.iter_mut()) {
+            ballot.sequence = sequence;
+        }
+        self.apply(
+            ElectionEvent::Merged {
+                nominees,
+                ballots: merged,
+            },
+            now,
+        );
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ballot, SeededRandom};
+
+    fn election(nominees: &str, ballots: &[(&str, u64, u64)]) -> ElectionProcess {
+        let random = SeededRandom::new(1);
+        let mut election =
+            ElectionProcess::new_and_cleaned("e".to_string(), "Lead".to_string(), nominees, 0);
+        for (voter, vote, at) in ballots {
+            let ballot = Ballot::new(*vote, &random);
+            election
+                .add_vote(voter.to_string(), ballot, None, *at)
+                .unwrap();
+        }
+        election
+    }
+
+    #[test]
+    fn merges_ballots_by_nominee_name() {
+        let target = election("Ann\nBob", &[("v1", 0, 1), ("v2", 1, 2)]);
+        // Bob is 0 and Cid 1 here
+        let source = election("Bob\nCid", &[("v2", 1, 3), ("v3", 0, 4)]);
+
+        let mut latest = target.clone();
+        let report = latest.merge_from(source.clone(), MergeConflict::Latest, 5);
+        assert_eq!(
+            report,
+            MergeReport {
+                ballots_added: 1,
+                ballots_replaced: 1,
+                conflicting_voters: vec!["v2".to_string()],
+                nominees_added: vec!["Cid".to_string()],
+            }
+        );
+        assert_eq!(latest.nominee_name(2), "Cid");
+        let round = latest.current_round();
+        let votes = ["v1", "v2", "v3"].map(|v| (round[v].vote, round[v].sequence));
+        assert_eq!(votes, [(0, 1), (2, 3), (1, 4)]);
+        assert_eq!(latest.replay().unwrap(), latest);
+
+        let mut kept = target.clone();
+        let report = kept.merge_from(source, MergeConflict::KeepTarget, 5);
+        assert_eq!((report.ballots_added, report.ballots_replaced), (1, 0));
+        assert_eq!(kept.current_round()["v2"].vote, 1);
+    }
+}
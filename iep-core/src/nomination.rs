@@ -0,0 +1,56 @@
+use crate::{ElectionEvent, ElectionPhase, ElectionProcess, ProcessError};
+use serde::{Deserialize, Serialize};
+
+/// Whether someone nominated by others agreed to stand. Nominees without a
+/// status, like those of the creation form, need no acceptance.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+pub enum NominationStatus {
+    Pending,
+    Accepted,
+    Declined,
+}
+
+impl ElectionProcess {
+    /// Whether the nominee can be voted for: they did not have to accept
+    /// their nomination, or accepted it, and are in the runoff if this round
+    /// is one.
+    pub fn on_ballot(&self, nominee: u64) -> bool {
+        self.nominees.contains_key(&nominee)
+            && self
+                .runoff_nominees()
+                .is_none_or(|nominees| nominees.contains(&nominee))
+            && matches!(
+                self.nomination_status.get(&nominee),
+                None | Some(NominationStatus::Accepted)
+            )
+    }
+
+    /// Accepts or declines a pending nomination, until the first vote ends.
+    pub fn answer_nomination(
+        &mut self,
+        nominee: u64,
+        accepted: bool,
+        now: u64,
+    ) -> Result<(), ProcessError> {
+        if self.closed_at.is_some() {
+            return Err(ProcessError::Closed);
+        }
+        if !matches!(
+            self.phase,
+            ElectionPhase::Nomination | ElectionPhase::FirstVote
+        ) {
+            return Err(ProcessError::NotInNomination);
+        }
+        if self.nomination_status.get(&nominee) != Some(&NominationStatus::Pending) {
+            return Err(ProcessError::UnknownNominee);
+        }
+        self.apply(
+            ElectionEvent::NominationAnswered {
+                id: nominee,
+                accepted,
+            },
+            now,
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,987 @@
+use crate::{
+    CircleRoleRef, ElectionEvent, Feedback, NominationStatus, Objection, Random, RecordedEvent,
+    Round, Runoff, SafetyResponse, TieBreak, VotingWindow,
+};
+use itertools::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Debug,
+    Copy,
+    Clone,
+    strum_macros::EnumString,
+    strum_macros::Display,
+)]
+pub enum ElectionPhase {
+    /// Participants propose nominees before the first vote.
+    Nomination,
+    FirstVote,
+    FirstTally,
+    SecondVote,
+    SecondTally,
+    SafetyRound,
+    /// The facilitator confirmed who was elected.
+    Completed,
+}
+
+impl ElectionPhase {
+    pub fn nice_title(&self) -> &'static str {
+        match self {
+            ElectionPhase::Nomination => "Nomination",
+            ElectionPhase::FirstVote => "First Vote",
+            ElectionPhase::FirstTally => "Results of First Vote",
+            ElectionPhase::SecondVote => "Second Vote",
+            ElectionPhase::SecondTally => "Results of Second Vote",
+            ElectionPhase::SafetyRound => "Safety Round",
+            ElectionPhase::Completed => "Elected",
+        }
+    }
+
+    pub fn is_voting(&self) -> bool {
+        matches!(self, ElectionPhase::FirstVote | ElectionPhase::SecondVote)
+    }
+
+    pub fn next(&self) -> ElectionPhase {
+        match self {
+            ElectionPhase::Nomination => ElectionPhase::FirstVote,
+            ElectionPhase::FirstVote => ElectionPhase::FirstTally,
+            ElectionPhase::FirstTally => ElectionPhase::SecondVote,
+            ElectionPhase::SecondVote => ElectionPhase::SecondTally,
+            ElectionPhase::SecondTally => ElectionPhase::SafetyRound,
+            ElectionPhase::SafetyRound => ElectionPhase::Completed,
+            ElectionPhase::Completed => ElectionPhase::Completed,
+        }
+    }
+
+    pub fn prev(&self) -> ElectionPhase {
+        match self {
+            ElectionPhase::Nomination => ElectionPhase::Nomination,
+            ElectionPhase::FirstVote => ElectionPhase::FirstVote,
+            ElectionPhase::FirstTally => ElectionPhase::FirstVote,
+            ElectionPhase::SecondVote => ElectionPhase::FirstTally,
+            ElectionPhase::SecondTally => ElectionPhase::SecondVote,
+            ElectionPhase::SafetyRound => ElectionPhase::SecondTally,
+            ElectionPhase::Completed => ElectionPhase::SafetyRound,
+        }
+    }
+}
+
+/// The full Integrative Election Process, or a single round without
+/// discussion and safety round for quick temperature checks.
+#[derive(
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Debug,
+    Copy,
+    Clone,
+    Default,
+    strum_macros::EnumString,
+    strum_macros::Display,
+)]
+pub enum ElectionKind {
+    #[default]
+    Election,
+    StrawPoll,
+}
+
+/// How voters fill in their ballots and how the rounds are counted.
+#[derive(
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Debug,
+    Copy,
+    Clone,
+    Default,
+    strum_macros::EnumString,
+    strum_macros::Display,
+)]
+pub enum VotingMethod {
+    /// One nominee per ballot.
+    #[default]
+    Plurality,
+    /// Nominees in order of preference, counted by instant runoff.
+    Ranked,
+    /// A score for every nominee, the highest total wins.
+    Score,
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum ProcessError {
+    NotInVotingPhase,
+    UnknownNominee,
+    NotFinished,
+    FeedbackClosed,
+    InvalidRating,
+    IncompleteHistory,
+    NothingToUndo,
+    Closed,
+    InvalidSchedule,
+    NotOpenYet,
+    NotInSafetyRound,
+    ReasonRequired,
+    UnknownObjection,
+    UnresolvedObjections,
+    WriteInNotAllowed,
+    NotInNomination,
+    InvalidRanking,
+    InvalidScore,
+    AlreadyElected,
+    NoTie,
+    NoRunoff,
+    QuorumNotReached,
+    NoBallot,
+    NotOnRoster,
+    NoRoster,
+    InvalidToken,
+}
+
+impl std::fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProcessError::NotInVotingPhase => write!(f, "Election is not in a voting phase"),
+            ProcessError::UnknownNominee => write!(f, "Unknown nominee"),
+            ProcessError::NotFinished => write!(f, "Election is not finished yet"),
+            ProcessError::FeedbackClosed => write!(f, "Feedback is not open"),
+            ProcessError::InvalidRating => write!(f, "Invalid rating"),
+            ProcessError::IncompleteHistory => {
+                write!(f, "Election was stored before its history was recorded")
+            }
+            ProcessError::NothingToUndo => write!(f, "Nothing to undo"),
+            ProcessError::Closed => write!(f, "Election is closed"),
+            ProcessError::InvalidSchedule => write!(f, "Voting must close after it opens"),
+            ProcessError::NotOpenYet => write!(f, "Voting has not opened yet"),
+            ProcessError::NotInSafetyRound => write!(f, "Election is not in the safety round"),
+            ProcessError::ReasonRequired => write!(f, "Objections need a reason"),
+            ProcessError::UnknownObjection => write!(f, "Unknown objection"),
+            ProcessError::UnresolvedObjections => {
+                write!(f, "All objections need to be integrated or withdrawn")
+            }
+            ProcessError::WriteInNotAllowed => {
+                write!(f, "Names can only be written in during the first vote")
+            }
+            ProcessError::NotInNomination => write!(f, "Election is not in the nomination phase"),
+            ProcessError::InvalidRanking => write!(f, "Each nominee can only be ranked once"),
+            ProcessError::NoTie => write!(f, "There is no tie to break"),
+            ProcessError::NoRunoff => write!(f, "Runoffs need a second vote"),
+            ProcessError::NoBallot => write!(f, "There is no vote of yours in this round"),
+            ProcessError::NotOnRoster => write!(f, "Only the eligible voters can vote"),
+            ProcessError::NoRoster => write!(f, "There is no list of eligible voters"),
+            ProcessError::InvalidToken => write!(f, "This voting link was already used"),
+            ProcessError::QuorumNotReached => {
+                write!(f, "Not all expected voters have voted yet")
+            }
+            ProcessError::AlreadyElected => {
+                write!(f, "Nominee was already elected for another seat")
+            }
+            ProcessError::InvalidScore => write!(f, "Scores go from 0 to {}", crate::MAX_SCORE),
+        }
+    }
+}
+
+impl std::error::Error for ProcessError {}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct ElectionProcess {
+    pub id: String,
+    #[serde(default)]
+    pub kind: ElectionKind,
+    pub phase: ElectionPhase,
+    pub elected_role: String,
+    pub nominees: HashMap<u64, String>,
+    /// Label of the group a nominee belongs to; ungrouped nominees are absent.
+    #[serde(default)]
+    pub nominee_groups: HashMap<u64, String>,
+    /// Image type of the uploaded photo per nominee.
+    #[serde(default)]
+    pub nominee_photos: HashMap<u64, String>,
+    pub first_round_id: HashMap<String, Ballot>,
+    pub second_round_id: HashMap<String, Ballot>,
+    pub post_vote_message: Option<String>,
+    pub phase_history: Vec<PhaseTransition>,
+    /// Number of ballots submitted so far, across all rounds.
+    #[serde(default)]
+    pub ballots_submitted: u64,
+    /// Whether participants can currently leave feedback.
+    #[serde(default)]
+    pub feedback_open: bool,
+    #[serde(default)]
+    pub feedback: Vec<Feedback>,
+    /// Name of the meeting the election is part of.
+    #[serde(default)]
+    pub meeting: Option<String>,
+    /// Id of the election this one was started as a follow-up of.
+    #[serde(default)]
+    pub follows: Option<String>,
+    #[serde(default)]
+    pub follow_ups: Vec<String>,
+    /// The circle role whose holder is updated by the outcome.
+    #[serde(default)]
+    pub circle_role: Option<CircleRoleRef>,
+    /// Every change since the election was created, oldest first.
+    #[serde(default)]
+    pub events: Vec<RecordedEvent>,
+    /// Whether `events` go back to the creation; elections stored before
+    /// events were recorded only have the later ones.
+    #[serde(default)]
+    pub complete_history: bool,
+    /// When the election was last changed.
+    #[serde(default)]
+    pub last_activity: u64,
+    /// Seconds to keep the election after its last activity, instead of the
+    /// server's default.
+    #[serde(default)]
+    pub keep_for: Option<u64>,
+    /// When the election was closed; closed elections are kept as they are.
+    #[serde(default)]
+    pub closed_at: Option<u64>,
+    /// Who the facilitator confirmed as elected, once completed.
+    #[serde(default)]
+    pub elected: Option<Elected>,
+    /// Scheduled opening and closing of the voting phases.
+    #[serde(default)]
+    pub voting_windows: Vec<VotingWindow>,
+    /// Consents and objections of the safety round, by participant.
+    #[serde(default)]
+    pub safety_responses: HashMap<String, SafetyResponse>,
+    #[serde(default)]
+    pub objections: Vec<Objection>,
+    /// Nominees who were nominated by others and whether they accepted.
+    #[serde(default)]
+    pub nomination_status: HashMap<u64, NominationStatus>,
+    /// The nominee the safety round asks about, as chosen by the facilitator.
+    #[serde(default)]
+    pub proposed: Option<u64>,
+    #[serde(default)]
+    pub method: VotingMethod,
+    /// How many nominees are elected for the role.
+    #[serde(default = "crate::seats::one_seat")]
+    pub seats: usize,
+    /// Who was confirmed for the seats before the current one.
+    #[serde(default)]
+    pub seated: Vec<Elected>,
+    /// How many rounds are voted in; only elections have more than one.
+    #[serde(default = "crate::rounds::two_rounds")]
+    pub rounds: usize,
+    /// The ballots of the rounds between the first and the current one, for
+    /// elections with more than two rounds.
+    #[serde(default)]
+    pub earlier_rounds: Vec<HashMap<String, Ballot>>,
+    /// How a tie for the most votes of the final round was broken.
+    #[serde(default)]
+    pub tie_break: Option<TieBreak>,
+    /// Rounds added to decide a tie.
+    #[serde(default)]
+    pub runoffs: Vec<Runoff>,
+    /// How many voters are expected to vote in each round.
+    #[serde(default)]
+    pub expected_voters: Option<usize>,
+    /// Whether the next phase waits until the expected voters voted.
+    #[serde(default)]
+    pub require_quorum: bool,
+    /// Whether the election moves on to the tally once the expected voters
+    /// voted.
+    #[serde(default)]
+    pub auto_advance: bool,
+    /// The names of the voters eligible to vote, empty if anyone can.
+    #[serde(default)]
+    pub roster: Vec<String>,
+    /// Email addresses of voters of the roster, by their name.
+    #[serde(default)]
+    pub voter_emails: HashMap<String, String>,
+    /// Phone numbers of voters of the roster for SMS ballots, by their name.
+    #[serde(default)]
+    pub voter_phones: HashMap<String, String>,
+    /// The unspent tokens of voting links and their voters.
+    #[serde(default)]
+    pub voting_tokens: HashMap<String, String>,
+    /// Needed to facilitate the election, if it has one.
+    #[serde(default)]
+    pub facilitator_key: Option<String>,
+    /// Needed to vote, if the creator set one.
+    #[serde(default)]
+    pub passcode: Option<String>,
+    /// The account that created the election, for its dashboard.
+    #[serde(default)]
+    pub created_by: Option<String>,
+    /// The keys given to co-facilitators by the facilitator, and their names.
+    #[serde(default)]
+    pub co_facilitators: HashMap<String, String>,
+    /// URLs told about new votes and phases.
+    #[serde(default)]
+    pub webhooks: Vec<String>,
+    /// Signs the payloads of the webhooks.
+    #[serde(default)]
+    pub webhook_secret: Option<String>,
+    /// Slack incoming webhook announcing the phases in a channel.
+    #[serde(default)]
+    pub slack_webhook: Option<String>,
+    /// Discord webhook announcing the phases with embeds.
+    #[serde(default)]
+    pub discord_webhook: Option<String>,
+    /// Addresses emailed the results on closing.
+    #[serde(default)]
+    pub report_recipients: Vec<String>,
+    /// Whether the voters of the roster are emailed the results on closing.
+    #[serde(default)]
+    pub report_to_roster: bool,
+    /// Telegram chats sent the ballot whenever a voting round opens.
+    #[serde(default)]
+    pub telegram_chats: Vec<i64>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Elected {
+    pub nominee_id: u64,
+    pub name: String,
+    pub at: u64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct PhaseTransition {
+    pub phase: ElectionPhase,
+    pub at: u64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Ballot {
+    pub vote: u64,
+    pub manually_entered: bool,
+    pub receipt: String,
+    /// Position in the order ballots were submitted to the election.
+    #[serde(default)]
+    pub sequence: u64,
+    #[serde(default)]
+    pub submitted_at: u64,
+    /// Why the voter chose the nominee.
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// The first round vote of the voter, if this second round ballot is for
+    /// someone else.
+    #[serde(default)]
+    pub changed_from: Option<u64>,
+    /// Nominees in order of preference, starting with `vote`; only for
+    /// ranked elections.
+    #[serde(default)]
+    pub ranking: Vec<u64>,
+    /// Score per nominee, only for score elections; `vote` is the highest
+    /// scored.
+    #[serde(default)]
+    pub scores: HashMap<u64, u8>,
+}
+
+impl Ballot {
+    pub fn new(vote: u64, random: &dyn Random) -> Self {
+        Ballot {
+            vote,
+            manually_entered: false,
+            receipt: random.alphanumeric(8).to_ascii_uppercase(),
+            sequence: 0,
+            submitted_at: 0,
+            reason: None,
+            changed_from: None,
+            ranking: vec![],
+            scores: HashMap::new(),
+        }
+    }
+
+    pub fn manual(vote: u64, random: &dyn Random) -> Self {
+        Ballot {
+            manually_entered: true,
+            ..Ballot::new(vote, random)
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct VoteChange<'a> {
+    pub voter: &'a str,
+    pub from: &'a str,
+    pub to: &'a str,
+    pub reason: Option<&'a str>,
+}
+
+/// Optional group label with the ids and names of its nominees.
+pub type NomineeGroup<'a> = (Option<&'a str>, Vec<(u64, &'a str)>);
+
+/// Votes per nominee of one round, ordered by descending vote count and then
+/// by nominee name.
+#[derive(PartialEq, Debug)]
+pub struct AccumulatedVotes<'a> {
+    pub votes: Vec<(&'a str, usize)>,
+}
+
+impl<'a> AccumulatedVotes<'a> {
+    pub fn max_votes(&self) -> usize {
+        self.votes.iter().map(|(_k, v)| *v).max().unwrap_or(1)
+    }
+
+    /// The nominees with the `count` highest numbers of votes, more when
+    /// several are tied for the last of them.
+    pub fn top(&self, count: usize) -> Vec<&'a str> {
+        let Some(least) = self.votes.iter().map(|(_, v)| *v).take(count).min() else {
+            return vec![];
+        };
+        self.votes
+            .iter()
+            .filter(|(_, v)| *v >= least)
+            .map(|(k, _)| *k)
+            .collect()
+    }
+
+    pub fn all_with_max_votes(&self) -> Vec<&'a str> {
+        let max_votes = self.max_votes();
+        self.votes
+            .iter()
+            .filter(|(_k, v)| *v == max_votes)
+            .map(|(k, _v)| *k)
+            .collect()
+    }
+}
+
+impl ElectionProcess {
+    /// Creates an election in its first phase from a newline separated list of
+    /// nominees, dropping blank lines and duplicates. A `[Label]` line puts the
+    /// nominees following it into a group, `[]` ends the group.
+    pub fn new_and_cleaned(id: String, elected_role: String, nominees: &str, now: u64) -> Self {
+        let mut group = None;
+        let mut grouped = Vec::new();
+        for line in nominees.lines().map(|n| n.trim()).filter(|n| !n.is_empty()) {
+            match line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                Some(label) => group = Some(label.trim()).filter(|l| !l.is_empty()),
+                None => grouped.push((line, group)),
+            }
+        }
+        let (nominees, nominee_groups): (HashMap<_, _>, Vec<_>) = grouped
+            .into_iter()
+            .sorted_by_key(|(n, _)| *n)
+            .dedup_by(|a, b| a.0 == b.0)
+            .enumerate()
+            .map(|(i, (n, g))| {
+                (
+                    (i as u64, n.to_string()),
+                    g.map(|g| (i as u64, g.to_string())),
+                )
+            })
+            .unzip();
+        ElectionProcess {
+            id,
+            kind: ElectionKind::Election,
+            phase: ElectionPhase::FirstVote,
+            elected_role: elected_role.trim().to_string(),
+            nominees,
+            nominee_groups: nominee_groups.into_iter().flatten().collect(),
+            nominee_photos: HashMap::new(),
+            first_round_id: HashMap::new(),
+            second_round_id: HashMap::new(),
+            post_vote_message: None,
+            phase_history: vec![PhaseTransition {
+                phase: ElectionPhase::FirstVote,
+                at: now,
+            }],
+            ballots_submitted: 0,
+            feedback_open: false,
+            feedback: Vec::new(),
+            meeting: None,
+            follows: None,
+            follow_ups: Vec::new(),
+            circle_role: None,
+            events: Vec::new(),
+            complete_history: true,
+            last_activity: now,
+            keep_for: None,
+            closed_at: None,
+            elected: None,
+            voting_windows: vec![],
+            safety_responses: HashMap::new(),
+            objections: vec![],
+            nomination_status: HashMap::new(),
+            proposed: None,
+            method: VotingMethod::Plurality,
+            seats: 1,
+            seated: vec![],
+            rounds: 2,
+            earlier_rounds: vec![],
+            tie_break: None,
+            runoffs: vec![],
+            expected_voters: None,
+            require_quorum: false,
+            auto_advance: false,
+            roster: vec![],
+            voter_emails: HashMap::new(),
+            voter_phones: HashMap::new(),
+            voting_tokens: HashMap::new(),
+            facilitator_key: None,
+            passcode: None,
+            created_by: None,
+            co_facilitators: HashMap::new(),
+            webhooks: Vec::new(),
+            webhook_secret: None,
+            slack_webhook: None,
+            discord_webhook: None,
+            report_recipients: vec![],
+            report_to_roster: false,
+            telegram_chats: vec![],
+        }
+    }
+
+    /// When the election may be removed, given the server's default of how
+    /// long to keep elections after their last activity; `None` keeps it.
+    pub fn expires_at(&self, default_keep_for: Option<u64>) -> Option<u64> {
+        let keep_for = self.keep_for.or(default_keep_for)?;
+        // elections stored before activity was tracked
+        let last_phase_change = self.phase_history.last().map_or(0, |t| t.at);
+        Some(
+            self.last_activity
+                .max(last_phase_change)
+                .saturating_add(keep_for),
+        )
+    }
+
+    /// The nominees in the format read by [`ElectionProcess::new_and_cleaned`],
+    /// leaving out `exclude`.
+    pub fn nominees_text(&self, exclude: &[&str]) -> String {
+        let mut lines = Vec::new();
+        for (group, nominees) in self.grouped_nominees() {
+            if let Some(group) = group {
+                lines.push(format!("[{}]", group));
+            }
+            lines.extend(
+                nominees
+                    .into_iter()
+                    .filter(|(_, n)| !exclude.contains(n))
+                    .map(|(_, n)| n.to_string()),
+            );
+        }
+        lines.join("\n")
+    }
+
+    /// The phase after the current one; the last phase returns itself.
+    pub fn next_phase(&self) -> ElectionPhase {
+        match (self.kind, self.phase) {
+            (ElectionKind::StrawPoll, ElectionPhase::Nomination) => ElectionPhase::FirstVote,
+            (ElectionKind::StrawPoll, ElectionPhase::FirstVote) => ElectionPhase::FirstTally,
+            (ElectionKind::StrawPoll, phase) => phase,
+            (ElectionKind::Election, ElectionPhase::FirstTally) if self.rounds < 2 => {
+                ElectionPhase::SafetyRound
+            }
+            (ElectionKind::Election, ElectionPhase::SecondTally)
+                if self.round_number() < self.rounds =>
+            {
+                ElectionPhase::SecondVote
+            }
+            (ElectionKind::Election, phase) => phase.next(),
+        }
+    }
+
+    pub fn is_last_phase(&self) -> bool {
+        self.next_phase() == self.phase
+    }
+
+    pub fn phase_title(&self) -> &'static str {
+        match (self.kind, self.phase) {
+            (ElectionKind::StrawPoll, ElectionPhase::Nomination) => "Nomination",
+            (ElectionKind::StrawPoll, ElectionPhase::FirstVote) => "Straw Poll",
+            (ElectionKind::StrawPoll, _) => "Results of Straw Poll",
+            (ElectionKind::Election, phase) => phase.nice_title(),
+        }
+    }
+
+    /// The nominee with the most votes in the final round; for a tie the one
+    /// first by name. For ranked elections the winner of the instant runoff,
+    /// for score elections the highest total.
+    pub fn most_voted_nominee(&self) -> Option<u64> {
+        self.winners(1).first().copied()
+    }
+
+    /// Confirms `nominee` for the current seat, and ends the safety round
+    /// once all seats are filled.
+    pub fn complete(&mut self, nominee: u64, now: u64) -> Result<(), ProcessError> {
+        if self.closed_at.is_some() {
+            return Err(ProcessError::Closed);
+        }
+        if self.kind != ElectionKind::Election || self.phase != ElectionPhase::SafetyRound {
+            return Err(ProcessError::NotFinished);
+        }
+        if !self.nominees.contains_key(&nominee) {
+            return Err(ProcessError::UnknownNominee);
+        }
+        if self.is_seated(nominee) {
+            return Err(ProcessError::AlreadyElected);
+        }
+        if self.open_objections() > 0 {
+            return Err(ProcessError::UnresolvedObjections);
+        }
+        match self.current_seat() < self.seats {
+            true => self.apply(ElectionEvent::SeatFilled { nominee }, now),
+            false => self.apply(ElectionEvent::Completed { nominee }, now),
+        }
+        Ok(())
+    }
+
+    /// Ends a finished election for good, keeping it as a read-only record.
+    pub fn close(&mut self, now: u64) -> Result<(), ProcessError> {
+        if self.closed_at.is_some() {
+            return Err(ProcessError::Closed);
+        }
+        if !self.is_last_phase() {
+            return Err(ProcessError::NotFinished);
+        }
+        self.apply(ElectionEvent::Closed, now);
+        Ok(())
+    }
+
+    /// Moves the election into `phase`, recording when that happened.
+    pub fn set_phase(&mut self, phase: ElectionPhase, now: u64) {
+        if phase != self.phase {
+            self.apply(ElectionEvent::PhaseChanged { phase }, now);
+        }
+    }
+
+    pub fn current_round(&self) -> &HashMap<String, Ballot> {
+        match self.phase {
+            ElectionPhase::Nomination | ElectionPhase::FirstVote | ElectionPhase::FirstTally => {
+                &self.first_round_id
+            }
+            ElectionPhase::SecondVote | ElectionPhase::SecondTally => &self.second_round_id,
+            ElectionPhase::SafetyRound | ElectionPhase::Completed => self.final_round(),
+        }
+    }
+
+    /// The round that decides the outcome.
+    pub fn final_round(&self) -> &HashMap<String, Ballot> {
+        match self.kind {
+            ElectionKind::Election if self.rounds > 1 => &self.second_round_id,
+            _ => &self.first_round_id,
+        }
+    }
+
+    /// The ballots of the round currently voted in.
+    pub fn voting_round_ballots(&self) -> Result<&HashMap<String, Ballot>, ProcessError> {
+        Ok(match self.voting_round()? {
+            Round::First => &self.first_round_id,
+            Round::Second => &self.second_round_id,
+        })
+    }
+
+    /// Adds `name` as a nominee during the first vote, unless a nominee of
+    /// that name exists, ignoring case. Returns the id of the nominee and
+    /// whether it was added.
+    pub fn write_in(&mut self, name: &str, now: u64) -> Result<(u64, bool), ProcessError> {
+        if self.phase != ElectionPhase::FirstVote {
+            return Err(ProcessError::WriteInNotAllowed);
+        }
+        self.add_nominee(name, false, now)
+    }
+
+    /// Lets a new election start with the nomination phase.
+    pub fn start_with_nomination(&mut self) {
+        self.phase = ElectionPhase::Nomination;
+        for transition in self.phase_history.iter_mut().take(1) {
+            transition.phase = ElectionPhase::Nomination;
+        }
+    }
+
+    /// Proposes `name` in the nomination phase, like [`Self::write_in`].
+    /// Unless `by` is the nominee themselves, they need to accept before
+    /// they are on the ballot.
+    pub fn nominate(
+        &mut self,
+        name: &str,
+        by: Option<&str>,
+        now: u64,
+    ) -> Result<(u64, bool), ProcessError> {
+        if self.phase != ElectionPhase::Nomination {
+            return Err(ProcessError::NotInNomination);
+        }
+        let by_themselves =
+            by.is_some_and(|by| by.trim().to_lowercase() == name.trim().to_lowercase());
+        self.add_nominee(name, !by_themselves, now)
+    }
+
+    /// Removes a duplicate or withdrawn name during the nomination phase.
+    pub fn remove_nominee(&mut self, nominee: u64, now: u64) -> Result<(), ProcessError> {
+        if self.phase != ElectionPhase::Nomination {
+            return Err(ProcessError::NotInNomination);
+        }
+        let name = self
+            .nominees
+            .get(&nominee)
+            .ok_or(ProcessError::UnknownNominee)?
+            .clone();
+        self.apply(
+            ElectionEvent::NomineeRemoved {
+                id: nominee,
+                name,
+                group: self.nominee_groups.get(&nominee).cloned(),
+            },
+            now,
+        );
+        Ok(())
+    }
+
+    fn add_nominee(
+        &mut self,
+        name: &str,
+        pending: bool,
+        now: u64,
+    ) -> Result<(u64, bool), ProcessError> {
+        if self.closed_at.is_some() {
+            return Err(ProcessError::Closed);
+        }
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(ProcessError::UnknownNominee);
+        }
+        if let Some((id, _)) = self
+            .nominees
+            .iter()
+            .find(|(_, n)| n.to_lowercase() == name.to_lowercase())
+        {
+            return Ok((*id, false));
+        }
+        let id = self.nominees.keys().max().map_or(0, |max| max + 1);
+        self.apply(
+            ElectionEvent::NomineeWrittenIn {
+                id,
+                name: name.to_string(),
+                pending,
+            },
+            now,
+        );
+        Ok((id, true))
+    }
+
+    /// Records `ballot` in the current voting round, stamping it with its
+    /// submission order and time and replacing an earlier ballot of the same
+    /// voter.
+    pub fn add_vote(
+        &mut self,
+        voter_name: String,
+        mut ballot: Ballot,
+        reason: Option<String>,
+        now: u64,
+    ) -> Result<(), ProcessError> {
+        if self.closed_at.is_some() {
+            return Err(ProcessError::Closed);
+        }
+        let voter_name = self
+            .roster_name(&voter_name)
+            .ok_or(ProcessError::NotOnRoster)?;
+        if !self.on_ballot(ballot.vote) {
+            return Err(ProcessError::UnknownNominee);
+        }
+        self.check_ranking(&mut ballot)?;
+        self.check_scores(&mut ballot)?;
+        let round = self.voting_round()?;
+        if self.opens_at(now).is_some() {
+            return Err(ProcessError::NotOpenYet);
+        }
+        ballot.sequence = self.ballots_submitted + 1;
+        ballot.submitted_at = now;
+        ballot.reason = reason
+            .map(|r| r.trim().to_string())
+            .filter(|r| !r.is_empty());
+        ballot.changed_from = match round {
+            Round::First => None,
+            Round::Second => self
+                .previous_round()
+                .get(&voter_name)
+                .map(|first| first.vote)
+                .filter(|vote| *vote != ballot.vote),
+        };
+        self.apply(
+            ElectionEvent::VoteCast {
+                round,
+                voter_name,
+                ballot,
+            },
+            now,
+        );
+        Ok(())
+    }
+
+    /// Clears the ballots of the current voting round; does nothing outside of
+    /// voting phases.
+    pub fn reset_votes(&mut self, now: u64) {
+        if let Ok(round) = self.voting_round() {
+            self.apply(ElectionEvent::VotesReset { round }, now);
+        }
+    }
+
+    /// Removes the ballot of `voter_name` from the current voting round.
+    pub fn withdraw_vote(&mut self, voter_name: &str, now: u64) -> Result<(), ProcessError> {
+        if self.closed_at.is_some() {
+            return Err(ProcessError::Closed);
+        }
+        let round = self.voting_round()?;
+        if !self.voting_round_ballots()?.contains_key(voter_name) {
+            return Err(ProcessError::NoBallot);
+        }
+        self.apply(
+            ElectionEvent::VoteWithdrawn {
+                round,
+                voter_name: voter_name.to_string(),
+            },
+            now,
+        );
+        Ok(())
+    }
+
+    /// The voters and their reasons per nominee name, for the ballots of
+    /// `round` that have a reason, sorted by nominee and voter.
+    pub fn reasons<'a>(
+        &'a self,
+        round: &'a HashMap<String, Ballot>,
+    ) -> Vec<(&'a str, Vec<(&'a str, &'a str)>)> {
+        round
+            .iter()
+            .filter_map(|(voter, ballot)| {
+                let reason = ballot.reason.as_deref()?;
+                Some((self.nominee_name(ballot.vote), (voter.as_str(), reason)))
+            })
+            .into_group_map()
+            .into_iter()
+            .map(|(nominee, mut reasons)| {
+                reasons.sort();
+                (nominee, reasons)
+            })
+            .sorted()
+            .collect()
+    }
+
+    /// Who changed their vote in the second round, sorted by voter, with the
+    /// nominee names they changed from and to and their reason.
+    pub fn vote_changes(&self) -> Vec<VoteChange<'_>> {
+        self.second_round_id
+            .iter()
+            .filter_map(|(voter, ballot)| {
+                Some(VoteChange {
+                    voter,
+                    from: self.nominee_name(ballot.changed_from?),
+                    to: self.nominee_name(ballot.vote),
+                    reason: ballot.reason.as_deref(),
+                })
+            })
+            .sorted_by_key(|c| c.voter)
+            .collect()
+    }
+
+    pub fn accumulated_votes<'a>(
+        &'a self,
+        round: &'a HashMap<String, Ballot>,
+    ) -> AccumulatedVotes<'a> {
+        let votes = round
+            .iter()
+            .into_group_map_by(|(_, b)| b.vote)
+            .iter()
+            .map(|(k, v)| (self.nominee_name(*k), v.len()))
+            .filter(|(_k, v)| *v > 0)
+            .sorted_by(|a, b| Ord::cmp(&a.1, &b.1).then_with(|| Ord::cmp(&a.0, &b.0).reverse()))
+            .rev()
+            .collect::<Vec<_>>();
+        AccumulatedVotes { votes }
+    }
+
+    /// The nominees on the ballot by group, ungrouped ones first and groups
+    /// ordered by label.
+    pub fn grouped_nominees(&self) -> Vec<NomineeGroup<'_>> {
+        self.nominees
+            .iter()
+            .filter(|(id, _)| self.on_ballot(**id))
+            .map(|(id, name)| {
+                (
+                    self.nominee_groups.get(id).map(|g| g.as_str()),
+                    (*id, name.as_str()),
+                )
+            })
+            .into_group_map()
+            .into_iter()
+            .map(|(group, nominees)| (group, nominees.into_iter().sorted().collect()))
+            .sorted_by(|a, b| a.0.cmp(&b.0))
+            .collect()
+    }
+
+    pub fn group_of_nominee_named(&self, name: &str) -> Option<&str> {
+        self.nominees
+            .iter()
+            .find(|(_, n)| *n == name)
+            .and_then(|(id, _)| self.nominee_groups.get(id))
+            .map(|g| g.as_str())
+    }
+
+    pub fn nominee_name(&self, nominee: u64) -> &str {
+        self.nominees.get(&nominee).map_or("", |n| n.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SeededRandom;
+
+    #[test]
+    fn cleans_up_nominees_and_groups() {
+        let election = ElectionProcess::new_and_cleaned(
+            "e".to_string(),
+            " Lead ".to_string(),
+            "Cid\n\n[Board]\n Bob \nAnn\n[]\nBob\nDan",
+            0,
+        );
+        assert_eq!(election.elected_role, "Lead");
+        let names = (0..4).map(|id| election.nominee_name(id)).collect_vec();
+        assert_eq!(names, ["Ann", "Bob", "Cid", "Dan"]);
+        // the first Bob is in the group, the second one is dropped
+        assert_eq!(election.group_of_nominee_named("Ann"), Some("Board"));
+        assert_eq!(election.group_of_nominee_named("Bob"), Some("Board"));
+        assert_eq!(election.group_of_nominee_named("Cid"), None);
+        assert_eq!(election.group_of_nominee_named("Dan"), None);
+    }
+
+    #[test]
+    fn replaces_an_earlier_ballot_of_the_same_voter() {
+        let random = SeededRandom::new(1);
+        let mut election =
+            ElectionProcess::new_and_cleaned("e".to_string(), "Lead".to_string(), "Ann\nBob", 0);
+        for (voter, vote, at) in [("v1", 0, 1), ("v2", 1, 2), ("v1", 1, 3)] {
+            let ballot = Ballot::new(vote, &random);
+            election
+                .add_vote(voter.to_string(), ballot, None, at)
+                .unwrap();
+        }
+        let round = election.current_round();
+        assert_eq!(round.len(), 2);
+        assert_eq!(round["v1"].vote, 1);
+        assert_eq!(round["v1"].sequence, 3);
+        assert_eq!(round["v1"].submitted_at, 3);
+        assert_eq!(election.ballots_submitted, 3);
+        assert_eq!(
+            election.add_vote("v3".to_string(), Ballot::new(7, &random), None, 4),
+            Err(ProcessError::UnknownNominee)
+        );
+    }
+
+    #[test]
+    fn orders_accumulated_votes_by_count_then_name() {
+        let random = SeededRandom::new(1);
+        let mut election = ElectionProcess::new_and_cleaned(
+            "e".to_string(),
+            "Lead".to_string(),
+            "Ann\nBob\nCid\nDan",
+            0,
+        );
+        for (voter, vote) in [("v1", 2), ("v2", 1), ("v3", 2), ("v4", 0)] {
+            let ballot = Ballot::new(vote, &random);
+            election
+                .add_vote(voter.to_string(), ballot, None, 1)
+                .unwrap();
+        }
+        let votes = election.accumulated_votes(election.current_round()).votes;
+        assert_eq!(votes, [("Cid", 2), ("Ann", 1), ("Bob", 1)]);
+    }
+}
@@ -0,0 +1,62 @@
+//! The number of voters expected in each voting round, to wait for them or
+//! to move on to the tally once all of them voted.
+
+use crate::ElectionProcess;
+
+impl ElectionProcess {
+    /// How many voters voted in the current round.
+    pub fn voted_count(&self) -> usize {
+        self.current_round().len()
+    }
+
+    /// Whether the expected voters all voted in the current voting round.
+    pub fn everyone_voted(&self) -> bool {
+        self.voting_round().is_ok()
+            && self
+                .expected_voters
+                .is_some_and(|expected| self.voted_count() >= expected)
+    }
+
+    /// Whether the election may leave the current phase: always, unless it
+    /// is a voting round that waits for the expected voters.
+    pub fn quorum_reached(&self) -> bool {
+        !self.require_quorum
+            || self.expected_voters.is_none()
+            || self.voting_round().is_err()
+            || self.everyone_voted()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ballot, SeededRandom};
+
+    #[test]
+    fn waits_for_the_expected_voters_only_when_required() {
+        let random = SeededRandom::new(1);
+        let mut election = ElectionProcess {
+            expected_voters: Some(2),
+            ..ElectionProcess::new_and_cleaned("e".to_string(), "Lead".to_string(), "Ann", 0)
+        };
+        assert!(election.quorum_reached());
+        election.require_quorum = true;
+        assert!(!election.quorum_reached());
+
+        for voter in ["v1", "v2"] {
+            assert!(!election.everyone_voted());
+            let ballot = Ballot::new(0, &random);
+            election
+                .add_vote(voter.to_string(), ballot, None, 1)
+                .unwrap();
+        }
+        assert_eq!(election.voted_count(), 2);
+        assert!(election.everyone_voted() && election.quorum_reached());
+
+        // the tally does not wait, and the second round waits again
+        election.step_next(2);
+        assert!(election.quorum_reached() && !election.everyone_voted());
+        election.step_next(3);
+        assert!(!election.quorum_reached());
+    }
+}
@@ -0,0 +1,44 @@
+use rand::{distributions::DistString, rngs::StdRng, Rng, SeedableRng};
+use std::sync::Mutex;
+
+/// Source of randomness for ids, receipts and draws.
+pub trait Random: Send + Sync {
+    fn alphanumeric(&self, len: usize) -> String;
+    /// A number from `0` to `n - 1`.
+    fn below(&self, n: usize) -> usize;
+}
+
+pub struct ThreadRandom;
+
+impl Random for ThreadRandom {
+    fn alphanumeric(&self, len: usize) -> String {
+        rand::distributions::Alphanumeric.sample_string(&mut rand::thread_rng(), len)
+    }
+
+    fn below(&self, n: usize) -> usize {
+        rand::thread_rng().gen_range(0..n)
+    }
+}
+
+/// A deterministic generator, producing the same sequence for the same seed.
+pub struct SeededRandom(Mutex<StdRng>);
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> Self {
+        SeededRandom(Mutex::new(StdRng::seed_from_u64(seed)))
+    }
+}
+
+impl Random for SeededRandom {
+    fn alphanumeric(&self, len: usize) -> String {
+        let mut rng = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        rand::distributions::Alphanumeric.sample_string(&mut *rng, len)
+    }
+
+    fn below(&self, n: usize) -> usize {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .gen_range(0..n)
+    }
+}
@@ -0,0 +1,214 @@
+//! Ranked ballots, counted by instant runoff: the nominee with the fewest
+//! votes is eliminated and their ballots count for the next preference, until
+//! a nominee has a majority of the ballots still counting.
+
+use crate::{Ballot, ElectionProcess, ProcessError, VotingMethod};
+use itertools::*;
+use std::collections::{HashMap, HashSet};
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct RunoffRound {
+    /// Votes per nominee still in the count, most first.
+    pub counts: Vec<(u64, usize)>,
+    /// Ballots ranking none of the nominees still in the count.
+    pub exhausted: usize,
+    /// Nominees with the fewest votes, out from the next round on.
+    pub eliminated: Vec<u64>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct InstantRunoff {
+    pub rounds: Vec<RunoffRound>,
+    /// `None` without ballots or when the last nominees are tied.
+    pub winner: Option<u64>,
+}
+
+impl Ballot {
+    pub fn ranked(ranking: Vec<u64>, random: &dyn crate::Random) -> Self {
+        Ballot {
+            ranking: ranking.clone(),
+            ..Ballot::new(ranking.first().copied().unwrap_or_default(), random)
+        }
+    }
+
+    /// The nominees in order of preference; just the vote for unranked
+    /// ballots.
+    pub fn preferences(&self) -> &[u64] {
+        match self.ranking.is_empty() {
+            true => std::slice::from_ref(&self.vote),
+            false => &self.ranking,
+        }
+    }
+}
+
+impl ElectionProcess {
+    /// Makes the ranking of `ballot` fit the voting method: ranked ballots
+    /// start with their vote and rank nominees on the ballot at most once.
+    pub(crate) fn check_ranking(&self, ballot: &mut Ballot) -> Result<(), ProcessError> {
+        if self.method != VotingMethod::Ranked {
+            ballot.ranking.clear();
+            return Ok(());
+        }
+        if ballot.ranking.is_empty() {
+            ballot.ranking.push(ballot.vote);
+        }
+        if ballot.ranking[0] != ballot.vote || !ballot.ranking.iter().all_unique() {
+            return Err(ProcessError::InvalidRanking);
+        }
+        if !ballot
+            .ranking
+            .iter()
+            .all(|nominee| self.on_ballot(*nominee))
+        {
+            return Err(ProcessError::UnknownNominee);
+        }
+        Ok(())
+    }
+
+    /// Counts the ballots of `round` by instant runoff. All nominees tied
+    /// for the fewest votes are eliminated together.
+    pub fn instant_runoff(&self, round: &HashMap<String, Ballot>) -> InstantRunoff {
+        self.instant_runoff_without(round, &HashSet::new())
+    }
+
+    /// The instant runoff as if the nominees in `excluded` were not on the
+    /// ballot.
+    pub(crate) fn instant_runoff_without(
+        &self,
+        round: &HashMap<String, Ballot>,
+        excluded: &HashSet<u64>,
+    ) -> InstantRunoff {
+        let mut continuing = self
+            .nominees
+            .keys()
+            .filter(|id| self.on_ballot(**id) && !excluded.contains(id))
+            .copied()
+            .collect::<HashSet<_>>();
+        let mut rounds = vec![];
+        loop {
+            let mut counts = continuing
+                .iter()
+                .map(|id| (*id, 0))
+                .collect::<HashMap<_, _>>();
+            let mut exhausted = 0;
+            for ballot in round.values() {
+                match ballot
+                    .preferences()
+                    .iter()
+                    .find(|id| continuing.contains(id))
+                {
+                    Some(id) => *counts.entry(*id).or_default() += 1,
+                    None => exhausted += 1,
+                }
+            }
+            let counting = round.len() - exhausted;
+            let counts = counts
+                .into_iter()
+                .sorted_by(|a, b| {
+                    b.1.cmp(&a.1)
+                        .then_with(|| self.nominee_name(a.0).cmp(self.nominee_name(b.0)))
+                })
+                .collect::<Vec<_>>();
+            let winner = counts
+                .first()
+                .filter(|(_, votes)| votes * 2 > counting)
+                .map(|(id, _)| *id);
+            let fewest = counts.last().map_or(0, |(_, votes)| *votes);
+            let eliminated = counts
+                .iter()
+                .filter(|(_, votes)| *votes == fewest)
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>();
+            // eliminating everyone left would be a tie
+            if winner.is_some() || eliminated.len() == counts.len() {
+                rounds.push(RunoffRound {
+                    counts,
+                    exhausted,
+                    eliminated: vec![],
+                });
+                return InstantRunoff { rounds, winner };
+            }
+            for id in &eliminated {
+                continuing.remove(id);
+            }
+            rounds.push(RunoffRound {
+                counts,
+                exhausted,
+                eliminated,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SeededRandom;
+
+    /// Ann, Bob and Cid have the ids 0, 1 and 2.
+    fn count(rankings: &[&[u64]]) -> InstantRunoff {
+        let random = SeededRandom::new(1);
+        let election = ElectionProcess {
+            method: VotingMethod::Ranked,
+            ..ElectionProcess::new_and_cleaned(
+                "e".to_string(),
+                "Lead".to_string(),
+                "Ann\nBob\nCid",
+                0,
+            )
+        };
+        let round = rankings
+            .iter()
+            .enumerate()
+            .map(|(i, r)| (format!("v{}", i), Ballot::ranked(r.to_vec(), &random)))
+            .collect();
+        election.instant_runoff(&round)
+    }
+
+    #[test]
+    fn transfers_the_ballots_of_eliminated_nominees() {
+        let runoff = count(&[&[0, 1], &[0, 2], &[1, 0], &[1], &[2, 1]]);
+        assert_eq!(runoff.winner, Some(1));
+        assert_eq!(
+            runoff.rounds,
+            [
+                RunoffRound {
+                    counts: vec![(0, 2), (1, 2), (2, 1)],
+                    exhausted: 0,
+                    eliminated: vec![2],
+                },
+                RunoffRound {
+                    counts: vec![(1, 3), (0, 2)],
+                    exhausted: 0,
+                    eliminated: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_exhausted_ballots_and_ties() {
+        let runoff = count(&[&[0], &[0], &[1], &[2]]);
+        assert_eq!(runoff.winner, Some(0));
+        assert_eq!(runoff.rounds[1].exhausted, 2);
+        assert_eq!(count(&[&[0], &[1]]).winner, None);
+        assert_eq!(count(&[]).winner, None);
+    }
+
+    #[test]
+    fn checks_rankings() {
+        let random = SeededRandom::new(1);
+        let mut election = ElectionProcess {
+            method: VotingMethod::Ranked,
+            ..ElectionProcess::new_and_cleaned("e".to_string(), "Lead".to_string(), "Ann\nBob", 0)
+        };
+        let mut vote = |ranking: Vec<u64>| {
+            let ballot = Ballot::ranked(ranking, &random);
+            election.add_vote("v".to_string(), ballot, None, 1)
+        };
+        assert_eq!(vote(vec![0, 0]), Err(ProcessError::InvalidRanking));
+        assert_eq!(vote(vec![0, 7]), Err(ProcessError::UnknownNominee));
+        assert_eq!(vote(vec![1, 0]), Ok(()));
+        assert_eq!(election.first_round_id["v"].preferences(), [1, 0]);
+    }
+}
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+/// A circle of the organization with the roles it fills through elections.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Circle {
+    pub id: String,
+    pub name: String,
+    pub roles: Vec<Role>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Role {
+    pub id: String,
+    pub name: String,
+    pub holder: Option<RoleHolder>,
+    /// Length of a term in weeks, if the role is elected for a limited time.
+    #[serde(default)]
+    pub term_weeks: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct RoleHolder {
+    pub name: String,
+    /// Id of the election the holder was elected in.
+    pub election: String,
+    pub since: u64,
+    /// End of the term, if the role has a term length.
+    #[serde(default)]
+    pub until: Option<u64>,
+    /// Whether a reminder about the ending term was already sent.
+    #[serde(default)]
+    pub reminded: bool,
+}
+
+pub const DAY_SECS: u64 = 24 * 60 * 60;
+pub const WEEK_SECS: u64 = 7 * DAY_SECS;
+
+impl Role {
+    /// Whether the holder's term ends before `now + window` (seconds).
+    pub fn term_ends_within(&self, now: u64, window: u64) -> bool {
+        self.holder
+            .as_ref()
+            .and_then(|h| h.until)
+            .is_some_and(|until| until <= now + window)
+    }
+}
+
+/// Points from an election to the circle role it fills.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct CircleRoleRef {
+    pub circle: String,
+    pub role: String,
+}
+
+impl CircleRoleRef {
+    /// Parses the `circle/role` form used in forms and links.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (circle, role) = value.split_once('/')?;
+        (!circle.is_empty() && !role.is_empty()).then(|| CircleRoleRef {
+            circle: circle.to_string(),
+            role: role.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for CircleRoleRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.circle, self.role)
+    }
+}
+
+impl Circle {
+    pub fn role(&self, id: &str) -> Option<&Role> {
+        self.roles.iter().find(|r| r.id == id)
+    }
+
+    pub fn role_mut(&mut self, id: &str) -> Option<&mut Role> {
+        self.roles.iter_mut().find(|r| r.id == id)
+    }
+}
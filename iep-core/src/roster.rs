@@ -0,0 +1,175 @@
+//! The list of voters eligible to vote, to follow the turnout of each round.
+
+use crate::ElectionProcess;
+use itertools::*;
+
+impl ElectionProcess {
+    /// Sets the roster to the names of `text`, one per line, without
+    /// duplicates ignoring case. A line may end with the email address or
+    /// phone number of its voter in angle brackets, like
+    /// `Ann <ann@example.org>` or `Bob <+49 170 1234567>`.
+    pub fn set_roster(&mut self, text: &str) {
+        let voters = text
+            .lines()
+            .map(split_address)
+            .filter(|(name, _)| !name.is_empty())
+            .unique_by(|(name, _)| name.to_lowercase())
+            .collect::<Vec<_>>();
+        let contacts = |kind: Contact| {
+            voters
+                .iter()
+                .filter_map(|(name, contact)| match contact {
+                    Some((k, contact)) if *k == kind => Some((name.to_string(), contact.clone())),
+                    _ => None,
+                })
+                .collect()
+        };
+        self.voter_emails = contacts(Contact::Email);
+        self.voter_phones = contacts(Contact::Phone);
+        self.roster = voters
+            .into_iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+    }
+
+    /// The roster as [`set_roster`](Self::set_roster) takes it.
+    pub fn roster_text(&self) -> String {
+        self.roster
+            .iter()
+            .map(
+                |name| match self.voter_emails.get(name).or(self.voter_phones.get(name)) {
+                    Some(contact) => format!("{} <{}>", name, contact),
+                    None => name.clone(),
+                },
+            )
+            .join("\n")
+    }
+
+    /// How `voter_name` is spelled on the roster, ignoring case and
+    /// surrounding spaces; anyone can vote without a roster.
+    pub fn roster_name(&self, voter_name: &str) -> Option<String> {
+        if self.roster.is_empty() {
+            return Some(voter_name.to_string());
+        }
+        let wanted = voter_name.trim().to_lowercase();
+        self.roster
+            .iter()
+            .find(|name| name.to_lowercase() == wanted)
+            .cloned()
+    }
+
+    /// The voters of the roster without a ballot in the current round,
+    /// ignoring case.
+    pub fn missing_voters(&self) -> Vec<&str> {
+        let voted = self
+            .current_round()
+            .keys()
+            .map(|name| name.trim().to_lowercase())
+            .collect::<Vec<_>>();
+        self.roster
+            .iter()
+            .filter(|name| !voted.contains(&name.to_lowercase()))
+            .map(|name| &name[..])
+            .collect()
+    }
+
+    /// The email address of `voter_name` of the roster.
+    pub fn voter_email(&self, voter_name: &str) -> Option<&str> {
+        self.voter_emails.get(voter_name).map(|a| &a[..])
+    }
+
+    /// The phone number of `voter_name` of the roster.
+    pub fn voter_phone(&self, voter_name: &str) -> Option<&str> {
+        self.voter_phones.get(voter_name).map(|p| &p[..])
+    }
+
+    /// The voter of the roster with the phone number `phone`, ignoring
+    /// spaces.
+    pub fn phone_voter(&self, phone: &str) -> Option<&str> {
+        self.voter_phones
+            .iter()
+            .find(|(_, p)| phone_digits(p) == phone_digits(phone))
+            .map(|(name, _)| &name[..])
+    }
+
+    /// How many of the roster voted in the current round, in percent.
+    pub fn turnout(&self) -> Option<f32> {
+        match self.roster.len() {
+            0 => None,
+            total => {
+                let voted = total - self.missing_voters().len();
+                Some(voted as f32 * 100.0 / total as f32)
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum Contact {
+    Email,
+    Phone,
+}
+
+/// The number without spaces, dashes and the like, e.g. `+491701234567`.
+fn phone_digits(phone: &str) -> String {
+    phone
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '+')
+        .collect()
+}
+
+/// Splits a line of the roster into the name and the email address or phone
+/// number in angle brackets at its end, if any.
+fn split_address(line: &str) -> (&str, Option<(Contact, String)>) {
+    let line = line.trim();
+    let Some((name, contact)) = line
+        .strip_suffix('>')
+        .and_then(|rest| rest.rsplit_once('<'))
+    else {
+        return (line, None);
+    };
+    let contact = contact.trim();
+    if contact.contains('@') {
+        (name.trim(), Some((Contact::Email, contact.to_string())))
+    } else if contact.starts_with('+')
+        && contact
+            .chars()
+            .all(|c| c.is_ascii_digit() || " +-/()".contains(c))
+    {
+        (name.trim(), Some((Contact::Phone, phone_digits(contact))))
+    } else {
+        (line, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ballot, ProcessError, SeededRandom};
+
+    #[test]
+    fn follows_the_turnout_of_the_roster() {
+        let random = SeededRandom::new(1);
+        let mut election =
+            ElectionProcess::new_and_cleaned("e".to_string(), "Lead".to_string(), "Ann", 0);
+        assert_eq!(
+            election.roster_name(" anyone "),
+            Some(" anyone ".to_string())
+        );
+        assert_eq!(election.turnout(), None);
+
+        election.set_roster("Ann\n\n ann \nBob\nCid\nDan\n");
+        assert_eq!(election.roster, ["Ann", "Bob", "Cid", "Dan"]);
+        assert_eq!(election.roster_name(" bob "), Some("Bob".to_string()));
+        assert_eq!(
+            election.add_vote("Eve".to_string(), Ballot::new(0, &random), None, 1),
+            Err(ProcessError::NotOnRoster)
+        );
+        election
+            .add_vote("BOB".to_string(), Ballot::new(0, &random), None, 1)
+            .unwrap();
+        assert!(election.current_round().contains_key("Bob"));
+        assert_eq!(election.missing_voters(), ["Ann", "Cid", "Dan"]);
+        assert_eq!(election.turnout(), Some(25.0));
+    }
+}
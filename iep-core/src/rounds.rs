@@ -0,0 +1,164 @@
+//! Elections with a configurable number of voting rounds. The first round
+//! has phases of its own; every later round goes through the second vote and
+//! its tally again, keeping the ballots of the rounds before it.
+
+use crate::{Ballot, ElectionEvent, ElectionKind, ElectionPhase, ElectionProcess};
+use std::collections::HashMap;
+
+pub(crate) fn two_rounds() -> usize {
+    2
+}
+
+impl ElectionProcess {
+    /// The round the current phase belongs to, starting at 1.
+    pub fn round_number(&self) -> usize {
+        match self.phase {
+            ElectionPhase::Nomination | ElectionPhase::FirstVote | ElectionPhase::FirstTally => 1,
+            _ if self.kind == ElectionKind::StrawPoll || self.rounds < 2 => 1,
+            _ => 2 + self.earlier_rounds.len(),
+        }
+    }
+
+    /// Every round with its number, the last one being the current or final
+    /// round.
+    pub fn numbered_rounds(&self) -> Vec<(usize, &HashMap<String, Ballot>)> {
+        let mut rounds = vec![(1, &self.first_round_id)];
+        if self.kind == ElectionKind::Election && self.rounds > 1 {
+            rounds.extend(
+                self.earlier_rounds
+                    .iter()
+                    .enumerate()
+                    .map(|(i, r)| (i + 2, r)),
+            );
+            rounds.push((2 + self.earlier_rounds.len(), &self.second_round_id));
+        }
+        rounds
+    }
+
+    /// The round the current later round is compared to.
+    pub fn previous_round(&self) -> &HashMap<String, Ballot> {
+        self.earlier_rounds.last().unwrap_or(&self.first_round_id)
+    }
+
+    /// The phase before the current one; after the start of a third or later
+    /// round it is the tally of the round before.
+    pub fn prev_phase(&self) -> ElectionPhase {
+        match (self.kind, self.phase) {
+            (ElectionKind::Election, ElectionPhase::SecondVote)
+                if !self.earlier_rounds.is_empty() =>
+            {
+                ElectionPhase::SecondTally
+            }
+            (ElectionKind::Election, ElectionPhase::SafetyRound) if self.rounds < 2 => {
+                ElectionPhase::FirstTally
+            }
+            (_, phase) => phase.prev(),
+        }
+    }
+
+    /// Moves on to the next phase, starting another round after the tally of
+    /// one that is not the last.
+    pub fn step_next(&mut self, now: u64) {
+        match (self.phase, self.next_phase()) {
+            (ElectionPhase::SecondTally, ElectionPhase::SecondVote) => {
+                self.apply(ElectionEvent::RoundStarted, now)
+            }
+            (_, phase) => self.set_phase(phase, now),
+        }
+    }
+
+    /// Goes back to the previous phase. Going back from the start of a
+    /// third or later round discards its ballots and reopens the tally of
+    /// the round before.
+    pub fn step_prev(&mut self, now: u64) {
+        match (self.phase, self.prev_phase()) {
+            (ElectionPhase::SecondVote, ElectionPhase::SecondTally) => {
+                self.apply(ElectionEvent::RoundReverted, now)
+            }
+            (_, phase) => self.set_phase(phase, now),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Ballot, ElectionKind, ElectionPhase, ElectionProcess, SeededRandom};
+
+    fn election(rounds: usize) -> ElectionProcess {
+        ElectionProcess {
+            rounds,
+            ..ElectionProcess::new_and_cleaned("e".to_string(), "Lead".to_string(), "Ann\nBob", 0)
+        }
+    }
+
+    /// The phases `step_next` goes through until the last one.
+    fn phases(mut election: ElectionProcess) -> Vec<(ElectionPhase, usize)> {
+        let mut phases = vec![(election.phase, election.round_number())];
+        while !election.is_last_phase() {
+            election.step_next(1);
+            phases.push((election.phase, election.round_number()));
+        }
+        phases
+    }
+
+    #[test]
+    fn goes_through_the_phases_of_each_round() {
+        use ElectionPhase::*;
+        assert_eq!(
+            phases(election(2)),
+            [
+                (FirstVote, 1),
+                (FirstTally, 1),
+                (SecondVote, 2),
+                (SecondTally, 2),
+                (SafetyRound, 2),
+                (Completed, 2)
+            ]
+        );
+        assert_eq!(
+            phases(election(1)),
+            [
+                (FirstVote, 1),
+                (FirstTally, 1),
+                (SafetyRound, 1),
+                (Completed, 1)
+            ]
+        );
+        assert_eq!(phases(election(3)).len(), 8);
+        let poll = ElectionProcess {
+            kind: ElectionKind::StrawPoll,
+            ..election(2)
+        };
+        assert_eq!(phases(poll), [(FirstVote, 1), (FirstTally, 1)]);
+    }
+
+    #[test]
+    fn keeps_the_ballots_of_earlier_rounds() {
+        let random = SeededRandom::new(1);
+        let mut election = election(3);
+        election.step_next(1);
+        election.step_next(2);
+        let ballot = Ballot::new(0, &random);
+        election.add_vote("v".to_string(), ballot, None, 3).unwrap();
+        election.step_next(4);
+        election.step_next(5);
+        assert_eq!(
+            (election.phase, election.round_number()),
+            (ElectionPhase::SecondVote, 3)
+        );
+        assert!(election.second_round_id.is_empty());
+        assert_eq!(election.previous_round().len(), 1);
+
+        election.step_prev(6);
+        assert_eq!(
+            (election.phase, election.round_number()),
+            (ElectionPhase::SecondTally, 2)
+        );
+        assert_eq!(election.second_round_id.len(), 1);
+        election.step_prev(7);
+        election.step_prev(8);
+        election.step_prev(9);
+        assert_eq!(election.phase, ElectionPhase::FirstVote);
+        assert_eq!(election.phase_history.len(), 9);
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{ElectionEvent, ElectionPhase, ElectionProcess, ProcessError};
+use itertools::*;
+use serde::{Deserialize, Serialize};
+
+/// A participant's answer to whether the outcome is safe enough to try.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct SafetyResponse {
+    pub consent: bool,
+    /// Required for objections.
+    pub reason: Option<String>,
+    pub at: u64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, strum_macros::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum ObjectionStatus {
+    Open,
+    /// Changed the outcome or the role so that it is safe enough.
+    Integrated,
+    Withdrawn,
+}
+
+/// An objection raised in the safety round, by a participant or recorded by
+/// the facilitator.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Objection {
+    pub by: Option<String>,
+    pub text: String,
+    pub status: ObjectionStatus,
+    pub raised_at: u64,
+}
+
+impl ElectionProcess {
+    /// Records the consent or objection of `participant`, replacing their
+    /// earlier one.
+    pub fn respond_to_safety(
+        &mut self,
+        participant: String,
+        consent: bool,
+        reason: Option<String>,
+        now: u64,
+    ) -> Result<(), ProcessError> {
+        self.check_safety_round()?;
+        let reason = reason
+            .map(|r| r.trim().to_string())
+            .filter(|r| !r.is_empty());
+        if !consent && reason.is_none() {
+            return Err(ProcessError::ReasonRequired);
+        }
+        self.apply(
+            ElectionEvent::SafetyResponseGiven {
+                participant: participant.trim().to_string(),
+                consent,
+                reason,
+            },
+            now,
+        );
+        Ok(())
+    }
+
+    /// Asks the safety round about `nominee`. Changing the proposal discards
+    /// the consents and objections given so far.
+    pub fn propose(&mut self, nominee: u64, now: u64) -> Result<(), ProcessError> {
+        self.check_safety_round()?;
+        if !self.on_ballot(nominee) {
+            return Err(ProcessError::UnknownNominee);
+        }
+        if self.is_seated(nominee) {
+            return Err(ProcessError::AlreadyElected);
+        }
+        if self.proposed != Some(nominee) {
+            self.apply(ElectionEvent::Proposed { nominee }, now);
+        }
+        Ok(())
+    }
+
+    /// The nominees with votes in the final round, most votes first, to
+    /// choose the proposal from; without those elected for earlier seats.
+    pub fn top_voted_nominees(&self) -> Vec<u64> {
+        self.final_round()
+            .values()
+            .counts_by(|b| b.vote)
+            .into_iter()
+            .filter(|(id, _)| self.on_ballot(*id) && !self.is_seated(*id))
+            .sorted_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    pub fn raise_objection(
+        &mut self,
+        by: Option<String>,
+        text: String,
+        now: u64,
+    ) -> Result<(), ProcessError> {
+        self.check_safety_round()?;
+        let text = text.trim().to_string();
+        if text.is_empty() {
+            return Err(ProcessError::ReasonRequired);
+        }
+        let by = by.map(|b| b.trim().to_string()).filter(|b| !b.is_empty());
+        self.apply(ElectionEvent::ObjectionRaised { by, text }, now);
+        Ok(())
+    }
+
+    /// Marks the objection at `index` as integrated or withdrawn.
+    pub fn resolve_objection(
+        &mut self,
+        index: usize,
+        status: ObjectionStatus,
+        now: u64,
+    ) -> Result<(), ProcessError> {
+        self.check_safety_round()?;
+        if status == ObjectionStatus::Open || index >= self.objections.len() {
+            return Err(ProcessError::UnknownObjection);
+        }
+        self.apply(ElectionEvent::ObjectionResolved { index, status }, now);
+        Ok(())
+    }
+
+    pub fn open_objections(&self) -> usize {
+        self.objections
+            .iter()
+            .filter(|o| o.status == ObjectionStatus::Open)
+            .count()
+    }
+
+    fn check_safety_round(&self) -> Result<(), ProcessError> {
+        if self.closed_at.is_some() {
+            return Err(ProcessError::Closed);
+        }
+        if self.phase != ElectionPhase::SafetyRound {
+            return Err(ProcessError::NotInSafetyRound);
+        }
+        Ok(())
+    }
+
+    /// Keeps the open objection of `participant` in line with their
+    /// response: objecting again updates it, consenting withdraws it.
+    pub(crate) fn track_objection(&mut self, participant: &str, reason: Option<&str>, at: u64) {
+        let open = self
+            .objections
+            .iter_mut()
+            .find(|o| o.status == ObjectionStatus::Open && o.by.as_deref() == Some(participant));
+        match (open, reason) {
+            (Some(objection), Some(reason)) => objection.text = reason.to_string(),
+            (Some(objection), None) => objection.status = ObjectionStatus::Withdrawn,
+            (None, Some(reason)) => self.objections.push(Objection {
+                by: Some(participant.to_string()),
+                text: reason.to_string(),
+                status: ObjectionStatus::Open,
+                raised_at: at,
+            }),
+            (None, None) => {}
+        }
+    }
+
+    /// The number of consents and objections.
+    pub fn safety_counts(&self) -> (usize, usize) {
+        let consents = self.safety_responses.values().filter(|r| r.consent).count();
+        (consents, self.safety_responses.len() - consents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ballot, SeededRandom};
+
+    /// An election of Ann and Bob in which Ann got the votes, in its safety
+    /// round.
+    fn in_safety_round() -> ElectionProcess {
+        let random = SeededRandom::new(1);
+        let mut election =
+            ElectionProcess::new_and_cleaned("e".to_string(), "Lead".to_string(), "Ann\nBob", 0);
+        while election.phase != ElectionPhase::SafetyRound {
+            if election.voting_round().is_ok() {
+                let ballot = Ballot::new(0, &random);
+                election
+                    .add_vote("v1".to_string(), ballot, None, 1)
+                    .unwrap();
+            }
+            election.step_next(2);
+        }
+        election
+    }
+
+    #[test]
+    fn collects_consents_and_objections() {
+        let mut election = in_safety_round();
+        assert_eq!(election.top_voted_nominees(), [0]);
+        election.propose(0, 3).unwrap();
+        assert_eq!(
+            election.respond_to_safety("v1".to_string(), false, Some(" ".to_string()), 4),
+            Err(ProcessError::ReasonRequired)
+        );
+        election
+            .respond_to_safety(" v1 ".to_string(), true, None, 4)
+            .unwrap();
+        election
+            .respond_to_safety("v2".to_string(), true, None, 5)
+            .unwrap();
+        assert_eq!(election.safety_counts(), (2, 0));
+        election
+            .respond_to_safety("v2".to_string(), false, Some("Too busy".to_string()), 6)
+            .unwrap();
+        assert_eq!(election.safety_counts(), (1, 1));
+        assert_eq!(
+            election.safety_responses["v2"].reason.as_deref(),
+            Some("Too busy")
+        );
+        assert_eq!(election.replay().unwrap(), election);
+
+        election.step_prev(7);
+        assert_eq!(
+            election.respond_to_safety("v3".to_string(), true, None, 8),
+            Err(ProcessError::NotInSafetyRound)
+        );
+    }
+
+    #[test]
+    fn completes_only_once_all_objections_are_resolved() {
+        let mut election = in_safety_round();
+        election.propose(0, 3).unwrap();
+        election
+            .respond_to_safety("v1".to_string(), false, Some("Too busy".to_string()), 4)
+            .unwrap();
+        election
+            .raise_objection(None, " No time for the role ".to_string(), 5)
+            .unwrap();
+        assert_eq!(election.open_objections(), 2);
+        assert_eq!(election.objections[1].text, "No time for the role");
+        assert_eq!(
+            election.complete(0, 6),
+            Err(ProcessError::UnresolvedObjections)
+        );
+
+        // consenting withdraws the objection of the participant
+        election
+            .respond_to_safety("v1".to_string(), true, None, 7)
+            .unwrap();
+        assert_eq!(election.objections[0].status, ObjectionStatus::Withdrawn);
+        assert_eq!(
+            election.resolve_objection(1, ObjectionStatus::Open, 8),
+            Err(ProcessError::UnknownObjection)
+        );
+        assert_eq!(
+            election.resolve_objection(2, ObjectionStatus::Integrated, 8),
+            Err(ProcessError::UnknownObjection)
+        );
+        election
+            .resolve_objection(1, ObjectionStatus::Integrated, 8)
+            .unwrap();
+        assert_eq!(election.open_objections(), 0);
+        assert_eq!(election.replay().unwrap(), election);
+
+        election.complete(0, 9).unwrap();
+        assert_eq!(election.phase, ElectionPhase::Completed);
+    }
+}
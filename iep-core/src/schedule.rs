@@ -0,0 +1,84 @@
+use crate::{ElectionEvent, ElectionKind, ElectionPhase, ElectionProcess, ProcessError};
+use serde::{Deserialize, Serialize};
+
+/// When voting in a phase opens and closes; the election moves into the
+/// phase at the opening and on to the tally at the closing.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct VotingWindow {
+    pub phase: ElectionPhase,
+    pub opens_at: Option<u64>,
+    pub closes_at: Option<u64>,
+}
+
+impl ElectionProcess {
+    /// The voting phases of this kind of election, in order.
+    pub fn voting_phases(&self) -> Vec<ElectionPhase> {
+        match self.kind {
+            ElectionKind::StrawPoll => vec![ElectionPhase::FirstVote],
+            ElectionKind::Election => vec![ElectionPhase::FirstVote, ElectionPhase::SecondVote],
+        }
+    }
+
+    pub fn voting_window(&self, phase: ElectionPhase) -> Option<&VotingWindow> {
+        self.voting_windows.iter().find(|w| w.phase == phase)
+    }
+
+    /// Replaces the window of `phase`; a window without times is removed.
+    pub fn set_voting_window(
+        &mut self,
+        window: VotingWindow,
+        now: u64,
+    ) -> Result<(), ProcessError> {
+        if self.closed_at.is_some() {
+            return Err(ProcessError::Closed);
+        }
+        if !self.voting_phases().contains(&window.phase) {
+            return Err(ProcessError::NotInVotingPhase);
+        }
+        if let (Some(opens_at), Some(closes_at)) = (window.opens_at, window.closes_at) {
+            if closes_at <= opens_at {
+                return Err(ProcessError::InvalidSchedule);
+            }
+        }
+        let mut windows = self.voting_windows.clone();
+        windows.retain(|w| w.phase != window.phase);
+        if window.opens_at.is_some() || window.closes_at.is_some() {
+            windows.push(window);
+        }
+        if windows != self.voting_windows {
+            let previous = self.voting_windows.clone();
+            self.apply(
+                ElectionEvent::VotingWindowsChanged { windows, previous },
+                now,
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether the schedule moves the election to its next phase by `now`:
+    /// the current phase closed or the next one opened, and the election has
+    /// not been in the next phase since. Going back to a phase after its
+    /// deadline therefore keeps the election there.
+    pub fn scheduled_step_due(&self, now: u64) -> bool {
+        if self.closed_at.is_some() || self.is_last_phase() {
+            return false;
+        }
+        let next = self.next_phase();
+        let taken_since = |deadline: u64| {
+            self.phase_history
+                .iter()
+                .any(|t| t.phase == next && t.at >= deadline)
+        };
+        let due = |deadline: Option<u64>| deadline.is_some_and(|d| d <= now && !taken_since(d));
+        let closes_at = self.voting_window(self.phase).and_then(|w| w.closes_at);
+        let opens_at = self.voting_window(next).and_then(|w| w.opens_at);
+        due(closes_at) || due(opens_at)
+    }
+
+    /// When voting in the current phase opens, if that is still ahead.
+    pub fn opens_at(&self, now: u64) -> Option<u64> {
+        self.voting_window(self.phase)
+            .and_then(|w| w.opens_at)
+            .filter(|opens_at| *opens_at > now)
+    }
+}
@@ -0,0 +1,132 @@
+//! Score ballots: every voter gives each nominee a score from 0 to
+//! [`MAX_SCORE`], and the nominee with the highest total wins.
+
+use crate::{Ballot, ElectionProcess, ProcessError, VotingMethod};
+use itertools::*;
+use std::collections::HashMap;
+
+pub const MAX_SCORE: u8 = 5;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ScoreTotal {
+    pub nominee: u64,
+    pub total: u32,
+    /// Over all ballots, counting nominees without a score as 0.
+    pub average: f32,
+}
+
+impl Ballot {
+    /// A ballot with `scores` that votes for the highest scored nominee.
+    pub fn scored(scores: HashMap<u64, u8>, random: &dyn crate::Random) -> Self {
+        let vote = scores
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map_or(0, |(id, _)| *id);
+        Ballot {
+            scores,
+            ..Ballot::new(vote, random)
+        }
+    }
+}
+
+impl ElectionProcess {
+    /// Makes the scores of `ballot` fit the voting method: a ballot without
+    /// scores gives the full score to its vote.
+    pub(crate) fn check_scores(&self, ballot: &mut Ballot) -> Result<(), ProcessError> {
+        if self.method != VotingMethod::Score {
+            ballot.scores.clear();
+            return Ok(());
+        }
+        if ballot.scores.is_empty() {
+            ballot.scores.insert(ballot.vote, MAX_SCORE);
+        }
+        if ballot.scores.values().any(|score| *score > MAX_SCORE) {
+            return Err(ProcessError::InvalidScore);
+        }
+        if !ballot.scores.keys().all(|nominee| self.on_ballot(*nominee)) {
+            return Err(ProcessError::UnknownNominee);
+        }
+        Ok(())
+    }
+
+    /// The total and average score of every nominee on the ballot in
+    /// `round`, highest first.
+    pub fn score_totals(&self, round: &HashMap<String, Ballot>) -> Vec<ScoreTotal> {
+        self.nominees
+            .keys()
+            .filter(|id| self.on_ballot(**id))
+            .map(|nominee| {
+                let total = round
+                    .values()
+                    .map(|b| b.scores.get(nominee).copied().unwrap_or_default() as u32)
+                    .sum::<u32>();
+                ScoreTotal {
+                    nominee: *nominee,
+                    total,
+                    average: total as f32 / round.len().max(1) as f32,
+                }
+            })
+            .sorted_by(|a, b| {
+                b.total.cmp(&a.total).then_with(|| {
+                    self.nominee_name(a.nominee)
+                        .cmp(self.nominee_name(b.nominee))
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SeededRandom;
+
+    fn election() -> ElectionProcess {
+        ElectionProcess {
+            method: VotingMethod::Score,
+            ..ElectionProcess::new_and_cleaned(
+                "e".to_string(),
+                "Lead".to_string(),
+                "Ann\nBob\nCid",
+                0,
+            )
+        }
+    }
+
+    #[test]
+    fn totals_the_scores() {
+        let random = SeededRandom::new(1);
+        let mut election = election();
+        for (voter, scores) in [("v1", [(0, 5), (1, 3)]), ("v2", [(1, 4), (2, 1)])] {
+            let ballot = Ballot::scored(HashMap::from(scores), &random);
+            election
+                .add_vote(voter.to_string(), ballot, None, 1)
+                .unwrap();
+        }
+        assert_eq!(election.first_round_id["v2"].vote, 1);
+        let totals = election
+            .score_totals(&election.first_round_id)
+            .into_iter()
+            .map(|s| (s.nominee, s.total, s.average))
+            .collect::<Vec<_>>();
+        assert_eq!(totals, [(1, 7, 3.5), (0, 5, 2.5), (2, 1, 0.5)]);
+    }
+
+    #[test]
+    fn checks_the_scores() {
+        let random = SeededRandom::new(1);
+        let mut election = election();
+        let too_high = Ballot::scored(HashMap::from([(0, MAX_SCORE + 1)]), &random);
+        assert_eq!(
+            election.add_vote("v".to_string(), too_high, None, 1),
+            Err(ProcessError::InvalidScore)
+        );
+        // a plain vote gives the full score
+        let plain = Ballot::new(2, &random);
+        election.add_vote("v".to_string(), plain, None, 1).unwrap();
+        assert_eq!(
+            election.first_round_id["v"].scores,
+            HashMap::from([(2, MAX_SCORE)])
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Elections of several people for the same role. The safety round asks
+//! about one seat after the other, and the election is completed once every
+//! seat is filled.
+
+use crate::{Elected, ElectionProcess, VotingMethod};
+use itertools::*;
+use std::collections::HashSet;
+
+pub(crate) fn one_seat() -> usize {
+    1
+}
+
+impl ElectionProcess {
+    /// Everyone elected so far, in the order of their seats.
+    pub fn elected_seats(&self) -> Vec<&Elected> {
+        self.seated.iter().chain(&self.elected).collect()
+    }
+
+    /// The seat the safety round is asking about, starting at 1.
+    pub fn current_seat(&self) -> usize {
+        self.seated.len() + 1
+    }
+
+    pub fn is_seated(&self, nominee: u64) -> bool {
+        self.seated.iter().any(|e| e.nominee_id == nominee)
+    }
+
+    /// Up to `count` nominees who won the final round, best first: by votes,
+    /// by total score, or by one instant runoff after the other without the
+    /// nominees who already won one. Ties go to the winner of the tie break,
+    /// or else the nominee first by name.
+    pub fn winners(&self, count: usize) -> Vec<u64> {
+        let mut winners = self.winners_by_method(count);
+        if let Some(nominee) = self.tie_winner() {
+            winners.retain(|id| *id != nominee);
+            winners.insert(0, nominee);
+            winners.truncate(count);
+        }
+        winners
+    }
+
+    fn winners_by_method(&self, count: usize) -> Vec<u64> {
+        let round = self.final_round();
+        match self.method {
+            VotingMethod::Plurality => round
+                .values()
+                .counts_by(|b| b.vote)
+                .into_iter()
+                .filter(|(id, _)| self.on_ballot(*id))
+                .sorted_by(|a, b| {
+                    b.1.cmp(&a.1)
+                        .then_with(|| self.nominee_name(a.0).cmp(self.nominee_name(b.0)))
+                        .then_with(|| a.0.cmp(&b.0))
+                })
+                .map(|(id, _)| id)
+                .take(count)
+                .collect(),
+            VotingMethod::Score => self
+                .score_totals(round)
+                .into_iter()
+                .filter(|s| s.total > 0)
+                .map(|s| s.nominee)
+                .take(count)
+                .collect(),
+            VotingMethod::Ranked => {
+                let mut won = HashSet::new();
+                let mut winners = vec![];
+                while winners.len() < count {
+                    let Some(winner) = self.instant_runoff_without(round, &won).winner else {
+                        break;
+                    };
+                    won.insert(winner);
+                    winners.push(winner);
+                }
+                winners
+            }
+        }
+    }
+
+    /// Who to propose for the current seat: the best winner not elected for
+    /// an earlier seat yet.
+    pub fn next_seat_nominee(&self) -> Option<u64> {
+        self.winners(self.current_seat())
+            .into_iter()
+            .find(|id| !self.is_seated(*id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ballot, ElectionPhase, ProcessError, SeededRandom};
+
+    /// Two seats for Ann, Bob and Cid, with votes in a single round.
+    fn two_seats(method: VotingMethod, ballots: &[Ballot]) -> ElectionProcess {
+        let mut election = ElectionProcess {
+            method,
+            seats: 2,
+            rounds: 1,
+            ..ElectionProcess::new_and_cleaned(
+                "e".to_string(),
+                "Lead".to_string(),
+                "Ann\nBob\nCid",
+                0,
+            )
+        };
+        for (i, ballot) in ballots.iter().enumerate() {
+            let voter = format!("v{}", i);
+            election.add_vote(voter, ballot.clone(), None, 1).unwrap();
+        }
+        election.step_next(2);
+        election.step_next(3);
+        election
+    }
+
+    #[test]
+    fn fills_one_seat_after_the_other() {
+        let random = SeededRandom::new(1);
+        let ballots = [2, 0, 2, 1].map(|vote| Ballot::new(vote, &random));
+        let mut election = two_seats(VotingMethod::Plurality, &ballots);
+        assert_eq!(election.phase, ElectionPhase::SafetyRound);
+        assert_eq!(election.winners(3), [2, 0, 1]);
+
+        assert_eq!(election.next_seat_nominee(), Some(2));
+        election.complete(2, 4).unwrap();
+        assert_eq!(election.phase, ElectionPhase::SafetyRound);
+        assert_eq!(election.current_seat(), 2);
+        assert_eq!(election.complete(2, 5), Err(ProcessError::AlreadyElected));
+        assert_eq!(election.next_seat_nominee(), Some(0));
+        election.complete(0, 5).unwrap();
+        assert_eq!(election.phase, ElectionPhase::Completed);
+        let elected = election
+            .elected_seats()
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(elected, ["Cid", "Ann"]);
+    }
+
+    #[test]
+    fn runs_an_instant_runoff_per_seat() {
+        let random = SeededRandom::new(1);
+        let ballots = [vec![0, 1], vec![0, 1], vec![1, 2], vec![2, 1]]
+            .map(|ranking| Ballot::ranked(ranking, &random));
+        let election = two_seats(VotingMethod::Ranked, &ballots);
+        // without Ann, Bob wins 3 to 1
+        assert_eq!(election.winners(2), [0, 1]);
+    }
+}
@@ -0,0 +1,150 @@
+//! Changes to who facilitates an election, where it reports to and which
+//! elections it is linked with. Like votes and phases they are recorded as
+//! events, so they are part of the audit trail and can be undone.
+
+use crate::{ElectionEvent, ElectionProcess};
+
+impl ElectionProcess {
+    /// Gives the co-facilitator `name` their own `key`.
+    pub fn invite_co_facilitator(&mut self, key: String, name: String, now: u64) {
+        self.apply(ElectionEvent::CoFacilitatorInvited { key, name }, now);
+    }
+
+    /// Takes back the key of a co-facilitator; false if no one has it.
+    pub fn revoke_co_facilitator(&mut self, key: &str, now: u64) -> bool {
+        let Some(name) = self.co_facilitators.get(key).cloned() else {
+            return false;
+        };
+        let key = key.to_string();
+        self.apply(ElectionEvent::CoFacilitatorRevoked { key, name }, now);
+        true
+    }
+
+    /// Registers the webhook `url` unless it is already, creating the secret
+    /// signing the payloads with the first one.
+    pub fn add_webhook(&mut self, url: &str, secret: impl FnOnce() -> String, now: u64) {
+        if self.webhooks.iter().any(|u| u == url) {
+            return;
+        }
+        let url = url.to_string();
+        let secret = self.webhook_secret.is_none().then(secret);
+        self.apply(ElectionEvent::WebhookAdded { url, secret }, now);
+    }
+
+    pub fn remove_webhook(&mut self, url: &str, now: u64) {
+        if let Some(index) = self.webhooks.iter().position(|u| u == url) {
+            let url = url.to_string();
+            self.apply(ElectionEvent::WebhookRemoved { index, url }, now);
+        }
+    }
+
+    /// Sends the ballots to the Telegram `chat` from now on.
+    pub fn join_telegram_chat(&mut self, chat: i64, now: u64) {
+        if !self.telegram_chats.contains(&chat) {
+            self.apply(ElectionEvent::TelegramChatJoined { chat }, now);
+        }
+    }
+
+    /// Replaces the election this one follows and its follow-ups, e.g. with
+    /// the one another of them was merged into.
+    pub fn set_links(&mut self, follows: Option<String>, follow_ups: Vec<String>, now: u64) {
+        if follows == self.follows && follow_ups == self.follow_ups {
+            return;
+        }
+        let event = ElectionEvent::LinksChanged {
+            follows,
+            follow_ups,
+            previous_follows: self.follows.clone(),
+            previous_follow_ups: self.follow_ups.clone(),
+        };
+        self.apply(event, now);
+    }
+
+    /// Takes back the change of a setting by `event`, for finding the
+    /// settings the election was created with.
+    pub(crate) fn revert_setting(&mut self, event: &ElectionEvent) {
+        match event {
+            ElectionEvent::CoFacilitatorInvited { key, .. } => {
+                self.co_facilitators.remove(key);
+            }
+            ElectionEvent::CoFacilitatorRevoked { key, name } => {
+                self.co_facilitators.insert(key.clone(), name.clone());
+            }
+            ElectionEvent::WebhookAdded { url, secret } => {
+                self.webhooks.retain(|u| u != url);
+                if secret.is_some() {
+                    self.webhook_secret = None;
+                }
+            }
+            ElectionEvent::WebhookRemoved { index, url } => {
+                let index = (*index).min(self.webhooks.len());
+                self.webhooks.insert(index, url.clone());
+            }
+            ElectionEvent::TelegramChatJoined { chat } => {
+                self.telegram_chats.retain(|c| c != chat);
+            }
+            ElectionEvent::VotingWindowsChanged { previous, .. } => {
+                self.voting_windows = previous.clone();
+            }
+            ElectionEvent::LinksChanged {
+                previous_follows,
+                previous_follow_ups,
+                ..
+            } => {
+                self.follows = previous_follows.clone();
+                self.follow_ups = previous_follow_ups.clone();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ElectionPhase, VotingWindow};
+
+    #[test]
+    fn replays_and_undoes_setting_changes() {
+        let mut election =
+            ElectionProcess::new_and_cleaned("e".to_string(), "Lead".to_string(), "Ann\nBob", 10);
+        election.follows = Some("a".to_string());
+        let created = election.clone();
+
+        election.invite_co_facilitator("k".to_string(), "Cid".to_string(), 11);
+        election.add_webhook("https://a.test", || "s1".to_string(), 12);
+        election.add_webhook("https://b.test", || "s2".to_string(), 13);
+        election.remove_webhook("https://a.test", 14);
+        election.join_telegram_chat(7, 15);
+        election.join_telegram_chat(7, 16);
+        let window = VotingWindow {
+            phase: ElectionPhase::FirstVote,
+            opens_at: None,
+            closes_at: Some(100),
+        };
+        election.set_voting_window(window, 17).unwrap();
+        election.set_links(None, vec!["b".to_string()], 18);
+        assert!(election.revoke_co_facilitator("k", 19));
+        assert!(!election.revoke_co_facilitator("k", 20));
+
+        assert_eq!(election.webhooks, ["https://b.test"]);
+        assert_eq!(election.webhook_secret.as_deref(), Some("s1"));
+        assert_eq!(election.telegram_chats, [7]);
+        assert_eq!(election.follow_ups, ["b"]);
+        assert_eq!(election.events.len(), 8);
+        assert_eq!(election.replay().unwrap(), election);
+
+        election.undo().unwrap();
+        assert_eq!(election.co_facilitators.get("k").unwrap(), "Cid");
+        while !election.events.is_empty() {
+            election.undo().unwrap();
+        }
+        assert_eq!(election.co_facilitators, created.co_facilitators);
+        assert_eq!(election.webhooks, created.webhooks);
+        assert_eq!(election.webhook_secret, None);
+        assert_eq!(election.telegram_chats, created.telegram_chats);
+        assert_eq!(election.voting_windows, created.voting_windows);
+        assert_eq!(election.follows, created.follows);
+        assert_eq!(election.follow_ups, created.follow_ups);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{Ballot, ElectionPhase, ElectionProcess};
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Serialize, PartialEq, Debug)]
+pub struct ElectionStatistics {
+    pub id: String,
+    pub phase: ElectionPhase,
+    pub first_round: RoundStatistics,
+    pub second_round: RoundStatistics,
+    /// Voters who cast a ballot in either round.
+    pub distinct_voters: usize,
+    /// Voters who voted in both rounds.
+    pub voters_in_both_rounds: usize,
+    /// Voters whose second vote differs from their first one.
+    pub vote_changes: usize,
+    pub phase_durations: Vec<PhaseDuration>,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub struct RoundStatistics {
+    pub turnout: usize,
+    /// Shannon entropy of the vote distribution in bits; 0 means everyone
+    /// voted for the same nominee.
+    pub entropy: f64,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub struct PhaseDuration {
+    pub phase: ElectionPhase,
+    pub started_at: u64,
+    pub seconds: u64,
+}
+
+fn round_statistics(round: &HashMap<String, Ballot>) -> RoundStatistics {
+    let mut counts = HashMap::new();
+    for ballot in round.values() {
+        *counts.entry(ballot.vote).or_insert(0usize) += 1;
+    }
+    let total = round.len() as f64;
+    let entropy = counts
+        .values()
+        .map(|&c| c as f64 / total)
+        .map(|p| -p * p.log2())
+        .sum::<f64>();
+    RoundStatistics {
+        turnout: round.len(),
+        // an empty or unanimous round sums to -0.0
+        entropy: if entropy > 0.0 { entropy } else { 0.0 },
+    }
+}
+
+impl ElectionProcess {
+    /// Summarizes the election so far; `now` closes the duration of the
+    /// current phase.
+    pub fn statistics(&self, now: u64) -> ElectionStatistics {
+        let first = &self.first_round_id;
+        let second = &self.second_round_id;
+        let voters_in_both_rounds = first.keys().filter(|v| second.contains_key(*v)).count();
+        let vote_changes = first
+            .iter()
+            .filter(|(voter, ballot)| second.get(*voter).is_some_and(|b| b.vote != ballot.vote))
+            .count();
+
+        let phase_durations = self
+            .phase_history
+            .iter()
+            .enumerate()
+            .map(|(i, transition)| {
+                let ended_at = self.phase_history.get(i + 1).map_or(now, |next| next.at);
+                PhaseDuration {
+                    phase: transition.phase,
+                    started_at: transition.at,
+                    seconds: ended_at.saturating_sub(transition.at),
+                }
+            })
+            .collect();
+
+        ElectionStatistics {
+            id: self.id.clone(),
+            phase: self.phase,
+            first_round: round_statistics(first),
+            second_round: round_statistics(second),
+            distinct_voters: first.len() + second.len() - voters_in_both_rounds,
+            voters_in_both_rounds,
+            vote_changes,
+            phase_durations,
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Breaking a tie for the most votes of the final round: by the decision of
+//! the facilitator, by a draw whose seed is shown so anyone can repeat it, or
+//! by a runoff round between the tied nominees.
+
+use crate::{
+    ElectionEvent, ElectionKind, ElectionPhase, ElectionProcess, ProcessError, Random,
+    SeededRandom, VotingMethod,
+};
+use itertools::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+pub enum TieBreakMethod {
+    Facilitator,
+    /// Drawn with [`SeededRandom`] from the tied nominees ordered by name.
+    Draw {
+        seed: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct TieBreak {
+    pub nominee: u64,
+    pub method: TieBreakMethod,
+    pub at: u64,
+}
+
+/// A round in which only some nominees can be voted for.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct Runoff {
+    pub round: usize,
+    pub nominees: Vec<u64>,
+}
+
+impl ElectionProcess {
+    /// The nominees tied for the most votes of the final round, ordered by
+    /// name; empty without a tie.
+    pub fn tied_nominees(&self) -> Vec<u64> {
+        let round = self.final_round();
+        let scored = match self.method {
+            VotingMethod::Plurality => round
+                .values()
+                .counts_by(|b| b.vote)
+                .into_iter()
+                .filter(|(id, _)| self.on_ballot(*id) && !self.is_seated(*id))
+                .collect::<Vec<_>>(),
+            VotingMethod::Score => self
+                .score_totals(round)
+                .into_iter()
+                .map(|s| (s.nominee, s.total as usize))
+                .collect(),
+            VotingMethod::Ranked => {
+                let runoff = self.instant_runoff(round);
+                match (runoff.winner, runoff.rounds.last()) {
+                    (None, Some(last)) => last.counts.clone(),
+                    _ => vec![],
+                }
+            }
+        };
+        let most = scored.iter().map(|(_, votes)| *votes).max().unwrap_or(0);
+        let tied = scored
+            .into_iter()
+            .filter(|(_, votes)| most > 0 && *votes == most)
+            .map(|(id, _)| id)
+            .sorted_by(|a, b| {
+                self.nominee_name(*a)
+                    .cmp(self.nominee_name(*b))
+                    .then(a.cmp(b))
+            })
+            .collect::<Vec<_>>();
+        match tied.len() {
+            0 | 1 => vec![],
+            _ => tied,
+        }
+    }
+
+    /// Who the tie break chose, while the tie it broke still stands.
+    pub fn tie_winner(&self) -> Option<u64> {
+        self.tie_break
+            .as_ref()
+            .map(|t| t.nominee)
+            .filter(|nominee| self.tied_nominees().contains(nominee))
+    }
+
+    /// Whether the current phase is the tally of the round that decides.
+    pub fn is_final_tally(&self) -> bool {
+        match (self.kind, self.phase) {
+            (ElectionKind::StrawPoll, ElectionPhase::FirstTally) => true,
+            (ElectionKind::Election, ElectionPhase::FirstTally) => self.rounds < 2,
+            (ElectionKind::Election, ElectionPhase::SecondTally) => {
+                self.round_number() >= self.rounds
+            }
+            _ => false,
+        }
+    }
+
+    fn check_tie(&self) -> Result<Vec<u64>, ProcessError> {
+        if self.closed_at.is_some() {
+            return Err(ProcessError::Closed);
+        }
+        let tied = self.tied_nominees();
+        if !self.is_final_tally() || tied.is_empty() {
+            return Err(ProcessError::NoTie);
+        }
+        Ok(tied)
+    }
+
+    /// The facilitator decides the tie for `nominee`.
+    pub fn decide_tie(&mut self, nominee: u64, now: u64) -> Result<(), ProcessError> {
+        if !self.check_tie()?.contains(&nominee) {
+            return Err(ProcessError::UnknownNominee);
+        }
+        self.apply(
+            ElectionEvent::TieBroken {
+                nominee,
+                method: TieBreakMethod::Facilitator,
+            },
+            now,
+        );
+        Ok(())
+    }
+
+    /// Draws the winner of the tie by lot and returns them.
+    pub fn draw_tie(&mut self, random: &dyn Random, now: u64) -> Result<u64, ProcessError> {
+        let tied = self.check_tie()?;
+        let seed = random.below(1_000_000) as u64;
+        let nominee = tied[SeededRandom::new(seed).below(tied.len())];
+        self.apply(
+            ElectionEvent::TieBroken {
+                nominee,
+                method: TieBreakMethod::Draw { seed },
+            },
+            now,
+        );
+        Ok(nominee)
+    }
+
+    /// Adds a round in which only the tied nominees can be voted for.
+    pub fn start_runoff(&mut self, now: u64) -> Result<(), ProcessError> {
+        let nominees = self.check_tie()?;
+        if self.phase != ElectionPhase::SecondTally {
+            return Err(ProcessError::NoRunoff);
+        }
+        self.apply(ElectionEvent::RunoffStarted { nominees }, now);
+        Ok(())
+    }
+
+    /// The nominees the current round is restricted to, if it is a runoff.
+    pub fn runoff_nominees(&self) -> Option<&[u64]> {
+        let round = self.round_number();
+        self.runoffs
+            .iter()
+            .find(|r| r.round == round)
+            .map(|r| &r.nominees[..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ballot;
+
+    /// A one-round election in its tally, with Bob and Cid tied ahead of Ann.
+    fn tied() -> ElectionProcess {
+        let random = SeededRandom::new(1);
+        let mut election = ElectionProcess {
+            rounds: 1,
+            ..ElectionProcess::new_and_cleaned(
+                "e".to_string(),
+                "Lead".to_string(),
+                "Cid\nBob\nAnn",
+                0,
+            )
+        };
+        for (voter, vote) in [("v1", 2), ("v2", 1), ("v3", 0), ("v4", 1), ("v5", 2)] {
+            let ballot = Ballot::new(vote, &random);
+            election
+                .add_vote(voter.to_string(), ballot, None, 1)
+                .unwrap();
+        }
+        election.step_next(2);
+        election
+    }
+
+    #[test]
+    fn draws_the_same_nominee_from_the_same_seed() {
+        let mut election = tied();
+        assert!(election.is_final_tally());
+        assert_eq!(election.tied_nominees(), [1, 2]);
+
+        let nominee = election.draw_tie(&SeededRandom::new(5), 3).unwrap();
+        let seed = SeededRandom::new(5).below(1_000_000) as u64;
+        assert_eq!(nominee, [1, 2][SeededRandom::new(seed).below(2)]);
+        assert_eq!(
+            election.tie_break,
+            Some(TieBreak {
+                nominee,
+                method: TieBreakMethod::Draw { seed },
+                at: 3,
+            })
+        );
+        assert_eq!(
+            election.events.last().map(|e| &e.event),
+            Some(&ElectionEvent::TieBroken {
+                nominee,
+                method: TieBreakMethod::Draw { seed },
+            })
+        );
+        assert_eq!(election.tie_winner(), Some(nominee));
+        assert_eq!(election.replay().unwrap(), election);
+
+        let mut again = tied();
+        assert_eq!(again.draw_tie(&SeededRandom::new(5), 3), Ok(nominee));
+    }
+
+    #[test]
+    fn lets_the_facilitator_decide_only_between_the_tied() {
+        let mut election = tied();
+        assert_eq!(election.decide_tie(0, 3), Err(ProcessError::UnknownNominee));
+        election.decide_tie(2, 3).unwrap();
+        assert_eq!(election.tie_winner(), Some(2));
+        assert_eq!(election.start_runoff(4), Err(ProcessError::NoRunoff));
+
+        election.step_prev(5);
+        assert_eq!(election.decide_tie(1, 6), Err(ProcessError::NoTie));
+    }
+}
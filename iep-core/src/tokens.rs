@@ -0,0 +1,109 @@
+//! Single-use voting links for the voters of the roster, so they can vote
+//! without entering their name. A token is spent by its vote, and all of them
+//! are when the votes of the round are reset.
+
+use crate::{Ballot, ElectionEvent, ElectionProcess, ProcessError, Random};
+use std::collections::HashMap;
+
+/// Characters of a token, enough not to be guessed.
+const TOKEN_LENGTH: usize = 20;
+
+impl ElectionProcess {
+    /// Issues a new token for every voter of the roster, replacing the
+    /// tokens issued before.
+    pub fn issue_tokens(&mut self, random: &dyn Random, now: u64) -> Result<(), ProcessError> {
+        if self.closed_at.is_some() {
+            return Err(ProcessError::Closed);
+        }
+        if self.roster.is_empty() {
+            return Err(ProcessError::NoRoster);
+        }
+        let tokens = self
+            .roster
+            .iter()
+            .map(|voter_name| (random.alphanumeric(TOKEN_LENGTH), voter_name.clone()))
+            .collect::<HashMap<_, _>>();
+        self.apply(ElectionEvent::TokensIssued { tokens }, now);
+        Ok(())
+    }
+
+    /// The voter a token was issued for, while it is not spent.
+    pub fn token_voter(&self, token: &str) -> Option<&str> {
+        self.voting_tokens.get(token).map(|name| &name[..])
+    }
+
+    /// The unspent token of `voter_name`.
+    pub fn voter_token(&self, voter_name: &str) -> Option<&str> {
+        self.voting_tokens
+            .iter()
+            .find(|(_, name)| *name == voter_name)
+            .map(|(token, _)| &token[..])
+    }
+
+    /// Casts the ballot of the voter of `token` and spends it. Returns the
+    /// name of the voter.
+    pub fn vote_with_token(
+        &mut self,
+        token: &str,
+        ballot: Ballot,
+        reason: Option<String>,
+        now: u64,
+    ) -> Result<String, ProcessError> {
+        let voter_name = self
+            .token_voter(token)
+            .ok_or(ProcessError::InvalidToken)?
+            .to_string();
+        self.add_vote(voter_name.clone(), ballot, reason, now)?;
+        self.apply(
+            ElectionEvent::TokenUsed {
+                token: token.to_string(),
+            },
+            now,
+        );
+        Ok(voter_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SeededRandom;
+
+    #[test]
+    fn spends_a_token_with_its_vote() {
+        let random = SeededRandom::new(1);
+        let mut election =
+            ElectionProcess::new_and_cleaned("e".to_string(), "Lead".to_string(), "Ann", 0);
+        assert_eq!(
+            election.issue_tokens(&random, 1),
+            Err(ProcessError::NoRoster)
+        );
+        election.set_roster("Ann\nBob");
+        election.issue_tokens(&random, 1).unwrap();
+        let ann = election.voter_token("Ann").unwrap().to_string();
+        let bob = election.voter_token("Bob").unwrap().to_string();
+        assert_eq!(ann.len(), TOKEN_LENGTH);
+        assert_ne!(ann, bob);
+        assert_eq!(election.token_voter(&ann), Some("Ann"));
+
+        let ballot = Ballot::new(0, &random);
+        assert_eq!(
+            election.vote_with_token(&ann, ballot.clone(), None, 2),
+            Ok("Ann".to_string())
+        );
+        assert!(election.current_round().contains_key("Ann"));
+        assert_eq!(election.token_voter(&ann), None);
+        assert_eq!(
+            election.vote_with_token(&ann, ballot.clone(), None, 3),
+            Err(ProcessError::InvalidToken)
+        );
+        assert_eq!(election.replay().unwrap(), election);
+
+        // new tokens replace the old ones, and a reset spends them all
+        election.issue_tokens(&random, 4).unwrap();
+        assert_eq!(election.token_voter(&bob), None);
+        assert!(election.voter_token("Ann").is_some());
+        election.reset_votes(5);
+        assert!(election.voting_tokens.is_empty());
+    }
+}
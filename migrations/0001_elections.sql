@@ -0,0 +1,5 @@
+CREATE TABLE elections (
+    id text PRIMARY KEY,
+    data jsonb NOT NULL,
+    updated_at timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,13 @@
+-- A queryable copy of the ballots in the election data, rewritten on every
+-- change of the election.
+CREATE TABLE votes (
+    election_id text NOT NULL REFERENCES elections (id) ON DELETE CASCADE,
+    round text NOT NULL,
+    voter text NOT NULL,
+    nominee_id bigint NOT NULL,
+    nominee text NOT NULL,
+    sequence bigint NOT NULL,
+    submitted_at bigint NOT NULL,
+    manually_entered boolean NOT NULL,
+    PRIMARY KEY (election_id, round, voter)
+);
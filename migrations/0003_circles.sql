@@ -0,0 +1,4 @@
+CREATE TABLE circles (
+    id text PRIMARY KEY,
+    data jsonb NOT NULL
+);
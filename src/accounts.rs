@@ -0,0 +1,101 @@
+//! Lightweight accounts, so facilitators coming back later find the elections
+//! they created. The account is the user logged in with single sign-on or
+//! else an anonymous one kept in a cookie of the browser.
+
+use crate::{base_html, cookies, facilitator, oidc, ElectionDB};
+use axum::{
+    extract::State,
+    http::{header::SET_COOKIE, HeaderMap},
+};
+use iep_core::UtcDateTime;
+use itertools::*;
+use maud::{html, Markup};
+
+const ACCOUNT_LENGTH: usize = 24;
+
+/// The account of this browser, if it has one.
+pub fn account(state: &ElectionDB, headers: &HeaderMap) -> Option<String> {
+    match oidc::user(state, headers) {
+        Some(user) => Some(format!("user:{}", user)),
+        None => cookies::account(state, headers).map(|a| format!("browser:{}", a)),
+    }
+}
+
+/// The account of this browser, and the header creating an anonymous one if
+/// it has none yet.
+pub fn account_or_new(
+    state: &ElectionDB,
+    headers: &HeaderMap,
+) -> (String, Option<(axum::http::HeaderName, String)>) {
+    match account(state, headers) {
+        Some(account) => (account, None),
+        None => {
+            let account = state.random.alphanumeric(ACCOUNT_LENGTH);
+            let cookie = cookies::account_cookie(state, &account);
+            (format!("browser:{}", account), Some((SET_COOKIE, cookie)))
+        }
+    }
+}
+
+pub async fn view_my_elections(State(state): State<ElectionDB>, headers: HeaderMap) -> Markup {
+    let account = account(&state, &headers);
+    let elections = state
+        .store
+        .list()
+        .into_iter()
+        .filter(|e| account.is_some() && e.created_by == account)
+        .sorted_by_key(|e| std::cmp::Reverse(e.phase_history.first().map_or(0, |t| t.at)))
+        .collect::<Vec<_>>();
+
+    base_html(
+        "My Elections",
+        html!("My Elections"),
+        html! {
+            @if elections.is_empty() {
+                p { "The elections you create from this browser will be listed here." }
+            } @else {
+                figure {
+                    table ."striped" {
+                        thead {
+                            tr {
+                                th { "Role" }
+                                th { "Phase" }
+                                th { "Created (UTC)" }
+                                th {}
+                            }
+                        }
+                        tbody {
+                            @for election in &elections {
+                                @let created = election.phase_history.first().map(|t| UtcDateTime::from_unix(t.at));
+                                tr {
+                                    td {
+                                        (election.elected_role)
+                                        @if let Some(meeting) = &election.meeting {
+                                            br; small { (meeting) }
+                                        }
+                                    }
+                                    td {
+                                        @match election.closed_at {
+                                            Some(_) => "Closed",
+                                            None => (election.phase_title()),
+                                        }
+                                    }
+                                    td { @if let Some(created) = created { (created.date_time()) } }
+                                    td {
+                                        a href=(facilitator::eval_path(election)) { "Evaluation" }
+                                        @if election.closed_at.is_some() || election.is_last_phase() {
+                                            " · "
+                                            a ."secondary" href={"/election/" (election.id) "/voting"} { "Outcome" }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            p { a href="/" ."secondary" { "Start a new election" } }
+        },
+        html! {},
+    )
+}
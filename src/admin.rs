@@ -0,0 +1,158 @@
+//! Overview of all elections for the admin, with actions on each of them.
+
+use crate::{base_html, url_encode, AdminQuery, ElectionDB, ElectionUpdate};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use iep_core::{ElectionProcess, UtcDateTime};
+use itertools::*;
+use maud::{html, Markup};
+use serde::Serialize;
+
+pub async fn view_admin(
+    Query(query): Query<AdminQuery>,
+    State(state): State<ElectionDB>,
+) -> Result<Markup, (StatusCode, &'static str)> {
+    state.check_admin(query.token.as_deref())?;
+    let token = url_encode(query.token.as_deref().unwrap_or_default());
+    let elections = state
+        .store
+        .list()
+        .into_iter()
+        .sorted_by_key(|e| std::cmp::Reverse(e.phase_history.first().map_or(0, |t| t.at)))
+        .collect::<Vec<_>>();
+
+    Ok(base_html(
+        "Admin",
+        html!("All Elections"),
+        html! {
+            p {
+                (elections.len()) " elections · "
+                a ."secondary" href={"/admin/export.zip?token=" (token)} { "Export all as ZIP" }
+            }
+            figure {
+                table ."striped" {
+                    thead {
+                        tr {
+                            th { "Id" }
+                            th { "Role" }
+                            th { "Phase" }
+                            th { "Votes" }
+                            th { "Created (UTC)" }
+                            th {}
+                        }
+                    }
+                    tbody {
+                        @for election in &elections {
+                            @let created = election.phase_history.first().map(|t| UtcDateTime::from_unix(t.at));
+                            @let actions = format!("/admin/elections/{}", election.id);
+                            tr {
+                                td { code { (election.id) } }
+                                td {
+                                    (election.elected_role)
+                                    @if let Some(meeting) = &election.meeting {
+                                        br; small { (meeting) }
+                                    }
+                                }
+                                td {
+                                    @match election.closed_at {
+                                        Some(_) => "Closed",
+                                        None => (election.phase_title()),
+                                    }
+                                }
+                                td { (election.first_round_id.len()) " / " (election.second_round_id.len()) }
+                                td { @if let Some(created) = created { (created.date_time()) } }
+                                td {
+                                    a href=(crate::facilitator::eval_path(election)) { "Open" }
+                                    " · "
+                                    a ."secondary" href="#"
+                                      hx-post={(actions) "/reset?token=" (token)}
+                                      hx-trigger="click" hx-swap="none"
+                                      hx-confirm={"Reset the votes of the current round of " (election.elected_role) "?"} {
+                                        "Reset"
+                                    }
+                                    " · "
+                                    a ."secondary" href="#"
+                                      hx-post={(actions) "/delete?token=" (token)}
+                                      hx-trigger="click" hx-swap="none"
+                                      hx-confirm={"Delete the election of " (election.elected_role) " for good?"} {
+                                        "Delete"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        },
+        html! {},
+    ))
+}
+
+pub async fn post_admin_election_action(
+    Path((id, action)): Path<(String, String)>,
+    Query(query): Query<AdminQuery>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    state.check_admin(query.token.as_deref())?;
+    match &action[..] {
+        "reset" => {
+            let removed = state.modify_election(&id, |election| {
+                if election.closed_at.is_some() {
+                    return Err(crate::process_error(iep_core::ProcessError::Closed));
+                }
+                election.voting_round().map_err(crate::process_error)?;
+                let voters = election.current_round().keys().cloned().collect::<Vec<_>>();
+                election.reset_votes(state.clock.now());
+                Ok(crate::removed_voters(election, election.phase, voters))
+            })?;
+            state.notify(&id, ElectionUpdate::VotesChanged)?;
+            for voter in removed {
+                state.notify_voter(&id, &voter, ElectionUpdate::VoteRemoved)?;
+            }
+        }
+        "delete" => {
+            state
+                .remove_election(&id)
+                .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+        }
+        _ => return Err((StatusCode::BAD_REQUEST, "Invalid action")),
+    }
+    Ok((StatusCode::ACCEPTED, [("HX-Refresh", "true")]))
+}
+
+#[derive(Serialize)]
+pub struct ImportReport {
+    imported: Vec<String>,
+}
+
+/// Restores one election as exported from `/election/:id/export.json`, or
+/// an array of them, e.g. from another server. Nothing is imported if any of
+/// the ids is taken.
+pub async fn post_admin_import(
+    Query(query): Query<AdminQuery>,
+    State(state): State<ElectionDB>,
+    Json(import): Json<serde_json::Value>,
+) -> Result<(StatusCode, Json<ImportReport>), (StatusCode, &'static str)> {
+    state.check_admin(query.token.as_deref())?;
+    // not an untagged enum, which cannot read the numeric keys of the nominees
+    let elections = match import {
+        serde_json::Value::Array(_) => serde_json::from_value::<Vec<ElectionProcess>>(import),
+        _ => serde_json::from_value::<ElectionProcess>(import).map(|e| vec![e]),
+    }
+    .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid election"))?;
+    if !elections.iter().map(|e| &e.id).all_unique() {
+        return Err((StatusCode::BAD_REQUEST, "The ids are not unique"));
+    }
+    if elections.iter().any(|e| state.store.contains(&e.id)) {
+        return Err((StatusCode::CONFLICT, "An election with the id exists"));
+    }
+    let imported = elections.iter().map(|e| e.id.clone()).collect();
+    for election in elections {
+        state.add_election(election);
+    }
+    Ok((StatusCode::CREATED, Json(ImportReport { imported })))
+}
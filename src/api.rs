@@ -0,0 +1,470 @@
+//! JSON API for external tools, versioned under `/api/v1`.
+//!
+//! Besides listing elections for the admin, scripts can create elections,
+//! cast votes, read the state of an election and move it through its phases.
+//! Changing the phase needs the facilitator key as bearer token, like the
+//! evaluation page needs it in its link, and reading an election with a
+//! passcode needs that key or the passcode.
+
+use crate::{
+    accounts, cast_vote, create_election, election_results_json, facilitator, facilitator_step,
+    passcode, process_error, AdminQuery, ElectionCreation, ElectionDB, Vote,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    Json,
+};
+use iep_core::{Elected, ElectionKind, ElectionPhase, ElectionProcess, ProcessError, VotingMethod};
+use itertools::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, str::FromStr};
+
+const DEFAULT_PAGE_SIZE: usize = 20;
+const MAX_PAGE_SIZE: usize = 100;
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ElectionSort {
+    #[default]
+    Created,
+    Role,
+    Phase,
+}
+
+#[derive(Deserialize)]
+pub struct ElectionListQuery {
+    phase: Option<String>,
+    /// Case-insensitive part of the elected role.
+    role: Option<String>,
+    kind: Option<String>,
+    meeting: Option<String>,
+    #[serde(default)]
+    sort: ElectionSort,
+    #[serde(default)]
+    desc: bool,
+    limit: Option<usize>,
+    /// `next_cursor` of the previous page.
+    cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ElectionSummary {
+    id: String,
+    kind: ElectionKind,
+    elected_role: String,
+    phase: ElectionPhase,
+    meeting: Option<String>,
+    nominees: usize,
+    first_round_ballots: usize,
+    second_round_ballots: usize,
+    created_at: u64,
+}
+
+#[derive(Serialize)]
+pub struct ElectionList {
+    elections: Vec<ElectionSummary>,
+    /// Pass as `cursor` to get the next page; absent on the last page.
+    next_cursor: Option<String>,
+}
+
+fn created_at(election: &ElectionProcess) -> u64 {
+    election.phase_history.first().map_or(0, |t| t.at)
+}
+
+/// The key elections are ordered by, with the id to break ties.
+fn sort_key(election: &ElectionProcess, sort: ElectionSort) -> (String, String) {
+    let key = match sort {
+        // zero padded so that the string order matches the numeric one
+        ElectionSort::Created => format!("{:020}", created_at(election)),
+        ElectionSort::Role => election.elected_role.to_lowercase(),
+        ElectionSort::Phase => format!("{}", election.phase as u8),
+    };
+    (key, election.id.clone())
+}
+
+/// Cursors are the hex encoded sort key of the last returned election, so
+/// pages stay stable when elections are added or removed in between.
+fn encode_cursor((key, id): &(String, String)) -> String {
+    format!("{}\n{}", key, id)
+        .bytes()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn decode_cursor(cursor: &str) -> Option<(String, String)> {
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(cursor.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+    let text = String::from_utf8(bytes).ok()?;
+    let (key, id) = text.split_once('\n')?;
+    Some((key.to_string(), id.to_string()))
+}
+
+pub async fn get_elections(
+    Query(admin): Query<AdminQuery>,
+    Query(query): Query<ElectionListQuery>,
+    State(state): State<ElectionDB>,
+) -> Result<Json<ElectionList>, (StatusCode, &'static str)> {
+    state.check_admin(admin.token.as_deref())?;
+    let phase = query
+        .phase
+        .as_deref()
+        .map(ElectionPhase::from_str)
+        .transpose()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid phase"))?;
+    let kind = query
+        .kind
+        .as_deref()
+        .map(ElectionKind::from_str)
+        .transpose()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid kind"))?;
+    let cursor = query
+        .cursor
+        .as_deref()
+        .map(|c| decode_cursor(c).ok_or((StatusCode::BAD_REQUEST, "Invalid cursor")))
+        .transpose()?;
+    let role = query.role.map(|r| r.to_lowercase());
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let elections = state.store.list();
+    let mut matching = elections
+        .iter()
+        .filter(|e| phase.is_none_or(|p| e.phase == p))
+        .filter(|e| kind.is_none_or(|k| e.kind == k))
+        .filter(|e| {
+            role.as_ref()
+                .is_none_or(|r| e.elected_role.to_lowercase().contains(r))
+        })
+        .filter(|e| {
+            query
+                .meeting
+                .as_ref()
+                .is_none_or(|m| e.meeting.as_ref() == Some(m))
+        })
+        .map(|e| (sort_key(e, query.sort), e))
+        .sorted_by(|a, b| match query.desc {
+            true => b.0.cmp(&a.0),
+            false => a.0.cmp(&b.0),
+        })
+        .filter(|(key, _)| match (&cursor, query.desc) {
+            (None, _) => true,
+            (Some(cursor), false) => key > cursor,
+            (Some(cursor), true) => key < cursor,
+        })
+        .take(limit + 1)
+        .collect::<Vec<_>>();
+
+    let next_cursor = match matching.len() > limit {
+        true => {
+            matching.truncate(limit);
+            matching.last().map(|(key, _)| encode_cursor(key))
+        }
+        false => None,
+    };
+    Ok(Json(ElectionList {
+        elections: matching
+            .into_iter()
+            .map(|(_, e)| ElectionSummary {
+                id: e.id.clone(),
+                kind: e.kind,
+                elected_role: e.elected_role.clone(),
+                phase: e.phase,
+                meeting: e.meeting.clone(),
+                nominees: e.nominees.len(),
+                first_round_ballots: e.first_round_id.len(),
+                second_round_ballots: e.second_round_id.len(),
+                created_at: created_at(e),
+            })
+            .collect(),
+        next_cursor,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct NewElection {
+    #[serde(default)]
+    kind: ElectionKind,
+    elected_role: String,
+    #[serde(default)]
+    nominees: Vec<String>,
+    #[serde(default)]
+    method: VotingMethod,
+    seats: Option<usize>,
+    rounds: Option<usize>,
+    /// Start with a nomination phase.
+    #[serde(default)]
+    nomination: bool,
+    post_vote_message: Option<String>,
+    meeting: Option<String>,
+    slug: Option<String>,
+    keep_days: Option<u64>,
+    expected_voters: Option<usize>,
+    #[serde(default)]
+    require_quorum: bool,
+    #[serde(default)]
+    auto_advance: bool,
+    #[serde(default)]
+    roster: Vec<String>,
+    passcode: Option<String>,
+    /// Told about new votes and phases, see the README.
+    #[serde(default)]
+    webhooks: Vec<String>,
+    /// Announces the phases in a Slack channel.
+    slack_webhook: Option<String>,
+    /// Announces the phases in a Discord channel.
+    discord_webhook: Option<String>,
+    /// Emailed the results on closing.
+    #[serde(default)]
+    report_to: Vec<String>,
+    #[serde(default)]
+    report_to_roster: bool,
+}
+
+#[derive(Serialize)]
+pub struct CreatedElection {
+    id: String,
+    /// Needed as bearer token to change the phase.
+    facilitator_key: Option<String>,
+    /// Signs the payloads of the webhooks, if there are any.
+    webhook_secret: Option<String>,
+    eval_url: String,
+    voting_url: String,
+}
+
+pub async fn post_election(
+    State(state): State<ElectionDB>,
+    headers: HeaderMap,
+    Json(new): Json<NewElection>,
+) -> Result<(StatusCode, Json<CreatedElection>), (StatusCode, &'static str)> {
+    let flag = |set: bool| set.then(String::new);
+    let form = ElectionCreation {
+        kind: new.kind,
+        elected_role: new.elected_role,
+        nominees: new.nominees.join("\n"),
+        post_vote_message: new.post_vote_message,
+        meeting: new.meeting,
+        keep_days: new.keep_days.map(|d| d.to_string()),
+        slug: new.slug,
+        nomination: flag(new.nomination),
+        method: new.method,
+        seats: new.seats.map(|s| s.to_string()),
+        rounds: new.rounds.map(|r| r.to_string()),
+        expected_voters: new.expected_voters.map(|v| v.to_string()),
+        require_quorum: flag(new.require_quorum),
+        auto_advance: flag(new.auto_advance),
+        roster: new.roster.join("\n"),
+        passcode: new.passcode,
+        webhooks: new.webhooks.join("\n"),
+        slack_webhook: new.slack_webhook,
+        discord_webhook: new.discord_webhook,
+        report_to: new.report_to.join(","),
+        report_to_roster: flag(new.report_to_roster),
+        ..Default::default()
+    };
+    let election = create_election(&state, form, accounts::account(&state, &headers))?;
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedElection {
+            eval_url: format!("{}{}", state.base_url, facilitator::eval_path(&election)),
+            voting_url: format!("{}/election/{}/voting", state.base_url, election.id),
+            id: election.id,
+            facilitator_key: election.facilitator_key,
+            webhook_secret: election.webhook_secret,
+        }),
+    ))
+}
+
+#[derive(Serialize)]
+pub struct ElectionState {
+    id: String,
+    kind: ElectionKind,
+    elected_role: String,
+    method: VotingMethod,
+    phase: ElectionPhase,
+    closed: bool,
+    /// Nominee names by id, the ids to vote with.
+    nominees: HashMap<u64, String>,
+    /// Ballots in the current voting round.
+    voted: usize,
+    expected_voters: Option<usize>,
+    elected: Option<Elected>,
+    /// Like `/election/:id/results.json`.
+    results: serde_json::Value,
+}
+
+fn election_state(election: &ElectionProcess) -> ElectionState {
+    ElectionState {
+        id: election.id.clone(),
+        kind: election.kind,
+        elected_role: election.elected_role.clone(),
+        method: election.method,
+        phase: election.phase,
+        closed: election.closed_at.is_some(),
+        nominees: election.nominees.clone(),
+        voted: election.voted_count(),
+        expected_voters: election.expected_voters,
+        elected: election.elected.clone(),
+        results: election_results_json(election),
+    }
+}
+
+/// The token of the `Authorization: Bearer` header.
+pub fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+pub async fn get_election(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    headers: HeaderMap,
+) -> Result<Json<ElectionState>, (StatusCode, &'static str)> {
+    let election = state
+        .store
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    if !passcode::allows_key(&election, bearer(&headers)) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Needs the passcode or the facilitator key",
+        ));
+    }
+    Ok(Json(election_state(&election)))
+}
+
+#[derive(Deserialize)]
+pub struct NewVote {
+    /// Taken from the token instead if there is one.
+    #[serde(default)]
+    voter_name: String,
+    /// The token of a voting link.
+    token: Option<String>,
+    /// The nominee id, for plurality elections.
+    vote: Option<u64>,
+    /// Nominee ids in order of preference, for ranked elections.
+    #[serde(default)]
+    ranking: Vec<u64>,
+    /// Scores by nominee id, for score elections.
+    #[serde(default)]
+    scores: HashMap<u64, u8>,
+    reason: Option<String>,
+    /// A new nominee to vote for instead, during the first vote.
+    write_in: Option<String>,
+    /// Replace an earlier ballot of the same name.
+    #[serde(default)]
+    update: bool,
+    /// Needed if the election has one.
+    passcode: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CastVote {
+    message: String,
+    receipt: String,
+}
+
+pub async fn post_vote(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    Json(vote): Json<NewVote>,
+) -> Result<(StatusCode, Json<CastVote>), (StatusCode, &'static str)> {
+    let election = state
+        .store
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    let passcode_given = vote
+        .passcode
+        .as_deref()
+        .is_some_and(|given| passcode::is_passcode(&election, given));
+    if election.passcode.is_some() && vote.token.is_none() && !passcode_given {
+        return Err((StatusCode::FORBIDDEN, "This election needs its passcode"));
+    }
+    let voter_name = match &vote.token {
+        Some(token) => election
+            .token_voter(token)
+            .ok_or_else(|| process_error(ProcessError::InvalidToken))?
+            .to_string(),
+        None => election
+            .roster_name(&vote.voter_name)
+            .ok_or_else(|| process_error(ProcessError::NotOnRoster))?,
+    };
+    let voted = election
+        .voting_round_ballots()
+        .is_ok_and(|round| round.contains_key(&voter_name));
+    if voted && !vote.update && vote.token.is_none() {
+        return Err((
+            StatusCode::CONFLICT,
+            "Already voted; set update to replace the vote",
+        ));
+    }
+    let join = |ids: Vec<String>| (!ids.is_empty()).then(|| ids.join(","));
+    let (message, receipt) = cast_vote(
+        &state,
+        &id,
+        Vote {
+            voter_name,
+            update: vote.update.then(|| "true".to_string()),
+            token: vote.token,
+            vote: vote.vote,
+            reason: vote.reason,
+            write_in: vote.write_in,
+            ranking: join(vote.ranking.iter().map(|id| id.to_string()).collect()),
+            scores: join(
+                vote.scores
+                    .iter()
+                    .map(|(id, score)| format!("{}:{}", id, score))
+                    .collect(),
+            ),
+            protect_earlier: !vote.update,
+        },
+    )?;
+    Ok((StatusCode::CREATED, Json(CastVote { message, receipt })))
+}
+
+#[derive(Deserialize)]
+pub struct PhaseStep {
+    /// `next`, `prev`, `reset`, `undo` or `close`.
+    step: String,
+    /// Only take the step if the election is still in this phase.
+    from: Option<ElectionPhase>,
+}
+
+pub async fn post_phase(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    headers: HeaderMap,
+    Json(step): Json<PhaseStep>,
+) -> Result<Json<ElectionState>, (StatusCode, &'static str)> {
+    let election = state
+        .store
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    let key = bearer(&headers);
+    let facilitator = match election.facilitator_key {
+        None => None,
+        Some(_) => Some(
+            key.and_then(|key| facilitator::facilitator_name(&election, key))
+                .ok_or((StatusCode::FORBIDDEN, "Needs the facilitator key"))?,
+        ),
+    };
+    let change = async { facilitator_step(&state, &id, &step.step, step.from) };
+    let stepped = match facilitator {
+        Some(facilitator) => facilitator::acting_as(facilitator, change).await?,
+        None => change.await?,
+    };
+    if !stepped {
+        return Err((StatusCode::CONFLICT, "Phase does not match current phase"));
+    }
+    let election = state
+        .store
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    Ok(Json(election_state(&election)))
+}
@@ -0,0 +1,160 @@
+//! A single HTML file with everything about a finished election, with its
+//! styles inlined and without scripts, to keep in the records of the
+//! organisation long after the election was removed from the server.
+
+use crate::{ballot_summary, runoff_rounds, ElectionDB};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use iep_core::{Ballot, ElectionKind, ElectionPhase, ElectionProcess, UtcDateTime, VotingMethod};
+use itertools::*;
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+use std::collections::HashMap;
+
+const STYLES: &str = include_str!("../static/archive.css");
+
+fn bar(value: f32, max: f32, label: String) -> Markup {
+    html! {
+        td ."bar" {
+            span style={"width: " (format!("{:.1}", 100.0 * value / max.max(1.0))) "%"} { (label) }
+        }
+    }
+}
+
+fn round_tally(election: &ElectionProcess, round: &HashMap<String, Ballot>) -> Markup {
+    html! {
+        table {
+            @if election.method == VotingMethod::Score {
+                @let totals = election.score_totals(round);
+                @let max_total = totals.first().map_or(0, |s| s.total);
+                thead { tr { th { "Nominee" } th { "Total score (average)" } } }
+                tbody {
+                    @for score in &totals {
+                        tr {
+                            td { (election.nominee_name(score.nominee)) }
+                            (bar(score.total as f32, max_total as f32, format!("{} ({:.1})", score.total, score.average)))
+                        }
+                    }
+                }
+            } @else {
+                @let votes = election.accumulated_votes(round);
+                @let max_votes = votes.max_votes();
+                thead { tr { th { "Nominee" } th { "Votes" } } }
+                tbody {
+                    @for (nominee, count) in &votes.votes {
+                        tr {
+                            td { (nominee) }
+                            (bar(*count as f32, max_votes as f32, count.to_string()))
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn archive(election: &ElectionProcess, archived_at: u64) -> Markup {
+    let date_time = |at: u64| UtcDateTime::from_unix(at).date_time();
+    html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="UTF-8" {}
+                meta name="viewport" content="width=device-width, initial-scale=1" {}
+                title { "IEP - " (election.elected_role) }
+                style { (PreEscaped(STYLES)) }
+            }
+            body {
+                h1 { (election.elected_role) }
+                @if let Some(meeting) = &election.meeting {
+                    p { (meeting) }
+                }
+                p {
+                    @match election.kind {
+                        ElectionKind::Election => "Election",
+                        ElectionKind::StrawPoll => "Straw poll",
+                    }
+                    " with " (election.method.to_string().to_lowercase()) " voting"
+                    @if election.seats > 1 { " for " (election.seats) " seats" }
+                }
+                h2 { "Outcome" }
+                @let elected = election.elected_seats();
+                @if elected.is_empty() {
+                    p { "Nobody was elected." }
+                } @else {
+                    ul {
+                        @for elected in elected {
+                            li { strong { (elected.name) } ", confirmed " (date_time(elected.at)) " UTC" }
+                        }
+                    }
+                }
+                @if let Some(closed_at) = election.closed_at {
+                    p { "Closed " (date_time(closed_at)) " UTC" }
+                }
+                @for (number, round) in election.numbered_rounds() {
+                    h2 { "Round " (number) }
+                    @if round.is_empty() {
+                        p { "No ballots." }
+                    } @else {
+                        p { (round.len()) " ballots" }
+                        (round_tally(election, round))
+                        @if election.method == VotingMethod::Ranked {
+                            (runoff_rounds(election, round))
+                        }
+                        table {
+                            thead { tr { th { "Voter" } th { "Vote" } th { "Reason" } th { "Submitted (UTC)" } } }
+                            tbody {
+                                @for (voter_name, ballot) in round.iter().sorted_by_key(|(_, b)| b.sequence) {
+                                    tr {
+                                        td {
+                                            (voter_name)
+                                            @if ballot.manually_entered { " (paper)" }
+                                        }
+                                        td { (ballot_summary(election, ballot)) }
+                                        td { (ballot.reason.as_deref().unwrap_or_default()) }
+                                        td { (date_time(ballot.submitted_at)) }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                h2 { "Phases" }
+                table {
+                    thead { tr { th { "Phase" } th { "Since (UTC)" } } }
+                    tbody {
+                        @for transition in &election.phase_history {
+                            tr { td { (transition.phase.nice_title()) } td { (date_time(transition.at)) } }
+                        }
+                    }
+                }
+                footer {
+                    "Election " (election.id) ", archived " (date_time(archived_at))
+                    " UTC with IEP Tool v" (env!("CARGO_PKG_VERSION"))
+                }
+            }
+        }
+    }
+}
+
+pub async fn get_election_archive(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let election = state
+        .store
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    if election.phase != ElectionPhase::Completed && election.closed_at.is_none() {
+        return Err((StatusCode::CONFLICT, "The election is not finished yet"));
+    }
+    Ok((
+        [(
+            "Content-Disposition",
+            format!("attachment; filename=\"election-{}.html\"", id),
+        )],
+        archive(&election, state.clock.now()),
+    ))
+}
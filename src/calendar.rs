@@ -0,0 +1,182 @@
+//! The scheduled voting windows as an iCalendar file at
+//! `/election/:id/calendar.ics`, one event per window with the voting link,
+//! and emailed to the voters of the roster with an address.
+
+use crate::{
+    mail::{Attachment, Mail},
+    ElectionDB,
+};
+use axum::{
+    extract::{Path, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::IntoResponse,
+};
+use iep_core::{ElectionProcess, UtcDateTime};
+use itertools::*;
+
+/// Escapes a TEXT value as in RFC 5545.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Folds a content line after 75 octets, not within a character.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
+
+/// The calendar of the voting windows that have an opening, `None` if none
+/// has one.
+fn calendar(election: &ElectionProcess, host: &str, link: &str, now: u64) -> Option<String> {
+    let time = |at: u64| UtcDateTime::from_unix(at).basic_format();
+    let events = election
+        .voting_windows
+        .iter()
+        .filter_map(|window| {
+            let opens_at = window.opens_at?;
+            Some(vec![
+                "BEGIN:VEVENT".to_string(),
+                format!("UID:{}-{}@{}", election.id, window.phase, host),
+                format!("DTSTAMP:{}", time(now)),
+                format!("DTSTART:{}", time(opens_at)),
+                format!("DTEND:{}", time(window.closes_at.unwrap_or(opens_at))),
+                format!(
+                    "SUMMARY:{}",
+                    escape(&format!(
+                        "{}: {}",
+                        window.phase.nice_title(),
+                        election.elected_role
+                    ))
+                ),
+                format!("DESCRIPTION:{}", escape(&format!("Vote here: {}", link))),
+                format!("URL:{}", link),
+                "END:VEVENT".to_string(),
+            ])
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    if events.is_empty() {
+        return None;
+    }
+    let lines = [
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:-//IEP Tool//{}//EN", env!("CARGO_PKG_VERSION")),
+        "METHOD:PUBLISH".to_string(),
+    ]
+    .into_iter()
+    .chain(events)
+    .chain(["END:VCALENDAR".to_string()]);
+    Some(lines.map(|line| fold(&line) + "\r\n").collect())
+}
+
+fn host(base_url: &str) -> &str {
+    base_url
+        .split_once("://")
+        .map_or(base_url, |(_, rest)| rest)
+        .split('/')
+        .next()
+        .unwrap_or_default()
+}
+
+pub async fn get_election_calendar(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let election = state
+        .store
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    let link = format!("{}/election/{}/voting", state.base_url, id);
+    let calendar = calendar(&election, host(&state.base_url), &link, state.clock.now())
+        .ok_or((StatusCode::NOT_FOUND, "No voting window is scheduled"))?;
+    Ok((
+        [
+            (CONTENT_TYPE, "text/calendar; charset=utf-8".to_string()),
+            (
+                axum::http::header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.ics\"", id),
+            ),
+        ],
+        calendar,
+    ))
+}
+
+/// Emails the calendar to the voters of the roster with an address, with
+/// their voting link if they have an unused one.
+pub async fn post_calendar_invitations(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let smtp = state.mail.clone().ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "No mail server is configured",
+    ))?;
+    let election = state
+        .store
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    let now = state.clock.now();
+    let mut mails = vec![];
+    for voter_name in &election.roster {
+        let Some(to) = election.voter_email(voter_name) else {
+            continue;
+        };
+        let link = match election.voter_token(voter_name) {
+            Some(token) => format!("{}/election/{}/vote/{}", state.base_url, id, token),
+            None => format!("{}/election/{}/voting", state.base_url, id),
+        };
+        let calendar = calendar(&election, host(&state.base_url), &link, now)
+            .ok_or((StatusCode::CONFLICT, "No voting window is scheduled"))?;
+        let windows = election
+            .voting_windows
+            .iter()
+            .filter_map(|w| {
+                Some(format!(
+                    "{}: {} (UTC)",
+                    w.phase.nice_title(),
+                    UtcDateTime::from_unix(w.opens_at?).date_time()
+                ))
+            })
+            .join("\n");
+        mails.push(Mail {
+            to: to.to_string(),
+            subject: format!("Voting times: {}", election.elected_role),
+            body: format!(
+                "Hello {},\n\nvoting for {} opens at these times:\n\n{}\n\nVote here: {}\n\nThe attached calendar has them with the link.\n",
+                voter_name, election.elected_role, windows, link
+            ),
+            attachment: Some(Attachment {
+                filename: format!("{}.ics", id),
+                content_type: "text/calendar; charset=utf-8; method=PUBLISH".to_string(),
+                data: calendar.into_bytes(),
+            }),
+        });
+    }
+    if mails.is_empty() {
+        return Err((StatusCode::CONFLICT, "No voter has an email address"));
+    }
+    tokio::spawn(async move {
+        match smtp.send(&mails).await {
+            Ok(failed) => {
+                for (to, error) in failed {
+                    tracing::warn!("Emailing the calendar to {} failed: {}", to, error);
+                }
+            }
+            Err(error) => tracing::warn!("Emailing the calendar failed: {}", error),
+        }
+    });
+    Ok((StatusCode::ACCEPTED, [("HX-Refresh", "true")]))
+}
@@ -0,0 +1,216 @@
+//! Announcements of new elections, phase changes and outcomes in team chats,
+//! e.g. "Second Vote open for Circle Lead — vote here: <link>".
+//!
+//! A Slack incoming webhook can be set for all elections with
+//! `SLACK_WEBHOOK_URL` and for a single one on its creation, as can a Discord
+//! webhook, which gets embeds with the QR code and the tally. A Matrix room
+//! gets the announcements of all elections with `MATRIX_HOMESERVER_URL`,
+//! `MATRIX_ACCESS_TOKEN` of the posting user and `MATRIX_ROOM_ID`.
+
+use crate::{http, report, url_encode, ElectionDB, ElectionUpdate};
+use iep_core::{ElectionKind, ElectionPhase, ElectionProcess};
+use itertools::*;
+use maud::html;
+use serde_json::{json, Value};
+
+pub struct Matrix {
+    homeserver: String,
+    access_token: String,
+    room_id: String,
+}
+
+impl Matrix {
+    pub fn from_env() -> Option<Self> {
+        let var = |name| std::env::var(name).ok().filter(|v| !v.is_empty());
+        Some(Matrix {
+            homeserver: var("MATRIX_HOMESERVER_URL")?
+                .trim_end_matches('/')
+                .to_string(),
+            access_token: var("MATRIX_ACCESS_TOKEN")?,
+            room_id: var("MATRIX_ROOM_ID")?,
+        })
+    }
+
+    async fn send(&self, transaction: &str, message: &Value) -> Result<String, String> {
+        let url = format!(
+            "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+            self.homeserver,
+            url_encode(&self.room_id),
+            transaction
+        );
+        let headers = [
+            ("Authorization", format!("Bearer {}", self.access_token)),
+            ("Content-Type", "application/json".to_string()),
+        ];
+        http::request("PUT", &url, &headers, message.to_string().as_bytes()).await
+    }
+}
+
+/// What happened, and where to go about it.
+struct Announcement {
+    summary: String,
+    action: &'static str,
+    url: String,
+    /// The QR code of the voting link, while it makes sense to vote.
+    qr_url: Option<String>,
+    /// Votes or score totals per nominee, most first, once tallied.
+    tally: Vec<(String, u64)>,
+}
+
+impl Announcement {
+    fn slack(&self) -> Value {
+        json!({ "text": format!("{} — {}: <{}>", self.summary, self.action, self.url) })
+    }
+
+    fn matrix(&self) -> Value {
+        let formatted = html! { (self.summary) " — " a href=(self.url) { (self.action) } };
+        json!({
+            "msgtype": "m.text",
+            "body": format!("{} — {}: {}", self.summary, self.action, self.url),
+            "format": "org.matrix.custom.html",
+            "formatted_body": formatted.into_string(),
+        })
+    }
+
+    fn discord(&self) -> Value {
+        let most = self.tally.first().map_or(1, |(_, v)| *v).max(1);
+        let fields = self
+            .tally
+            .iter()
+            .map(|(nominee, votes)| {
+                let bar = "█".repeat((10 * votes / most) as usize);
+                json!({ "name": nominee, "value": format!("{} {}", bar, votes) })
+            })
+            .collect::<Vec<_>>();
+        let mut embed = json!({
+            "title": self.summary,
+            "url": self.url,
+            "description": format!("[{}]({})", self.action, self.url),
+            "color": DISCORD_COLOR,
+            "fields": fields,
+        });
+        if let Some(qr_url) = &self.qr_url {
+            embed["thumbnail"] = json!({ "url": qr_url });
+        }
+        json!({ "embeds": [embed] })
+    }
+}
+
+const DISCORD_COLOR: u32 = 0x1095c1;
+
+/// What to tell the channel about the current phase of `election`.
+fn announcement(state: &ElectionDB, election: &ElectionProcess) -> Announcement {
+    let url = format!("{}/election/{}/voting", state.base_url, election.id);
+    let role = &election.elected_role;
+    let elected = election.elected_seats();
+    let open = election.closed_at.is_none()
+        && (election.phase == ElectionPhase::Nomination || election.phase.is_voting());
+    let tally = match open {
+        true => vec![],
+        false => report::tally(election, election.current_round()),
+    };
+    let (summary, action) = match election.phase {
+        ElectionPhase::Completed if !elected.is_empty() => (
+            format!(
+                "{} elected as {}",
+                elected.iter().map(|e| &e.name).join(", "),
+                role
+            ),
+            "results",
+        ),
+        ElectionPhase::Nomination => (format!("Nominations open for {}", role), "nominate here"),
+        phase if phase.is_voting() => (
+            format!("{} open for {}", election.phase_title(), role),
+            "vote here",
+        ),
+        ElectionPhase::SafetyRound => (
+            format!("Safety round for {}", role),
+            "raise objections here",
+        ),
+        _ => {
+            let ballots = election.current_round().len();
+            (
+                format!(
+                    "{} for {}: {} ballot{}",
+                    election.phase_title(),
+                    role,
+                    ballots,
+                    if ballots == 1 { "" } else { "s" }
+                ),
+                "details",
+            )
+        }
+    };
+    Announcement {
+        summary,
+        action,
+        url,
+        qr_url: open.then(|| format!("{}/election/{}/qr.png", state.base_url, election.id)),
+        tally,
+    }
+}
+
+/// Checks the URL of a webhook given on creation.
+pub fn check_url(url: &str) -> Result<(), (axum::http::StatusCode, &'static str)> {
+    http::split_url(url).map(|_| ()).map_err(|_| {
+        (
+            axum::http::StatusCode::BAD_REQUEST,
+            "Chat webhooks need http:// or https:// URLs",
+        )
+    })
+}
+
+fn announce(state: &ElectionDB, election: &ElectionProcess, announcement: Announcement) {
+    let slack = state
+        .slack_webhook
+        .iter()
+        .chain(&election.slack_webhook)
+        .cloned()
+        .unique()
+        .collect::<Vec<_>>();
+    let discord = election.discord_webhook.clone();
+    let matrix = state.matrix.clone();
+    if slack.is_empty() && discord.is_none() && matrix.is_none() {
+        return;
+    }
+    let transaction = state.random.alphanumeric(16);
+    tokio::spawn(async move {
+        for url in slack {
+            if let Err(error) = http::post_json(&url, &announcement.slack()).await {
+                tracing::warn!("Posting to Slack failed: {}", error);
+            }
+        }
+        if let Some(url) = discord {
+            if let Err(error) = http::post_json(&url, &announcement.discord()).await {
+                tracing::warn!("Posting to Discord failed: {}", error);
+            }
+        }
+        if let Some(matrix) = matrix {
+            if let Err(error) = matrix.send(&transaction, &announcement.matrix()).await {
+                tracing::warn!("Posting to Matrix failed: {}", error);
+            }
+        }
+    });
+}
+
+/// Announces a new election with its voting link in the background.
+pub fn announce_created(state: &ElectionDB, election: &ElectionProcess) {
+    let announcement = Announcement {
+        summary: match election.kind {
+            ElectionKind::Election => format!("New election for {}", election.elected_role),
+            ElectionKind::StrawPoll => format!("New straw poll on {}", election.elected_role),
+        },
+        ..announcement(state, election)
+    };
+    announce(state, election, announcement);
+}
+
+/// Announces a phase change of election `id` in the background.
+pub fn deliver(state: &ElectionDB, id: &str, update: ElectionUpdate) {
+    if !matches!(update, ElectionUpdate::PhaseChanged) {
+        return;
+    }
+    if let Some(election) = state.store.get(id) {
+        announce(state, &election, announcement(state, &election));
+    }
+}
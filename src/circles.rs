@@ -0,0 +1,287 @@
+//! Registry of circles and their roles, kept up to date by elections.
+
+use crate::{base_html, url_encode, ElectionDB};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Form,
+};
+use iep_core::{Circle, CircleRoleRef, ElectionProcess, Role, RoleHolder, WEEK_SECS};
+use itertools::*;
+use maud::{html, Markup};
+use serde::Deserialize;
+
+pub async fn view_circles(State(state): State<ElectionDB>) -> Result<Markup, StatusCode> {
+    let now = state.clock.now();
+    let window = state.reminder_weeks * WEEK_SECS;
+    let circles = state
+        .circles
+        .lock()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(base_html(
+        "Circles",
+        html!("Circles and Roles"),
+        html! {
+            @for circle in circles.values().sorted_by_key(|c| &c.name) {
+                article {
+                    header { h3 { (circle.name) } }
+                    table ."striped" {
+                        thead { tr { th { "Role" } th { "Current Holder" } th { "Term" } th {} } }
+                        tbody {
+                            @for role in &circle.roles {
+                                tr {
+                                    td { (role.name) }
+                                    td {
+                                        @match &role.holder {
+                                            Some(holder) => {
+                                                (holder.name) " "
+                                                a ."secondary" href={"/election/" (holder.election) "/eval"} { "(election)" }
+                                            },
+                                            None => em { "vacant" },
+                                        }
+                                    }
+                                    td {
+                                        @if let Some(weeks) = role.term_weeks { (weeks) " weeks" }
+                                        @if let Some(until) = role.holder.as_ref().and_then(|h| h.until) {
+                                            br;
+                                            @if until <= now {
+                                                mark { "ended" }
+                                            } @else if role.term_ends_within(now, window) {
+                                                mark { "ends in " ((until - now) / 86400) " days" }
+                                            } @else {
+                                                small { "ends in " ((until - now) / 86400) " days" }
+                                            }
+                                        }
+                                    }
+                                    td {
+                                        a href={
+                                            "/election/new?role=" (url_encode(&role.name)) "&circle_role="
+                                            (CircleRoleRef { circle: circle.id.clone(), role: role.id.clone() })
+                                        } { "Start election" }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    form ."table rows" {
+                        label for="name" {
+                            "New role: ";
+                            input type="text" name="name" required {}
+                        }
+                        label for="term_weeks" {
+                            "Term in weeks (optional): ";
+                            input type="number" name="term_weeks" min="1" {}
+                        }
+                        button ."secondary"
+                          hx-post={"/circles/" (circle.id) "/roles"} hx-trigger="click" hx-swap="none" {
+                            "Add Role"
+                        }
+                    }
+                }
+            }
+
+            h2 { "New Circle" }
+            form #"new-circle" ."table rows" {
+                label for="name" {
+                    "Circle Name: ";
+                    input type="text" name="name" required {}
+                }
+                label for="roles" {
+                    "Roles :";
+                    textarea name="roles" placeholder="one role per line" style="min-height: 6em;" {}
+                }
+                button
+                  hx-post="/circles" hx-trigger="click" hx-swap="none"
+                  style="left: 50%; position: relative; translate: -50%;" {
+                    "Create Circle"
+                }
+            }
+        },
+        html! {},
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CircleCreation {
+    name: String,
+    #[serde(default)]
+    roles: String,
+}
+
+pub async fn post_circle(
+    State(state): State<ElectionDB>,
+    Form(form): Form<CircleCreation>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Circle name is missing"));
+    }
+    let circle = Circle {
+        id: state.generate_id(),
+        name: name.to_string(),
+        roles: form
+            .roles
+            .lines()
+            .map(|r| r.trim())
+            .filter(|r| !r.is_empty())
+            .dedup()
+            .map(|r| new_role(&state, r, None))
+            .collect(),
+    };
+    let mut circles = state
+        .circles
+        .lock()
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Circle Lock error"))?;
+    circles.insert(circle.id.clone(), circle);
+    state.circles_changed(&circles);
+
+    Ok((StatusCode::CREATED, [("HX-Refresh", "true")]))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RoleCreation {
+    name: String,
+    term_weeks: Option<String>,
+}
+
+pub async fn post_circle_role(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    Form(form): Form<RoleCreation>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Role name is missing"));
+    }
+    let term_weeks = form
+        .term_weeks
+        .filter(|t| !t.trim().is_empty())
+        .map(|t| t.trim().parse::<u32>())
+        .transpose()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid term length"))?;
+    let role = new_role(&state, name, term_weeks);
+    let mut circles = state
+        .circles
+        .lock()
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Circle Lock error"))?;
+    circles
+        .get_mut(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Circle not found"))?
+        .roles
+        .push(role);
+    state.circles_changed(&circles);
+
+    Ok((StatusCode::CREATED, [("HX-Refresh", "true")]))
+}
+
+fn new_role(state: &ElectionDB, name: &str, term_weeks: Option<u32>) -> Role {
+    Role {
+        id: state.generate_id(),
+        name: name.to_string(),
+        holder: None,
+        term_weeks,
+    }
+}
+
+/// All circle roles as (reference, label) pairs for selecting one in a form.
+pub fn circle_role_options(state: &ElectionDB) -> Vec<(CircleRoleRef, String)> {
+    let Ok(circles) = state.circles.lock() else {
+        return vec![];
+    };
+    circles
+        .values()
+        .sorted_by_key(|c| &c.name)
+        .flat_map(|circle| {
+            circle.roles.iter().map(|role| {
+                (
+                    CircleRoleRef {
+                        circle: circle.id.clone(),
+                        role: role.id.clone(),
+                    },
+                    format!("{}: {}", circle.name, role.name),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Makes the elected nominee the holder of the election's circle role; for
+/// multi-seat elections everyone elected holds it together.
+pub fn record_role_holder(state: &ElectionDB, election: &ElectionProcess) {
+    let Some(circle_role) = &election.circle_role else {
+        return;
+    };
+    let Some(elected) = &election.elected else {
+        return;
+    };
+    let Ok(mut circles) = state.circles.lock() else {
+        return;
+    };
+    if let Some(role) = circles
+        .get_mut(&circle_role.circle)
+        .and_then(|c| c.role_mut(&circle_role.role))
+    {
+        let since = elected.at;
+        role.holder = Some(RoleHolder {
+            name: election.elected_seats().iter().map(|e| &e.name).join(", "),
+            election: election.id.clone(),
+            since,
+            until: role.term_weeks.map(|w| since + w as u64 * WEEK_SECS),
+            reminded: false,
+        });
+        state.circles_changed(&circles);
+    }
+}
+
+/// Periodically looks for terms ending soon and, if `REMINDER_WEBHOOK_URL`
+/// is set, posts one reminder per term to it.
+pub async fn remind_expiring_terms(state: ElectionDB, interval: std::time::Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let Some(url) = state.reminder_webhook.clone() else {
+            continue;
+        };
+        let now = state.clock.now();
+        let window = state.reminder_weeks * WEEK_SECS;
+        let due = {
+            let Ok(mut circles) = state.circles.lock() else {
+                continue;
+            };
+            let mut due = Vec::new();
+            for circle in circles.values_mut() {
+                for role in circle.roles.iter_mut() {
+                    if !role.term_ends_within(now, window) {
+                        continue;
+                    }
+                    let Some(holder) = role.holder.as_mut().filter(|h| !h.reminded) else {
+                        continue;
+                    };
+                    holder.reminded = true;
+                    due.push(serde_json::json!({
+                        "event": "term_ending",
+                        "circle": circle.name,
+                        "role": role.name,
+                        "holder": holder.name,
+                        "until": holder.until,
+                        "new_election": format!(
+                            "{}/election/new?role={}&circle_role={}/{}",
+                            state.base_url, url_encode(&role.name), circle.id, role.id
+                        ),
+                    }));
+                }
+            }
+            if !due.is_empty() {
+                state.circles_changed(&circles);
+            }
+            due
+        };
+        for reminder in due {
+            if let Err(error) = crate::http::post_json(&url, &reminder).await {
+                tracing::warn!("Sending term reminder failed: {}", error);
+            }
+        }
+    }
+}
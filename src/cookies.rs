@@ -0,0 +1,178 @@
+//! Signed cookies remembering who voted from a browser, so a second vote
+//! from it asks before replacing the first, the cookie keeping the key of
+//! the facilitator, the one showing the passcode of an election was given, the
+//! session of a user logged in with single sign-on and the anonymous account
+//! of a browser.
+
+use crate::{
+    crypto::{base64_decode, base64_encode, constant_time_eq, hex, hmac_sha256},
+    ElectionDB,
+};
+use axum::http::{header::COOKIE, HeaderMap};
+
+/// Long enough for elections over several days.
+const MAX_AGE_SECS: u64 = 30 * 24 * 60 * 60;
+
+fn cookie_name(id: &str) -> String {
+    format!("iep_voter_{}", id)
+}
+
+pub fn facilitator_cookie_name(id: &str) -> String {
+    format!("iep_facilitator_{}", id)
+}
+
+/// The value of a `Set-Cookie` header keeping the facilitator `key` of
+/// election `id`.
+pub fn facilitator_cookie(id: &str, key: &str) -> String {
+    format!(
+        "{}={}; Path=/election/{}; Max-Age={}; HttpOnly; SameSite=Lax",
+        facilitator_cookie_name(id),
+        key,
+        id,
+        MAX_AGE_SECS
+    )
+}
+
+fn signature(state: &ElectionDB, id: &str, voter_name: &str) -> String {
+    let data = format!("{}\n{}", id, voter_name);
+    hex(&hmac_sha256(&state.cookie_secret[..], data.as_bytes()))
+}
+
+/// The value of a `Set-Cookie` header remembering that `voter_name` voted in
+/// election `id`.
+pub fn voter_cookie(state: &ElectionDB, id: &str, voter_name: &str) -> String {
+    format!(
+        "{}={}.{}; Path=/election/{}; Max-Age={}; HttpOnly; SameSite=Lax",
+        cookie_name(id),
+        base64_encode(voter_name.as_bytes()),
+        signature(state, id, voter_name),
+        id,
+        MAX_AGE_SECS
+    )
+}
+
+/// The value of a `Set-Cookie` header removing the cookie of election `id`.
+pub fn forget_voter(id: &str) -> String {
+    format!("{}=; Path=/election/{}; Max-Age=0", cookie_name(id), id)
+}
+
+/// The value of the cookie `name`.
+pub fn read<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|header| header.to_str().ok())
+        .flat_map(|header| header.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+fn passcode_cookie_name(id: &str) -> String {
+    format!("iep_passcode_{}", id)
+}
+
+fn passcode_signature(state: &ElectionDB, id: &str, passcode: &str) -> String {
+    let data = format!("{}\npasscode\n{}", id, passcode);
+    hex(&hmac_sha256(&state.cookie_secret[..], data.as_bytes()))
+}
+
+/// The value of a `Set-Cookie` header remembering that the `passcode` of
+/// election `id` was given, without containing it.
+pub fn passcode_cookie(state: &ElectionDB, id: &str, passcode: &str) -> String {
+    format!(
+        "{}={}; Path=/election/{}; Max-Age={}; HttpOnly; SameSite=Lax",
+        passcode_cookie_name(id),
+        passcode_signature(state, id, passcode),
+        id,
+        MAX_AGE_SECS
+    )
+}
+
+/// Whether the `passcode` of election `id` was given from this browser.
+pub fn knows_passcode(state: &ElectionDB, headers: &HeaderMap, id: &str, passcode: &str) -> bool {
+    read(headers, &passcode_cookie_name(id)).is_some_and(|signed| {
+        constant_time_eq(
+            signed.as_bytes(),
+            passcode_signature(state, id, passcode).as_bytes(),
+        )
+    })
+}
+
+const SESSION_COOKIE: &str = "iep_session";
+
+fn session_signature(state: &ElectionDB, user: &str) -> String {
+    // election ids never contain a slash, so this differs from any voter
+    let data = format!("/session\n{}", user);
+    hex(&hmac_sha256(&state.cookie_secret[..], data.as_bytes()))
+}
+
+/// The value of a `Set-Cookie` header logging in `user` on the whole site.
+pub fn session_cookie(state: &ElectionDB, user: &str) -> String {
+    format!(
+        "{}={}.{}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+        SESSION_COOKIE,
+        base64_encode(user.as_bytes()),
+        session_signature(state, user),
+        MAX_AGE_SECS
+    )
+}
+
+/// The value of a `Set-Cookie` header logging out.
+pub fn forget_session() -> String {
+    format!("{}=; Path=/; Max-Age=0", SESSION_COOKIE)
+}
+
+/// Who is logged in from this browser, if the cookie is intact.
+pub fn logged_in_as(state: &ElectionDB, headers: &HeaderMap) -> Option<String> {
+    let (encoded, signed) = read(headers, SESSION_COOKIE)?.split_once('.')?;
+    let user = String::from_utf8(base64_decode(encoded)?).ok()?;
+    constant_time_eq(
+        session_signature(state, &user).as_bytes(),
+        signed.as_bytes(),
+    )
+    .then_some(user)
+}
+
+const ACCOUNT_COOKIE: &str = "iep_account";
+/// Facilitators may come back to their elections much later.
+const ACCOUNT_MAX_AGE_SECS: u64 = 400 * 24 * 60 * 60;
+
+fn account_signature(state: &ElectionDB, account: &str) -> String {
+    let data = format!("/account\n{}", account);
+    hex(&hmac_sha256(&state.cookie_secret[..], data.as_bytes()))
+}
+
+/// The value of a `Set-Cookie` header keeping the anonymous `account` of a
+/// browser.
+pub fn account_cookie(state: &ElectionDB, account: &str) -> String {
+    format!(
+        "{}={}.{}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+        ACCOUNT_COOKIE,
+        account,
+        account_signature(state, account),
+        ACCOUNT_MAX_AGE_SECS
+    )
+}
+
+/// The anonymous account of this browser, if the cookie is intact.
+pub fn account(state: &ElectionDB, headers: &HeaderMap) -> Option<String> {
+    let (account, signed) = read(headers, ACCOUNT_COOKIE)?.split_once('.')?;
+    constant_time_eq(
+        account_signature(state, account).as_bytes(),
+        signed.as_bytes(),
+    )
+    .then(|| account.to_string())
+}
+
+/// Who voted in election `id` from this browser, if the cookie is intact.
+pub fn voted_as(state: &ElectionDB, headers: &HeaderMap, id: &str) -> Option<String> {
+    let value = read(headers, &cookie_name(id))?;
+    let (encoded, signed) = value.split_once('.')?;
+    let voter_name = String::from_utf8(base64_decode(encoded)?).ok()?;
+    constant_time_eq(
+        signature(state, id, &voter_name).as_bytes(),
+        signed.as_bytes(),
+    )
+    .then_some(voter_name)
+}
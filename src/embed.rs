@@ -0,0 +1,107 @@
+//! A bare widget with the phase and the live tally of an election, for
+//! embedding in intranets or wikis with an `iframe`. It may be framed by any
+//! site and stays up to date with the update stream of its own.
+
+use crate::{tally_chart, ElectionDB};
+use axum::{
+    extract::{Path, State},
+    http::{header::CONTENT_SECURITY_POLICY, StatusCode},
+    response::IntoResponse,
+};
+use iep_core::{ElectionKind, ElectionPhase, ElectionProcess};
+use itertools::*;
+use maud::{html, Markup, DOCTYPE};
+
+const FRAMING: [(axum::http::HeaderName, &str); 1] =
+    [(CONTENT_SECURITY_POLICY, "frame-ancestors *")];
+
+fn embed_content(election: &ElectionProcess, voting_url: &str) -> Markup {
+    let tallied = election.kind == ElectionKind::StrawPoll
+        || matches!(
+            election.phase,
+            ElectionPhase::FirstTally
+                | ElectionPhase::SecondTally
+                | ElectionPhase::SafetyRound
+                | ElectionPhase::Completed
+        );
+    let elected = election.elected_seats();
+    html! {
+        p {
+            strong { (election.elected_role) }
+            " · "
+            @match election.closed_at {
+                Some(_) => "Closed",
+                None => (election.phase_title()),
+            }
+        }
+        @if !elected.is_empty() {
+            p { "Elected: " strong { (elected.iter().map(|e| &e.name).join(", ")) } }
+        }
+        @if tallied {
+            (tally_chart(election, election.current_round()))
+        } @else {
+            p {
+                (election.voted_count())
+                @if let Some(expected) = election.expected_voters {
+                    " of " (expected)
+                }
+                " voted so far"
+            }
+        }
+        @if election.voting_round().is_ok() && election.closed_at.is_none() {
+            p { small { a href=(voting_url) { "Vote" } } }
+        }
+    }
+}
+
+pub async fn view_embed(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let voting_url = format!("{}/election/{}/voting", &state.base_url, id);
+
+    let page = html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="UTF-8" {}
+                meta name="viewport" content="width=device-width, initial-scale=1" {}
+                // links leave the frame
+                base target="_blank" {}
+                script src="https://unpkg.com/htmx.org" {}
+                script src="https://unpkg.com/htmx.org/dist/ext/sse.js" {}
+                script src="/poll.js" defer {}
+                link
+                  rel="stylesheet"
+                  href="https://cdn.jsdelivr.net/npm/@picocss/pico@2/css/pico.min.css" {}
+                link rel="stylesheet" href="https://unpkg.com/charts.css/dist/charts.min.css" {}
+                link rel="stylesheet" href="/styles.css" {}
+                title { "IEP - " (election.elected_role) }
+            }
+            body {
+                main ."container-fluid" {
+                    div hx-ext="sse" sse-connect={"/election/" (id) "/stream"} {
+                        div
+                          hx-get={"/election/" (id) "/embed/content"}
+                          hx-trigger="sse:phase-changed,sse:votes-changed,sse:nominees-changed,sse:resync"
+                          hx-swap="innerHTML" {
+                            (embed_content(election, &voting_url))
+                        }
+                    }
+                }
+            }
+        }
+    };
+    Ok((FRAMING, page))
+}
+
+pub async fn get_embed_content(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let voting_url = format!("{}/election/{}/voting", &state.base_url, id);
+
+    Ok((FRAMING, embed_content(election, &voting_url)))
+}
@@ -0,0 +1,234 @@
+//! The secret key of the facilitator, so only they can open the evaluation
+//! page and change the election. The key is part of the link the creator is
+//! sent to and kept in a cookie from then on. Elections created without a key,
+//! like the demo election, stay open to everyone.
+//!
+//! The facilitator can invite co-facilitators, who get their own key, and the
+//! events of each change are marked with who of them made it.
+
+use crate::{cookies, crypto::constant_time_eq, ElectionDB};
+use axum::{
+    extract::{Path, Request, State},
+    http::{header::SET_COOKIE, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+    Form,
+};
+use iep_core::ElectionProcess;
+use itertools::*;
+use maud::{html, Markup};
+use serde::Deserialize;
+
+const KEY_LENGTH: usize = 24;
+
+tokio::task_local! {
+    /// The facilitator handling the current request.
+    static ACTING: String;
+}
+
+/// The facilitator handling the current request, if known.
+pub fn acting() -> Option<String> {
+    ACTING.try_with(|facilitator| facilitator.clone()).ok()
+}
+
+/// Runs `future` as handled by `facilitator`, for requests not going through
+/// the middleware.
+pub async fn acting_as<F: std::future::Future>(facilitator: String, future: F) -> F::Output {
+    ACTING.scope(facilitator, future).await
+}
+
+/// The name of the facilitator with `key`, if it is one of the election.
+pub fn facilitator_name(election: &ElectionProcess, key: &str) -> Option<String> {
+    let is_key = |other: &str| constant_time_eq(other.as_bytes(), key.as_bytes());
+    match election.facilitator_key.as_deref().is_some_and(is_key) {
+        true => Some("Facilitator".to_string()),
+        false => election
+            .co_facilitators
+            .iter()
+            .find(|(other, _)| is_key(other))
+            .map(|(_, name)| name.clone()),
+    }
+}
+
+/// The name of the facilitator whose browser sent `headers`, known from the
+/// cookie set when they gave their key.
+pub fn facilitator_of(election: &ElectionProcess, headers: &HeaderMap) -> Option<String> {
+    let cookie = cookies::facilitator_cookie_name(&election.id);
+    cookies::read(headers, &cookie).and_then(|key| facilitator_name(election, key))
+}
+
+/// Whether the browser that sent `headers` may see what the evaluation page
+/// shows of the election, like who voted; anyone may if it has no key.
+pub fn is_facilitating(state: &ElectionDB, headers: &HeaderMap, id: &str) -> bool {
+    state
+        .store
+        .get(id)
+        .is_some_and(|e| e.facilitator_key.is_none() || facilitator_of(&e, headers).is_some())
+}
+
+pub fn new_key(state: &ElectionDB) -> String {
+    state.random.alphanumeric(KEY_LENGTH)
+}
+
+/// The evaluation page with the key, for the creator to keep.
+pub fn eval_path(election: &ElectionProcess) -> String {
+    match &election.facilitator_key {
+        Some(key) => format!("/election/{}/eval?key={}", election.id, key),
+        None => format!("/election/{}/eval", election.id),
+    }
+}
+
+fn co_facilitator_path(election: &ElectionProcess, key: &str) -> String {
+    format!("/election/{}/eval?key={}", election.id, key)
+}
+
+/// Whether the path after `/election/:id/` is for the facilitator.
+pub fn is_facilitator_route(method: &Method, rest: &str) -> bool {
+    let segments = rest.split('/').collect::<Vec<_>>();
+    matches!(
+        (method == Method::POST, &segments[..]),
+        (
+            _,
+            ["eval"]
+                | ["eval", "content"]
+                | ["tokens", ..]
+                | ["export.csv"]
+                | ["export.json"]
+                | ["archive.html"]
+                | ["report.pdf"]
+        ) | (
+            true,
+            ["step", ..]
+                | ["complete"]
+                | ["schedule", ..]
+                | ["proposal"]
+                | ["tie-break", _]
+                | ["nominations", "merge"]
+                | ["objections"]
+                | ["objections", _, _]
+                | ["voting", "manual"]
+                | ["feedback", _]
+                | ["nominees", _, "photo"]
+                | ["co-facilitators"]
+                | ["co-facilitators", _, "revoke"]
+                | ["webhooks"]
+                | ["webhooks", "remove"]
+                | ["template"]
+        )
+    )
+}
+
+fn query_key(request: &Request) -> Option<&str> {
+    request
+        .uri()
+        .query()?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("key="))
+}
+
+/// Refuses the facilitator routes of an election without one of its keys,
+/// taken from the `key` query parameter or the cookie set when it was last
+/// given, and notes who of the facilitators handles the request.
+pub async fn require_facilitator(
+    State(state): State<ElectionDB>,
+    request: Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let Some((id, _)) = request
+        .uri()
+        .path()
+        .strip_prefix("/election/")
+        .and_then(|rest| rest.split_once('/'))
+        .filter(|(_, rest)| is_facilitator_route(request.method(), rest))
+    else {
+        return next.run(request).await;
+    };
+    let Some(election) = state.store.get(id).filter(|e| e.facilitator_key.is_some()) else {
+        return next.run(request).await;
+    };
+    if let Some(facilitator) = facilitator_of(&election, request.headers()) {
+        return ACTING.scope(facilitator, next.run(request)).await;
+    }
+    let Some((key, facilitator)) = query_key(&request)
+        .and_then(|key| Some((key.to_string(), facilitator_name(&election, key)?)))
+    else {
+        return (
+            StatusCode::FORBIDDEN,
+            "Only the facilitator can do this; open the link you got when creating the election",
+        )
+            .into_response();
+    };
+    let id = election.id;
+    let mut response = ACTING.scope(facilitator, next.run(request)).await;
+    if let Ok(cookie) = cookies::facilitator_cookie(&id, &key).parse() {
+        response.headers_mut().append(SET_COOKIE, cookie);
+    }
+    response
+}
+
+#[derive(Deserialize)]
+pub struct CoFacilitatorForm {
+    name: String,
+}
+
+/// Gives a co-facilitator named in the form their own key.
+pub async fn post_co_facilitator(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    Form(form): Form<CoFacilitatorForm>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let name = form.name.trim().to_string();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Co-facilitators need a name"));
+    }
+    let key = new_key(&state);
+    state.modify_election(&id, |election| {
+        election.invite_co_facilitator(key, name, state.clock.now());
+        Ok(())
+    })?;
+    Ok((StatusCode::CREATED, [("HX-Refresh", "true")]))
+}
+
+pub async fn post_revoke_co_facilitator(
+    Path((id, key)): Path<(String, String)>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    state.modify_election(&id, |election| {
+        election
+            .revoke_co_facilitator(&key, state.clock.now())
+            .then_some(())
+            .ok_or((StatusCode::NOT_FOUND, "Co-facilitator not found"))
+    })?;
+    Ok((StatusCode::ACCEPTED, [("HX-Refresh", "true")]))
+}
+
+/// The co-facilitators with their links, and the form to invite another.
+pub fn co_facilitators(election: &ElectionProcess) -> Markup {
+    html! {
+        details {
+            summary { "Co-facilitators" }
+            small { "Each of them gets a link that allows everything the facilitator can do." }
+            table {
+                tbody {
+                    @for (key, name) in election.co_facilitators.iter().sorted_by_key(|(_, n)| n.to_lowercase()) {
+                        tr {
+                            td { (name) }
+                            td { a href=(co_facilitator_path(election, key)) { "link" } }
+                            td {
+                                a ."secondary" href="#"
+                                  hx-post={"/election/" (election.id) "/co-facilitators/" (key) "/revoke"}
+                                  hx-trigger="click" hx-swap="none"
+                                  hx-confirm={"Revoke the link of " (name) "?"} {
+                                    "Revoke"
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            form hx-post={"/election/" (election.id) "/co-facilitators"} hx-swap="none" {
+                input type="text" name="name" placeholder="Name" required {}
+                button type="submit" ."secondary" { "Invite co-facilitator" }
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Optional feedback round after an election, to improve the next one.
+
+use crate::{process_error, ElectionDB, ElectionUpdate};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Form,
+};
+use iep_core::{ElectionProcess, MAX_RATING};
+use maud::{html, Markup};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct FeedbackForm {
+    rating: u8,
+    comment: Option<String>,
+}
+
+pub async fn post_feedback(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    Form(form): Form<FeedbackForm>,
+) -> Result<Markup, (StatusCode, &'static str)> {
+    state.modify_election(&id, |election| {
+        election
+            .add_feedback(form.rating, form.comment, state.clock.now())
+            .map_err(process_error)
+    })?;
+    state.notify(&id, ElectionUpdate::VotesChanged)?;
+
+    Ok(html! { p { "Thank you for your feedback!" } })
+}
+
+/// Opens or closes the feedback round.
+pub async fn post_feedback_action(
+    Path((id, action)): Path<(String, String)>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    state.modify_election(&id, |election| match &action[..] {
+        "open" => election
+            .open_feedback(state.clock.now())
+            .map_err(process_error),
+        "close" => {
+            election.close_feedback(state.clock.now());
+            Ok(())
+        }
+        _ => Err((StatusCode::BAD_REQUEST, "Invalid feedback action")),
+    })?;
+    state.notify(&id, ElectionUpdate::PhaseChanged)?;
+    Ok((StatusCode::ACCEPTED, [("HX-Refresh", "true")]))
+}
+
+pub fn feedback_form(election: &ElectionProcess) -> Markup {
+    html! {
+        article #"feedback" {
+            header { "How did this election go for you? Your feedback is anonymous." }
+            form hx-post={"/election/" (election.id) "/feedback"} hx-target="#feedback" {
+                fieldset {
+                    legend { "Rating" }
+                    @for rating in 1..=MAX_RATING {
+                        label {
+                            input type="radio" name="rating" value=(rating) required {}
+                            (rating)
+                        }
+                    }
+                }
+                label for="comment" {
+                    "Comment (optional): ";
+                    textarea name="comment" {}
+                }
+                button type="submit" { "Send feedback" }
+            }
+        }
+    }
+}
+
+/// Facilitator controls and a summary of the feedback received so far.
+pub fn feedback_summary(election: &ElectionProcess) -> Markup {
+    let action = if election.feedback_open {
+        "close"
+    } else {
+        "open"
+    };
+    html! {
+        br;
+        details open[!election.feedback.is_empty()] {
+            summary { "Feedback (" (election.feedback.len()) ")" }
+            @if let Some(average) = election.average_rating() {
+                p { "Average rating: " (format!("{:.1}", average)) " / " (MAX_RATING) }
+            }
+            ul {
+                @for comment in election.feedback.iter().filter_map(|f| f.comment.as_ref()) {
+                    li { (comment) }
+                }
+            }
+            button ."secondary outline"
+              hx-post={"/election/" (election.id) "/feedback/" (action)} hx-swap="none" {
+                @if election.feedback_open { "Close feedback round" } @else { "Open feedback round" }
+            }
+        }
+    }
+}
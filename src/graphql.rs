@@ -0,0 +1,684 @@
+//! A read-only GraphQL endpoint at `/api/graphql`, so reporting tools can
+//! fetch exactly the fields they need of many elections in one request.
+//!
+//! Only what reporting needs of GraphQL is implemented: one query per
+//! document with fields, aliases, arguments and variables; no fragments,
+//! directives, mutations or introspection. The schema:
+//!
+//! ```graphql
+//! type Query {
+//!   # needs the passcode or facilitator key as bearer token if it has a passcode
+//!   election(id: String!): Election
+//!   # needs the admin token as `?token=`
+//!   elections(phase: Phase, kind: ElectionKind, meeting: String): [Election!]!
+//! }
+//! type Election {
+//!   id: String!  kind: ElectionKind!  electedRole: String!  method: VotingMethod!
+//!   phase: Phase!  phaseTitle: String!  closed: Boolean!  meeting: String
+//!   createdAt: Int!  seats: Int!  expectedVoters: Int  voted: Int!
+//!   nominees: [Nominee!]!  rounds: [Round!]!  elected: [Elected!]!
+//!   phaseHistory: [PhaseTransition!]!
+//! }
+//! type Nominee { id: Int!  name: String!  group: String }
+//! type Round { number: Int!  ballots: Int!  votes: [VoteCount!]!  mostVotes: [String!]! }
+//! type VoteCount { nomineeId: Int!  name: String!  votes: Int! }
+//! type Elected { nomineeId: Int!  name: String!  at: Int! }
+//! type PhaseTransition { phase: Phase!  at: Int! }
+//! ```
+
+use crate::{api, passcode, ElectionDB};
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use iep_core::{ElectionKind, ElectionPhase, ElectionProcess};
+use itertools::*;
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+use serde_json::{json, Map, Value};
+use std::{iter::Peekable, str::Chars, str::FromStr};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Punct(char),
+    Spread,
+    Name(String),
+    Str(String),
+    Number(Value),
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = source.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() || c == ',' || c == '\u{feff}' => {
+                chars.next();
+            }
+            '#' => {
+                chars.by_ref().take_while(|c| *c != '\n').for_each(drop);
+            }
+            '!' | '$' | '(' | ')' | ':' | '=' | '@' | '[' | ']' | '{' | '}' | '|' => {
+                chars.next();
+                tokens.push(Token::Punct(c));
+            }
+            '.' => {
+                if chars.by_ref().take(3).collect::<String>() != "..." {
+                    return Err("Unexpected \".\"".to_string());
+                }
+                tokens.push(Token::Spread);
+            }
+            '"' => tokens.push(Token::Str(string(&mut chars)?)),
+            c if c == '_' || c.is_ascii_alphabetic() => {
+                let mut name = String::new();
+                while let Some(&c) = chars
+                    .peek()
+                    .filter(|c| **c == '_' || c.is_ascii_alphanumeric())
+                {
+                    name.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Name(name));
+            }
+            c if c == '-' || c.is_ascii_digit() => {
+                let mut number = String::new();
+                while let Some(&c) = chars
+                    .peek()
+                    .filter(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+                {
+                    number.push(c);
+                    chars.next();
+                }
+                let value = match number.parse::<i64>() {
+                    Ok(int) => json!(int),
+                    Err(_) => number
+                        .parse::<f64>()
+                        .ok()
+                        .and_then(serde_json::Number::from_f64)
+                        .map(Value::Number)
+                        .ok_or(format!("Invalid number {}", number))?,
+                };
+                tokens.push(Token::Number(value));
+            }
+            c => return Err(format!("Unexpected character {:?}", c)),
+        }
+    }
+    Ok(tokens)
+}
+
+fn string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    chars.next();
+    if chars.next_if_eq(&'"').is_some() {
+        if chars.next_if_eq(&'"').is_some() {
+            return Err("Block strings are not supported".to_string());
+        }
+        return Ok(String::new());
+    }
+    let mut string = String::new();
+    loop {
+        match chars.next() {
+            None | Some('\n') => return Err("Unterminated string".to_string()),
+            Some('"') => return Ok(string),
+            Some('\\') => string.push(match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some('b') => '\u{8}',
+                Some('f') => '\u{c}',
+                Some('u') => {
+                    let hex = chars.by_ref().take(4).collect::<String>();
+                    u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or(format!("Invalid escape \\u{}", hex))?
+                }
+                Some(c @ ('"' | '\\' | '/')) => c,
+                c => return Err(format!("Invalid escape {:?}", c)),
+            }),
+            Some(c) => string.push(c),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Field {
+    alias: Option<String>,
+    name: String,
+    arguments: Map<String, Value>,
+    selection: Vec<Field>,
+}
+
+impl Field {
+    fn key(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// Deepest nesting of selection sets, lists, objects and types; the parser
+/// recurses at every level.
+const MAX_DEPTH: usize = 32;
+/// Largest request body, far more than any reporting query needs.
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+struct Parser<'a> {
+    tokens: std::vec::IntoIter<Token>,
+    peeked: Option<Token>,
+    variables: &'a Map<String, Value>,
+    defaults: Map<String, Value>,
+    depth: usize,
+}
+
+/// The selection set of the only query in `source`.
+fn parse(source: &str, variables: &Map<String, Value>) -> Result<Vec<Field>, String> {
+    Parser {
+        tokens: tokenize(source)?.into_iter(),
+        peeked: None,
+        variables,
+        defaults: Map::new(),
+        depth: 0,
+    }
+    .document()
+}
+
+impl Parser<'_> {
+    /// Runs `parse` one level deeper, failing instead past [`MAX_DEPTH`].
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, String>,
+    ) -> Result<T, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err(format!(
+                "The query is nested deeper than {} levels",
+                MAX_DEPTH
+            ));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn peek(&mut self) -> Option<&Token> {
+        if self.peeked.is_none() {
+            self.peeked = self.tokens.next();
+        }
+        self.peeked.as_ref()
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        self.peek();
+        self.peeked
+            .take()
+            .ok_or("Unexpected end of the query".to_string())
+    }
+
+    fn eat(&mut self, punct: char) -> bool {
+        let found = self.peek() == Some(&Token::Punct(punct));
+        if found {
+            self.peeked = None;
+        }
+        found
+    }
+
+    fn expect(&mut self, punct: char) -> Result<(), String> {
+        match self.eat(punct) {
+            true => Ok(()),
+            false => Err(format!("Expected \"{}\"", punct)),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        match self.next()? {
+            Token::Name(name) => Ok(name),
+            token => Err(format!("Expected a name, found {:?}", token)),
+        }
+    }
+
+    /// The selection set of the only query of the document.
+    fn document(&mut self) -> Result<Vec<Field>, String> {
+        if let Some(Token::Name(keyword)) = self.peek().cloned() {
+            self.next()?;
+            match keyword.as_str() {
+                "query" => {}
+                "mutation" | "subscription" => return Err("Only queries are supported".to_string()),
+                "fragment" => return Err("Fragments are not supported".to_string()),
+                _ => return Err(format!("Unexpected {}", keyword)),
+            }
+            if let Some(Token::Name(_)) = self.peek() {
+                self.next()?;
+            }
+            if self.eat('(') {
+                while !self.eat(')') {
+                    self.variable_definition()?;
+                }
+            }
+        }
+        let selection = self.nested(|p| p.selection_set())?;
+        match self.peek() {
+            None => Ok(selection),
+            Some(_) => Err("Only one operation per document is supported".to_string()),
+        }
+    }
+
+    fn variable_definition(&mut self) -> Result<(), String> {
+        self.expect('$')?;
+        let name = self.name()?;
+        self.expect(':')?;
+        self.skip_type()?;
+        if self.eat('=') {
+            let default = self.value()?;
+            self.defaults.insert(name, default);
+        }
+        Ok(())
+    }
+
+    fn skip_type(&mut self) -> Result<(), String> {
+        if self.eat('[') {
+            self.nested(|p| p.skip_type())?;
+            self.expect(']')?;
+        } else {
+            self.name()?;
+        }
+        self.eat('!');
+        Ok(())
+    }
+
+    fn selection_set(&mut self) -> Result<Vec<Field>, String> {
+        self.expect('{')?;
+        let mut fields = vec![];
+        while !self.eat('}') {
+            match self.peek() {
+                Some(Token::Spread) => return Err("Fragments are not supported".to_string()),
+                _ => fields.push(self.field()?),
+            }
+        }
+        Ok(fields)
+    }
+
+    fn field(&mut self) -> Result<Field, String> {
+        let mut name = self.name()?;
+        let mut alias = None;
+        if self.eat(':') {
+            alias = Some(name);
+            name = self.name()?;
+        }
+        let mut arguments = Map::new();
+        if self.eat('(') {
+            while !self.eat(')') {
+                let argument = self.name()?;
+                self.expect(':')?;
+                let value = self.value()?;
+                arguments.insert(argument, value);
+            }
+        }
+        if self.peek() == Some(&Token::Punct('@')) {
+            return Err("Directives are not supported".to_string());
+        }
+        let selection = match self.peek() {
+            Some(Token::Punct('{')) => self.nested(|p| p.selection_set())?,
+            _ => vec![],
+        };
+        Ok(Field {
+            alias,
+            name,
+            arguments,
+            selection,
+        })
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        Ok(match self.next()? {
+            Token::Punct('$') => {
+                let name = self.name()?;
+                self.variables
+                    .get(&name)
+                    .or(self.defaults.get(&name))
+                    .cloned()
+                    .unwrap_or(Value::Null)
+            }
+            Token::Punct('[') => {
+                let mut list = vec![];
+                while !self.eat(']') {
+                    list.push(self.nested(|p| p.value())?);
+                }
+                Value::Array(list)
+            }
+            Token::Punct('{') => {
+                let mut object = Map::new();
+                while !self.eat('}') {
+                    let name = self.name()?;
+                    self.expect(':')?;
+                    object.insert(name, self.nested(|p| p.value())?);
+                }
+                Value::Object(object)
+            }
+            Token::Str(string) => Value::String(string),
+            Token::Number(number) => number,
+            Token::Name(name) => match name.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" => Value::Null,
+                // enum values, e.g. `phase: FirstVote`
+                _ => Value::String(name),
+            },
+            token => return Err(format!("Expected a value, found {:?}", token)),
+        })
+    }
+}
+
+/// A result keeping the fields in the order they were asked for.
+enum Data {
+    Value(Value),
+    List(Vec<Data>),
+    Object(Vec<(String, Data)>),
+}
+
+impl Serialize for Data {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Data::Value(value) => value.serialize(serializer),
+            Data::List(list) => serializer.collect_seq(list),
+            Data::Object(fields) => {
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (key, value) in fields {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+fn election_object(election: &ElectionProcess) -> Value {
+    let name = |id: &u64| election.nominee_name(*id).to_string();
+    let rounds = election
+        .numbered_rounds()
+        .into_iter()
+        .map(|(number, round)| {
+            let votes = round.values().counts_by(|b| b.vote);
+            let max_votes = votes.values().max().copied().unwrap_or(0);
+            json!({
+                "__typename": "Round",
+                "number": number,
+                "ballots": round.len(),
+                "votes": votes
+                    .iter()
+                    .sorted_by_key(|(id, votes)| (std::cmp::Reverse(**votes), name(id)))
+                    .map(|(id, votes)| json!({
+                        "__typename": "VoteCount",
+                        "nomineeId": id,
+                        "name": name(id),
+                        "votes": votes,
+                    }))
+                    .collect::<Vec<_>>(),
+                "mostVotes": votes
+                    .iter()
+                    .filter(|(_, v)| **v == max_votes)
+                    .map(|(id, _)| name(id))
+                    .sorted()
+                    .collect::<Vec<_>>(),
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "__typename": "Election",
+        "id": election.id,
+        "kind": election.kind,
+        "electedRole": election.elected_role,
+        "method": election.method,
+        "phase": election.phase,
+        "phaseTitle": election.phase_title(),
+        "closed": election.closed_at.is_some(),
+        "meeting": election.meeting,
+        "createdAt": election.phase_history.first().map_or(0, |t| t.at),
+        "seats": election.seats,
+        "expectedVoters": election.expected_voters,
+        "voted": election.voted_count(),
+        "nominees": election
+            .nominees
+            .iter()
+            .sorted()
+            .map(|(id, nominee)| json!({
+                "__typename": "Nominee",
+                "id": id,
+                "name": nominee,
+                "group": election.nominee_groups.get(id),
+            }))
+            .collect::<Vec<_>>(),
+        "rounds": rounds,
+        "elected": election
+            .elected_seats()
+            .into_iter()
+            .map(|e| json!({
+                "__typename": "Elected",
+                "nomineeId": e.nominee_id,
+                "name": e.name,
+                "at": e.at,
+            }))
+            .collect::<Vec<_>>(),
+        "phaseHistory": election
+            .phase_history
+            .iter()
+            .map(|t| json!({ "__typename": "PhaseTransition", "phase": t.phase, "at": t.at }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// Picks the selected fields out of `value`.
+fn complete(field: &Field, value: &Value) -> Result<Data, String> {
+    match value {
+        Value::Array(list) => Ok(Data::List(
+            list.iter()
+                .map(|v| complete(field, v))
+                .collect::<Result<_, _>>()?,
+        )),
+        Value::Object(object) => {
+            let typename = object["__typename"].as_str().unwrap_or_default();
+            if field.selection.is_empty() {
+                return Err(format!(
+                    "Field \"{}\" of type {} needs a selection of subfields",
+                    field.name, typename
+                ));
+            }
+            let fields = field
+                .selection
+                .iter()
+                .map(|f| {
+                    if let Some(argument) = f.arguments.keys().next() {
+                        return Err(format!(
+                            "Unknown argument \"{}\" of \"{}\"",
+                            argument, f.name
+                        ));
+                    }
+                    let value = object.get(&f.name).ok_or(format!(
+                        "Cannot query field \"{}\" on type {}",
+                        f.name, typename
+                    ))?;
+                    Ok((f.key().to_string(), complete(f, value)?))
+                })
+                .collect::<Result<_, String>>()?;
+            Ok(Data::Object(fields))
+        }
+        _ if !field.selection.is_empty() => {
+            Err(format!("Field \"{}\" has no subfields", field.name))
+        }
+        scalar => Ok(Data::Value(scalar.clone())),
+    }
+}
+
+fn argument<T: FromStr>(field: &Field, name: &str) -> Result<Option<T>, String> {
+    match field.arguments.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => T::from_str(value)
+            .map(Some)
+            .map_err(|_| format!("Invalid {} {:?}", name, value)),
+        Some(value) => Err(format!("Invalid {} {}", name, value)),
+    }
+}
+
+/// Resolves a field of the query, with the admin token and the bearer token
+/// of the request.
+fn root_field(
+    state: &ElectionDB,
+    admin: Option<&str>,
+    key: Option<&str>,
+    field: &Field,
+) -> Result<Data, String> {
+    match field.name.as_str() {
+        "__typename" => Ok(Data::Value(json!("Query"))),
+        "election" => {
+            let id = argument::<String>(field, "id")?.ok_or("Argument \"id\" is required")?;
+            match state.store.get(&id) {
+                Some(election)
+                    if !passcode::allows_key(&election, key)
+                        && state.check_admin(admin).is_err() =>
+                {
+                    Err(
+                        "This election needs its passcode or the facilitator key as bearer token"
+                            .to_string(),
+                    )
+                }
+                Some(election) => complete(field, &election_object(&election)),
+                None => Ok(Data::Value(Value::Null)),
+            }
+        }
+        "elections" => {
+            state
+                .check_admin(admin)
+                .map_err(|(_, message)| format!("{}: \"elections\" needs ?token=", message))?;
+            let phase = argument::<ElectionPhase>(field, "phase")?;
+            let kind = argument::<ElectionKind>(field, "kind")?;
+            let meeting = argument::<String>(field, "meeting")?;
+            let elections = state
+                .store
+                .list()
+                .iter()
+                .filter(|e| phase.is_none_or(|p| e.phase == p))
+                .filter(|e| kind.is_none_or(|k| e.kind == k))
+                .filter(|e| meeting.is_none() || e.meeting == meeting)
+                .sorted_by_key(|e| (e.phase_history.first().map_or(0, |t| t.at), e.id.clone()))
+                .map(election_object)
+                .collect();
+            complete(field, &Value::Array(elections))
+        }
+        name => Err(format!("Cannot query field \"{}\" on type Query", name)),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GraphQlRequest {
+    query: String,
+    #[serde(default)]
+    variables: Option<Map<String, Value>>,
+}
+
+#[derive(Deserialize)]
+pub struct GraphQlQuery {
+    token: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct GraphQlResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Data>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<Value>,
+}
+
+fn request_error(status: StatusCode, message: String) -> (StatusCode, Json<GraphQlResponse>) {
+    let errors = vec![json!({ "message": message })];
+    (status, Json(GraphQlResponse { data: None, errors }))
+}
+
+pub async fn post_graphql(
+    Query(query): Query<GraphQlQuery>,
+    State(state): State<ElectionDB>,
+    headers: HeaderMap,
+    body: Body,
+) -> (StatusCode, Json<GraphQlResponse>) {
+    let Ok(body) = axum::body::to_bytes(body, MAX_BODY_BYTES).await else {
+        return request_error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("The request is larger than {} bytes", MAX_BODY_BYTES),
+        );
+    };
+    let request = match serde_json::from_slice::<GraphQlRequest>(&body) {
+        Ok(request) => request,
+        Err(error) => return request_error(StatusCode::BAD_REQUEST, error.to_string()),
+    };
+    let variables = request.variables.unwrap_or_default();
+    let selection = match parse(&request.query, &variables) {
+        Ok(selection) => selection,
+        Err(message) => return request_error(StatusCode::BAD_REQUEST, message),
+    };
+
+    let mut errors = vec![];
+    let data = selection
+        .iter()
+        .map(|field| {
+            let key = api::bearer(&headers);
+            let value =
+                root_field(&state, query.token.as_deref(), key, field).unwrap_or_else(|message| {
+                    errors.push(json!({ "message": message, "path": [field.key()] }));
+                    Data::Value(Value::Null)
+                });
+            (field.key().to_string(), value)
+        })
+        .collect();
+    (
+        StatusCode::OK,
+        Json(GraphQlResponse {
+            data: Some(Data::Object(data)),
+            errors,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_aliases_arguments_and_variables() {
+        let variables = json!({ "id": "abc" }).as_object().unwrap().clone();
+        let selection = parse(
+            "query Q($id: String!, $n: [Int!] = [1]) { e: election(id: $id) { id rounds { votes { name } } } }",
+            &variables,
+        )
+        .unwrap();
+        assert_eq!(selection.len(), 1);
+        assert_eq!(selection[0].key(), "e");
+        assert_eq!(selection[0].name, "election");
+        assert_eq!(selection[0].arguments["id"], json!("abc"));
+        assert_eq!(
+            selection[0].selection[1].selection[0].selection[0].name,
+            "name"
+        );
+    }
+
+    #[test]
+    fn rejects_what_is_not_supported() {
+        let variables = Map::new();
+        assert!(parse("mutation { a }", &variables).is_err());
+        assert!(parse("{ ...F }", &variables).is_err());
+        assert!(parse("{ a } { b }", &variables).is_err());
+        assert!(parse("{ a(s: \"open) }", &variables).is_err());
+    }
+
+    #[test]
+    fn limits_the_nesting() {
+        let variables = Map::new();
+        let nested = |depth: usize| format!("{}{}", "{a".repeat(depth), "}".repeat(depth));
+        assert!(parse(&nested(MAX_DEPTH), &variables).is_ok());
+        assert!(parse(&nested(MAX_DEPTH + 1), &variables).is_err());
+        // would overflow the stack without the limit
+        assert!(parse(&nested(200_000), &variables).is_err());
+        let list = format!(
+            "{{ a(x: {}1{}) }}",
+            "[".repeat(100_000),
+            "]".repeat(100_000)
+        );
+        assert!(parse(&list, &variables).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+//! A tiny HTTP/1.1 client for outgoing notifications and integrations, over
+//! plain `http://` or `https://` with the Mozilla root certificates.
+
+use std::{sync::Arc, sync::OnceLock, time::Duration};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+};
+use tokio_rustls::{
+    rustls::{pki_types::ServerName, ClientConfig, RootCertStore},
+    TlsConnector,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// POSTs `body` to `url` and returns the response body on a 2xx status.
+pub async fn post(
+    url: &str,
+    content_type: &str,
+    headers: &[(&str, String)],
+    body: &[u8],
+) -> Result<String, String> {
+    let mut headers = headers.to_vec();
+    headers.push(("Content-Type", content_type.to_string()));
+    request("POST", url, &headers, body).await
+}
+
+pub async fn post_json(url: &str, body: &serde_json::Value) -> Result<String, String> {
+    post(url, "application/json", &[], body.to_string().as_bytes()).await
+}
+
+/// Sends a request with any method and returns the response body on a 2xx
+/// status.
+pub async fn request(
+    method: &str,
+    url: &str,
+    headers: &[(&str, String)],
+    body: &[u8],
+) -> Result<String, String> {
+    tokio::time::timeout(TIMEOUT, request_inner(method, url, headers, body))
+        .await
+        .map_err(|_| format!("Request to {} timed out", url))?
+}
+
+/// Whether `url` is an `https://` URL.
+pub fn is_https(url: &str) -> bool {
+    url.starts_with("https://")
+}
+
+/// Splits an `http://` or `https://` URL into its authority and path.
+pub fn split_url(url: &str) -> Result<(&str, &str), String> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .ok_or_else(|| format!("Only http:// and https:// URLs are supported, got {}", url))?;
+    Ok(match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    })
+}
+
+/// The host and port to connect to for the authority of a URL.
+fn host_and_port(authority: &str, https: bool) -> (&str, u16) {
+    let default = if https { 443 } else { 80 };
+    match authority.rsplit_once(':') {
+        // not the end of an IPv6 address like `[::1]`
+        Some((host, port)) if !port.contains(']') => (host, port.parse().unwrap_or(default)),
+        _ => (authority, default),
+    }
+}
+
+fn tls_connector() -> TlsConnector {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    let config = CONFIG.get_or_init(|| {
+        let roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        Arc::new(
+            ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        )
+    });
+    TlsConnector::from(config.clone())
+}
+
+async fn request_inner(
+    method: &str,
+    url: &str,
+    headers: &[(&str, String)],
+    body: &[u8],
+) -> Result<String, String> {
+    let (authority, path) = split_url(url)?;
+    let https = is_https(url);
+    let (host, port) = host_and_port(authority, https);
+
+    let stream = TcpStream::connect((host.trim_matches(['[', ']']), port))
+        .await
+        .map_err(|e| format!("Could not connect to {}: {}", authority, e))?;
+    let mut request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        method,
+        path,
+        authority,
+        body.len()
+    );
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+
+    let response = match https {
+        true => {
+            let name = ServerName::try_from(host.trim_matches(['[', ']']).to_string())
+                .map_err(|e| format!("Invalid host {}: {}", host, e))?;
+            let stream = tls_connector()
+                .connect(name, stream)
+                .await
+                .map_err(|e| format!("TLS with {} failed: {}", authority, e))?;
+            exchange(stream, url, request.as_bytes(), body).await?
+        }
+        false => exchange(stream, url, request.as_bytes(), body).await?,
+    };
+    parse_response(url, &response)
+}
+
+/// Sends the request and reads the whole response.
+async fn exchange(
+    mut stream: impl AsyncRead + AsyncWrite + Unpin,
+    url: &str,
+    head: &[u8],
+    body: &[u8],
+) -> Result<Vec<u8>, String> {
+    stream
+        .write_all(head)
+        .await
+        .and(stream.write_all(body).await)
+        .and(stream.flush().await)
+        .map_err(|e| format!("Could not send request to {}: {}", url, e))?;
+    let mut response = Vec::new();
+    match stream.read_to_end(&mut response).await {
+        Ok(_) => Ok(response),
+        // servers often close TLS connections without a close_notify
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && !response.is_empty() => {
+            Ok(response)
+        }
+        Err(e) => Err(format!("Could not read response from {}: {}", url, e)),
+    }
+}
+
+fn parse_response(url: &str, response: &[u8]) -> Result<String, String> {
+    let response = String::from_utf8_lossy(response);
+    let status = response
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or_else(|| format!("Invalid response from {}", url))?;
+    if !(200..300).contains(&status) {
+        return Err(format!("{} answered with status {}", url, status));
+    }
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let chunked = head.lines().any(|l| {
+        l.split_once(':').is_some_and(|(k, v)| {
+            k.trim().eq_ignore_ascii_case("transfer-encoding") && v.trim() == "chunked"
+        })
+    });
+    match chunked {
+        true => dechunk(body).ok_or_else(|| format!("Invalid chunked response from {}", url)),
+        false => Ok(body.to_string()),
+    }
+}
+
+fn dechunk(mut body: &str) -> Option<String> {
+    let mut decoded = String::new();
+    loop {
+        let (size, rest) = body.split_once("\r\n")?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        if size == 0 {
+            return Some(decoded);
+        }
+        decoded.push_str(rest.get(..size)?);
+        body = rest.get(size..)?.strip_prefix("\r\n")?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_urls() {
+        assert_eq!(split_url("http://a:8080/x?y"), Ok(("a:8080", "/x?y")));
+        assert_eq!(
+            split_url("https://hooks.slack.com"),
+            Ok(("hooks.slack.com", "/"))
+        );
+        assert!(split_url("ftp://a/").is_err());
+        assert_eq!(host_and_port("a:8080", false), ("a", 8080));
+        assert_eq!(host_and_port("a", true), ("a", 443));
+        assert_eq!(host_and_port("[::1]", false), ("[::1]", 80));
+        assert_eq!(host_and_port("[::1]:81", false), ("[::1]", 81));
+    }
+
+    #[test]
+    fn reads_responses() {
+        let plain = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        assert_eq!(parse_response("u", plain), Ok("ok".to_string()));
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n3\r\n!!!\r\n0\r\n\r\n";
+        assert_eq!(parse_response("u", chunked), Ok("ok!!!".to_string()));
+        assert!(parse_response("u", b"HTTP/1.1 500 Oops\r\n\r\n").is_err());
+    }
+
+    #[tokio::test]
+    async fn talks_to_plain_servers() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 1024];
+            let read = socket.read(&mut request).await.unwrap();
+            assert!(String::from_utf8_lossy(&request[..read]).starts_with("POST /hook HTTP/1.1"));
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\ndone")
+                .await
+                .unwrap();
+        });
+        let url = format!("http://{}/hook", address);
+        assert_eq!(
+            post_json(&url, &serde_json::json!({})).await,
+            Ok("done".to_string())
+        );
+    }
+}
@@ -0,0 +1,56 @@
+//! Generation of election ids, either random characters or words that are
+//! easier to read out in the room.
+
+use iep_core::Random;
+use std::str::FromStr;
+
+const ADJECTIVES: &[&str] = &[
+    "amber", "bold", "brave", "bright", "calm", "clever", "cosy", "crisp", "eager", "fair",
+    "gentle", "golden", "happy", "jolly", "keen", "kind", "lively", "lucky", "merry", "mild",
+    "noble", "proud", "quick", "quiet", "rapid", "shy", "silver", "sunny", "swift", "tidy", "warm",
+    "wise",
+];
+
+const ANIMALS: &[&str] = &[
+    "badger", "bear", "beaver", "bison", "crane", "deer", "dolphin", "eagle", "falcon", "finch",
+    "fox", "gecko", "hare", "heron", "ibis", "koala", "lark", "lemur", "lynx", "marten", "moose",
+    "newt", "otter", "owl", "panda", "puffin", "raven", "robin", "seal", "swan", "tiger", "wolf",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdStyle {
+    /// Lowercase letters and digits of the given length.
+    Random(usize),
+    /// Like `blue-otter-42`.
+    Words,
+}
+
+impl FromStr for IdStyle {
+    type Err = String;
+
+    fn from_str(style: &str) -> Result<Self, Self::Err> {
+        match style {
+            "random" => Ok(IdStyle::Random(5)),
+            "words" => Ok(IdStyle::Words),
+            _ => Err(format!("Unknown id style {}", style)),
+        }
+    }
+}
+
+impl IdStyle {
+    /// A candidate id; `widen` grows the number of possible ids, for when
+    /// earlier candidates were taken.
+    pub fn generate(&self, random: &dyn Random, widen: u32) -> String {
+        match self {
+            IdStyle::Random(length) => random
+                .alphanumeric(length + widen as usize)
+                .to_ascii_lowercase(),
+            IdStyle::Words => format!(
+                "{}-{}-{}",
+                ADJECTIVES[random.below(ADJECTIVES.len())],
+                ANIMALS[random.below(ANIMALS.len())],
+                random.below(100usize.saturating_mul(10usize.saturating_pow(widen)))
+            ),
+        }
+    }
+}
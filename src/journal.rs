@@ -0,0 +1,236 @@
+//! An append-only journal of every change to an election, one file of JSON
+//! lines per election in `DATA_DIR/journal`, that is written next to any
+//! store. Each vote and phase change is appended as its event as soon as it
+//! happens, so elections can be rebuilt after a crash even if the store did
+//! not get to save them.
+//!
+//! Changes that are not just new events, such as undoing one or editing the
+//! nominees, rewrite the file with the whole election, which also keeps the
+//! files short.
+
+use crate::{store::ElectionStore, ElectionUpdate};
+use iep_core::{Circle, ElectionProcess, RecordedEvent};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[derive(Serialize, Deserialize)]
+enum Entry {
+    /// The whole election, replacing everything before it.
+    State(Box<ElectionProcess>),
+    Event(Box<RecordedEvent>),
+}
+
+pub struct JournalStore {
+    dir: PathBuf,
+    inner: Arc<dyn ElectionStore>,
+}
+
+impl JournalStore {
+    /// Journals the changes to the elections in `inner`, first restoring the
+    /// elections whose journal is ahead of what `inner` has.
+    pub fn open(data_dir: &Path, inner: Arc<dyn ElectionStore>) -> std::io::Result<Self> {
+        let store = JournalStore {
+            dir: data_dir.join("journal"),
+            inner,
+        };
+        std::fs::create_dir_all(&store.dir)?;
+        let mut recovered = 0;
+        for entry in std::fs::read_dir(&store.dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|e| e != "ndjson") {
+                continue;
+            }
+            let Some(election) = replay(&path)? else {
+                continue;
+            };
+            if store.inner.get(&election.id).as_ref() != Some(&election) {
+                store.inner.insert(election);
+                recovered += 1;
+            }
+        }
+        if recovered > 0 {
+            tracing::info!("Recovered {} elections from the journal", recovered);
+        }
+        Ok(store)
+    }
+
+    fn file(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.ndjson", id))
+    }
+
+    fn write(&self, id: &str, entries: &[Entry], append: bool) {
+        let path = self.file(id);
+        let written = OpenOptions::new()
+            .create(true)
+            .append(append)
+            .write(true)
+            .truncate(!append)
+            .open(&path)
+            .and_then(|mut file| {
+                for entry in entries {
+                    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+                }
+                file.sync_data()
+            });
+        if let Err(error) = written {
+            tracing::error!("Writing to {} failed: {}", path.display(), error);
+        }
+    }
+}
+
+/// Rebuilds the election from its journal, skipping a torn last line.
+fn replay(path: &Path) -> std::io::Result<Option<ElectionProcess>> {
+    let mut election: Option<ElectionProcess> = None;
+    for (number, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        match serde_json::from_str(&line?) {
+            Ok(Entry::State(state)) => election = Some(*state),
+            Ok(Entry::Event(event)) => match election.as_mut() {
+                Some(election) => election.apply_recorded(*event),
+                None => tracing::warn!("Skipping event before any state in {}", path.display()),
+            },
+            Err(error) => {
+                tracing::warn!(
+                    "Skipping line {} of {}: {}",
+                    number + 1,
+                    path.display(),
+                    error
+                )
+            }
+        }
+    }
+    Ok(election)
+}
+
+impl ElectionStore for JournalStore {
+    fn get(&self, id: &str) -> Option<ElectionProcess> {
+        self.inner.get(id)
+    }
+
+    fn list(&self) -> Vec<ElectionProcess> {
+        self.inner.list()
+    }
+
+    fn insert(&self, election: ElectionProcess) {
+        self.write(
+            &election.id,
+            &[Entry::State(Box::new(election.clone()))],
+            false,
+        );
+        self.inner.insert(election)
+    }
+
+    fn modify(&self, id: &str, change: &mut dyn FnMut(&mut ElectionProcess)) -> bool {
+        let mut changed = None;
+        let found = self.inner.modify(id, &mut |election| {
+            let before = election.clone();
+            change(election);
+            changed = Some((before, election.clone()));
+        });
+        let Some((before, after)) = changed.filter(|_| found) else {
+            return found;
+        };
+        let events = after
+            .events
+            .strip_prefix(&before.events[..])
+            .unwrap_or_default();
+        // only new events are cheap to journal, anything else needs the state
+        let mut replayed = before;
+        for event in events {
+            replayed.apply_recorded(event.clone());
+        }
+        match replayed == after {
+            true if events.is_empty() => {}
+            true => {
+                let entries = events
+                    .iter()
+                    .map(|e| Entry::Event(Box::new(e.clone())))
+                    .collect::<Vec<_>>();
+                self.write(id, &entries, true)
+            }
+            false => self.write(id, &[Entry::State(Box::new(after))], false),
+        }
+        found
+    }
+
+    fn delete(&self, id: &str) -> Option<ElectionProcess> {
+        let deleted = self.inner.delete(id);
+        match std::fs::remove_file(self.file(id)) {
+            Err(error) if error.kind() != ErrorKind::NotFound => {
+                tracing::error!("Deleting the journal of {} failed: {}", id, error)
+            }
+            _ => {}
+        }
+        deleted
+    }
+
+    fn contains(&self, id: &str) -> bool {
+        self.inner.contains(id)
+    }
+
+    fn publish(&self, id: &str, update: ElectionUpdate) {
+        self.inner.publish(id, update)
+    }
+
+    fn save_circles(&self, circles: &HashMap<String, Circle>) {
+        self.inner.save_circles(circles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+    use iep_core::{Ballot, SeededRandom};
+
+    #[test]
+    fn rebuilds_elections_from_the_journal() {
+        let dir = std::env::temp_dir().join(format!("ieptool-journal-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = JournalStore::open(&dir, Arc::new(MemoryStore::default())).unwrap();
+        store.insert(ElectionProcess::new_and_cleaned(
+            "abc".to_string(),
+            "Lead".to_string(),
+            "Ann\nBob",
+            0,
+        ));
+        let random = SeededRandom::new(1);
+        for (voter, vote) in [("v1", 0), ("v2", 1)] {
+            store.modify("abc", &mut |e| {
+                let ballot = Ballot::new(vote, &random);
+                e.add_vote(voter.to_string(), ballot, None, 1).unwrap();
+            });
+        }
+        let lines = |store: &JournalStore| {
+            let journal = std::fs::read_to_string(store.file("abc")).unwrap();
+            journal.lines().map(str::to_string).collect::<Vec<_>>()
+        };
+        // the state it was created with, then one line per vote
+        let journaled = lines(&store);
+        assert_eq!(journaled.len(), 3);
+        assert!(journaled[0].starts_with(r#"{"State""#));
+        assert!(journaled[1].starts_with(r#"{"Event""#));
+
+        // an undo rewrites the journal with the state it left
+        store.modify("abc", &mut |e| {
+            e.undo().unwrap();
+        });
+        assert_eq!(lines(&store).len(), 1);
+        store.modify("abc", &mut |e| e.step_next(2));
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(store.file("abc"))
+            .unwrap();
+        write!(file, r#"{{"Event":{{"at":3,"#).unwrap();
+
+        let recovered = JournalStore::open(&dir, Arc::new(MemoryStore::default())).unwrap();
+        let election = recovered.get("abc").unwrap();
+        assert_eq!(Some(election), store.get("abc"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+//! Voting on a single shared device that is handed from voter to voter.
+//!
+//! Everything happens on one page through htmx swaps, so the history holds
+//! no earlier ballot, and after each vote the page falls back to an empty form.
+
+use crate::{
+    base_html, cast_vote, nominee_options, photos, reason_input, roster_datalist, roster_list,
+    ElectionDB, PhaseDescription, Vote,
+};
+use axum::{
+    extract::{Path, State},
+    http::{header::CACHE_CONTROL, StatusCode},
+    response::IntoResponse,
+    Form,
+};
+use iep_core::ElectionProcess;
+use maud::{html, Markup};
+
+/// Seconds the confirmation stays visible before the form comes back.
+const RESET_SECS: u32 = 8;
+
+pub async fn view_kiosk(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+
+    let page = base_html(
+        election.elected_role.as_str(),
+        html! {(election.elected_role.as_str())},
+        html! {
+            div hx-ext="sse" sse-connect={"/election/" (id) "/stream"} hx-history="false" {
+              div #"kiosk-content"
+                hx-get={"/election/" (id) "/kiosk/form"}
+                hx-trigger="sse:phase-changed,sse:resync"
+                hx-swap="innerHTML" {
+                  (kiosk_form(election))
+              }
+            }
+        },
+        html!(strong { (id) }),
+    );
+    Ok(([(CACHE_CONTROL, "no-store")], page))
+}
+
+pub async fn get_kiosk_form(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<Markup, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(kiosk_form(election))
+}
+
+pub async fn post_kiosk_vote(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    Form(form): Form<Vote>,
+) -> Result<Markup, (StatusCode, &'static str)> {
+    let (message, receipt) = cast_vote(&state, &id, form)?;
+
+    Ok(html! {
+        article {
+            p { (message) }
+            p { "Your receipt: " code { (receipt) } }
+            p { "Please hand the device to the next voter." }
+            button
+              hx-get={"/election/" (id) "/kiosk/form"}
+              hx-target="#kiosk-content"
+              style="left: 50%; position: relative; translate: -50%;" {
+                "Next voter"
+            }
+        }
+        div hx-get={"/election/" (id) "/kiosk/form"}
+          hx-trigger={"load delay:" (RESET_SECS) "s"}
+          hx-target="#kiosk-content" {}
+    })
+}
+
+fn kiosk_form(election: &ElectionProcess) -> Markup {
+    if !election.phase.is_voting() {
+        return html! {
+            h2 { (election.phase_title()) }
+            p { "Voting is closed. This page opens again for the next voting round." }
+        };
+    }
+    html! {
+        h2 { (election.phase_title()) }
+        p { (election.phase.nice_description()) }
+        (photos::photo_gallery(election))
+        form ."table rows" autocomplete="off"
+          hx-post={"/election/" (election.id) "/kiosk"}
+          hx-target="#kiosk-content" {
+            (roster_datalist(election))
+            label for="voter_name" {
+                "Voter Name: ";
+                input type="text" name="voter_name" required autofocus list=[roster_list(election)] {}
+            }
+            label for="vote" {
+                "Vote :";
+                select name="vote" required {
+                    option value="" selected disabled { "Choose a nominee" }
+                    (nominee_options(election))
+                }
+            }
+            (reason_input())
+            button type="submit" style="left: 50%; position: relative; translate: -50%;" {
+                "Vote!"
+            }
+        }
+    }
+}
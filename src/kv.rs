@@ -0,0 +1,232 @@
+//! An embedded key-value store keeping all elections and circles in a single
+//! append-only file, for small installs that want persistence without running
+//! a database.
+//!
+//! Every change appends one JSON line with the new value of its key. On open
+//! the lines are replayed, later ones winning, and the file is compacted to a
+//! single line per key. A torn last line from a crash is skipped.
+
+use crate::store::{ElectionStore, MemoryStore};
+use iep_core::{Circle, ElectionProcess};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+/// The log is compacted once it holds this many times more lines than keys.
+const COMPACT_RATIO: usize = 4;
+const MIN_COMPACT_LINES: usize = 1000;
+
+#[derive(Serialize, Deserialize)]
+enum Record {
+    /// `None` marks a deleted election.
+    Election(String, Option<Box<ElectionProcess>>),
+    Circles(HashMap<String, Circle>),
+}
+
+struct Log {
+    file: File,
+    lines: usize,
+}
+
+pub struct KvStore {
+    path: PathBuf,
+    memory: MemoryStore,
+    circles: Mutex<HashMap<String, Circle>>,
+    log: Mutex<Log>,
+}
+
+impl KvStore {
+    pub fn open(data_dir: &Path) -> std::io::Result<Self> {
+        std::fs::create_dir_all(data_dir)?;
+        let path = data_dir.join("ieptool.db");
+        let memory = MemoryStore::default();
+        let mut circles = HashMap::new();
+        match File::open(&path) {
+            Ok(file) => {
+                for (number, line) in BufReader::new(file).lines().enumerate() {
+                    match serde_json::from_str(&line?) {
+                        Ok(Record::Election(id, Some(election))) if id == election.id => {
+                            memory.insert(*election)
+                        }
+                        Ok(Record::Election(id, _)) => drop(memory.delete(&id)),
+                        Ok(Record::Circles(stored)) => circles = stored,
+                        Err(error) => {
+                            tracing::warn!(
+                                "Skipping line {} of {}: {}",
+                                number + 1,
+                                path.display(),
+                                error
+                            )
+                        }
+                    }
+                }
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => {}
+            Err(error) => return Err(error),
+        }
+
+        let log = compact(&path, &memory, &circles)?;
+        Ok(KvStore {
+            path,
+            memory,
+            circles: Mutex::new(circles),
+            log: Mutex::new(log),
+        })
+    }
+
+    pub fn circles(&self) -> HashMap<String, Circle> {
+        self.circles
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Callers hold the log lock while changing the memory, or append the
+    /// current value after changing it, so the last line of a key is always
+    /// its latest value.
+    fn log(&self) -> MutexGuard<'_, Log> {
+        self.log.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn append(&self, log: &mut Log, record: &Record) {
+        let written = serde_json::to_string(record)
+            .map_err(std::io::Error::from)
+            .and_then(|line| writeln!(log.file, "{}", line))
+            .and_then(|_| log.file.flush());
+        if let Err(error) = written {
+            tracing::error!("Writing to {} failed: {}", self.path.display(), error);
+            return;
+        }
+        log.lines += 1;
+
+        let keys = self.memory.len() + 1;
+        if log.lines > MIN_COMPACT_LINES.max(keys * COMPACT_RATIO) {
+            let circles = self.circles.lock().unwrap_or_else(|e| e.into_inner());
+            match compact(&self.path, &self.memory, &circles) {
+                Ok(compacted) => *log = compacted,
+                Err(error) => {
+                    tracing::error!("Compacting {} failed: {}", self.path.display(), error)
+                }
+            }
+        }
+    }
+}
+
+/// Rewrites the log with only the current value of each key.
+fn compact(
+    path: &Path,
+    memory: &MemoryStore,
+    circles: &HashMap<String, Circle>,
+) -> std::io::Result<Log> {
+    let tmp = path.with_extension("db.tmp");
+    let mut file = File::create(&tmp)?;
+    let mut lines = 0;
+    for election in memory.list() {
+        let record = Record::Election(election.id.clone(), Some(Box::new(election)));
+        writeln!(file, "{}", serde_json::to_string(&record)?)?;
+        lines += 1;
+    }
+    if !circles.is_empty() {
+        writeln!(
+            file,
+            "{}",
+            serde_json::to_string(&Record::Circles(circles.clone()))?
+        )?;
+        lines += 1;
+    }
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    let file = OpenOptions::new().append(true).open(path)?;
+    Ok(Log { file, lines })
+}
+
+impl ElectionStore for KvStore {
+    fn get(&self, id: &str) -> Option<ElectionProcess> {
+        self.memory.get(id)
+    }
+
+    fn list(&self) -> Vec<ElectionProcess> {
+        self.memory.list()
+    }
+
+    fn insert(&self, election: ElectionProcess) {
+        let mut log = self.log();
+        self.memory.insert(election.clone());
+        self.append(
+            &mut log,
+            &Record::Election(election.id.clone(), Some(Box::new(election))),
+        );
+    }
+
+    fn modify(&self, id: &str, change: &mut dyn FnMut(&mut ElectionProcess)) -> bool {
+        // `change` may save the circles, which needs the log itself
+        if !self.memory.modify(id, change) {
+            return false;
+        }
+        let mut log = self.log();
+        // another change may have followed before the log was locked
+        if let Some(election) = self.memory.get(id) {
+            self.append(
+                &mut log,
+                &Record::Election(id.to_string(), Some(Box::new(election))),
+            );
+        }
+        true
+    }
+
+    fn delete(&self, id: &str) -> Option<ElectionProcess> {
+        let mut log = self.log();
+        let deleted = self.memory.delete(id)?;
+        self.append(&mut log, &Record::Election(id.to_string(), None));
+        Some(deleted)
+    }
+
+    fn contains(&self, id: &str) -> bool {
+        self.memory.contains(id)
+    }
+
+    fn save_circles(&self, circles: &HashMap<String, Circle>) {
+        let mut log = self.log();
+        *self.circles.lock().unwrap_or_else(|e| e.into_inner()) = circles.clone();
+        self.append(&mut log, &Record::Circles(circles.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_latest_values_across_reopening() {
+        let dir = std::env::temp_dir().join(format!("ieptool-kv-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = KvStore::open(&dir).unwrap();
+        store.insert(ElectionProcess::new_and_cleaned(
+            "abc".to_string(),
+            "Facilitator".to_string(),
+            "Ann\nBob",
+            0,
+        ));
+        assert!(store.modify("abc", &mut |e| e.elected_role = "Secretary".to_string()));
+        assert!(!store.modify("xyz", &mut |_| {}));
+        let mut circles = HashMap::new();
+        let circle = Circle {
+            id: "c".to_string(),
+            name: "Board".to_string(),
+            roles: vec![],
+        };
+        circles.insert("c".to_string(), circle);
+        store.save_circles(&circles);
+        drop(store);
+
+        let reopened = KvStore::open(&dir).unwrap();
+        assert_eq!(reopened.get("abc").unwrap().elected_role, "Secretary");
+        assert!(reopened.circles().contains_key("c"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+//! A tiny SMTP client for invitations to vote and other mails to voters.
+//!
+//! It speaks plain SMTP without TLS to `SMTP_SERVER` (`host:port`, port 25 if
+//! left out), so point it at a local relay. `SMTP_FROM` is the sender, and
+//! with `SMTP_USERNAME` and `SMTP_PASSWORD` it logs in with `AUTH PLAIN`.
+
+use crate::crypto::base64_encode;
+use std::time::Duration;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct Smtp {
+    server: String,
+    from: String,
+    login: Option<(String, String)>,
+}
+
+/// An email with a plain text body.
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+    pub attachment: Option<Attachment>,
+}
+
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+impl Mail {
+    /// The headers after `To` and `Subject` and the body, with the
+    /// attachment in a second part.
+    fn content(&self) -> String {
+        let text = format!(
+            "Content-Type: text/plain; charset=UTF-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n{}",
+            self.body
+                .lines()
+                // lines starting with a dot would end the message early
+                .map(|line| match line.starts_with('.') {
+                    true => format!(".{}", line),
+                    false => line.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("\r\n"),
+        );
+        let Some(attachment) = &self.attachment else {
+            return text;
+        };
+        let encoded = base64_encode(&attachment.data);
+        let lines = encoded
+            .as_bytes()
+            .chunks(76)
+            .map(|line| String::from_utf8_lossy(line).into_owned())
+            .collect::<Vec<_>>();
+        format!(
+            "Content-Type: multipart/mixed; boundary=\"{b}\"\r\n\r\n--{b}\r\n{}\r\n--{b}\r\n\
+             Content-Type: {}\r\nContent-Transfer-Encoding: base64\r\n\
+             Content-Disposition: attachment; filename=\"{}\"\r\n\r\n{}\r\n--{b}--",
+            text,
+            attachment.content_type,
+            attachment.filename,
+            lines.join("\r\n"),
+            b = BOUNDARY,
+        )
+    }
+}
+
+/// Separates the parts of mails with an attachment; base64 and the texts of
+/// this tool do not contain it.
+const BOUNDARY: &str = "=_ieptool_part";
+
+impl Smtp {
+    pub fn from_env() -> Option<Self> {
+        let var = |name| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let server = var("SMTP_SERVER")?;
+        Some(Smtp {
+            server: match server.contains(':') {
+                true => server,
+                false => format!("{}:25", server),
+            },
+            from: var("SMTP_FROM")?,
+            login: var("SMTP_USERNAME").zip(var("SMTP_PASSWORD")),
+        })
+    }
+
+    /// Sends all `mails` in one session, returning the failed recipients
+    /// with the reason.
+    pub async fn send(&self, mails: &[Mail]) -> Result<Vec<(String, String)>, String> {
+        tokio::time::timeout(TIMEOUT * (mails.len() as u32 + 1), self.session(mails))
+            .await
+            .map_err(|_| format!("Sending mail via {} timed out", self.server))?
+    }
+
+    async fn session(&self, mails: &[Mail]) -> Result<Vec<(String, String)>, String> {
+        let stream = TcpStream::connect(&self.server)
+            .await
+            .map_err(|e| format!("Could not connect to {}: {}", self.server, e))?;
+        let mut session = Session {
+            stream: BufReader::new(stream),
+        };
+        session.expect(None, 220).await?;
+        session.expect(Some("EHLO ieptool"), 250).await?;
+        if let Some((username, password)) = &self.login {
+            let credentials = base64_encode(format!("\0{}\0{}", username, password).as_bytes());
+            session
+                .expect(Some(&format!("AUTH PLAIN {}", credentials)), 235)
+                .await?;
+        }
+        let mut failed = vec![];
+        for mail in mails {
+            if let Err(error) = self.transfer(&mut session, mail).await {
+                failed.push((mail.to.clone(), error));
+                session.expect(Some("RSET"), 250).await?;
+            }
+        }
+        session.expect(Some("QUIT"), 221).await?;
+        Ok(failed)
+    }
+
+    async fn transfer(&self, session: &mut Session, mail: &Mail) -> Result<(), String> {
+        session
+            .expect(Some(&format!("MAIL FROM:<{}>", address(&self.from))), 250)
+            .await?;
+        session
+            .expect(Some(&format!("RCPT TO:<{}>", mail.to)), 250)
+            .await?;
+        session.expect(Some("DATA"), 354).await?;
+        let message = format!(
+            "From: {}\r\nTo: <{}>\r\nSubject: {}\r\nMIME-Version: 1.0\r\n{}\r\n.",
+            self.from,
+            mail.to,
+            encode_header(&mail.subject),
+            mail.content(),
+        );
+        session.expect(Some(&message), 250).await
+    }
+}
+
+struct Session {
+    stream: BufReader<TcpStream>,
+}
+
+impl Session {
+    /// Sends `command`, if any, and reads a reply with the `code`.
+    async fn expect(&mut self, command: Option<&str>, code: u16) -> Result<(), String> {
+        if let Some(command) = command {
+            self.stream
+                .get_mut()
+                .write_all(format!("{}\r\n", command).as_bytes())
+                .await
+                .map_err(|e| format!("Could not send to the mail server: {}", e))?;
+        }
+        let mut reply = String::new();
+        loop {
+            let mut line = String::new();
+            let read = self
+                .stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| format!("Could not read from the mail server: {}", e))?;
+            if read == 0 {
+                return Err("The mail server closed the connection".to_string());
+            }
+            reply.push_str(&line);
+            // `250-` continues a reply, `250 ` ends it
+            if line.as_bytes().get(3) != Some(&b'-') {
+                break;
+            }
+        }
+        match reply.get(..3).and_then(|c| c.parse::<u16>().ok()) {
+            Some(got) if got == code => Ok(()),
+            _ => Err(format!("The mail server answered {}", reply.trim_end())),
+        }
+    }
+}
+
+/// The bare address of `Name <address>`.
+fn address(mailbox: &str) -> &str {
+    mailbox
+        .rsplit_once('<')
+        .map_or(mailbox, |(_, rest)| rest.trim_end_matches('>'))
+        .trim()
+}
+
+/// Encodes a header value with non-ASCII characters as in RFC 2047.
+fn encode_header(value: &str) -> String {
+    match value.is_ascii() {
+        true => value.to_string(),
+        false => format!("=?UTF-8?B?{}?=", base64_encode(value.as_bytes())),
+    }
+}
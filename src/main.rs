@@ -1,27 +1,97 @@
 use axum::{
-    extract::{Path, Query, Request, State},
-    http::StatusCode,
-    response::{sse::Event, IntoResponse, Sse},
+    body::Bytes,
+    extract::{FromRequest, Path, Query, Request, State},
+    http::{
+        header::{CONTENT_TYPE, SET_COOKIE},
+        HeaderMap, StatusCode,
+    },
+    response::{sse::Event, AppendHeaders, IntoResponse, Sse},
     routing::{get, post},
-    Form, Router, ServiceExt,
+    Form, Json, Router, ServiceExt,
+};
+use iep_core::{
+    Ballot, Circle, CircleRoleRef, Clock, ElectionKind, ElectionPhase, ElectionProcess,
+    ElectionStatistics, MergeConflict, MergeReport, ProcessError, Random, SeededRandom,
+    SystemClock, ThreadRandom, UtcDateTime, VotingMethod, DAY_SECS, MAX_SCORE,
 };
 use itertools::*;
 use maud::{html, Markup, DOCTYPE};
 use qrcode::{render::svg::Color, QrCode};
-use rand::distributions::DistString;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     convert::Infallible,
+    future::IntoFuture,
     str::FromStr,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
     time::Duration,
 };
-use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+use store::{ElectionStore, MemoryStore};
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    Stream, StreamExt,
+};
 use tower::Layer;
-use tower_http::{normalize_path::NormalizePathLayer, services::ServeDir, trace::TraceLayer};
+use tower_http::{
+    normalize_path::{NormalizePath, NormalizePathLayer},
+    services::ServeDir,
+    trace::TraceLayer,
+};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod accounts;
+mod admin;
+mod api;
+mod archive;
+mod calendar;
+mod chat;
+mod circles;
+mod cookies;
+mod crypto;
+mod embed;
+mod facilitator;
+mod feedback;
+mod graphql;
+mod http;
+mod ids;
+mod journal;
+mod kiosk;
+mod kv;
+mod mail;
+mod meeting;
+mod multipart;
+mod my_vote;
+mod nomination;
+mod observer;
+mod oembed;
+mod oidc;
+mod openapi;
+mod passcode;
+mod pdf;
+mod photos;
+mod postgres;
+mod presence;
+mod qr;
+mod redis;
+mod report;
+mod s3;
+mod safety;
+mod schedule;
+mod seed;
+mod sms;
+mod snapshot;
+mod storage;
+mod store;
+mod telegram;
+mod templates;
+#[cfg(test)]
+mod tests;
+mod tie_break;
+mod tokens;
+mod webhooks;
+mod ws;
+mod zip;
+
 #[tokio::main]
 async fn main() {
     tracing_subscriber::registry()
@@ -29,94 +99,772 @@ async fn main() {
             tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
                 // axum logs rejections from built-in extractors with the `axum::rejection`
                 // target, at `TRACE` level. `axum::rejection=trace` enables showing those events
-                "example_tracing_aka_logging=info,tower_http=info,axum::rejection=trace".into()
+                "ieptool=info,tower_http=info,axum::rejection=trace".into()
             }),
         )
-        .with(tracing_subscriber::fmt::layer())
+        // stdout is kept for the results of `ieptool run`
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
         .init();
 
-    let mut state = HashMap::new();
-    let mut test_nominee_map = HashMap::new();
-    test_nominee_map.insert(13589, "Test Nominee 1".to_string());
-    test_nominee_map.insert(29852, "Test2".to_string());
-    test_nominee_map.insert(96109, "Test Nominee 3".to_string());
-    let mut test_vote_map = HashMap::new();
-    test_vote_map.insert("Test Voter 1".to_string(), 13589);
-    test_vote_map.insert("Test Voter 2".to_string(), 29852);
-    test_vote_map.insert("Test Voter 3".to_string(), 13589);
-    state.insert(
-        "1337".to_string(),
-        ElectionProcess {
-            id: "1337".to_string(),
-            phase: ElectionPhase::FirstVote,
-            elected_role: "Test Role".to_string(),
-            nominees: test_nominee_map,
-            first_round_id: test_vote_map,
-            second_round_id: HashMap::new(),
+    let command = match parse_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let data_dir =
+        std::path::PathBuf::from(std::env::var("DATA_DIR").unwrap_or("data".to_string()));
+    let exit_unreadable = |error: std::io::Error| -> ! {
+        tracing::error!("Could not load stored elections: {}", error);
+        std::process::exit(1);
+    };
+    let mut write_behind = None;
+    let mut snapshots = None;
+    let mut relay = None;
+    let mut circles = HashMap::new();
+    // one-shot elections are not worth keeping
+    let store: Arc<dyn ElectionStore> = match (&command, std::env::var("STORE").as_deref()) {
+        (Command::Run { .. }, _) => Arc::new(MemoryStore::default()),
+        (
+            Command::Serve {
+                database_url: Some(url),
+                ..
+            },
+            _,
+        ) => {
+            let store = postgres::PgStore::open(url).unwrap_or_else(|e| exit_unreadable(e));
+            circles = store.circles();
+            Arc::new(store)
+        }
+        (Command::Serve { .. }, Ok("memory")) => Arc::new(MemoryStore::default()),
+        (Command::Serve { .. }, Ok("redis")) => {
+            let url = std::env::var("REDIS_URL").unwrap_or("redis://localhost:6379".to_string());
+            let instance = ThreadRandom.alphanumeric(12);
+            let store = redis::RedisStore::open(&url, instance.clone())
+                .unwrap_or_else(|e| exit_unreadable(e));
+            relay = Some((url, instance));
+            circles = store.circles();
+            Arc::new(store)
+        }
+        (Command::Serve { .. }, Ok("kv")) => {
+            let store = kv::KvStore::open(&data_dir).unwrap_or_else(|e| exit_unreadable(e));
+            circles = store.circles();
+            Arc::new(store)
+        }
+        (Command::Serve { .. }, Ok("snapshot")) => {
+            let (store, restored) =
+                snapshot::SnapshotStore::open(&data_dir).unwrap_or_else(|e| exit_unreadable(e));
+            let store = Arc::new(store);
+            circles = restored;
+            snapshots = Some(store.clone());
+            store
+        }
+        (Command::Serve { .. }, _) => {
+            let store = storage::FileStore::open(&data_dir).unwrap_or_else(|e| exit_unreadable(e));
+            circles = store.load_circles().unwrap_or_else(|e| exit_unreadable(e));
+            let store = Arc::new(store);
+            write_behind = Some(store.clone());
+            store
+        }
+    };
+    let store: Arc<dyn ElectionStore> = match (&command, env_or("JOURNAL", false)) {
+        (Command::Serve { .. }, true) => Arc::new(
+            journal::JournalStore::open(&data_dir, store).unwrap_or_else(|e| exit_unreadable(e)),
+        ),
+        _ => store,
+    };
+    let streams = store
+        .list()
+        .into_iter()
+        .map(|e| (e.id, ElectionStream::new()))
+        .collect();
+
+    let random: Arc<dyn Random> = match std::env::var("RANDOM_SEED")
+        .ok()
+        .and_then(|s| s.parse().ok())
+    {
+        Some(seed) => Arc::new(SeededRandom::new(seed)),
+        None => Arc::new(ThreadRandom),
+    };
+    // without a configured secret, cookies are valid until the next restart
+    let cookie_secret = match std::env::var("COOKIE_SECRET") {
+        Ok(secret) if !secret.is_empty() => secret,
+        _ => random.alphanumeric(32),
+    };
+
+    let state = ElectionDB {
+        store,
+        circles: Arc::new(Mutex::new(circles)),
+        templates: Arc::new(Mutex::new(
+            templates::load(&data_dir).unwrap_or_else(|e| exit_unreadable(e)),
+        )),
+        streams: Arc::new(Mutex::new(streams)),
+        redirects: Arc::new(Mutex::new(HashMap::new())),
+        base_url: std::env::var("BASE_URL").unwrap_or("http://localhost:3000".to_string()),
+        data_dir,
+        admin_token: std::env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
+        reminder_weeks: env_or("REMINDER_WEEKS", 4),
+        keep_for: Some(env_or("KEEP_DAYS", 90u64))
+            .filter(|days| *days > 0)
+            .map(|days| days * DAY_SECS),
+        reminder_webhook: std::env::var("REMINDER_WEBHOOK_URL")
+            .ok()
+            .filter(|u| !u.is_empty()),
+        slack_webhook: std::env::var("SLACK_WEBHOOK_URL")
+            .ok()
+            .filter(|u| !u.is_empty()),
+        matrix: chat::Matrix::from_env().map(Arc::new),
+        mail: mail::Smtp::from_env().map(Arc::new),
+        sms: sms::Gateway::from_env().map(Arc::new),
+        telegram: telegram::Bot::from_env().map(Arc::new),
+        sse_keep_alive: Duration::from_secs(env_or("SSE_KEEP_ALIVE_SECS", 20)),
+        sse_retry: Duration::from_millis(env_or("SSE_RETRY_MS", 3000)),
+        id_style: match env_or("ID_STYLE", ids::IdStyle::Random(5)) {
+            ids::IdStyle::Random(_) => {
+                ids::IdStyle::Random(env_or("ID_LENGTH", 5usize).clamp(3, 32))
+            }
+            style => style,
+        },
+        clock: Arc::new(SystemClock),
+        random,
+        cookie_secret: Arc::new(crypto::sha256(cookie_secret.as_bytes())),
+        oidc: match oidc::Provider::from_env() {
+            Ok(provider) => provider.map(Arc::new),
+            Err(error) => {
+                tracing::error!("{}", error);
+                std::process::exit(1);
+            }
         },
+    };
+
+    if let Some(bot) = state.telegram.clone() {
+        tokio::spawn(telegram::run(state.clone(), bot));
+    }
+
+    match command {
+        Command::Serve {
+            demo, seed_file, ..
+        } => {
+            let backups = s3::Bucket::from_env();
+            if let Some(bucket) = &backups {
+                if let Err(error) = s3::restore_latest(&state, bucket).await {
+                    tracing::error!("Restoring the latest backup failed: {}", error);
+                    std::process::exit(1);
+                }
+            }
+            if demo {
+                seed::insert_demo_election(&state);
+            }
+            if let Some(path) = seed_file {
+                match seed::insert_seed_file(&state, &path) {
+                    Ok(inserted) => tracing::info!("Inserted {} elections from {}", inserted, path),
+                    Err(error) => {
+                        tracing::error!("{}", error);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            if let Some(storage) = &write_behind {
+                tokio::spawn(storage::write_behind(
+                    state.clone(),
+                    storage.clone(),
+                    Duration::from_millis(env_or("PERSIST_INTERVAL_MS", 1000)),
+                ));
+            }
+            if let Some((url, instance)) = relay {
+                let state = state.clone();
+                tokio::task::spawn_blocking(move || redis::relay_updates(state, url, instance));
+            }
+            if let Some(snapshots) = &snapshots {
+                tokio::spawn(snapshot::take_snapshots(
+                    state.clone(),
+                    snapshots.clone(),
+                    Duration::from_secs(env_or("SNAPSHOT_SECS", 30)),
+                ));
+            }
+            if let Some(bucket) = backups {
+                tokio::spawn(s3::back_up_periodically(
+                    state.clone(),
+                    bucket,
+                    Duration::from_secs(env_or("S3_BACKUP_SECS", 3600)),
+                ));
+            }
+            tokio::spawn(remove_expired_elections(
+                state.clone(),
+                Duration::from_secs(env_or("EXPIRY_CHECK_SECS", 3600)),
+            ));
+            tokio::spawn(schedule::advance_scheduled_phases(
+                state.clone(),
+                Duration::from_secs(env_or("SCHEDULE_CHECK_SECS", 10)),
+            ));
+            tokio::spawn(circles::remind_expiring_terms(
+                state.clone(),
+                Duration::from_secs(env_or("REMINDER_CHECK_SECS", 3600)),
+            ));
+            let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
+            tracing::debug!("listening on {}", listener.local_addr().unwrap());
+            let stopping = Arc::new(tokio::sync::Notify::new());
+            let server = axum::serve(
+                listener,
+                ServiceExt::<Request>::into_make_service(router(state.clone())),
+            )
+            .with_graceful_shutdown({
+                let state = state.clone();
+                let stopping = stopping.clone();
+                async move {
+                    shutdown_signal().await;
+                    tracing::info!("Shutting down");
+                    state.close_streams();
+                    stopping.notify_one();
+                }
+            });
+            tokio::select! {
+                result = server.into_future() => result.unwrap(),
+                _ = async {
+                    stopping.notified().await;
+                    tokio::time::sleep(Duration::from_secs(env_or("SHUTDOWN_GRACE_SECS", 10))).await
+                } => tracing::warn!("Requests still running, shutting down anyway"),
+            }
+
+            if let Some(storage) = &write_behind {
+                if let Err(error) = storage.flush(&state).await {
+                    tracing::error!("Writing elections to disk failed: {}", error);
+                    std::process::exit(1);
+                }
+            }
+            if let Some(snapshots) = &snapshots {
+                if let Err(error) = snapshots.save(&state).await {
+                    tracing::error!("Writing snapshot failed: {}", error);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Run {
+            elected_role,
+            nominees_file,
+        } => run_one_shot(state, elected_role, nominees_file).await,
+    }
+}
+
+async fn remove_expired_elections(state: ElectionDB, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let removed = state.remove_expired();
+        if removed > 0 {
+            tracing::info!("Removed {} expired elections", removed);
+        }
+    }
+}
+
+/// Resolves on Ctrl-C or `SIGTERM`, as sent by container runtimes.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => drop(signal.recv().await),
+            Err(error) => {
+                tracing::warn!("Cannot listen for SIGTERM: {}", error);
+                std::future::pending().await
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+const USAGE: &str = "Usage:
+  ieptool [OPTIONS]                            start the server
+  ieptool run --role <ROLE> --nominees <FILE>  run a single election and print its results
+
+Options:
+  --database-url <URL>  keep elections in PostgreSQL
+  --demo                add a demo election with the id 1337
+  --seed-file <FILE>    add the elections of a JSON file whose ids are not taken yet";
+
+enum Command {
+    Serve {
+        database_url: Option<String>,
+        demo: bool,
+        seed_file: Option<String>,
+    },
+    Run {
+        elected_role: String,
+        nominees_file: String,
+    },
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.peekable();
+    match args.peek().map(String::as_str) {
+        None | Some("--database-url" | "--demo" | "--seed-file") => {
+            let mut database_url = None;
+            let mut demo = false;
+            let mut seed_file = None;
+            while let Some(arg) = args.next() {
+                let mut value = || {
+                    args.next()
+                        .ok_or_else(|| format!("Missing value for {}", arg))
+                };
+                match &arg[..] {
+                    "--database-url" => database_url = Some(value()?),
+                    "--seed-file" => seed_file = Some(value()?),
+                    "--demo" => demo = true,
+                    _ => return Err(format!("Unknown option {}", arg)),
+                }
+            }
+            Ok(Command::Serve {
+                database_url,
+                demo,
+                seed_file,
+            })
+        }
+        Some("run") => {
+            args.next();
+            let mut elected_role = None;
+            let mut nominees_file = None;
+            while let Some(arg) = args.next() {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("Missing value for {}", arg))?;
+                match &arg[..] {
+                    "--role" => elected_role = Some(value),
+                    "--nominees" => nominees_file = Some(value),
+                    _ => return Err(format!("Unknown option {}", arg)),
+                }
+            }
+            Ok(Command::Run {
+                elected_role: elected_role.ok_or("Missing --role")?,
+                nominees_file: nominees_file.ok_or("Missing --nominees")?,
+            })
+        }
+        Some(other) => Err(format!("Unknown command {}", other)),
+    }
+}
+
+/// Serves a single election until it is completed or closed, then prints its
+/// results as JSON to stdout and returns.
+async fn run_one_shot(state: ElectionDB, elected_role: String, nominees_file: String) {
+    let content = std::fs::read_to_string(&nominees_file).unwrap_or_else(|e| {
+        tracing::error!("Could not read {}: {}", nominees_file, e);
+        std::process::exit(1);
+    });
+    let id = state.new_election_id();
+    let election = ElectionProcess::new_and_cleaned(
+        id.clone(),
+        elected_role,
+        &nominees_from_file(&nominees_file, &content).join("\n"),
+        state.clock.now(),
+    );
+    if election.nominees.is_empty() {
+        tracing::error!("No nominees found in {}", nominees_file);
+        std::process::exit(1);
+    }
+    state.add_election(election);
+    let mut updates = state.streams.lock().unwrap()[&id].sender.subscribe();
+
+    let voting_url = format!("{}/election/{}/voting", state.base_url, id);
+    let qrcode = QrCode::new(voting_url.as_bytes())
+        .unwrap()
+        .render::<qrcode::render::unicode::Dense1x2>()
+        .quiet_zone(true)
+        .build();
+    eprintln!("{}", qrcode);
+    eprintln!("Vote at:     {}", voting_url);
+    eprintln!("Facilitate:  {}/election/{}/eval", state.base_url, id);
+
+    let store = state.store.clone();
+    let finished = async move {
+        loop {
+            match updates.recv().await {
+                Ok(SequencedUpdate {
+                    update: ElectionUpdate::PhaseChanged,
+                    ..
+                })
+                // a missed update may have been the last phase change
+                | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                    let Some(election) = store.get(&id) else {
+                        return Err("The election was deleted before it finished");
+                    };
+                    if election.phase == ElectionPhase::Completed || election.closed_at.is_some() {
+                        return Ok(election_results_json(&election));
+                    }
+                }
+                Ok(_) => {}
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => {
+                    return Err("The election stopped sending updates before it finished")
+                }
+            }
+        }
+    };
+
+    let listener = match tokio::net::TcpListener::bind("0.0.0.0:3000").await {
+        Ok(listener) => listener,
+        Err(error) => {
+            tracing::error!("Could not listen on port 3000: {}", error);
+            std::process::exit(1);
+        }
+    };
+    let server = axum::serve(
+        listener,
+        ServiceExt::<Request>::into_make_service(router(state)),
     );
+    tokio::select! {
+        results = finished => match results {
+            Ok(results) => println!("{:#}", results),
+            Err(error) => {
+                tracing::error!("{}", error);
+                std::process::exit(1);
+            }
+        },
+        result = server.into_future() => {
+            if let Err(error) = result {
+                tracing::error!("Serving the election failed: {}", error);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// The public results, without any voter names; the votes of a round are
+/// only shown once it was tallied.
+fn election_results_json(election: &ElectionProcess) -> serde_json::Value {
+    let round_json = |round: &HashMap<String, Ballot>, tallied: bool| {
+        if !tallied {
+            return serde_json::json!({ "ballots": round.len() });
+        }
+        // ballots for nominees removed since are not counted
+        let votes = round
+            .values()
+            .filter_map(|b| election.nominees.get(&b.vote))
+            .counts();
+        let max_votes = votes.values().max().copied().unwrap_or(0);
+        let runoff = (election.method == VotingMethod::Ranked).then(|| {
+            let runoff = election.instant_runoff(round);
+            let name = |id: &u64| election.nominee_name(*id).to_string();
+            serde_json::json!({
+                "rounds": runoff.rounds.iter().map(|r| serde_json::json!({
+                    "votes": r.counts.iter().map(|(id, votes)| (name(id), *votes)).collect::<HashMap<_, _>>(),
+                    "exhausted": r.exhausted,
+                    "eliminated": r.eliminated.iter().map(name).collect::<Vec<_>>(),
+                })).collect::<Vec<_>>(),
+                "winner": runoff.winner.as_ref().map(name),
+            })
+        });
+        let scores = (election.method == VotingMethod::Score).then(|| {
+            election
+                .score_totals(round)
+                .iter()
+                .map(|s| {
+                    let score = serde_json::json!({ "total": s.total, "average": s.average });
+                    (election.nominee_name(s.nominee).to_string(), score)
+                })
+                .collect::<HashMap<_, _>>()
+        });
+        serde_json::json!({
+            "ballots": round.len(),
+            "votes": votes,
+            "most_votes": votes
+                .iter()
+                .filter(|(_, v)| **v == max_votes)
+                .map(|(k, _)| k)
+                .sorted()
+                .collect::<Vec<_>>(),
+            "runoff": runoff,
+            "scores": scores,
+        })
+    };
+    let closed = election.closed_at.is_some();
+    let first_tallied = closed
+        || !matches!(
+            election.phase,
+            ElectionPhase::Nomination | ElectionPhase::FirstVote
+        );
+    let second_tallied = closed
+        || matches!(
+            election.phase,
+            ElectionPhase::SecondTally | ElectionPhase::SafetyRound | ElectionPhase::Completed
+        );
+    serde_json::json!({
+        "id": election.id,
+        "elected_role": election.elected_role,
+        "method": election.method,
+        "phase": election.phase,
+        "nominees": election.nominees.values().sorted().collect::<Vec<_>>(),
+        "first_round": round_json(&election.first_round_id, first_tallied),
+        "second_round": round_json(&election.second_round_id, second_tallied),
+        "rounds": election.rounds,
+        "earlier_rounds": election
+            .earlier_rounds
+            .iter()
+            .map(|round| round_json(round, true))
+            .collect::<Vec<_>>(),
+        "phase_history": election.phase_history,
+        "elected": election.elected,
+        "seats": election.seats,
+        "elected_seats": election.elected_seats(),
+        "tie_break": election.tie_break,
+        "runoffs": election.runoffs,
+        "expected_voters": election.expected_voters,
+    })
+}
+
+/// Whether the evaluation page can patch its vote count, voter lists and
+/// tally with [`live_update_json`] instead of reloading on new votes.
+fn live_patchable(election: &ElectionProcess) -> bool {
+    election.closed_at.is_none()
+        && election.voting_round().is_ok()
+        && (election.kind != ElectionKind::StrawPoll || election.method == VotingMethod::Plurality)
+}
 
-    let mut streams = HashMap::new();
-    streams.insert("1337".to_string(), tokio::sync::broadcast::channel(16).0);
+/// What changes with every vote, sent along with the updates of the stream so
+/// that pages can patch their fragments instead of reloading everything. Who
+/// voted is left out, see [`facilitator_update_json`].
+fn live_update_json(election: &ElectionProcess) -> serde_json::Value {
+    let round = election.current_round();
+    let tally = (live_patchable(election) && election.kind == ElectionKind::StrawPoll).then(|| {
+        election
+            .accumulated_votes(round)
+            .votes
+            .into_iter()
+            .collect::<HashMap<_, _>>()
+    });
+    serde_json::json!({
+        "phase": election.phase.to_string(),
+        "votes": election.voted_count(),
+        "expected": election.expected_voters,
+        "quorum_reached": election.quorum_reached(),
+        "turnout": election.turnout().map(|t| format!("{:.0}", t)),
+        "tally": tally,
+    })
+}
+
+/// Like [`live_update_json`] with the voters of the round and those of the
+/// roster still missing, only for the connections of facilitators.
+fn facilitator_update_json(election: &ElectionProcess) -> serde_json::Value {
+    let mut data = live_update_json(election);
+    data["voters"] =
+        serde_json::json!(election.current_round().keys().sorted().collect::<Vec<_>>());
+    data["missing"] = serde_json::json!(election
+        .missing_voters()
+        .into_iter()
+        .sorted_by_key(|n| n.to_lowercase())
+        .collect::<Vec<_>>());
+    data
+}
 
+fn router(state: ElectionDB) -> NormalizePath<Router> {
+    let redirects = axum::middleware::from_fn_with_state(state.clone(), redirect_merged);
+    let facilitator =
+        axum::middleware::from_fn_with_state(state.clone(), facilitator::require_facilitator);
+    let passcode = axum::middleware::from_fn_with_state(state.clone(), passcode::require_passcode);
+    let login = axum::middleware::from_fn_with_state(state.clone(), oidc::require_login);
     let router = Router::new()
         .route("/", get(view_home))
         .route("/election", post(post_election))
         .route("/election/join", get(get_election_join))
+        .route("/election/new", get(view_election_new))
         .route("/election/:id/voting", get(view_election_voting))
         .route("/election/:id/voting", post(post_election_voting))
         .route("/election/:id/voting/form", get(get_election_voting_form))
+        .route(
+            "/election/:id/voting/options",
+            get(get_election_voting_options),
+        )
+        .route(
+            "/election/:id/voting/previous",
+            get(get_election_voting_previous),
+        )
+        .route(
+            "/election/:id/voting/manual",
+            post(post_election_manual_votes),
+        )
+        .route("/election/:id/voting/check", get(view_election_vote_check))
+        .route("/election/:id/voting/mine", get(my_vote::view_my_vote))
+        .route("/election/:id/vote/:token", get(tokens::view_token_voting))
+        .route(
+            "/election/:id/vote/:token/form",
+            get(tokens::get_token_voting_form),
+        )
+        .route("/election/:id/tokens", get(tokens::view_voting_links))
+        .route("/election/:id/tokens", post(tokens::post_voting_links))
+        .route(
+            "/election/:id/tokens/invite",
+            post(tokens::post_invitations),
+        )
+        .route("/election/:id/tokens/remind", post(tokens::post_reminders))
+        .route("/election/:id/tokens/sms", post(sms::post_sms_ballots))
+        .route("/sms", post(sms::post_sms))
+        .route(
+            "/election/:id/voting/withdraw",
+            post(my_vote::post_withdraw),
+        )
+        .route("/election/:id/passcode", post(passcode::post_passcode))
+        .route("/election/:id/webhooks", post(webhooks::post_webhook))
+        .route(
+            "/election/:id/webhooks/remove",
+            post(webhooks::post_remove_webhook),
+        )
+        .route(
+            "/election/:id/co-facilitators",
+            post(facilitator::post_co_facilitator),
+        )
+        .route(
+            "/election/:id/co-facilitators/:key/revoke",
+            post(facilitator::post_revoke_co_facilitator),
+        )
+        .route("/election/:id/kiosk", get(kiosk::view_kiosk))
+        .route("/election/:id/kiosk", post(kiosk::post_kiosk_vote))
+        .route("/election/:id/kiosk/form", get(kiosk::get_kiosk_form))
         .route("/election/:id/eval", get(view_election_eval))
         .route("/election/:id/eval/content", get(get_election_eval_content))
+        .route("/election/:id/observe", get(observer::view_observer))
+        .route("/election/:id/embed", get(embed::view_embed))
+        .route("/election/:id/qr.png", get(qr::get_election_qr_png))
+        .route("/oembed", get(oembed::get_oembed))
+        .route("/election/:id/embed/content", get(embed::get_embed_content))
+        .route(
+            "/election/:id/observe/content",
+            get(observer::get_observer_content),
+        )
+        .route("/election/:id/ballots", get(view_election_ballots))
+        .route("/election/:id/export.csv", get(get_election_export_csv))
+        .route("/election/:id/export.json", get(get_election_export_json))
+        .route(
+            "/election/:id/archive.html",
+            get(archive::get_election_archive),
+        )
+        .route(
+            "/election/:id/report.pdf",
+            get(pdf::get_election_report_pdf),
+        )
+        .route("/election/:id/stats", get(get_election_stats))
+        .route("/election/:id/results.json", get(get_election_results_json))
+        .route("/election/:id/follow-up", get(view_election_follow_up))
+        .route("/election/:id/clone", get(view_election_clone))
+        .route(
+            "/election/:id/template",
+            post(templates::post_election_template),
+        )
+        .route("/templates", post(templates::post_template))
+        .route(
+            "/templates/:id/:action",
+            post(templates::post_template_action),
+        )
         .route("/election/:id/step/:type/:step", post(post_election_step))
+        .route("/election/:id/complete", post(post_election_complete))
+        .route("/election/:id/schedule", post(schedule::post_schedule))
+        .route(
+            "/election/:id/schedule/invite",
+            post(calendar::post_calendar_invitations),
+        )
+        .route(
+            "/election/:id/calendar.ics",
+            get(calendar::get_election_calendar),
+        )
+        .route("/election/:id/safety", post(safety::post_safety))
+        .route("/election/:id/proposal", post(safety::post_proposal))
+        .route(
+            "/election/:id/tie-break/:method",
+            post(tie_break::post_tie_break),
+        )
+        .route("/election/:id/nominations", get(nomination::get_nominees))
+        .route(
+            "/election/:id/nominations",
+            post(nomination::post_nomination),
+        )
+        .route(
+            "/election/:id/nominations/pending",
+            get(nomination::get_pending_nominations),
+        )
+        .route(
+            "/election/:id/nominations/merge",
+            post(nomination::post_nominee_merge),
+        )
+        .route(
+            "/election/:id/nominations/:nominee/:answer",
+            post(nomination::post_nomination_answer),
+        )
+        .route("/election/:id/objections", post(safety::post_objection))
+        .route(
+            "/election/:id/objections/:index/:status",
+            post(safety::post_objection_status),
+        )
+        .route(
+            "/election/:id/nominees/:nominee/photo",
+            get(photos::get_nominee_photo).post(photos::post_nominee_photo),
+        )
+        .route("/election/:id/feedback", post(feedback::post_feedback))
+        .route(
+            "/election/:id/feedback/:action",
+            post(feedback::post_feedback_action),
+        )
         .route("/election/:id/stream", get(get_election_sse_stream))
-        .with_state(ElectionDB {
-            db: Arc::new(Mutex::new(state)),
-            streams: Arc::new(Mutex::new(streams)),
-            base_url: std::env::var("BASE_URL").unwrap_or("http://localhost:3000".to_string()),
-        })
+        .route("/election/:id/ws", get(ws::get_election_ws))
+        .route("/election/:id/updates", get(get_election_updates))
+        .route("/election/:id/state", get(get_election_state))
+        .route("/meeting/:meeting", get(meeting::view_meeting))
+        .route(
+            "/meeting/:meeting/content",
+            get(meeting::get_meeting_content),
+        )
+        .route("/meeting/:meeting/stream", get(meeting::get_meeting_stream))
+        .route("/auth/login", get(oidc::get_login))
+        .route("/auth/callback", get(oidc::get_callback))
+        .route("/auth/logout", get(oidc::get_logout))
+        .route("/my-elections", get(accounts::view_my_elections))
+        .route("/circles", get(circles::view_circles))
+        .route("/circles", post(circles::post_circle))
+        .route("/circles/:id/roles", post(circles::post_circle_role))
+        .route("/admin", get(admin::view_admin))
+        .route(
+            "/admin/elections/:id/:action",
+            post(admin::post_admin_election_action),
+        )
+        .route("/admin/export.zip", get(get_admin_export_zip))
+        .route("/admin/import", post(admin::post_admin_import))
+        .route("/api/openapi.json", get(openapi::get_openapi_json))
+        .route("/api/docs", get(openapi::view_api_docs))
+        .route("/api/v1/elections", get(api::get_elections))
+        .route("/api/v1/elections", post(api::post_election))
+        .route("/api/v1/elections/:id", get(api::get_election))
+        .route("/api/v1/elections/:id/votes", post(api::post_vote))
+        .route("/api/v1/elections/:id/phase", post(api::post_phase))
+        .route("/api/graphql", post(graphql::post_graphql))
+        .route("/admin/merge", post(post_admin_merge))
+        .layer(facilitator)
+        .layer(passcode)
+        .layer(login)
+        .layer(redirects)
+        .with_state(state)
         .fallback_service(ServeDir::new("static"))
         .layer(TraceLayer::new_for_http());
-    let router = NormalizePathLayer::trim_trailing_slash().layer(router);
+    NormalizePathLayer::trim_trailing_slash().layer(router)
+}
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    tracing::debug!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, ServiceExt::<Request>::into_make_service(router))
-        .await
-        .unwrap();
-}
-
-#[derive(
-    Serialize,
-    Deserialize,
-    PartialEq,
-    Debug,
-    Copy,
-    Clone,
-    strum_macros::EnumString,
-    strum_macros::Display,
-)]
-enum ElectionPhase {
-    FirstVote,
-    FirstTally,
-    SecondVote,
-    SecondTally,
-    SafetyRound,
-}
-
-impl ElectionPhase {
-    fn nice_title(&self) -> &'static str {
-        match self {
-            ElectionPhase::FirstVote => "First Vote",
-            ElectionPhase::FirstTally => "Results of First Vote",
-            ElectionPhase::SecondVote => "Second Vote",
-            ElectionPhase::SecondTally => "Results of Second Vote",
-            ElectionPhase::SafetyRound => "Safety Round",
-        }
-    }
+trait PhaseDescription {
+    fn nice_description(&self) -> Markup;
+}
 
+impl PhaseDescription for ElectionPhase {
     fn nice_description(&self) -> Markup {
         match self {
+            ElectionPhase::Nomination => {
+                html!(p {"Who should take on this role? Nominate someone, or yourself."})
+            }
             ElectionPhase::FirstVote => html!(p {"Please vote for your preferred candidate."}),
             ElectionPhase::FirstTally => {
                 html!(
@@ -129,186 +877,1078 @@ impl ElectionPhase {
             ElectionPhase::SafetyRound => html!(
                 p {"Is this decision safe enough to try?"}
             ),
+            ElectionPhase::Completed => html!(p {"The election is complete. Congratulations!"}),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
-struct ElectionProcess {
-    id: String,
-    phase: ElectionPhase,
-    elected_role: String,
-    nominees: HashMap<u64, String>,
-    first_round_id: HashMap<String, u64>,
-    second_round_id: HashMap<String, u64>,
-}
-
 #[derive(Clone)]
 struct ElectionDB {
-    db: Arc<Mutex<HashMap<String, ElectionProcess>>>,
-    streams: Arc<Mutex<HashMap<String, tokio::sync::broadcast::Sender<ElectionUpdate>>>>,
+    store: Arc<dyn ElectionStore>,
+    circles: Arc<Mutex<HashMap<String, Circle>>>,
+    templates: Arc<Mutex<HashMap<String, templates::Template>>>,
+    streams: Arc<Mutex<HashMap<String, ElectionStream>>>,
+    /// Ids of merged elections and the election they were merged into.
+    redirects: Arc<Mutex<HashMap<String, String>>>,
     base_url: String,
+    /// Where elections and uploaded files like nominee photos are stored.
+    data_dir: std::path::PathBuf,
+    admin_token: Option<String>,
+    /// Terms ending within this many weeks are flagged.
+    reminder_weeks: u64,
+    reminder_webhook: Option<String>,
+    /// Slack incoming webhook announcing the phases of all elections.
+    slack_webhook: Option<String>,
+    /// Matrix room announcing the phases of all elections.
+    matrix: Option<Arc<chat::Matrix>>,
+    /// Emails voting links to the voters.
+    mail: Option<Arc<mail::Smtp>>,
+    /// Texts ballots to voters and takes their votes by SMS.
+    sms: Option<Arc<sms::Gateway>>,
+    /// Lets participants vote in a Telegram chat.
+    telegram: Option<Arc<telegram::Bot>>,
+    /// Seconds to keep elections after their last activity, unless they set
+    /// their own; `None` keeps them forever.
+    keep_for: Option<u64>,
+    sse_keep_alive: Duration,
+    sse_retry: Duration,
+    /// How ids of new elections look.
+    id_style: ids::IdStyle,
+    clock: Arc<dyn Clock>,
+    random: Arc<dyn Random>,
+    /// Signs the cookies of voters.
+    cookie_secret: Arc<[u8; 32]>,
+    /// Restricts creating and facilitating elections to logged-in users.
+    oidc: Option<Arc<oidc::Provider>>,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+impl ElectionDB {
+    /// Publishes an update to everyone following the election.
+    fn notify(&self, id: &str, update: ElectionUpdate) -> Result<(), (StatusCode, &'static str)> {
+        let election = self.store.get(id);
+        self.streams
+            .lock()
+            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Stream Lock error"))?
+            .entry(id.to_string())
+            .or_insert_with(ElectionStream::new)
+            .publish_about(update, election.as_ref());
+        self.store.publish(id, update);
+        webhooks::deliver(self, id, update);
+        chat::deliver(self, id, update);
+        telegram::deliver(self, id, update);
+        Ok(())
+    }
+
+    /// Publishes an update only to the connections of `voter` to this
+    /// instance, which they are known on by their voter cookie.
+    fn notify_voter(
+        &self,
+        id: &str,
+        voter: &str,
+        update: ElectionUpdate,
+    ) -> Result<(), (StatusCode, &'static str)> {
+        self.streams
+            .lock()
+            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Stream Lock error"))?
+            .entry(id.to_string())
+            .or_insert_with(ElectionStream::new)
+            .publish_for(voter, update, serde_json::json!({ "voter": voter }));
+        Ok(())
+    }
+
+    /// Locks the streams, first adding one for the election if it was created
+    /// by another instance sharing the store.
+    fn streams_of(
+        &self,
+        id: &str,
+    ) -> Result<MutexGuard<'_, HashMap<String, ElectionStream>>, (StatusCode, &'static str)> {
+        let mut streams = self
+            .streams
+            .lock()
+            .map_err(|_e| (StatusCode::INTERNAL_SERVER_ERROR, "Lock error"))?;
+        if !streams.contains_key(id) && self.store.contains(id) {
+            streams.insert(id.to_string(), ElectionStream::new());
+        }
+        Ok(streams)
+    }
+
+    /// Removes the elections that were inactive for longer than they are
+    /// kept, with their streams and photos.
+    fn remove_expired(&self) -> usize {
+        let now = self.clock.now();
+        let expired = self
+            .store
+            .list()
+            .into_iter()
+            .filter(|e| e.expires_at(self.keep_for).is_some_and(|at| at <= now))
+            .collect::<Vec<_>>();
+        for election in &expired {
+            self.remove_election(&election.id);
+        }
+        expired.len()
+    }
+
+    /// Deletes the election with its stream, redirects and photos.
+    fn remove_election(&self, id: &str) -> Option<ElectionProcess> {
+        let election = self.store.delete(id)?;
+        self.streams.lock().unwrap().remove(id);
+        self.redirects
+            .lock()
+            .unwrap()
+            .retain(|from, into| from != id && into != id);
+        photos::remove_photos(self, &election);
+        Some(election)
+    }
+
+    /// Ends all SSE streams and long polls, so their connections close.
+    fn close_streams(&self) {
+        self.streams.lock().unwrap().clear();
+    }
+
+    fn add_election(&self, election: ElectionProcess) {
+        self.streams
+            .lock()
+            .unwrap()
+            .insert(election.id.clone(), ElectionStream::new());
+        self.store.insert(election);
+    }
+
+    /// Applies `change` to the election, which fails with `404` if it does
+    /// not exist.
+    fn modify_election<R>(
+        &self,
+        id: &str,
+        change: impl FnOnce(&mut ElectionProcess) -> Result<R, (StatusCode, &'static str)>,
+    ) -> Result<R, (StatusCode, &'static str)> {
+        let mut change = Some(change);
+        let mut result = None;
+        let now = self.clock.now();
+        let found = self.store.modify(id, &mut |election| {
+            if let Some(change) = change.take() {
+                let since = election.events.len();
+                result = Some(change(election));
+                election.last_activity = now;
+                if let Some(facilitator) = facilitator::acting() {
+                    election.attribute_events(since, &facilitator);
+                }
+            }
+        });
+        match (found, result) {
+            (true, Some(result)) => result,
+            _ => Err((StatusCode::NOT_FOUND, "Election not found")),
+        }
+    }
+
+    fn circles_changed(&self, circles: &HashMap<String, Circle>) {
+        self.store.save_circles(circles);
+    }
+
+    fn generate_id(&self) -> String {
+        self.random.alphanumeric(5).to_ascii_lowercase()
+    }
+
+    /// Whether `id` cannot be used for a new election.
+    fn id_taken(&self, id: &str) -> bool {
+        RESERVED_IDS.contains(&id)
+            || self.store.contains(id)
+            || self.redirects.lock().unwrap().contains_key(id)
+    }
+
+    /// An id no other election uses, in the configured style.
+    fn new_election_id(&self) -> String {
+        let mut attempt = 0;
+        loop {
+            // every few collisions the ids get longer, so this ends even when
+            // most short ids are taken
+            let id = self.id_style.generate(self.random.as_ref(), attempt / 8);
+            if !self.id_taken(&id) {
+                return id;
+            }
+            attempt += 1;
+        }
+    }
+
+    fn check_admin(&self, token: Option<&str>) -> Result<(), (StatusCode, &'static str)> {
+        match (&self.admin_token, token) {
+            (None, _) => Err((StatusCode::FORBIDDEN, "Admin access is disabled")),
+            (Some(expected), Some(token))
+                if crypto::constant_time_eq(expected.as_bytes(), token.as_bytes()) =>
+            {
+                Ok(())
+            }
+            _ => Err((StatusCode::UNAUTHORIZED, "Invalid admin token")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Default)]
 struct ElectionCreation {
+    #[serde(default)]
+    kind: ElectionKind,
     elected_role: String,
+    #[serde(default)]
     nominees: String,
+    post_vote_message: Option<String>,
+    meeting: Option<String>,
+    /// Id of the election this is a follow-up of.
+    follows: Option<String>,
+    /// `circle/role` reference of the role being elected.
+    circle_role: Option<String>,
+    /// Days to keep the election after its last activity.
+    keep_days: Option<String>,
+    /// Human-readable id to use instead of a random one.
+    slug: Option<String>,
+    /// Set to start with a nomination phase.
+    nomination: Option<String>,
+    #[serde(default)]
+    method: VotingMethod,
+    /// How many people to elect, one if empty.
+    seats: Option<String>,
+    /// How many rounds to vote in, two if empty.
+    rounds: Option<String>,
+    expected_voters: Option<String>,
+    require_quorum: Option<String>,
+    auto_advance: Option<String>,
+    /// The eligible voters, one per line.
+    #[serde(default)]
+    roster: String,
+    /// Needed to vote, none if empty.
+    passcode: Option<String>,
+    /// Webhook URLs, one per line.
+    #[serde(default)]
+    webhooks: String,
+    /// Slack incoming webhook for announcements, none if empty.
+    slack_webhook: Option<String>,
+    /// Discord webhook for announcements, none if empty.
+    discord_webhook: Option<String>,
+    /// Addresses to email the results to on closing, separated by commas.
+    #[serde(default)]
+    report_to: String,
+    report_to_roster: Option<String>,
+}
+
+/// Reads the creation form either url-encoded or, when a nominee file was
+/// uploaded, as multipart form data.
+async fn read_election_creation(
+    state: &ElectionDB,
+    request: Request,
+) -> Result<ElectionCreation, (StatusCode, &'static str)> {
+    let content_type = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let Some(boundary) = multipart::boundary(&content_type) else {
+        return Form::<ElectionCreation>::from_request(request, state)
+            .await
+            .map(|Form(form)| form)
+            .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid form data"));
+    };
+
+    let body = Bytes::from_request(request, state)
+        .await
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid request body"))?;
+    let parts = multipart::parse(boundary, &body)
+        .ok_or((StatusCode::BAD_REQUEST, "Invalid multipart data"))?;
+
+    let mut form = ElectionCreation::default();
+    for part in parts {
+        let text = String::from_utf8_lossy(&part.data);
+        match (&part.name[..], &part.filename) {
+            ("kind", _) => form.kind = text.parse().unwrap_or_default(),
+            ("elected_role", _) => form.elected_role = text.to_string(),
+            ("post_vote_message", _) => form.post_vote_message = Some(text.to_string()),
+            ("meeting", _) => form.meeting = Some(text.to_string()),
+            ("follows", _) => form.follows = Some(text.to_string()),
+            ("circle_role", _) => form.circle_role = Some(text.to_string()),
+            ("keep_days", _) => form.keep_days = Some(text.to_string()),
+            ("slug", _) => form.slug = Some(text.to_string()),
+            ("nomination", _) => form.nomination = Some(text.to_string()),
+            ("method", _) => form.method = text.parse().unwrap_or_default(),
+            ("seats", _) => form.seats = Some(text.to_string()),
+            ("rounds", _) => form.rounds = Some(text.to_string()),
+            ("expected_voters", _) => form.expected_voters = Some(text.to_string()),
+            ("require_quorum", _) => form.require_quorum = Some(text.to_string()),
+            ("auto_advance", _) => form.auto_advance = Some(text.to_string()),
+            ("passcode", _) => form.passcode = Some(text.to_string()),
+            ("webhooks", _) => form.webhooks.push_str(&format!("{}\n", text)),
+            ("slack_webhook", _) => form.slack_webhook = Some(text.to_string()),
+            ("discord_webhook", _) => form.discord_webhook = Some(text.to_string()),
+            ("report_to", _) => form.report_to = text.to_string(),
+            ("report_to_roster", _) => form.report_to_roster = Some(text.to_string()),
+            ("roster", _) => form.roster.push_str(&format!("{}\n", text)),
+            ("roster_file", Some(filename)) => {
+                form.roster
+                    .push_str(&nominees_from_file(filename, &text).join("\n"));
+                form.roster.push('\n');
+            }
+            ("nominees", _) => form.nominees.push_str(&format!("{}\n", text)),
+            ("nominees_file", Some(filename)) => {
+                form.nominees
+                    .push_str(&nominees_from_file(filename, &text).join("\n"));
+                form.nominees.push('\n');
+            }
+            _ => {}
+        }
+    }
+    Ok(form)
+}
+
+/// Reads one nominee per line; for CSV files only the first column is used
+/// and a `name`/`nominee` header row is skipped.
+fn nominees_from_file(filename: &str, content: &str) -> Vec<String> {
+    let is_csv = filename.to_ascii_lowercase().ends_with(".csv");
+    let nominees = content
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(|line| match is_csv {
+            true => line.split([',', ';']).next().unwrap_or_default(),
+            false => line,
+        })
+        .map(|n| n.trim().trim_matches('"').to_string())
+        .collect::<Vec<_>>();
+    match nominees.first() {
+        Some(header)
+            if is_csv && ["name", "nominee", "nominees"].contains(&&header.to_lowercase()[..]) =>
+        {
+            nominees[1..].to_vec()
+        }
+        _ => nominees,
+    }
 }
 
 async fn post_election(
     State(state): State<ElectionDB>,
-    Form(form): Form<ElectionCreation>,
+    request: Request,
 ) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
-    let mut db = state
-        .db
-        .lock()
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "DB Lock error"))?;
-    let id = rand::distributions::Alphanumeric
-        .sample_string(&mut rand::thread_rng(), 5)
-        .to_ascii_lowercase();
-    let nominees = form
-        .nominees
-        .lines()
-        .filter(|n| !n.is_empty())
-        .sorted()
-        .dedup()
-        .enumerate()
-        .map(|(i, n)| (i as u64, n.to_string()))
-        .collect::<HashMap<_, _>>();
-    let election = ElectionProcess {
-        id: id.clone(),
-        phase: ElectionPhase::FirstVote,
-        elected_role: form.elected_role,
-        nominees,
-        first_round_id: HashMap::new(),
-        second_round_id: HashMap::new(),
-    };
-    db.insert(id.clone(), election);
-
-    state
-        .streams
-        .lock()
-        .unwrap()
-        .insert(id.clone(), tokio::sync::broadcast::channel(16).0);
-
+    let (account, cookie) = accounts::account_or_new(&state, request.headers());
+    let form = read_election_creation(&state, request).await?;
+    let election = create_election(&state, form, Some(account))?;
     Ok((
         StatusCode::CREATED,
-        [("HX-Redirect", format!("/election/{}/eval", id))],
+        AppendHeaders(cookie),
+        [("HX-Redirect", facilitator::eval_path(&election))],
     )
         .into_response())
 }
 
-async fn post_election_step(
-    Path((id, step_type, step)): Path<(String, String, String)>,
-    State(state): State<ElectionDB>,
-) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
-    let mut db = state
-        .db
-        .lock()
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "DB Lock error"))?;
-    let election = db
-        .get_mut(&id)
-        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
-    let stream = state
-        .streams
-        .lock()
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Stream Lock error"))?
-        .get(&id)
-        .ok_or((StatusCode::NOT_FOUND, "Stream not found"))?
-        .clone();
+/// Path segments after `/election/` that are not election ids.
+const RESERVED_IDS: &[&str] = &["join", "new"];
 
-    if election
-        .phase
-        .eq(&ElectionPhase::from_str(&step).map_err(|_e| {
-            (
-                StatusCode::BAD_REQUEST,
-                "Phase does not match current phase",
-            )
-        })?)
+fn is_valid_slug(slug: &str) -> bool {
+    (3..=64).contains(&slug.len())
+        && slug
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+        && !slug.starts_with('-')
+        && !slug.ends_with('-')
+        && !RESERVED_IDS.contains(&slug)
+}
+
+/// Starts the election described by the form for `account` and returns it.
+fn create_election(
+    state: &ElectionDB,
+    form: ElectionCreation,
+    account: Option<String>,
+) -> Result<ElectionProcess, (StatusCode, &'static str)> {
+    let id = match form
+        .slug
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
     {
-        match &step_type[..] {
-            "next" => {
-                election.phase = match election.phase {
-                    ElectionPhase::FirstVote => ElectionPhase::FirstTally,
-                    ElectionPhase::FirstTally => ElectionPhase::SecondVote,
-                    ElectionPhase::SecondVote => ElectionPhase::SecondTally,
-                    ElectionPhase::SecondTally => ElectionPhase::SafetyRound,
-                    ElectionPhase::SafetyRound => ElectionPhase::SafetyRound,
-                };
-                Ok(())
-            }
-            "prev" => {
-                election.phase = match election.phase {
-                    ElectionPhase::FirstVote => ElectionPhase::FirstVote,
-                    ElectionPhase::FirstTally => ElectionPhase::FirstVote,
-                    ElectionPhase::SecondVote => ElectionPhase::FirstTally,
-                    ElectionPhase::SecondTally => ElectionPhase::SecondVote,
-                    ElectionPhase::SafetyRound => ElectionPhase::SecondTally,
-                };
-                Ok(())
+        Some(slug) => {
+            let slug = slug.to_ascii_lowercase();
+            if !is_valid_slug(&slug) {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "Custom ids need 3 to 64 letters, digits or inner dashes",
+                ));
             }
-            "reset" => {
-                if election.phase == ElectionPhase::FirstVote {
-                    election.first_round_id.clear();
-                } else if election.phase == ElectionPhase::SecondVote {
-                    election.second_round_id.clear();
-                }
-                Ok(())
+            if state.id_taken(&slug) {
+                return Err((StatusCode::CONFLICT, "Election id is already taken"));
             }
-            _ => Err((StatusCode::BAD_REQUEST, "Invalid step type")),
-        }?;
-
-        stream
-            .send(ElectionUpdate::PhaseChanged)
-            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Stream send error"))?;
-        Ok((StatusCode::ACCEPTED, [("HX-Refresh", "true")]).into_response())
-    } else {
-        Ok((StatusCode::BAD_REQUEST, [("HX-Refresh", "true")]).into_response())
-    }
-}
-
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
-struct Vote {
-    voter_name: String,
-    vote: u64,
-}
-
-async fn post_election_voting(
-    State(state): State<ElectionDB>,
-    Path(id): Path<String>,
-    Form(form): Form<Vote>,
-) -> Result<Markup, (StatusCode, &'static str)> {
-    let mut db = state
-        .db
-        .lock()
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "DB Lock error"))?;
-    let election = db
-        .get_mut(&id)
-        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
-    match election.phase {
-        ElectionPhase::FirstVote => {
-            election.first_round_id.insert(form.voter_name, form.vote);
+            slug
         }
-        ElectionPhase::SecondVote => {
-            election.second_round_id.insert(form.voter_name, form.vote);
-        }
-        _ => {}
+        None => state.new_election_id(),
+    };
+    let mut election = ElectionProcess::new_and_cleaned(
+        id.clone(),
+        form.elected_role,
+        &form.nominees,
+        state.clock.now(),
+    );
+    if form.nomination.is_some() {
+        election.start_with_nomination();
+    } else if election.nominees.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "No nominees given"));
+    }
+    election.kind = form.kind;
+    election.method = form.method;
+    election.seats = match form.seats.as_deref().map(str::trim) {
+        None | Some("") => 1,
+        Some(seats) => seats
+            .parse::<usize>()
+            .ok()
+            .filter(|s| *s >= 1)
+            .ok_or((StatusCode::BAD_REQUEST, "Invalid number of seats"))?,
+    };
+    election.rounds = match form.rounds.as_deref().map(str::trim) {
+        None | Some("") => 2,
+        Some(rounds) => rounds
+            .parse::<usize>()
+            .ok()
+            .filter(|r| *r >= 1)
+            .ok_or((StatusCode::BAD_REQUEST, "Invalid number of rounds"))?,
+    };
+    election.expected_voters = match form.expected_voters.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(voters) => Some(
+            voters
+                .parse::<usize>()
+                .ok()
+                .filter(|v| *v >= 1)
+                .ok_or((StatusCode::BAD_REQUEST, "Invalid number of voters"))?,
+        ),
+    };
+    election.set_roster(&form.roster);
+    if !election.roster.is_empty() && election.expected_voters.is_none() {
+        election.expected_voters = Some(election.roster.len());
+    }
+    election.require_quorum = form.require_quorum.is_some();
+    election.auto_advance = form.auto_advance.is_some();
+    election.passcode = form
+        .passcode
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty());
+    for url in form
+        .webhooks
+        .lines()
+        .map(str::trim)
+        .filter(|u| !u.is_empty())
+    {
+        webhooks::add(state, &mut election, url)?;
+    }
+    election.slack_webhook = form
+        .slack_webhook
+        .map(|u| u.trim().to_string())
+        .filter(|u| !u.is_empty());
+    election.discord_webhook = form
+        .discord_webhook
+        .map(|u| u.trim().to_string())
+        .filter(|u| !u.is_empty());
+    for url in election
+        .slack_webhook
+        .iter()
+        .chain(&election.discord_webhook)
+    {
+        chat::check_url(url)?;
+    }
+    election.report_recipients = form
+        .report_to
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .map(str::to_string)
+        .collect();
+    election.report_to_roster = form.report_to_roster.is_some();
+    if (!election.report_recipients.is_empty() || election.report_to_roster) && state.mail.is_none()
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "No mail server is configured to email the results",
+        ));
+    }
+    election.meeting = form
+        .meeting
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty());
+    election.post_vote_message = form
+        .post_vote_message
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty());
+    election.circle_role = form.circle_role.as_deref().and_then(CircleRoleRef::parse);
+    election.keep_for = match form.keep_days.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(days) => Some(
+            days.parse::<u64>()
+                .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid number of days to keep"))?
+                .saturating_mul(DAY_SECS),
+        ),
+    };
+    if let Some(previous) = form.follows {
+        let now = state.clock.now();
+        if state.store.modify(&previous, &mut |p| {
+            let follow_ups = p.follow_ups.iter().cloned().chain([id.clone()]).collect();
+            p.set_links(p.follows.clone(), follow_ups, now)
+        }) {
+            election.follows = Some(previous);
+        }
+    }
+    election.facilitator_key = Some(facilitator::new_key(state));
+    election.created_by = account;
+    state.add_election(election.clone());
+    chat::announce_created(state, &election);
+    Ok(election)
+}
+
+async fn post_election_step(
+    Path((id, step_type, step)): Path<(String, String, String)>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let step = ElectionPhase::from_str(&step).map_err(|_e| {
+        (
+            StatusCode::BAD_REQUEST,
+            "Phase does not match current phase",
+        )
+    })?;
+    if facilitator_step(&state, &id, &step_type, Some(step))? {
+        Ok((StatusCode::ACCEPTED, [("HX-Refresh", "true")]).into_response())
+    } else {
+        Ok((StatusCode::BAD_REQUEST, [("HX-Refresh", "true")]).into_response())
     }
-    state
-        .streams
+}
+
+/// Takes a step like `next` or `reset` for the facilitator, unless the
+/// election is no longer in the phase `from`, and tells everyone following
+/// it, including the voters whose votes it removed. Returns whether the step
+/// was taken.
+fn facilitator_step(
+    state: &ElectionDB,
+    id: &str,
+    step_type: &str,
+    from: Option<ElectionPhase>,
+) -> Result<bool, (StatusCode, &'static str)> {
+    let removed = state.modify_election(id, |election| {
+        if election.closed_at.is_some() {
+            return Err(process_error(ProcessError::Closed));
+        }
+        if from.is_some_and(|from| from != election.phase) {
+            return Ok(None);
+        }
+        // the schedule still closes a round that waits for voters
+        if step_type == "next" && !election.quorum_reached() {
+            return Err(process_error(ProcessError::QuorumNotReached));
+        }
+        let phase = election.phase;
+        let voters = election.current_round().keys().cloned().collect::<Vec<_>>();
+        step_election(state, election, step_type)?;
+        Ok(Some(removed_voters(election, phase, voters)))
+    })?;
+
+    let Some(removed) = removed else {
+        return Ok(false);
+    };
+    state.notify(id, ElectionUpdate::PhaseChanged)?;
+    for voter in removed {
+        state.notify_voter(id, &voter, ElectionUpdate::VoteRemoved)?;
+    }
+    Ok(true)
+}
+
+/// Who of `voters` lost their ballot of the round of `phase`, e.g. by a reset
+/// or an undo, so they can be asked to vote again.
+fn removed_voters(
+    election: &ElectionProcess,
+    phase: ElectionPhase,
+    voters: Vec<String>,
+) -> Vec<String> {
+    // moving to another round does not remove anyone's vote
+    if election.phase != phase {
+        return vec![];
+    }
+    let round = election.current_round();
+    voters
+        .into_iter()
+        .filter(|voter| !round.contains_key(voter))
+        .collect()
+}
+
+/// Takes a step like `next` or `undo`, for the facilitator and the schedule.
+fn step_election(
+    state: &ElectionDB,
+    election: &mut ElectionProcess,
+    step_type: &str,
+) -> Result<(), (StatusCode, &'static str)> {
+    match step_type {
+        "next" if election.next_phase() == ElectionPhase::Completed => {
+            Err((StatusCode::CONFLICT, "Choose who was elected"))
+        }
+        "next" if election.phase == ElectionPhase::Nomination && election.nominees.is_empty() => {
+            Err((StatusCode::CONFLICT, "Nobody was nominated yet"))
+        }
+        "next" => {
+            election.step_next(state.clock.now());
+            Ok(())
+        }
+        "prev" => {
+            election.step_prev(state.clock.now());
+            Ok(())
+        }
+        "reset" => {
+            election.reset_votes(state.clock.now());
+            Ok(())
+        }
+        "undo" => election.undo().map(|_| ()).map_err(process_error),
+        "close" => {
+            election.close(state.clock.now()).map_err(process_error)?;
+            report::send(state, election);
+            Ok(())
+        }
+        _ => Err((StatusCode::BAD_REQUEST, "Invalid step type")),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Completion {
+    nominee: u64,
+}
+
+/// Ends the safety round with the nominee the facilitator confirmed.
+async fn post_election_complete(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    Form(completion): Form<Completion>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let election = state.modify_election(&id, |election| {
+        election
+            .complete(completion.nominee, state.clock.now())
+            .map_err(process_error)?;
+        Ok(election.clone())
+    })?;
+    // not while changing the election, as saving the circles may need the
+    // store itself
+    circles::record_role_holder(&state, &election);
+    state.notify(&id, ElectionUpdate::PhaseChanged)?;
+    Ok((StatusCode::ACCEPTED, [("HX-Refresh", "true")]))
+}
+
+fn process_error(error: ProcessError) -> (StatusCode, &'static str) {
+    let status = match error {
+        ProcessError::UnknownNominee
+        | ProcessError::InvalidRating
+        | ProcessError::InvalidSchedule
+        | ProcessError::ReasonRequired
+        | ProcessError::UnknownObjection
+        | ProcessError::InvalidScore
+        | ProcessError::InvalidRanking => StatusCode::BAD_REQUEST,
+        ProcessError::InvalidToken | ProcessError::NotOnRoster => StatusCode::FORBIDDEN,
+        ProcessError::NoBallot => StatusCode::NOT_FOUND,
+        ProcessError::NotInVotingPhase
+        | ProcessError::NotFinished
+        | ProcessError::FeedbackClosed
+        | ProcessError::IncompleteHistory
+        | ProcessError::NothingToUndo
+        | ProcessError::Closed
+        | ProcessError::NotOpenYet
+        | ProcessError::NotInSafetyRound
+        | ProcessError::NoTie
+        | ProcessError::NoRunoff
+        | ProcessError::NoRoster
+        | ProcessError::QuorumNotReached
+        | ProcessError::AlreadyElected
+        | ProcessError::NotInNomination
+        | ProcessError::WriteInNotAllowed
+        | ProcessError::UnresolvedObjections => StatusCode::CONFLICT,
+    };
+    (status, error_message(error))
+}
+
+/// The message of `error` as the handlers answer with it, made static once
+/// for each kind of error.
+fn error_message(error: ProcessError) -> &'static str {
+    static MESSAGES: OnceLock<Mutex<HashMap<ProcessError, &'static str>>> = OnceLock::new();
+    let mut messages = MESSAGES
+        .get_or_init(Default::default)
         .lock()
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Stream Lock error"))?
+        .unwrap_or_else(|e| e.into_inner());
+    messages
+        .entry(error)
+        .or_insert_with(|| error.to_string().leak())
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Vote {
+    /// Taken from the token instead if there is one.
+    #[serde(default)]
+    voter_name: String,
+    /// The token of a voting link.
+    #[serde(default)]
+    token: Option<String>,
+    /// Missing for score ballots.
+    vote: Option<u64>,
+    #[serde(default)]
+    reason: Option<String>,
+    /// Set to replace an earlier ballot of the same name.
+    #[serde(default)]
+    update: Option<String>,
+    /// A new nominee to vote for instead of `vote`, during the first vote.
+    #[serde(default)]
+    write_in: Option<String>,
+    /// Comma separated nominee ids in order of preference, for ranked
+    /// elections.
+    #[serde(default)]
+    ranking: Option<String>,
+    /// Comma separated `id:score` pairs, for score elections.
+    #[serde(default)]
+    scores: Option<String>,
+    /// Refuses to replace an earlier ballot of the name, as the vote is not
+    /// known to come from the same voter.
+    #[serde(skip)]
+    protect_earlier: bool,
+}
+
+/// Records a vote and returns the post-vote message and the ballot receipt.
+fn cast_vote(
+    state: &ElectionDB,
+    id: &str,
+    vote: Vote,
+) -> Result<(String, String), (StatusCode, &'static str)> {
+    let ranking = vote
+        .ranking
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .filter(|id| !id.trim().is_empty())
+        .map(|id| id.trim().parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid ranking"))?;
+    let scores = vote
+        .scores
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .filter(|pair| !pair.trim().is_empty())
+        .map(|pair| {
+            let (id, score) = pair.split_once(':')?;
+            Some((
+                id.trim().parse::<u64>().ok()?,
+                score.trim().parse::<u8>().ok()?,
+            ))
+        })
+        .collect::<Option<HashMap<_, _>>>()
+        .ok_or((StatusCode::BAD_REQUEST, "Invalid scores"))?;
+    let random = state.random.as_ref();
+    let mut ballot = match (vote.vote, ranking.is_empty(), scores.is_empty()) {
+        (_, false, _) => Ballot::ranked(ranking, random),
+        (_, _, false) => Ballot::scored(scores, random),
+        (Some(vote), _, _) => Ballot::new(vote, random),
+        (None, _, _) => return Err((StatusCode::BAD_REQUEST, "No vote given")),
+    };
+    let receipt = ballot.receipt.clone();
+    let write_in = vote.write_in.filter(|w| !w.trim().is_empty());
+    let (message, written_in, advanced) = state.modify_election(id, |election| {
+        let now = state.clock.now();
+        if vote.protect_earlier && vote.token.is_none() {
+            let earlier = election.roster_name(&vote.voter_name).is_some_and(|name| {
+                election
+                    .voting_round_ballots()
+                    .is_ok_and(|round| round.contains_key(&name))
+            });
+            if earlier {
+                return Err((
+                    StatusCode::CONFLICT,
+                    "Someone already voted with this name; only they can change the vote",
+                ));
+            }
+        }
+        let mut written_in = false;
+        let before = election.clone();
+        if let Some(name) = &write_in {
+            (ballot.vote, written_in) = election.write_in(name, now).map_err(process_error)?;
+            // the written in nominee replaces the first choice
+            if let Some(first) = ballot.ranking.first_mut() {
+                *first = ballot.vote;
+            }
+            if !ballot.scores.is_empty() {
+                ballot.scores.insert(ballot.vote, MAX_SCORE);
+            }
+        }
+        let added = match &vote.token {
+            Some(token) => election
+                .vote_with_token(token, ballot, vote.reason, now)
+                .map(|_| ()),
+            None => election.add_vote(vote.voter_name, ballot, vote.reason, now),
+        };
+        if let Err(error) = added {
+            // no nominee without the vote that wrote them in
+            *election = before;
+            return Err(process_error(error));
+        }
+        let message = election
+            .post_vote_message
+            .clone()
+            .unwrap_or("Vote added!".to_string());
+        let advanced = election.auto_advance && election.everyone_voted();
+        if advanced {
+            election.step_next(now);
+        }
+        Ok((message, written_in, advanced))
+    })?;
+    if written_in {
+        state.notify(id, ElectionUpdate::NomineesChanged)?;
+    }
+    state.notify(id, ElectionUpdate::VotesChanged)?;
+    if advanced {
+        state.notify(id, ElectionUpdate::PhaseChanged)?;
+    }
+    Ok((message, receipt))
+}
+
+async fn post_election_voting(
+    State(state): State<ElectionDB>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Form(mut form): Form<Vote>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let election = state
+        .store
         .get(&id)
-        .ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Stream not found"))?
-        .send(ElectionUpdate::VotesChanged)
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Stream send error"))?;
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    let warn = |warning: Markup| {
+        (
+            [("HX-Retarget", "#vote-warning"), ("HX-Reswap", "innerHTML")],
+            warning,
+        )
+            .into_response()
+    };
+    if let Some(token) = &form.token {
+        match election.token_voter(token) {
+            Some(voter_name) => form.voter_name = voter_name.to_string(),
+            None => {
+                return Ok(warn(html! {
+                    p { mark { "This voting link was already used." } }
+                }))
+            }
+        }
+    }
+    match election.roster_name(&form.voter_name) {
+        Some(name) => form.voter_name = name,
+        None => {
+            return Ok(warn(html! {
+                p { mark { (form.voter_name) " is not on the list of eligible voters." } }
+            }))
+        }
+    }
+    let previous = election
+        .voting_round_ballots()
+        .ok()
+        .and_then(|round| round.get(&form.voter_name));
+    // only who voted from this browser gets to see or replace the earlier vote
+    let own = cookies::voted_as(&state, &headers, &id).as_ref() == Some(&form.voter_name);
+    match (previous, &form.update, &form.token) {
+        (Some(_), _, None) if !own => {
+            return Ok(warn(html! {
+                p {
+                    mark { "Someone already voted as " (form.voter_name) "." }
+                    br; small { "Only they can change the vote, from the browser they voted with." }
+                }
+            }))
+        }
+        (Some(previous), None, None) => {
+            return Ok(warn(html! {
+                input type="hidden" name="update" value="true" {}
+                p {
+                    mark {
+                        "You already voted (for " (ballot_summary(&election, previous)) ") — update your vote?"
+                    }
+                    br; small { "Press the button again to replace it." }
+                }
+            }))
+        }
+        _ => {}
+    }
+    form.protect_earlier = !own;
+    let cookie = cookies::voter_cookie(&state, &id, &form.voter_name);
+    let (message, receipt) = cast_vote(&state, &id, form)?;
+
+    Ok((
+        [(SET_COOKIE, cookie)],
+        html! {
+            p { (message) }
+            p {
+                "Your receipt: " code { (receipt) } br;
+                a href={"/election/" (id) "/voting/check?receipt=" (receipt)} target="_blank" {
+                    "Did my vote count?"
+                }
+                " · "
+                a href={"/election/" (id) "/voting/mine"} { "Change or withdraw my vote" }
+            }
+        },
+    )
+        .into_response())
+}
+
+#[derive(Deserialize, Debug)]
+struct ReceiptQuery {
+    receipt: Option<String>,
+}
+
+async fn view_election_vote_check(
+    Path(id): Path<String>,
+    Query(query): Query<ReceiptQuery>,
+    State(state): State<ElectionDB>,
+) -> Result<Markup, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let receipt = query
+        .receipt
+        .map(|r| r.trim().to_ascii_uppercase())
+        .filter(|r| !r.is_empty());
+    let found = receipt.as_ref().map(|receipt| {
+        election
+            .current_round()
+            .iter()
+            .find(|(_, b)| &b.receipt == receipt)
+            .map(|(name, _)| name.clone())
+    });
+
+    Ok(base_html(
+        format!("{} - Vote Check", election.elected_role).as_str(),
+        html! { (election.elected_role) br; "Did my vote count?" },
+        html! {
+            @match found {
+                Some(Some(voter_name)) => article {
+                    p { "✅ A ballot by " strong { (voter_name) } " is recorded in the current round ("
+                        (election.phase_title()) ")." }
+                },
+                Some(None) => article {
+                    p { "❌ No ballot with this receipt exists in the current round ("
+                        (election.phase_title()) ")." }
+                    p { "If you voted again or the votes were reset, your old receipt is no longer valid." }
+                },
+                None => {},
+            }
+            form #"vote-check" ."table rows" method="get" {
+                label for="receipt" {
+                    "Receipt: ";
+                    input type="text" name="receipt" required {}
+                }
+                button type="submit" style="left: 50%; position: relative; translate: -50%;" {
+                    "Check"
+                }
+            }
+        },
+        html!(strong { a href={"/election/" (id) "/voting"} ."secondary" {(id)} }),
+    ))
+}
+
+const MANUAL_VOTE_ROWS: usize = 8;
+
+async fn post_election_manual_votes(
+    State(state): State<ElectionDB>,
+    Path(id): Path<String>,
+    Form(form): Form<Vec<(String, String)>>,
+) -> Result<Markup, (StatusCode, &'static str)> {
+    let names = form
+        .iter()
+        .filter(|(k, _)| k == "voter_name")
+        .map(|(_, v)| v);
+    let votes = form.iter().filter(|(k, _)| k == "vote").map(|(_, v)| v);
+    let entries = names
+        .zip(votes)
+        .map(|(name, vote)| (name.trim(), vote))
+        .filter(|(name, _)| !name.is_empty())
+        .collect::<Vec<_>>();
+
+    let recorded = state.modify_election(&id, |election| {
+        record_manual_votes(&state, election, entries)
+    })?;
+    let added = match recorded {
+        Ok(added) => added,
+        Err(errors) => {
+            return Ok(html! {
+                article #"manual-votes-result" {
+                    p { "No ballots were recorded:" }
+                    ul { @for error in errors { li { (error) } } }
+                }
+            })
+        }
+    };
+
+    state.notify(&id, ElectionUpdate::VotesChanged)?;
 
     Ok(html! {
-        p { "Vote added!" }
+        article #"manual-votes-result" { p { (added) " ballots recorded." } }
     })
 }
 
+/// Records all paper ballots or, if any of them is invalid, none.
+#[allow(clippy::type_complexity)]
+fn record_manual_votes(
+    state: &ElectionDB,
+    election: &mut ElectionProcess,
+    entries: Vec<(&str, &String)>,
+) -> Result<Result<usize, Vec<String>>, (StatusCode, &'static str)> {
+    let nominees = election.nominees.keys().copied().collect::<Vec<_>>();
+    let round = election.voting_round_ballots().map_err(process_error)?;
+
+    let mut errors = Vec::new();
+    let mut ballots: Vec<(String, Ballot)> = Vec::new();
+    for (name, vote) in entries {
+        match vote.parse::<u64>() {
+            Ok(vote) if nominees.contains(&vote) => {
+                if round.contains_key(name) {
+                    errors.push(format!("{} has already voted in this round", name));
+                } else if ballots.iter().any(|(n, _)| n == name) {
+                    errors.push(format!("{} was entered more than once", name));
+                } else {
+                    ballots.push((
+                        name.to_string(),
+                        Ballot::manual(vote, state.random.as_ref()),
+                    ));
+                }
+            }
+            _ => errors.push(format!("{} has an invalid vote", name)),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Ok(Err(errors));
+    }
+
+    let added = ballots.len();
+    let now = state.clock.now();
+    for (name, ballot) in ballots {
+        election
+            .add_vote(name, ballot, None, now)
+            .map_err(process_error)?;
+    }
+    Ok(Ok(added))
+}
+
+fn manual_votes_form(election: &ElectionProcess) -> Markup {
+    html! {
+        details {
+            summary { "Enter paper ballots" }
+            form #"manual-votes" {
+                (roster_datalist(election))
+                table {
+                    thead { tr { th { "Voter Name" } th { "Vote" } } }
+                    tbody {
+                        @for _ in 0..MANUAL_VOTE_ROWS {
+                            tr {
+                                td { input type="text" name="voter_name" list=[roster_list(election)] {} }
+                                td {
+                                    select name="vote" {
+                                        (nominee_options(election))
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                button
+                  hx-post={"/election/" (election.id.to_string()) "/voting/manual"}
+                  hx-trigger="click" hx-target="#manual-votes-result" hx-swap="outerHTML" {
+                    "Record Ballots"
+                }
+            }
+            div #"manual-votes-result" {}
+        }
+    }
+}
+
 async fn get_election_join(Query(params): Query<HashMap<String, String>>) -> impl IntoResponse {
     let id = params.get("election_id");
     match id {
@@ -330,24 +1970,63 @@ async fn get_election_eval_content(
     Path(id): Path<String>,
     State(state): State<ElectionDB>,
 ) -> Result<Markup, StatusCode> {
-    let db = state
-        .db
-        .lock()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let election = db.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
 
     Ok(eval_election(election))
 }
 
+async fn get_election_stats(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<Json<ElectionStatistics>, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(election.statistics(state.clock.now())))
+}
+
+/// Tallies without any voter names, for dashboards polling the results. The
+/// ETag lets them skip unchanged responses, and pages on other sites like
+/// wikis may fetch them too.
+async fn get_election_results_json(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, StatusCode> {
+    let body = {
+        let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+        election_results_json(election).to_string()
+    };
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    std::hash::Hash::hash(&body, &mut hasher);
+    let etag = format!("\"{:016x}\"", std::hash::Hasher::finish(&hasher));
+
+    let cache_headers = [
+        (axum::http::header::ETAG, etag.clone()),
+        (axum::http::header::CACHE_CONTROL, "no-cache".to_string()),
+        (
+            axum::http::header::ACCESS_CONTROL_ALLOW_ORIGIN,
+            "*".to_string(),
+        ),
+    ];
+    let not_modified = headers
+        .get(axum::http::header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|t| t.trim() == etag || t.trim() == "*"));
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+    Ok((
+        cache_headers,
+        [(CONTENT_TYPE, "application/json".to_string())],
+        body,
+    )
+        .into_response())
+}
+
 async fn view_election_eval(
     Path(id): Path<String>,
     State(state): State<ElectionDB>,
 ) -> Result<Markup, StatusCode> {
-    let db = state
-        .db
-        .lock()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let election = db.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
     let voting_path = format!("/election/{}/voting", id);
     let voting_url = format!("{}{}", &state.base_url, voting_path);
     let qrcode_svg = QrCode::with_error_correction_level(voting_url.as_bytes(), qrcode::EcLevel::H)
@@ -362,12 +2041,61 @@ async fn view_election_eval(
         format!("{} - Evaluation", election.elected_role).as_str(),
         html! { (election.elected_role) br; "Evaluation" },
         html! {
-            div hx-ext="sse" sse-connect={"/election/" (id.to_string()) "/stream"} {
-                div #"eval"
-                  hx-get={"/election/" (id.to_string()) "/eval/content"}
-                  hx-trigger="sse:phase-changed,sse:votes-changed"
-                  hx-swap="innerHTML" {
-                    {(eval_election(election))}
+            @if election.closed_at.is_some() {
+                (eval_election(election))
+                (linked_elections(election, state.store.as_ref()))
+                (reuse_links(election))
+            } @else {
+                div hx-ext="sse" sse-connect={"/election/" (id.to_string()) "/stream"} {
+                    div #"eval"
+                      hx-get={"/election/" (id.to_string()) "/eval/content"}
+                      hx-trigger="sse:phase-changed,sse:nominees-changed,sse:resync,reload"
+                      hx-swap="innerHTML" {
+                        {(eval_election(election))}
+                    }
+                    (live_votes())
+                    (presence::presence(presence::connected(&state, &id)))
+                }
+                (linked_elections(election, state.store.as_ref()))
+                @if election.phase == ElectionPhase::FirstVote || election.phase == ElectionPhase::SecondVote {
+                    br;
+                    (manual_votes_form(election))
+                }
+                (photos::photos_form(election))
+                @if election.facilitator_key.is_some() {
+                    (facilitator::co_facilitators(election))
+                }
+                (webhooks::webhooks_form(election))
+                (reuse_links(election))
+                @if election.facilitator_key.is_some() {
+                    p {
+                        small {
+                            "Keep the link to facilitate from another device: "
+                            a href=(facilitator::eval_path(election)) { "facilitator link" }
+                        }
+                    }
+                }
+                p {
+                    small {
+                        "For a projector or people who should only watch: "
+                        a href={"/election/" (id) "/observe"} target="_blank" { "observer link" }
+                        " · To embed the live results in an intranet or wiki page: "
+                        a href={"/election/" (id) "/embed"} target="_blank" { "widget" }
+                    }
+                }
+                p {
+                    small {
+                        "For the records: "
+                        a href={"/election/" (id) "/export.csv"} download { "all votes as CSV" }
+                        " · "
+                        a href={"/election/" (id) "/export.json"} download { "the whole election as JSON" }
+                        @if election.phase == ElectionPhase::Completed || election.closed_at.is_some() {
+                            " · "
+                            a href={"/election/" (id) "/archive.html"} download { "an HTML archive of the results" }
+                            " · "
+                            a href={"/election/" (id) "/report.pdf"} { "a PDF report to print and sign" }
+                        }
+                    }
                 }
             }
         },
@@ -380,6 +2108,20 @@ async fn view_election_eval(
                     (maud::PreEscaped(qrcode_svg))
                     br; br;
                     a ."contrast" href=(voting_path) { (voting_url) }
+                    br; br;
+                    a ."secondary" href={"/election/" (id) "/ballots"} target="_blank" {
+                        "Print paper ballots"
+                    }
+                    " · "
+                    a ."secondary" href={"/election/" (id) "/kiosk"} target="_blank" {
+                        "Vote on a shared device"
+                    }
+                    @if let Some(meeting) = &election.meeting {
+                        " · "
+                        a ."secondary" href={"/meeting/" (url_encode(meeting))} target="_blank" {
+                            "Meeting dashboard"
+                        }
+                    }
                     footer {
                         button style="margin-right:unset;"
                             onclick="document.getElementById('share-dialog').close()" { "Close" }
@@ -394,10 +2136,83 @@ async fn view_election_eval(
     ))
 }
 
+/// Receives the `votes-changed` events for live.js, which patches `#eval` with
+/// their data or reloads it.
+fn live_votes() -> Markup {
+    html! {
+        div hidden hx-trigger="sse:votes-changed" {}
+        script src="/live.js" {}
+    }
+}
+
+/// The outcome of a closed election, which no longer changes.
+fn closed_results(election: &ElectionProcess) -> Markup {
+    let all_with_max_votes = election
+        .accumulated_votes(election.final_round())
+        .all_with_max_votes();
+    let closed_at = election.closed_at.map(UtcDateTime::from_unix);
+    html! {
+        h2 { "Closed" }
+        p {
+            "This election was closed"
+            @if let Some(at) = closed_at {
+                " on " (at.date())
+            }
+            " and is kept as a record."
+        }
+        @match &election.elected {
+            Some(_) => p { "Elected: " strong { (elected_names(election)) } },
+            None => p { "The most votes were for: " ( all_with_max_votes.join(", ") ) },
+        }
+    }
+}
+
+/// The names of everyone elected so far, in the order of their seats.
+fn elected_names(election: &ElectionProcess) -> String {
+    election.elected_seats().iter().map(|e| &e.name).join(", ")
+}
+
+/// Who was elected, for everyone once the election is completed.
+fn elected_view(election: &ElectionProcess) -> Markup {
+    let Some(elected) = &election.elected else {
+        return html! {};
+    };
+    html! {
+        article ."elected" {
+            header { (election.phase_title()) }
+            h2 { "🎉 " (elected_names(election)) " 🎉" }
+            p {
+                @if election.seats > 1 { "are the new " } @else { "is the new " }
+                strong { (election.elected_role) }
+                " since " (UtcDateTime::from_unix(elected.at).date_time()) " (UTC)."
+            }
+            (election.phase.nice_description())
+        }
+    }
+}
+
 fn eval_election(election: &ElectionProcess) -> Markup {
-    let buttons = html! {
+    eval_view(election, true)
+}
+
+/// The evaluation page content, without anything changing the election
+/// unless `controls`.
+fn eval_view(election: &ElectionProcess, controls: bool) -> Markup {
+    if election.closed_at.is_some() {
+        return html! {
+            (closed_results(election))
+            @if controls {
+                (feedback::feedback_summary(election))
+            }
+        };
+    }
+
+    let buttons = if !controls {
+        html! {}
+    } else {
+        html! {
         div ."button-grid" {
-            button ."lbut" disabled[election.phase == ElectionPhase::FirstVote]
+            button ."lbut" disabled[election.prev_phase() == election.phase]
             hx-post={"/election/" (election.id.to_string()) "/step/prev/" (election.phase.to_string())}
             hx-trigger="click" hx-swap="none" hx-confirm="Are you sure?" {
                 "Previous Phase"
@@ -410,80 +2225,182 @@ fn eval_election(election: &ElectionProcess) -> Markup {
                 "Reset Votes"
             }
 
-            button ."rbut" disabled[election.phase == ElectionPhase::SafetyRound]
+            button ."rbut"
+            disabled[election.is_last_phase() || election.next_phase() == ElectionPhase::Completed || !election.quorum_reached()]
             hx-post={"/election/" (election.id.to_string()) "/step/next/" (election.phase.to_string())}
             hx-trigger="click" hx-swap="none" hx-confirm="Are you sure?" {
                 "Next Phase"
             }
         }
+        @if election.complete_history && !election.events.is_empty() {
+            a ."secondary" href="#"
+            hx-post={"/election/" (election.id.to_string()) "/step/undo/" (election.phase.to_string())}
+            hx-trigger="click" hx-swap="none" hx-confirm="Undo the last change, e.g. the last vote?" {
+                small { "Undo last change" }
+            }
+        }
+        @if election.is_last_phase() {
+            br;
+            a ."secondary" href="#"
+            hx-post={"/election/" (election.id.to_string()) "/step/close/" (election.phase.to_string())}
+            hx-trigger="click" hx-swap="none"
+            hx-confirm="Close the election? It cannot be changed afterwards." {
+                small { "Close election" }
+            }
+        }
+        }
     };
 
     if election.phase == ElectionPhase::SafetyRound {
-        let accumulated_votes = election
-            .second_round_id
-            .iter()
-            .into_group_map_by(|(_, &v)| v)
-            .iter()
-            .map(|(k, v)| (election.nominees.get(k).unwrap(), v.len()))
-            .filter(|(_k, v)| *v > 0)
-            .sorted_by(|a, b| Ord::cmp(&a.1, &b.1).then_with(|| Ord::cmp(&a.0, &b.0).reverse()))
-            .rev()
-            .collect::<Vec<_>>();
-        let max_votes = accumulated_votes
-            .iter()
-            .map(|(_k, v)| *v)
-            .max()
-            .unwrap_or(1);
-        let all_with_max_votes = accumulated_votes
-            .iter()
-            .filter(|(_k, v)| *v == max_votes)
-            .map(|(k, _v)| k.to_string())
-            .collect::<Vec<_>>();
+        let most_voted = election.proposed.or(election.next_seat_nominee());
+        let last_seat = election.current_seat() >= election.seats;
         return html! {
-            h2 { (election.phase.nice_title()) }
+            h2 { (election.phase_title()) }
             p { (election.phase.nice_description()) }
-            p { "The most votes were for: " ( all_with_max_votes.join(", ") ) }
+            @if election.seats > 1 {
+                (safety::seats_overview(election))
+            }
+            (safety::proposal(election))
+            @if controls {
+                (safety::proposal_form(election))
+            }
+            (safety::safety_summary(election))
+
+            @if controls {
+            form #"complete" ."table rows" {
+                label for="nominee" {
+                    "Elected: ";
+                    select name="nominee" required {
+                        @for (nominee_id, nominee) in election.nominees.iter()
+                            .filter(|(id, _)| election.on_ballot(**id) && !election.is_seated(**id))
+                            .sorted_by_key(|(_, n)| n.to_lowercase()) {
+                            option value=(nominee_id) selected[most_voted == Some(*nominee_id)] { (nominee) }
+                        }
+                    }
+                }
+                button disabled[election.open_objections() > 0]
+                  hx-post={"/election/" (election.id) "/complete"}
+                  hx-trigger="click" hx-swap="none"
+                  hx-confirm={
+                      @if last_seat { "Complete the election with this nominee elected?" }
+                      @else { "Confirm this nominee for seat " (election.current_seat()) "?" }
+                  } {
+                    @if last_seat { "Complete Election" } @else { "Confirm Seat " (election.current_seat()) }
+                }
+            }
+            }
 
             {( buttons )}
         };
     }
 
-    let tally = eval_tally(election);
-
-    let eval_count = {
-        match election.phase {
-            ElectionPhase::FirstVote | ElectionPhase::FirstTally => {
-                html! { p { "Number of votes: " (election.first_round_id.len()) } }
-            }
-            ElectionPhase::SecondVote | ElectionPhase::SecondTally => {
-                html! { p { "Number of votes: " (election.second_round_id.len()) } }
+    if election.phase == ElectionPhase::Nomination {
+        return html! {
+            h2 { (election.phase_title()) }
+            @if controls {
+                (nomination::nomination_review(election))
+            } @else {
+                p { "Nominations so far:" }
+                ul {
+                    @for nominee in election.nominees.values().sorted_by_key(|n| n.to_lowercase()) {
+                        li { (nominee) }
+                    }
+                }
+            }
+            {( buttons )}
+        };
+    }
+
+    if election.phase == ElectionPhase::Completed {
+        return html! {
+            (elected_view(election))
+
+            {( buttons )}
+            @if controls {
+                br;
+                a role="button" ."secondary outline" href={"/election/" (election.id) "/follow-up"} {
+                    "Elect a backup/deputy next"
+                }
+
+                (feedback::feedback_summary(election))
+            }
+        };
+    }
+
+    let tally = eval_tally(election);
+
+    let eval_count = {
+        let count = match election.phase {
+            ElectionPhase::Nomination => unreachable!(),
+            ElectionPhase::FirstVote | ElectionPhase::FirstTally => election.first_round_id.len(),
+            ElectionPhase::SecondVote | ElectionPhase::SecondTally => {
+                election.second_round_id.len()
+            }
+            ElectionPhase::SafetyRound | ElectionPhase::Completed => unreachable!(),
+        };
+        match election.expected_voters {
+            Some(expected) => html! {
+                p {
+                    span ."vote-count" { (count) } " of " (expected) " voted"
+                    @if !election.quorum_reached() {
+                        br; small { "The next phase waits until everyone expected voted." }
+                    } @else if election.auto_advance && election.voting_round().is_ok() {
+                        br; small { "The tally opens once everyone expected voted." }
+                    }
+                }
+            },
+            None => html! { p { "Number of votes: " span ."vote-count" { (count) } } },
+        }
+    };
+    let eval_count = html! {
+        (eval_count)
+        @if let Some(turnout) = election.turnout() {
+            p {
+                "Turnout: " span ."turnout" { (format!("{:.0}", turnout)) } "% of the eligible voters"
+                @if controls {
+                    " · "
+                    a ."secondary" href={"/election/" (election.id) "/tokens"} { "Voting links" }
+                }
             }
-            ElectionPhase::SafetyRound => unreachable!(),
         }
     };
 
     html! {
-        h2 { (election.phase.nice_title()) }
+        h2 { (election.phase_title()) }
+        (round_note(election))
 
-        {( eval_count )}
+        // marks what live.js may patch on new votes
+        div data-live-phase=[live_patchable(election).then(|| election.phase.to_string())]
+          data-live-quorum=(election.quorum_reached()) {
+            {( eval_count )}
 
-        {( tally )}
+            {( tally )}
+        }
+
+        @if controls {
+            (tie_break::tie_break_actions(election))
+        }
 
         {( buttons )}
+
+        @if controls && !election.is_last_phase() {
+            br;
+            (schedule::schedule_form(election))
+        }
+
+        @if controls && election.is_last_phase() {
+            (feedback::feedback_summary(election))
+        }
     }
 }
 
 fn eval_tally(election: &ElectionProcess) -> Markup {
-    let round = match election.phase {
-        ElectionPhase::FirstVote => &election.first_round_id,
-        ElectionPhase::FirstTally => &election.first_round_id,
-        ElectionPhase::SecondVote => &election.second_round_id,
-        ElectionPhase::SecondTally => &election.second_round_id,
-        ElectionPhase::SafetyRound => &election.second_round_id,
-    };
+    let round = election.current_round();
 
+    // Straw polls show the tally live while voting.
     if !(election.phase == ElectionPhase::FirstTally
-        || election.phase == ElectionPhase::SecondTally)
+        || election.phase == ElectionPhase::SecondTally
+        || election.kind == ElectionKind::StrawPoll)
     {
         return html! {
             p { "The following users have voted:" }
@@ -492,24 +2409,10 @@ fn eval_tally(election: &ElectionProcess) -> Markup {
                     li { (voter_name) }
                 }
             }
+            (voter_checklist(election))
         };
     }
 
-    let accumulated_votes = round
-        .iter()
-        .into_group_map_by(|(_, &v)| v)
-        .iter()
-        .map(|(k, v)| (election.nominees.get(k).unwrap(), v.len()))
-        .filter(|(_k, v)| *v > 0)
-        .sorted_by(|a, b| Ord::cmp(&a.1, &b.1).then_with(|| Ord::cmp(&a.0, &b.0).reverse()))
-        .rev()
-        .collect::<Vec<_>>();
-    let max_votes = accumulated_votes
-        .iter()
-        .map(|(_k, v)| *v)
-        .max()
-        .unwrap_or(1);
-
     html! {
         br;
         details open {
@@ -522,196 +2425,1164 @@ fn eval_tally(election: &ElectionProcess) -> Markup {
                     }
                 }
                 tbody {
-                    @for (voter_name, vote) in round.iter().sorted_by_key(|(n, _)| &n[..]) {
+                    @for (voter_name, ballot) in round.iter().sorted_by_key(|(n, _)| &n[..]) {
+                        tr {
+                            td {
+                                (voter_name)
+                                @if ballot.manually_entered {
+                                    small title="Entered manually by the facilitator" { " (paper)" }
+                                }
+                            }
+                            td { (ballot_summary(election, ballot)) }
+                        }
+                    }
+                }
+            }
+        }
+        br;
+        @if election.method == VotingMethod::Ranked {
+            (runoff_rounds(election, round))
+            (pairwise_comparison(election, round))
+        }
+        (vote_reasons(election, round))
+        @if election.phase == ElectionPhase::SecondTally {
+            (vote_changes(election))
+        }
+        (tally_chart(election, round))
+        br;
+    }
+}
+
+/// The votes per nominee of `round` as bar chart, grouped like the nominees.
+fn tally_chart(election: &ElectionProcess, round: &HashMap<String, Ballot>) -> Markup {
+    let accumulated_votes = election.accumulated_votes(round);
+    let max_votes = accumulated_votes.max_votes();
+    let grouped_votes = accumulated_votes
+        .votes
+        .iter()
+        .into_group_map_by(|(votee, _)| election.group_of_nominee_named(votee))
+        .into_iter()
+        .sorted_by(|a, b| a.0.cmp(&b.0))
+        .collect::<Vec<_>>();
+
+    html! {
+        @if election.method == VotingMethod::Score {
+            (score_chart(election, round))
+        } @else {
+            div #"eval-chart" {
+                @for (group, votes) in grouped_votes {
+                    @if let Some(group) = group {
+                        h3 { (group) }
+                    }
+                    table
+                        ."charts-css bar show-labels data-spacing-1 data-start show-data-on-hover"
+                        style="--labels-size: 10em;" {
+                        thead {
+                            tr {
+                                th { "Nominee" }
+                                th { "Votes" }
+                            }
+                        }
+                        tbody {
+                            @for (votee, vote_count) in votes {
+                                tr {
+                                    th scope="row" { (photos::nominee_photo_named(election, votee)) " " (votee) }
+                                    td data-nominee=(votee) style={"--size: " (*vote_count as f32 / (max_votes as f32))}{
+                                        span ."data" {(vote_count)}
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct BallotQuery {
+    count: Option<usize>,
+}
+
+const MAX_PRINTED_BALLOTS: usize = 200;
+
+async fn view_election_ballots(
+    Path(id): Path<String>,
+    Query(query): Query<BallotQuery>,
+    State(state): State<ElectionDB>,
+) -> Result<Markup, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let count = query.count.unwrap_or(1).clamp(1, MAX_PRINTED_BALLOTS);
+    let sorted_nominees = election
+        .nominees
+        .iter()
+        .filter(|(id, _)| election.on_ballot(**id))
+        .sorted_by_key(|(k, _)| *k)
+        .collect::<Vec<_>>();
+
+    Ok(base_html(
+        format!("{} - Ballots", election.elected_role).as_str(),
+        html! { (election.elected_role) br; "Paper Ballots" },
+        html! {
+            form ."no-print" method="get" {
+                label for="count" {
+                    "Number of ballots: ";
+                    input type="number" name="count" min="1" max=(MAX_PRINTED_BALLOTS) value=(count) {}
+                }
+                button type="submit" { "Update" }
+                button type="button" ."secondary" onclick="window.print()" { "Print" }
+            }
+            @for _ in 0..count {
+                article ."ballot" {
+                    header {
+                        strong { "Ballot: " (election.elected_role) }
+                        span ."ballot-id" { "Election " (election.id) }
+                    }
+                    p { "Voter Name: " span ."ballot-blank" {} }
+                    p { "Mark exactly one nominee:" }
+                    ol {
+                        @for (_, nominee) in &sorted_nominees {
+                            li { span ."ballot-box" {} (nominee) }
+                        }
+                    }
+                }
+            }
+        },
+        html! {},
+    ))
+}
+
+async fn view_election_voting(
+    Path(id): Path<String>,
+    Query(query): Query<VotingFormQuery>,
+    State(state): State<ElectionDB>,
+    headers: HeaderMap,
+) -> Result<Markup, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let voter = cookies::voted_as(&state, &headers, &id);
+
+    Ok(base_html_with_head(
+        election.elected_role.as_str(),
+        oembed::link_preview(&state, election),
+        html! {(election.elected_role.as_str())},
+        html! {
+            @if election.closed_at.is_some() {
+                (closed_results(election))
+            } @else {
+                div hx-ext="sse" sse-connect={"/election/" (id.to_string()) "/stream"} {
+                  p #"vote-removed" hidden hx-trigger="sse:vote-removed" {
+                      mark { "The facilitator removed your vote, please vote again." }
+                  }
+                  div #"vote-content"
+                    hx-get={"/election/" (id.to_string()) "/voting/form"}
+                    hx-trigger="sse:phase-changed,sse:resync,sse:vote-removed"
+                    hx-swap="innerHTML" {
+                      ({ voting_form_for(election, state.clock.now(), voter.as_deref(), query.update) })
+                  }
+                  script {
+                      (maud::PreEscaped("document.addEventListener('sse:vote-removed', () => document.getElementById('vote-removed').hidden = false);"))
+                  }
+                }
+            }
+        },
+        html!(strong { a href={"/election/" (id) "/voting"} ."secondary" {(id)} }),
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+struct VotingFormQuery {
+    #[serde(default)]
+    update: bool,
+}
+
+async fn get_election_voting_form(
+    Path(id): Path<String>,
+    Query(query): Query<VotingFormQuery>,
+    State(state): State<ElectionDB>,
+    headers: HeaderMap,
+) -> Result<Markup, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let voter = cookies::voted_as(&state, &headers, &id);
+    Ok(voting_form_for(
+        election,
+        state.clock.now(),
+        voter.as_deref(),
+        query.update,
+    ))
+}
+
+/// The voting form, or for `voter` who voted in this round from this
+/// browser the question whether to update the vote, unless `update`.
+fn voting_form_for(
+    election: &ElectionProcess,
+    now: u64,
+    voter: Option<&str>,
+    update: bool,
+) -> Markup {
+    let previous = voter.and_then(|voter| {
+        election
+            .voting_round_ballots()
+            .ok()
+            .and_then(|round| round.get(voter))
+    });
+    match (voter, previous) {
+        (Some(voter), Some(previous)) if !update => my_vote::my_vote(election, voter, previous),
+        (Some(voter), Some(_)) => voting_form(election, now, FormVoter::Updating(voter)),
+        _ => voting_form(election, now, FormVoter::Anyone),
+    }
+}
+
+/// Options for a nominee select, with grouped nominees in optgroups.
+fn nominee_options(election: &ElectionProcess) -> Markup {
+    nominee_options_selecting(election, None)
+}
+
+fn nominee_options_selecting(election: &ElectionProcess, selected: Option<u64>) -> Markup {
+    html! {
+        @for (group, nominees) in election.grouped_nominees() {
+            @match group {
+                Some(group) => optgroup label=(group) {
+                    @for (id, nominee) in nominees {
+                        option value=(id.to_string()) selected[selected == Some(id)] { (nominee) }
+                    }
+                },
+                None => @for (id, nominee) in nominees {
+                    option value=(id.to_string()) selected[selected == Some(id)] { (nominee) }
+                },
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct OptionsQuery {
+    vote: Option<u64>,
+}
+
+/// The nominee options, keeping the current choice, for when nominees were
+/// written in.
+async fn get_election_voting_options(
+    Path(id): Path<String>,
+    Query(query): Query<OptionsQuery>,
+    State(state): State<ElectionDB>,
+) -> Result<Markup, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(nominee_options_selecting(election, query.vote))
+}
+
+#[derive(Deserialize, Debug)]
+struct PreviousVoteQuery {
+    voter_name: Option<String>,
+}
+
+/// The vote fields of the second round, prefilled with the voter's first
+/// round vote.
+async fn get_election_voting_previous(
+    Path(id): Path<String>,
+    Query(query): Query<PreviousVoteQuery>,
+    State(state): State<ElectionDB>,
+) -> Result<Markup, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let previous = query
+        .voter_name
+        .and_then(|name| election.previous_round().get(name.trim()));
+    Ok(second_vote_fields(election, previous))
+}
+
+fn second_vote_fields(election: &ElectionProcess, previous: Option<&Ballot>) -> Markup {
+    html! {
+        div #"vote-fields" {
+            @if let Some(previous) = previous {
+                p {
+                    "In the previous round you voted for " strong { (ballot_summary(election, previous)) }
+                    ". Do you want to change your vote, and why?"
+                }
+            }
+            @match election.method {
+                VotingMethod::Ranked => {
+                    (ranking_fields(election, previous.map_or(&[], |b| b.preferences())))
+                },
+                VotingMethod::Score => {
+                    (score_fields(election, &previous.map(|b| b.scores.clone()).unwrap_or_default()))
+                },
+                VotingMethod::Plurality => label for="vote" {
+                    "Vote :";
+                    select name="vote" required {
+                        (nominee_options_selecting(election, previous.map(|b| b.vote)))
+                    }
+                },
+            }
+            label for="reason" {
+                @match previous {
+                    Some(_) => "Why did you change or keep your vote? ",
+                    None => "Reason: ",
+                }
+                textarea name="reason" rows="2" {}
+            }
+        }
+    }
+}
+
+/// The id of the datalist suggesting the eligible voters, if there are any.
+fn roster_list(election: &ElectionProcess) -> Option<&'static str> {
+    (!election.roster.is_empty()).then_some("roster-names")
+}
+
+fn roster_datalist(election: &ElectionProcess) -> Markup {
+    html! {
+        @if let Some(id) = roster_list(election) {
+            datalist id=(id) {
+                @for voter_name in election.roster.iter().sorted_by_key(|n| n.to_lowercase()) {
+                    option value=(voter_name) {}
+                }
+            }
+        }
+    }
+}
+
+/// The eligible voters with a check mark for those who voted in the current
+/// round, which live.js sets as their votes come in.
+fn voter_checklist(election: &ElectionProcess) -> Markup {
+    let missing = election.missing_voters();
+    html! {
+        @if !election.roster.is_empty() {
+            p {
+                "Eligible voters, "
+                span ."missing-count" { (missing.len()) } " not voted yet:"
+            }
+            ul #"voter-checklist" {
+                @for voter_name in election.roster.iter().sorted_by_key(|n| n.to_lowercase()) {
+                    li data-voter=(voter_name) {
+                        span ."check" {
+                            @if missing.contains(&&voter_name[..]) { "⬜" } @else { "✅" }
+                        }
+                        " " (voter_name)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Which round is voted in, for elections without exactly two rounds.
+fn round_note(election: &ElectionProcess) -> Markup {
+    html! {
+        @if election.kind == ElectionKind::Election && election.rounds != 2 {
+            p { small { "Round " (election.round_number()) " of " (election.rounds) } }
+        }
+    }
+}
+
+/// The name of a round in exports, like `first` or `third`.
+fn round_name(number: usize) -> String {
+    match number {
+        1 => "first".to_string(),
+        2 => "second".to_string(),
+        3 => "third".to_string(),
+        number => ordinal(number),
+    }
+}
+
+/// Who changed their vote in the second round and why.
+fn vote_changes(election: &ElectionProcess) -> Markup {
+    let changes = election.vote_changes();
+    html! {
+        details open {
+            summary { "Changed Votes (" (changes.len()) ")" }
+            @if changes.is_empty() {
+                p { "Nobody changed their vote." }
+            }
+            ul {
+                @for change in changes {
+                    li {
+                        (change.voter) " changed from " strong { (change.from) } " to " strong { (change.to) }
+                        @if let Some(reason) = change.reason {
+                            ": " (reason)
+                        }
+                    }
+                }
+            }
+        }
+        br;
+    }
+}
+
+/// Keeps the hidden ranking in line with the choices; without scripts only
+/// the first choice is sent.
+const RANKING_SCRIPT: &str = "this.form.ranking.value = Array.from(this.form.querySelectorAll('select.rank'), s => s.value).filter(v => v).join(',')";
+
+/// One select per position of a ranked ballot, prefilled with `previous`.
+fn ranking_fields(election: &ElectionProcess, previous: &[u64]) -> Markup {
+    let positions = election
+        .grouped_nominees()
+        .iter()
+        .map(|(_, nominees)| nominees.len())
+        .sum::<usize>();
+    html! {
+        input type="hidden" name="ranking" value=(previous.iter().join(",")) {}
+        @for position in 0..positions {
+            label {
+                (ordinal(position + 1)) " choice: "
+                @if position == 0 {
+                    select ."rank" name="vote" required onchange=(RANKING_SCRIPT) {
+                        (nominee_options_selecting(election, previous.first().copied()))
+                    }
+                } @else {
+                    select ."rank" onchange=(RANKING_SCRIPT) {
+                        option value="" { "—" }
+                        (nominee_options_selecting(election, previous.get(position).copied()))
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Keeps the hidden scores in line with the score selects.
+const SCORES_SCRIPT: &str = "this.form.scores.value = Array.from(this.form.querySelectorAll('select.score'), s => s.dataset.nominee + ':' + s.value).join(',')";
+
+/// A score select per nominee, prefilled with `previous`.
+fn score_fields(election: &ElectionProcess, previous: &HashMap<u64, u8>) -> Markup {
+    html! {
+        input type="hidden" name="scores"
+          value=(previous.iter().map(|(id, score)| format!("{}:{}", id, score)).join(",")) {}
+        p { small { "Give every nominee a score from 0 (not at all) to " (MAX_SCORE) " (fully)." } }
+        @for (group, nominees) in election.grouped_nominees() {
+            @if let Some(group) = group {
+                h4 { (group) }
+            }
+            @for (id, nominee) in nominees {
+                label {
+                    (nominee) ": "
+                    select ."score" data-nominee=(id) onchange=(SCORES_SCRIPT) {
+                        @for score in 0..=MAX_SCORE {
+                            option value=(score) selected[previous.get(&id) == Some(&score)] { (score) }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// What a ballot votes for, in the form of its voting method.
+fn ballot_summary(election: &ElectionProcess, ballot: &Ballot) -> String {
+    match ballot.scores.is_empty() {
+        true => ballot
+            .preferences()
+            .iter()
+            .map(|n| election.nominee_name(*n))
+            .join(" > "),
+        false => ballot
+            .scores
+            .iter()
+            .sorted_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)))
+            .map(|(n, score)| format!("{} {}", election.nominee_name(*n), score))
+            .join(", "),
+    }
+}
+
+/// Total scores as bars, with the average score of each nominee.
+fn score_chart(election: &ElectionProcess, round: &HashMap<String, Ballot>) -> Markup {
+    let totals = election.score_totals(round);
+    let max_total = totals.first().map_or(0, |s| s.total).max(1);
+    html! {
+        div #"eval-chart" {
+            table
+                ."charts-css bar show-labels data-spacing-1 data-start show-data-on-hover"
+                style="--labels-size: 10em;" {
+                thead {
+                    tr {
+                        th { "Nominee" }
+                        th { "Total Score" }
+                    }
+                }
+                tbody {
+                    @for score in &totals {
+                        @let name = election.nominee_name(score.nominee);
+                        tr {
+                            th scope="row" { (photos::nominee_photo(election, score.nominee)) " " (name) }
+                            td style={"--size: " (score.total as f32 / max_total as f32)} {
+                                span ."data" { (score.total) " (⌀ " (format!("{:.1}", score.average)) ")" }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn ordinal(number: usize) -> String {
+    let suffix = match (number % 10, number % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", number, suffix)
+}
+
+/// The rounds of the instant runoff, with the nominees eliminated in each.
+/// The pairwise preference matrix of ranked ballots, rows preferred over
+/// columns, and the Condorcet winner if there is one.
+fn pairwise_comparison(election: &ElectionProcess, round: &HashMap<String, Ballot>) -> Markup {
+    let matrix = election.pairwise_matrix(round);
+    html! {
+        details open {
+            summary { "Pairwise Comparison" }
+            figure {
+                table ."striped" {
+                    thead {
+                        tr {
+                            th { small { "Preferred over" } }
+                            @for nominee in &matrix.nominees {
+                                th { (election.nominee_name(*nominee)) }
+                            }
+                        }
+                    }
+                    tbody {
+                        @for nominee in &matrix.nominees {
+                            tr {
+                                th scope="row" { (election.nominee_name(*nominee)) }
+                                @for other in &matrix.nominees {
+                                    td {
+                                        @if other == nominee {
+                                            "–"
+                                        } @else if matrix.beats(*nominee, *other) {
+                                            strong { (matrix.preferred(*nominee, *other)) }
+                                        } @else {
+                                            (matrix.preferred(*nominee, *other))
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            @match matrix.condorcet_winner() {
+                Some(winner) => p { "Condorcet winner: " strong { (election.nominee_name(winner)) } },
+                None => p { "There is no Condorcet winner: no nominee is preferred over all others." },
+            }
+        }
+    }
+}
+
+fn runoff_rounds(election: &ElectionProcess, round: &HashMap<String, Ballot>) -> Markup {
+    let runoff = election.instant_runoff(round);
+    let nominees = runoff
+        .rounds
+        .first()
+        .map(|r| r.counts.iter().map(|(id, _)| *id).collect::<Vec<_>>())
+        .unwrap_or_default();
+    html! {
+        details open {
+            summary { "Instant Runoff" }
+            figure {
+                table ."striped" {
+                    thead {
+                        tr {
+                            th { "Nominee" }
+                            @for number in 1..=runoff.rounds.len() {
+                                th { "Round " (number) }
+                            }
+                        }
+                    }
+                    tbody {
+                        @for nominee in &nominees {
+                            tr {
+                                th scope="row" { (election.nominee_name(*nominee)) }
+                                @for runoff_round in &runoff.rounds {
+                                    @let votes = runoff_round.counts.iter().find(|(id, _)| id == nominee);
+                                    td {
+                                        @if let Some((_, votes)) = votes {
+                                            @if runoff_round.eliminated.contains(nominee) {
+                                                s { (votes) } small { " out" }
+                                            } @else if runoff.winner == Some(*nominee) && runoff_round == runoff.rounds.last().unwrap() {
+                                                mark { (votes) }
+                                            } @else {
+                                                (votes)
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
                         tr {
-                            td { (voter_name) }
-                            td { (election.nominees.get(vote).unwrap()) }
+                            th scope="row" { small { "No nominee left" } }
+                            @for runoff_round in &runoff.rounds {
+                                td { small { (runoff_round.exhausted) } }
+                            }
                         }
                     }
                 }
             }
-        }
-        br;
-        div #"eval-chart" {
-            table
-                ."charts-css bar show-labels data-spacing-1 data-start show-data-on-hover"
-                style="--labels-size: 10em;" {
-                thead {
-                    tr {
-                        th { "Nominee" }
-                        th { "Votes" }
-                    }
-                }
-                tbody {
-                    @for (votee, vote_count) in accumulated_votes {
-                        tr {
-                            th scope="row" {(votee)}
-                            td style={"--size: " (vote_count as f32 / (max_votes as f32))}{
-                                span ."data" {(vote_count)}
+            @match runoff.winner {
+                Some(winner) => p { "Winner of the instant runoff: " strong { (election.nominee_name(winner)) } },
+                None => p { "The instant runoff ended without a majority." },
+            }
+        }
+    }
+}
+
+fn reason_input() -> Markup {
+    html! {
+        label for="reason" {
+            "Reason: ";
+            textarea name="reason" rows="2" placeholder="Why do you nominate them?" {}
+        }
+    }
+}
+
+/// The reasons given per nominee, as voters explain their votes in the tally.
+fn vote_reasons(election: &ElectionProcess, round: &HashMap<String, Ballot>) -> Markup {
+    let reasons = election.reasons(round);
+    if reasons.is_empty() {
+        return html! {};
+    }
+    html! {
+        details open {
+            summary { "Reasons" }
+            @for (nominee, reasons) in reasons {
+                h4 { (nominee) }
+                ul {
+                    @for (voter_name, reason) in reasons {
+                        li { (reason) " " small { "(" (voter_name) ")" } }
+                    }
+                }
+            }
+        }
+        br;
+    }
+}
+
+/// Who fills in the voting form.
+enum FormVoter<'a> {
+    /// Anyone, entering their name.
+    Anyone,
+    /// A voter replacing their vote, with their name prefilled.
+    Updating(&'a str),
+    /// The voter of a voting link, who does not enter a name.
+    Token { token: &'a str, voter_name: &'a str },
+}
+
+/// The form of the current phase.
+fn voting_form(election: &ElectionProcess, now: u64, voter: FormVoter) -> Markup {
+    let (updating, token) = match voter {
+        FormVoter::Anyone => (None, None),
+        FormVoter::Updating(voter_name) => (Some(voter_name), None),
+        FormVoter::Token { token, voter_name } => (None, Some((token, voter_name))),
+    };
+    if election.closed_at.is_some() {
+        return closed_results(election);
+    }
+    let content = match election.phase {
+        ElectionPhase::Nomination => nomination::nomination_form(election),
+        ElectionPhase::FirstVote | ElectionPhase::SecondVote => {
+            html! {
+                h2 { (election.phase_title()) }
+                (round_note(election))
+                p { (election.phase.nice_description()) }
+                (schedule::schedule_note(election, now))
+                (photos::photo_gallery(election))
+                form #"vote" ."table rows" {
+                    (roster_datalist(election))
+                    @if let Some((token, voter_name)) = token {
+                        p { "Voting as " strong { (voter_name) } }
+                        input type="hidden" name="token" value=(token) {}
+                        @if election.phase == ElectionPhase::SecondVote {
+                            (second_vote_fields(election, election.previous_round().get(voter_name)))
+                        }
+                    } @else if election.phase == ElectionPhase::SecondVote {
+                        label for="elected_role" {
+                            "Voter Name: ";
+                            input type="text" name="voter_name" required value=[updating]
+                              list=[roster_list(election)]
+                              hx-get={"/election/" (election.id) "/voting/previous"}
+                              hx-trigger="change" hx-target="#vote-fields" hx-swap="outerHTML" {}
+                        }
+                        (second_vote_fields(election, None))
+                    }
+                    @if election.phase == ElectionPhase::FirstVote {
+                        @if token.is_none() {
+                            label for="elected_role" {
+                                "Voter Name: ";
+                                input type="text" name="voter_name" required value=[updating]
+                                  list=[roster_list(election)] {}
+                            }
+                        }
+                        @match election.method {
+                            VotingMethod::Ranked => (ranking_fields(election, &[])),
+                            VotingMethod::Score => (score_fields(election, &HashMap::new())),
+                            VotingMethod::Plurality => label for="vote" {
+                                "Vote :";
+                                select name="vote" required
+                                  hx-get={"/election/" (election.id) "/voting/options"}
+                                  hx-trigger="sse:nominees-changed" hx-include="this" {
+                                    (nominee_options(election))
+                                }
+                            },
+                        }
+                        @if election.phase == ElectionPhase::FirstVote {
+                            div hx-get={"/election/" (election.id) "/nominations/pending"}
+                              hx-trigger="sse:nominees-changed" {
+                                (nomination::pending_nominations(election))
+                            }
+                            label for="write_in" {
+                                "Or write in someone else: ";
+                                input type="text" name="write_in" {}
+                            }
+                        }
+                        (reason_input())
+                    }
+                    div #"vote-warning" {
+                        @if updating.is_some() {
+                            input type="hidden" name="update" value="true" {}
+                        }
+                    }
+                    button
+                      hx-post={"/election/" (election.id.to_string()) "/voting"}
+                      hx-trigger="click" hx-target="#vote" hx-swap="outerHTML"
+                      style="left: 50%; position: relative; translate: -50%;" {
+                        "Vote!"
+                    }
+                }
+            }
+        }
+        ElectionPhase::FirstTally | ElectionPhase::SecondTally => {
+            html! {
+                h2 { (election.phase_title()) }
+                (round_note(election))
+                p { (election.phase.nice_description()) }
+                {( eval_tally(election) )}
+            }
+        }
+        ElectionPhase::SafetyRound => html!(
+            h2 { (election.phase_title()) }
+            p { (election.phase.nice_description()) }
+            (safety::proposal(election))
+            (safety::safety_form(election))
+        ),
+        ElectionPhase::Completed => elected_view(election),
+    };
+    html! {
+        (content)
+        @if election.feedback_open {
+            (feedback::feedback_form(election))
+        }
+    }
+}
+
+async fn view_home(State(state): State<ElectionDB>, headers: HeaderMap) -> Markup {
+    base_html(
+        "IEP Tool Home",
+        html!("IEP Tool Home"),
+        html! {
+            p { "Welcome to the Integrative Election Process Tool! Press the button below to start a new election or a quick straw poll." }
+
+            h2 { "Join Election" }
+            form #"join-election" ."table rows" {
+                label for="election_id" {
+                    "Election ID: ";
+                    input type="text" name="election_id" required {}
+                }
+                button
+                  hx-get={"/election/join"}
+                  hx-trigger="click" hx-swap="outerHTML"
+                  hx-include="[name='election_id']"
+                  style="left: 50%; position: relative; translate: -50%;" {
+                    "Join Election"
+                }
+            }
+
+
+            @if oidc::needs_login(&state, &headers) {
+                br;
+                p { a href="/auth/login" role="button" { "Log in to create elections" } }
+            } @else {
+                br;
+                (templates::templates_list(&state))
+
+                br;
+                h2 { "New Election" }
+
+                (election_creation_form(&ElectionCreation::default(), &circles::circle_role_options(&state)))
+
+                br;
+                p {
+                    a href="/circles" ."secondary" { "Manage circles and roles" }
+                    @if accounts::account(&state, &headers).is_some() {
+                        " · "
+                        a href="/my-elections" ."secondary" { "My elections" }
+                    }
+                }
+                @if let Some(user) = oidc::user(&state, &headers) {
+                    p { small { "Logged in as " (user) " · " a ."secondary" href="/auth/logout" { "Log out" } } }
+                }
+            }
+        },
+        html! {},
+    )
+}
+
+#[derive(Deserialize, Debug)]
+struct QuickCreateQuery {
+    kind: Option<String>,
+    role: Option<String>,
+    nominees: Option<String>,
+    message: Option<String>,
+    meeting: Option<String>,
+    circle_role: Option<String>,
+    method: Option<String>,
+}
+
+/// Pre-filled creation form for links like `/election/new?role=...&nominees=a,b,c`.
+async fn view_election_new(
+    State(state): State<ElectionDB>,
+    Query(query): Query<QuickCreateQuery>,
+) -> Markup {
+    let prefill = ElectionCreation {
+        kind: query.kind.and_then(|k| k.parse().ok()).unwrap_or_default(),
+        elected_role: query.role.unwrap_or_default(),
+        nominees: query
+            .nominees
+            .unwrap_or_default()
+            .split(',')
+            .map(|n| n.trim())
+            .filter(|n| !n.is_empty())
+            .join("\n"),
+        post_vote_message: query.message,
+        meeting: query.meeting,
+        follows: None,
+        circle_role: query.circle_role,
+        keep_days: None,
+        slug: None,
+        nomination: None,
+        method: query
+            .method
+            .and_then(|m| m.parse().ok())
+            .unwrap_or_default(),
+        seats: None,
+        rounds: None,
+        expected_voters: None,
+        require_quorum: None,
+        auto_advance: None,
+        passcode: None,
+        webhooks: String::new(),
+        slack_webhook: None,
+        discord_webhook: None,
+        report_to: String::new(),
+        report_to_roster: None,
+        roster: String::new(),
+    };
+
+    base_html(
+        "New Election",
+        html!("New Election"),
+        html! {
+            p { "Please check the details below and press the button to start the election." }
+            (election_creation_form(&prefill, &circles::circle_role_options(&state)))
+        },
+        html! {},
+    )
+}
+
+fn election_creation_form(
+    prefill: &ElectionCreation,
+    circle_roles: &[(CircleRoleRef, String)],
+) -> Markup {
+    html! {
+        form #"new-election" ."table rows" hx-encoding="multipart/form-data" {
+            label for="kind" {
+                "Type: ";
+                select name="kind" {
+                    option value=(ElectionKind::Election) selected[prefill.kind == ElectionKind::Election] {
+                        "Election (two rounds and safety round)"
+                    }
+                    option value=(ElectionKind::StrawPoll) selected[prefill.kind == ElectionKind::StrawPoll] {
+                        "Straw poll (single round with live tally)"
+                    }
+                }
+            }
+            label for="elected_role" {
+                "Elected Role or Question: ";
+                input type="text" name="elected_role" value=(prefill.elected_role) required {}
+            }
+            label for="nominees" {
+                "Nominees :";
+                textarea
+                  name="nominees" placeholder="one nominee per line, a [Group] line starts a group"
+                  style="min-height: 12em;" { (prefill.nominees) }
+            }
+            label for="nominees_file" {
+                "Or upload nominees (TXT/CSV, one per line): ";
+                input type="file" name="nominees_file" accept=".txt,.csv,text/plain,text/csv" {}
+            }
+            label for="method" {
+                "Ballots: ";
+                select name="method" {
+                    option value=(VotingMethod::Plurality) selected[prefill.method == VotingMethod::Plurality] {
+                        "One nominee per ballot"
+                    }
+                    option value=(VotingMethod::Ranked) selected[prefill.method == VotingMethod::Ranked] {
+                        "Ranked nominees (instant runoff)"
+                    }
+                    option value=(VotingMethod::Score) selected[prefill.method == VotingMethod::Score] {
+                        "Scores from 0 to 5 for every nominee"
+                    }
+                }
+            }
+            label for="nomination" {
+                input type="checkbox" name="nomination" checked[prefill.nomination.is_some()] {}
+                "Let participants nominate before the first vote"
+            }
+            label for="post_vote_message" {
+                "Message after voting (optional): ";
+                input type="text" name="post_vote_message" placeholder="Vote added!"
+                  value=[prefill.post_vote_message.as_deref()] {}
+            }
+            label for="meeting" {
+                "Meeting (optional, groups elections on one dashboard): ";
+                input type="text" name="meeting" value=[prefill.meeting.as_deref()] {}
+            }
+            label for="slug" {
+                "Custom id for the links (optional): ";
+                input type="text" name="slug" placeholder="circle-lead-2024"
+                  pattern="[a-zA-Z0-9][a-zA-Z0-9\\-]{1,62}[a-zA-Z0-9]"
+                  value=[prefill.slug.as_deref()] {}
+            }
+            label for="rounds" {
+                "Voting rounds of elections: ";
+                input type="number" name="rounds" min="1" value=(prefill.rounds.as_deref().unwrap_or("2")) {}
+            }
+            label for="seats" {
+                "People to elect: ";
+                input type="number" name="seats" min="1" value=(prefill.seats.as_deref().unwrap_or("1")) {}
+            }
+            label for="roster" {
+                "Eligible voters (optional): ";
+                textarea name="roster" placeholder="one voter per line, optionally followed by <email address> or <+phone number>" { (prefill.roster) }
+            }
+            label for="roster_file" {
+                "Or upload eligible voters (TXT/CSV, one per line): ";
+                input type="file" name="roster_file" accept=".txt,.csv,text/plain,text/csv" {}
+            }
+            label for="expected_voters" {
+                "Expected voters (optional, all eligible voters if empty): ";
+                input type="number" name="expected_voters" min="1" value=[prefill.expected_voters.as_deref()] {}
+            }
+            label for="require_quorum" {
+                input type="checkbox" name="require_quorum" checked[prefill.require_quorum.is_some()] {}
+                "Wait for the expected voters before the next phase"
+            }
+            label for="auto_advance" {
+                input type="checkbox" name="auto_advance" checked[prefill.auto_advance.is_some()] {}
+                "Move on to the tally once the expected voters voted"
+            }
+            label for="passcode" {
+                "Passcode to vote (optional): ";
+                input type="text" name="passcode" autocomplete="off" value=[prefill.passcode.as_deref()] {}
+            }
+            label for="webhooks" {
+                "Webhook URLs (optional): ";
+                textarea name="webhooks" placeholder="one URL per line" { (prefill.webhooks) }
+            }
+            label for="slack_webhook" {
+                "Slack webhook announcing the phases (optional): ";
+                input type="url" name="slack_webhook" placeholder="https://hooks.slack.com/..." value=[prefill.slack_webhook.as_deref()] {}
+            }
+            label for="discord_webhook" {
+                "Discord webhook announcing the phases (optional): ";
+                input type="url" name="discord_webhook" placeholder="https://discord.com/api/webhooks/..." value=[prefill.discord_webhook.as_deref()] {}
+            }
+            label for="report_to" {
+                "Email the results on closing to (optional, needs a mail server): ";
+                input type="text" name="report_to" placeholder="addresses separated by commas" value=(prefill.report_to) {}
+            }
+            label for="report_to_roster" {
+                input type="checkbox" name="report_to_roster" checked[prefill.report_to_roster.is_some()] {}
+                "Also email them to the eligible voters with an address"
+            }
+            label for="keep_days" {
+                "Keep for days after last activity (optional): ";
+                input type="number" name="keep_days" min="0" value=[prefill.keep_days.as_deref()] {}
+            }
+            @if !circle_roles.is_empty() {
+                label for="circle_role" {
+                    "Circle role (optional): ";
+                    select name="circle_role" {
+                        option value="" { "–" }
+                        @for (circle_role, label) in circle_roles {
+                            @let value = circle_role.to_string();
+                            option value=(value) selected[prefill.circle_role.as_ref() == Some(&value)] {
+                                (label)
                             }
                         }
                     }
                 }
             }
+            @if let Some(follows) = &prefill.follows {
+                input type="hidden" name="follows" value=(follows) {}
+            }
+            button
+              hx-post="/election" hx-trigger="click" hx-swap="none"
+              style="left: 50%; position: relative; translate: -50%;" {
+                "Start Election"
+            }
+            button ."secondary outline"
+              hx-post="/templates" hx-trigger="click" hx-swap="none"
+              style="left: 50%; position: relative; translate: -50%;" {
+                "Save as Template"
+            }
         }
-        br;
     }
 }
 
-async fn view_election_voting(
+/// Creation form for a follow-up election, e.g. for a deputy, with the same
+/// nominees except the one(s) most voted for.
+async fn view_election_follow_up(
     Path(id): Path<String>,
     State(state): State<ElectionDB>,
 ) -> Result<Markup, StatusCode> {
-    let db = state
-        .db
-        .lock()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let election = db.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let winners = election
+        .accumulated_votes(&election.second_round_id)
+        .all_with_max_votes();
+    let prefill = ElectionCreation {
+        kind: ElectionKind::Election,
+        elected_role: format!("Deputy {}", election.elected_role),
+        nominees: election.nominees_text(&winners),
+        post_vote_message: election.post_vote_message.clone(),
+        meeting: election.meeting.clone(),
+        follows: Some(election.id.clone()),
+        circle_role: None,
+        keep_days: election.keep_for.map(|s| (s / DAY_SECS).to_string()),
+        slug: None,
+        nomination: None,
+        method: election.method,
+        seats: None,
+        rounds: Some(election.rounds.to_string()),
+        expected_voters: election.expected_voters.map(|v| v.to_string()),
+        require_quorum: election.require_quorum.then(String::new),
+        auto_advance: election.auto_advance.then(String::new),
+        roster: election.roster_text(),
+        passcode: election.passcode.clone(),
+        webhooks: String::new(),
+        slack_webhook: election.slack_webhook.clone(),
+        discord_webhook: election.discord_webhook.clone(),
+        report_to: election.report_recipients.join(", "),
+        report_to_roster: election.report_to_roster.then(String::new),
+    };
 
     Ok(base_html(
-        election.elected_role.as_str(),
-        html! {(election.elected_role.as_str())},
+        "Follow-up Election",
+        html! { (election.elected_role) br; "Follow-up Election" },
         html! {
-            div hx-ext="sse" sse-connect={"/election/" (id.to_string()) "/stream"} {
-              div #"vote-content"
-                hx-get={"/election/" (id.to_string()) "/voting/form"}
-                hx-trigger="sse:phase-changed"
-                hx-swap="innerHTML" {
-                  ({ voting_form(election) })
-              }
-            }
+            p { "The follow-up election will be linked to the election of " (election.elected_role) "." }
+            (election_creation_form(&prefill, &circles::circle_role_options(&state)))
         },
-        html!(strong { a href={"/election/" (id) "/voting"} ."secondary" {(id)} }),
+        html! {},
     ))
 }
 
-async fn get_election_voting_form(
+/// Creation form with everything of an existing election but its ballots.
+async fn view_election_clone(
     Path(id): Path<String>,
     State(state): State<ElectionDB>,
 ) -> Result<Markup, StatusCode> {
-    let db = state
-        .db
-        .lock()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let election = db.get(&id).ok_or(StatusCode::NOT_FOUND)?;
-    Ok(voting_form(election))
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let prefill = ElectionCreation {
+        kind: election.kind,
+        elected_role: election.elected_role.clone(),
+        nominees: election.nominees_text(&[]),
+        post_vote_message: election.post_vote_message.clone(),
+        meeting: election.meeting.clone(),
+        follows: None,
+        circle_role: election.circle_role.as_ref().map(|r| r.to_string()),
+        keep_days: election.keep_for.map(|s| (s / DAY_SECS).to_string()),
+        slug: None,
+        nomination: None,
+        method: election.method,
+        seats: Some(election.seats.to_string()),
+        rounds: Some(election.rounds.to_string()),
+        expected_voters: election.expected_voters.map(|v| v.to_string()),
+        require_quorum: election.require_quorum.then(String::new),
+        auto_advance: election.auto_advance.then(String::new),
+        roster: election.roster_text(),
+        passcode: election.passcode.clone(),
+        webhooks: election.webhooks.join("\n"),
+        slack_webhook: election.slack_webhook.clone(),
+        discord_webhook: election.discord_webhook.clone(),
+        report_to: election.report_recipients.join(", "),
+        report_to_roster: election.report_to_roster.then(String::new),
+    };
+
+    Ok(base_html(
+        "Clone Election",
+        html! { (election.elected_role) br; "Clone Election" },
+        html! {
+            p { "The new election starts without any of the ballots of " (election.elected_role) "." }
+            (election_creation_form(&prefill, &circles::circle_role_options(&state)))
+        },
+        html! {},
+    ))
 }
 
-fn voting_form(election: &ElectionProcess) -> Markup {
-    match election.phase {
-        ElectionPhase::FirstVote | ElectionPhase::SecondVote => {
-            let sorted_nominees = election.nominees.iter().sorted_by_key(|(k, _)| *k);
-            html! {
-                h2 { (election.phase.nice_title()) }
-                p { (election.phase.nice_description()) }
-                form #"vote" ."table rows" {
-                    label for="elected_role" {
-                        "Voter Name: ";
-                        input type="text" name="voter_name" required {}
-                    }
-                    label for="vote" {
-                        "Vote :";
-                        select name="vote" required {
-                            @for (id, nominee) in sorted_nominees {
-                                option value=(id.to_string()) { (nominee) }
-                            }
-                        }
-                    }
-                    button
-                      hx-post={"/election/" (election.id.to_string()) "/voting"}
-                      hx-trigger="click" hx-target="#vote" hx-swap="outerHTML"
-                      style="left: 50%; position: relative; translate: -50%;" {
-                        "Vote!"
-                    }
+/// Links to start a similar election, now or later.
+fn reuse_links(election: &ElectionProcess) -> Markup {
+    html! {
+        p {
+            small {
+                a ."secondary" href={"/election/" (election.id) "/clone"} { "Clone election" }
+                " · "
+                a ."secondary" href="#"
+                  hx-post={"/election/" (election.id) "/template"} hx-trigger="click" hx-swap="none" {
+                    "Save as template"
                 }
             }
         }
-        ElectionPhase::FirstTally | ElectionPhase::SecondTally => {
-            html! {
-                h2 { (election.phase.nice_title()) }
-                p { (election.phase.nice_description()) }
-                {( eval_tally(election) )}
-            }
-        }
-        ElectionPhase::SafetyRound => {
-            let accumulated_votes = election
-                .second_round_id
-                .iter()
-                .into_group_map_by(|(_, &v)| v)
-                .iter()
-                .map(|(k, v)| (election.nominees.get(k).unwrap(), v.len()))
-                .filter(|(_k, v)| *v > 0)
-                .sorted_by(|a, b| Ord::cmp(&a.1, &b.1).then_with(|| Ord::cmp(&a.0, &b.0).reverse()))
-                .rev()
-                .collect::<Vec<_>>();
-            let max_votes = accumulated_votes
-                .iter()
-                .map(|(_k, v)| *v)
-                .max()
-                .unwrap_or(1);
-            let all_with_max_votes = accumulated_votes
-                .iter()
-                .filter(|(_k, v)| *v == max_votes)
-                .map(|(k, _v)| k.to_string())
-                .collect::<Vec<_>>();
-            html!(
-                h2 { (election.phase.nice_title()) }
-                p { (election.phase.nice_description()) }
-                p { "The most votes were for: " ( all_with_max_votes.join(", ") ) }
-            )
-        }
     }
 }
 
-async fn view_home() -> Markup {
-    base_html(
-        "IEP Tool Home",
-        html!("IEP Tool Home"),
+fn linked_elections(election: &ElectionProcess, store: &dyn ElectionStore) -> Markup {
+    let link = |id: &String| {
         html! {
-            p { "Welcome to the Integrative Election Process Tool! Press the button below to start a new election." }
-
-            h2 { "Join Election" }
-            form #"join-election" ."table rows" {
-                label for="election_id" {
-                    "Election ID: ";
-                    input type="text" name="election_id" required {}
-                }
-                button
-                  hx-get={"/election/join"}
-                  hx-trigger="click" hx-swap="outerHTML"
-                  hx-include="[name='election_id']"
-                  style="left: 50%; position: relative; translate: -50%;" {
-                    "Join Election"
-                }
+            a href={"/election/" (id) "/eval"} {
+                (store.get(id).map_or(id.clone(), |e| e.elected_role))
             }
-
-
-            br;
-            h2 { "New Election" }
-
-            form #"new-election" ."table rows" {
-                label for="elected_role" {
-                    "Elected Role: ";
-                    input type="text" name="elected_role" required {}
-                }
-                label for="nominees" {
-                    "Nominees :";
-                    textarea
-                      name="nominees" placeholder="one nominee per line" required
-                      style="min-height: 12em;" {}
-                }
-                button
-                  hx-post="/election" hx-trigger="click" hx-swap="none"
-                  style="left: 50%; position: relative; translate: -50%;" {
-                    "Start Election"
+        }
+    };
+    html! {
+        @if let Some(follows) = &election.follows {
+            p { "Follow-up of " (link(follows)) }
+        }
+        @if !election.follow_ups.is_empty() {
+            p {
+                "Follow-up elections: "
+                @for (i, id) in election.follow_ups.iter().enumerate() {
+                    @if i > 0 { ", " }
+                    (link(id))
                 }
             }
-        },
-        html! {},
-    )
+        }
+    }
 }
 
 fn base_html(title: &str, title_markup: Markup, content: Markup, fragment: Markup) -> Markup {
+    base_html_with_head(title, html! {}, title_markup, content, fragment)
+}
+
+/// Like [`base_html`] with more tags in the head.
+fn base_html_with_head(
+    title: &str,
+    head: Markup,
+    title_markup: Markup,
+    content: Markup,
+    fragment: Markup,
+) -> Markup {
     html! {
         (DOCTYPE)
         html {
@@ -720,6 +3591,7 @@ fn base_html(title: &str, title_markup: Markup, content: Markup, fragment: Marku
                 meta name="viewport" content="width=device-width, initial-scale=1, shrink-to-fit=no" {}
                 script src="https://unpkg.com/htmx.org" {}
                 script src="https://unpkg.com/htmx.org/dist/ext/sse.js" {}
+                script src="/poll.js" defer {}
                 //link rel="stylesheet" href="https://unpkg.com/missing.css@1.1.1" {}
                 link
                   rel="stylesheet"
@@ -727,6 +3599,7 @@ fn base_html(title: &str, title_markup: Markup, content: Markup, fragment: Marku
                 link rel="stylesheet" href="https://unpkg.com/charts.css/dist/charts.min.css" {}
                 link rel="stylesheet" href="/styles.css" {}
                 title { "IEP - " (title) }
+                (head)
             }
             body {
                 header ."container" {
@@ -747,40 +3620,607 @@ fn base_html(title: &str, title_markup: Markup, content: Markup, fragment: Marku
     }
 }
 
-#[derive(Debug, Serialize, Clone, Copy)]
+#[derive(Deserialize, Debug)]
+struct AdminQuery {
+    token: Option<String>,
+}
+
+/// Percent-encodes a value for use in a URL query.
+fn url_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// All ballots of all rounds, in the order they were submitted.
+fn election_votes_csv(election: &ElectionProcess) -> String {
+    let mut csv =
+        "sequence,submitted_at,round,voter,nominee_id,nominee,manually_entered,reason,ranking,scores\n"
+            .to_string();
+    for (round_name, (voter_name, ballot)) in election
+        .numbered_rounds()
+        .into_iter()
+        .flat_map(|(number, round)| round.iter().map(move |b| (round_name(number), b)))
+        .sorted_by_key(|(_, (n, b))| (b.sequence, &n[..]))
+    {
+        let nominee = election
+            .nominees
+            .get(&ballot.vote)
+            .map_or("", |n| n.as_str());
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{}\n",
+            ballot.sequence,
+            ballot.submitted_at,
+            round_name,
+            csv_field(voter_name),
+            ballot.vote,
+            csv_field(nominee),
+            ballot.manually_entered,
+            csv_field(ballot.reason.as_deref().unwrap_or_default()),
+            csv_field(
+                &ballot
+                    .ranking
+                    .iter()
+                    .map(|n| election.nominee_name(*n))
+                    .join(" > ")
+            ),
+            csv_field(
+                &ballot
+                    .scores
+                    .iter()
+                    .sorted()
+                    .map(|(n, score)| format!("{}: {}", election.nominee_name(*n), score))
+                    .join("; ")
+            )
+        ));
+    }
+    csv
+}
+
+async fn get_election_export_csv(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let election = state
+        .store
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    Ok((
+        [
+            ("Content-Type", "text/csv; charset=utf-8".to_string()),
+            (
+                "Content-Disposition",
+                format!("attachment; filename=\"election-{}.csv\"", id),
+            ),
+        ],
+        election_votes_csv(&election),
+    ))
+}
+
+/// The whole election, for a backup or to import it on another server.
+async fn get_election_export_json(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let election = state
+        .store
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    let body = serde_json::to_vec_pretty(&election)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Serialization error"))?;
+    Ok((
+        [
+            ("Content-Type", "application/json".to_string()),
+            (
+                "Content-Disposition",
+                format!("attachment; filename=\"election-{}.json\"", id),
+            ),
+        ],
+        body,
+    ))
+}
+
+fn election_phases_csv(election: &ElectionProcess) -> String {
+    let mut csv = "at,phase\n".to_string();
+    for transition in &election.phase_history {
+        csv.push_str(&format!("{},{:?}\n", transition.at, transition.phase));
+    }
+    csv
+}
+
+async fn get_admin_export_zip(
+    Query(query): Query<AdminQuery>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    state.check_admin(query.token.as_deref())?;
+    let exported_at = state.clock.now();
+    let mut archive = zip::ZipWriter::new(exported_at);
+    let mut manifest = Vec::new();
+    for election in state.store.list().iter().sorted_by(|a, b| a.id.cmp(&b.id)) {
+        let id = &election.id;
+        let json = serde_json::to_vec_pretty(election)
+            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Serialization error"))?;
+        let json_name = format!("elections/{}.json", id);
+        let csv_name = format!("elections/{}.csv", id);
+        let phases_name = format!("elections/{}-phases.csv", id);
+        archive.add_file(&json_name, &json);
+        archive.add_file(&csv_name, election_votes_csv(election).as_bytes());
+        archive.add_file(&phases_name, election_phases_csv(election).as_bytes());
+        manifest.push(serde_json::json!({
+            "id": id,
+            "elected_role": election.elected_role,
+            "phase": election.phase,
+            "files": [json_name, csv_name, phases_name],
+        }));
+    }
+    let manifest = serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "exported_at": exported_at,
+        "elections": manifest,
+    });
+    archive.add_file(
+        "manifest.json",
+        &serde_json::to_vec_pretty(&manifest)
+            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Serialization error"))?,
+    );
+
+    Ok((
+        [
+            ("Content-Type", "application/zip".to_string()),
+            (
+                "Content-Disposition",
+                format!(
+                    "attachment; filename=\"ieptool-export-{}.zip\"",
+                    exported_at
+                ),
+            ),
+        ],
+        archive.finish(),
+    ))
+}
+
+#[derive(Deserialize)]
+struct ElectionMerge {
+    /// The election whose ballots are moved; it is removed afterwards.
+    from: String,
+    into: String,
+    #[serde(default)]
+    conflict: MergeConflict,
+}
+
+async fn post_admin_merge(
+    Query(query): Query<AdminQuery>,
+    State(state): State<ElectionDB>,
+    Form(form): Form<ElectionMerge>,
+) -> Result<Json<MergeReport>, (StatusCode, &'static str)> {
+    state.check_admin(query.token.as_deref())?;
+    if form.from == form.into {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Cannot merge an election into itself",
+        ));
+    }
+    match state.store.get(&form.into) {
+        None => return Err((StatusCode::NOT_FOUND, "Election not found")),
+        Some(target) if target.closed_at.is_some() => {
+            return Err(process_error(ProcessError::Closed))
+        }
+        Some(_) => {}
+    }
+    let source = state
+        .store
+        .delete(&form.from)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    let follow_ups = source.follow_ups.clone();
+
+    let report = state.modify_election(&form.into, |target| {
+        let now = state.clock.now();
+        let report = target.merge_from(source, form.conflict, now);
+        let follow_ups = target
+            .follow_ups
+            .iter()
+            .cloned()
+            .chain(follow_ups)
+            .collect();
+        target.set_links(target.follows.clone(), follow_ups, now);
+        Ok(report)
+    })?;
+    for election in state.store.list() {
+        if election.follows.as_deref() != Some(&form.from)
+            && !election.follow_ups.contains(&form.from)
+        {
+            continue;
+        }
+        let rename = |id: &String| match *id == form.from {
+            true => form.into.clone(),
+            false => id.clone(),
+        };
+        let now = state.clock.now();
+        state.store.modify(&election.id, &mut |election| {
+            let follows = election.follows.as_ref().map(rename);
+            let follow_ups = election.follow_ups.iter().map(rename).collect();
+            election.set_links(follows, follow_ups, now);
+        });
+    }
+
+    let mut redirects = state
+        .redirects
+        .lock()
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Redirect Lock error"))?;
+    for target in redirects.values_mut() {
+        if *target == form.from {
+            *target = form.into.clone();
+        }
+    }
+    redirects.insert(form.from.clone(), form.into.clone());
+    drop(redirects);
+
+    if let Ok(mut streams) = state.streams.lock() {
+        // Clients of the old election reload and get redirected.
+        if let Some(mut stream) = streams.remove(&form.from) {
+            stream.publish(ElectionUpdate::Resync, serde_json::Value::Null);
+        }
+    }
+    state.notify(&form.into, ElectionUpdate::VotesChanged)?;
+
+    Ok(Json(report))
+}
+
+/// Sends requests for merged elections on to the election they were merged
+/// into.
+async fn redirect_merged(
+    State(state): State<ElectionDB>,
+    request: Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let path = request.uri().path();
+    let target = path.strip_prefix("/election/").and_then(|rest| {
+        let (id, rest) = rest.split_once('/').unwrap_or((rest, ""));
+        let redirects = state.redirects.lock().ok()?;
+        let target = redirects.get(id)?;
+        let mut location = format!("/election/{}", target);
+        if !rest.is_empty() {
+            location.push('/');
+            location.push_str(rest);
+        }
+        if let Some(query) = request.uri().query() {
+            location.push('?');
+            location.push_str(query);
+        }
+        Some(location)
+    });
+    match target {
+        Some(location) => (
+            StatusCode::PERMANENT_REDIRECT,
+            [(axum::http::header::LOCATION, location)],
+        )
+            .into_response(),
+        None => next.run(request).await,
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 enum ElectionUpdate {
     VotesChanged,
     PhaseChanged,
+    NomineesChanged,
+    /// Missed updates could not be replayed, clients should reload everything.
+    Resync,
+    /// Someone opened or left a page following the election.
+    PresenceChanged,
+    /// The facilitator removed the vote of the voter it is sent to, e.g. by
+    /// resetting the votes.
+    VoteRemoved,
+}
+
+impl ElectionUpdate {
+    fn event_name(&self) -> &'static str {
+        match self {
+            ElectionUpdate::VotesChanged => "votes-changed",
+            ElectionUpdate::PhaseChanged => "phase-changed",
+            ElectionUpdate::NomineesChanged => "nominees-changed",
+            ElectionUpdate::Resync => "resync",
+            ElectionUpdate::PresenceChanged => "presence-changed",
+            ElectionUpdate::VoteRemoved => "vote-removed",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct SequencedUpdate {
+    seq: u64,
+    update: ElectionUpdate,
+    /// The state of the election after the update, see [`live_update_json`].
+    data: serde_json::Value,
+    /// What facilitators get instead of `data`, see
+    /// [`facilitator_update_json`].
+    #[serde(skip)]
+    facilitator_data: Option<serde_json::Value>,
+    /// The only voter to get the update, known from their voter cookie.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    voter: Option<String>,
+}
+
+impl SequencedUpdate {
+    /// Whether a connection of `voter`, or of someone unknown, gets the update.
+    fn is_for(&self, voter: Option<&str>) -> bool {
+        self.voter.is_none() || self.voter.as_deref() == voter
+    }
+
+    /// The update as a connection gets it, with more data if it is a
+    /// facilitator's.
+    fn seen_by(mut self, facilitator: bool) -> Self {
+        if let Some(data) = self.facilitator_data.take().filter(|_| facilitator) {
+            self.data = data;
+        }
+        self
+    }
+}
+
+const RECENT_UPDATES: usize = 64;
+
+/// Broadcast channel of an election, remembering the latest updates so that
+/// clients can catch up on what they missed.
+struct ElectionStream {
+    sender: tokio::sync::broadcast::Sender<SequencedUpdate>,
+    seq: u64,
+    recent: VecDeque<SequencedUpdate>,
+    /// Open connections following the election, see [`presence`].
+    connected: usize,
+}
+
+impl ElectionStream {
+    fn new() -> Self {
+        ElectionStream {
+            sender: tokio::sync::broadcast::channel(16).0,
+            seq: 0,
+            recent: VecDeque::new(),
+            connected: 0,
+        }
+    }
+
+    fn publish(&mut self, update: ElectionUpdate, data: serde_json::Value) {
+        self.push(update, data, None, None);
+    }
+
+    /// Publishes an update of `election` with its state after the update.
+    fn publish_about(&mut self, update: ElectionUpdate, election: Option<&ElectionProcess>) {
+        match election {
+            Some(election) => self.push(
+                update,
+                live_update_json(election),
+                Some(facilitator_update_json(election)),
+                None,
+            ),
+            None => self.publish(update, serde_json::Value::Null),
+        }
+    }
+
+    /// Publishes an update only the connections of `voter` get.
+    fn publish_for(&mut self, voter: &str, update: ElectionUpdate, data: serde_json::Value) {
+        self.push(update, data, None, Some(voter.to_string()));
+    }
+
+    fn push(
+        &mut self,
+        update: ElectionUpdate,
+        data: serde_json::Value,
+        facilitator_data: Option<serde_json::Value>,
+        voter: Option<String>,
+    ) {
+        self.seq += 1;
+        let update = SequencedUpdate {
+            seq: self.seq,
+            update,
+            data,
+            facilitator_data,
+            voter,
+        };
+        self.recent.push_back(update.clone());
+        if self.recent.len() > RECENT_UPDATES {
+            self.recent.pop_front();
+        }
+        // no one listening is fine
+        let _ = self.sender.send(update);
+    }
+
+    /// Updates for `voter` published after `since`, or `None` if some of them
+    /// are no longer remembered.
+    fn since(&self, since: u64, voter: Option<&str>) -> Option<Vec<SequencedUpdate>> {
+        let oldest = self.recent.front().map_or(self.seq + 1, |u| u.seq);
+        if since > self.seq || since + 1 < oldest {
+            return None;
+        }
+        Some(
+            self.recent
+                .iter()
+                .filter(|u| u.seq > since && u.is_for(voter))
+                .cloned()
+                .collect(),
+        )
+    }
+
+    fn resync(&self) -> SequencedUpdate {
+        SequencedUpdate {
+            seq: self.seq,
+            update: ElectionUpdate::Resync,
+            data: serde_json::Value::Null,
+            facilitator_data: None,
+            voter: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct UpdatesQuery {
+    since: Option<u64>,
+}
+
+const LONG_POLL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Long-polling fallback for clients that cannot use the SSE stream: answers
+/// with the next update after `since`, or `204 No Content` after a timeout.
+async fn get_election_updates(
+    Path(id): Path<String>,
+    Query(query): Query<UpdatesQuery>,
+    State(state): State<ElectionDB>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, (StatusCode, &'static str)> {
+    let facilitator = facilitator::is_facilitating(&state, &headers, &id);
+    let mut rx = {
+        let streams = state.streams_of(&id)?;
+        let stream = streams
+            .get(&id)
+            .ok_or((StatusCode::NOT_FOUND, "Election stream not found"))?;
+        match stream.since(query.since.unwrap_or(stream.seq), None) {
+            Some(mut updates) if !updates.is_empty() => {
+                return Ok(Json(updates.swap_remove(0).seen_by(facilitator)).into_response())
+            }
+            Some(_) => stream.sender.subscribe(),
+            None => return Ok(Json(stream.resync()).into_response()),
+        }
+    };
+
+    // updates for single voters are left out
+    let next = async {
+        loop {
+            match rx.recv().await {
+                Ok(update) if !update.is_for(None) => continue,
+                next => return next,
+            }
+        }
+    };
+    match tokio::time::timeout(LONG_POLL_TIMEOUT, next).await {
+        Ok(Ok(update)) => Ok(Json(update.seen_by(facilitator)).into_response()),
+        Ok(Err(tokio::sync::broadcast::error::RecvError::Lagged(_))) => {
+            let streams = state
+                .streams
+                .lock()
+                .map_err(|_e| (StatusCode::INTERNAL_SERVER_ERROR, "Lock error"))?;
+            let stream = streams
+                .get(&id)
+                .ok_or((StatusCode::NOT_FOUND, "Election stream not found"))?;
+            Ok(Json(stream.resync()).into_response())
+        }
+        Ok(Err(tokio::sync::broadcast::error::RecvError::Closed)) => {
+            Err((StatusCode::NOT_FOUND, "Election stream closed"))
+        }
+        Err(_) => Ok(StatusCode::NO_CONTENT.into_response()),
+    }
+}
+
+/// For pages polling instead of following the stream: `304 Not Modified` if
+/// nothing happened since the version `since`, else the latest update of each
+/// kind since then with its data, and the version to poll with next.
+async fn get_election_state(
+    Path(id): Path<String>,
+    Query(query): Query<UpdatesQuery>,
+    State(state): State<ElectionDB>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, (StatusCode, &'static str)> {
+    let facilitator = facilitator::is_facilitating(&state, &headers, &id);
+    let streams = state.streams_of(&id)?;
+    let stream = streams
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election stream not found"))?;
+    let updates = match query.since.map(|since| stream.since(since, None)) {
+        None => vec![],
+        Some(Some(updates)) if updates.is_empty() => {
+            return Ok(StatusCode::NOT_MODIFIED.into_response())
+        }
+        Some(Some(updates)) => updates,
+        Some(None) => vec![stream.resync()],
+    };
+    let updates = updates
+        .into_iter()
+        .rev()
+        .unique_by(|u| u.update.event_name())
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .map(|u| u.seen_by(facilitator))
+        .map(|u| serde_json::json!({ "event": u.update.event_name(), "data": u.data }))
+        .collect::<Vec<_>>();
+    Ok(Json(serde_json::json!({ "version": stream.seq, "updates": updates })).into_response())
 }
 
 async fn get_election_sse_stream(
     Path(id): Path<String>,
     State(state): State<ElectionDB>,
+    headers: HeaderMap,
 ) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, &'static str)> {
-    let rx = state
-        .streams
-        .lock()
-        .map_err(|_e| (StatusCode::INTERNAL_SERVER_ERROR, "Lock error"))?
-        .get(&id)
-        .ok_or((StatusCode::NOT_FOUND, "Election stream not found"))?
-        .subscribe();
+    let last_event_id = headers
+        .get("Last-Event-ID")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let voter = cookies::voted_as(&state, &headers, &id);
+    let facilitator = facilitator::is_facilitating(&state, &headers, &id);
 
-    let stream = BroadcastStream::new(rx);
+    let (seq, replay, rx) = {
+        let streams = state.streams_of(&id)?;
+        let stream = streams
+            .get(&id)
+            .ok_or((StatusCode::NOT_FOUND, "Election stream not found"))?;
+        // a reconnecting client gets what it missed in the meantime
+        let replay = match last_event_id.map(|since| stream.since(since, voter.as_deref())) {
+            None => vec![],
+            Some(Some(missed)) => missed,
+            Some(None) => vec![stream.resync()],
+        };
+        (stream.seq, replay, stream.sender.subscribe())
+    };
+
+    let presence = presence::Presence::join(&state, &id);
+    let stream = tokio_stream::iter(replay.into_iter().map(Ok))
+        .chain(BroadcastStream::new(rx))
+        .filter(move |msg| !matches!(msg, Ok(u) if !u.is_for(voter.as_deref())));
+    // tells the browser how quickly to reconnect after losing the connection,
+    // and where it is, so that it sends a `Last-Event-ID` even if it loses
+    // the connection before the first update
+    let retry = tokio_stream::once(Event::default().id(seq.to_string()).retry(state.sse_retry));
 
     Ok(Sse::new(
-        stream
-            .map(|msg| {
-                let event = match msg.unwrap() {
-                    ElectionUpdate::VotesChanged => "votes-changed",
-                    ElectionUpdate::PhaseChanged => "phase-changed",
-                };
-                Event::default().event(event).data(event)
-            })
+        retry
+            .chain(stream.map(move |msg| {
+                // counted as connected for as long as the stream is open
+                let _ = &presence;
+                match msg {
+                    Ok(msg) => {
+                        let msg = msg.seen_by(facilitator);
+                        Event::default()
+                            .id(msg.seq.to_string())
+                            .event(msg.update.event_name())
+                            .data(msg.data.to_string())
+                    }
+                    // too slow to keep up, the client reloads everything instead
+                    // of what it missed and keeps following
+                    Err(BroadcastStreamRecvError::Lagged(missed)) => {
+                        tracing::debug!("SSE client of {} missed {} updates", id, missed);
+                        Event::default()
+                            .event(ElectionUpdate::Resync.event_name())
+                            .data(serde_json::Value::Null.to_string())
+                    }
+                }
+            }))
             .map(Ok),
     )
     .keep_alive(
         axum::response::sse::KeepAlive::new()
-            .interval(Duration::from_secs(600))
+            .interval(state.sse_keep_alive)
             .text("keep-alive-text"),
     ))
 }
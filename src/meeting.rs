@@ -0,0 +1,162 @@
+//! Dashboard of all elections of one meeting, e.g. for a projector in the room.
+
+use crate::{base_html, ElectionDB, ElectionStream, ElectionUpdate};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{sse::Event, Sse},
+};
+use iep_core::ElectionProcess;
+use itertools::*;
+use maud::{html, Markup};
+use std::convert::Infallible;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt, StreamMap};
+
+pub async fn view_meeting(
+    Path(meeting): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<Markup, StatusCode> {
+    let content = meeting_content(&state, &meeting)?;
+    let path = format!("/meeting/{}", crate::url_encode(&meeting));
+    Ok(base_html(
+        &meeting,
+        html! { (meeting) },
+        html! {
+            div hx-ext="sse" sse-connect={(path) "/stream"} {
+                // new elections of the meeting are picked up by the periodic reload
+                div #"meeting-content"
+                  hx-get={(path) "/content"}
+                  hx-trigger="sse:changed, every 30s"
+                  hx-swap="innerHTML" {
+                    (content)
+                }
+            }
+        },
+        html! {},
+    ))
+}
+
+pub async fn get_meeting_content(
+    Path(meeting): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<Markup, StatusCode> {
+    meeting_content(&state, &meeting)
+}
+
+/// One event for any update of any election of the meeting.
+pub async fn get_meeting_stream(
+    Path(meeting): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StatusCode> {
+    let ids = meeting_elections(&state, &meeting)?
+        .into_iter()
+        .map(|e| e.id)
+        .collect::<Vec<_>>();
+    let mut updates = StreamMap::new();
+    {
+        let mut streams = state
+            .streams
+            .lock()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        for id in ids {
+            // elections created by another instance sharing the store have none yet
+            let stream = streams
+                .entry(id.clone())
+                .or_insert_with(ElectionStream::new);
+            updates.insert(id, BroadcastStream::new(stream.sender.subscribe()));
+        }
+    }
+
+    let retry = tokio_stream::once(Event::default().retry(state.sse_retry));
+    // who is connected does not show on the dashboard, nor what is only for
+    // single voters
+    let changes = updates
+        .filter(|(_, update)| {
+            !matches!(update, Ok(u) if matches!(u.update, ElectionUpdate::PresenceChanged) || u.voter.is_some())
+        })
+        .map(|(id, _)| Event::default().event("changed").data(id));
+    Ok(Sse::new(retry.chain(changes).map(Ok)).keep_alive(
+        axum::response::sse::KeepAlive::new()
+            .interval(state.sse_keep_alive)
+            .text("keep-alive-text"),
+    ))
+}
+
+struct ElectionRow {
+    id: String,
+    elected_role: String,
+    phase: &'static str,
+    turnout: usize,
+    /// Nominees with the most votes in the final round, once decided.
+    outcome: Option<Vec<String>>,
+    /// Whether the election has a passcode, so its outcome is not shown.
+    passcode: bool,
+    started_at: u64,
+}
+
+fn meeting_elections(state: &ElectionDB, meeting: &str) -> Result<Vec<ElectionRow>, StatusCode> {
+    let rows = state
+        .store
+        .list()
+        .iter()
+        .filter(|e| e.meeting.as_deref() == Some(meeting))
+        .map(election_row)
+        .sorted_by_key(|r| (r.started_at, r.id.clone()))
+        .collect::<Vec<_>>();
+    match rows.is_empty() {
+        true => Err(StatusCode::NOT_FOUND),
+        false => Ok(rows),
+    }
+}
+
+fn election_row(election: &ElectionProcess) -> ElectionRow {
+    ElectionRow {
+        id: election.id.clone(),
+        elected_role: election.elected_role.clone(),
+        phase: election.phase_title(),
+        turnout: election.current_round().len(),
+        outcome: election.is_last_phase().then(|| match &election.elected {
+            Some(_) => election
+                .elected_seats()
+                .iter()
+                .map(|e| e.name.clone())
+                .collect(),
+            None => election
+                .accumulated_votes(election.final_round())
+                .all_with_max_votes()
+                .into_iter()
+                .map(|n| n.to_string())
+                .collect(),
+        }),
+        passcode: election.passcode.is_some(),
+        started_at: election.phase_history.first().map_or(0, |t| t.at),
+    }
+}
+
+fn meeting_content(state: &ElectionDB, meeting: &str) -> Result<Markup, StatusCode> {
+    let rows = meeting_elections(state, meeting)?;
+    let decided = rows.iter().filter(|r| r.outcome.is_some()).count();
+    Ok(html! {
+        progress value=(decided) max=(rows.len()) {}
+        p { (decided) " of " (rows.len()) " decided" }
+        table ."striped" {
+            thead { tr { th { "Role" } th { "Phase" } th { "Votes" } th { "Outcome" } } }
+            tbody {
+                @for row in rows {
+                    tr {
+                        td { a href={"/election/" (row.id) "/eval"} { (row.elected_role) } }
+                        td { (row.phase) }
+                        td { (row.turnout) }
+                        td {
+                            @match &row.outcome {
+                                Some(_) if row.passcode => "Decided",
+                                Some(outcome) => strong { (outcome.join(", ")) },
+                                None => "–",
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,117 @@
+//! A small parser for `multipart/form-data` request bodies.
+//!
+//! Only what the creation form needs: named text fields and uploaded files,
+//! both kept fully in memory.
+
+pub struct Part {
+    pub name: String,
+    pub filename: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// Extracts the boundary from a `multipart/form-data` content type header.
+pub fn boundary(content_type: &str) -> Option<&str> {
+    let (mime, params) = content_type.split_once(';')?;
+    if !mime.trim().eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params
+        .split(';')
+        .filter_map(|p| p.trim().split_once('='))
+        .find(|(k, _)| k.eq_ignore_ascii_case("boundary"))
+        .map(|(_, v)| v.trim_matches('"'))
+}
+
+pub fn parse(boundary: &str, body: &[u8]) -> Option<Vec<Part>> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut parts = Vec::new();
+    let mut rest = &body[find(body, &delimiter)? + delimiter.len()..];
+
+    loop {
+        if rest.starts_with(b"--") {
+            return Some(parts);
+        }
+        rest = rest.strip_prefix(b"\r\n")?;
+        let header_end = find(rest, b"\r\n\r\n")?;
+        let headers = std::str::from_utf8(&rest[..header_end]).ok()?;
+        rest = &rest[header_end + 4..];
+        let data_end = find(rest, &delimiter)?;
+        let data = rest[..data_end].strip_suffix(b"\r\n")?;
+
+        let disposition = headers
+            .lines()
+            .filter_map(|l| l.split_once(':'))
+            .find(|(k, _)| k.trim().eq_ignore_ascii_case("content-disposition"))
+            .map(|(_, v)| v)?;
+        let param = |key: &str| {
+            disposition
+                .split(';')
+                .filter_map(|p| p.trim().split_once('='))
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v.trim_matches('"').to_string())
+        };
+        parts.push(Part {
+            name: param("name")?,
+            filename: param("filename"),
+            data: data.to_vec(),
+        });
+
+        rest = &rest[data_end + delimiter.len()..];
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_boundary() {
+        let boundary = |content_type| super::boundary(content_type);
+        assert_eq!(
+            boundary("multipart/form-data; boundary=\"x-1\""),
+            Some("x-1")
+        );
+        assert_eq!(
+            boundary("Multipart/Form-Data; charset=utf-8; Boundary=abc"),
+            Some("abc")
+        );
+        assert_eq!(boundary("application/x-www-form-urlencoded"), None);
+        assert_eq!(boundary("text/plain; boundary=abc"), None);
+    }
+
+    #[test]
+    fn parses_fields_and_files() {
+        let body = b"preamble\r\n--b\r\n\
+            Content-Disposition: form-data; name=\"role\"\r\n\r\n\
+            Circle Lead\r\n--b\r\n\
+            Content-Disposition: form-data; name=\"roster\"; filename=\"voters.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\n\
+            Ann\r\nBob\r\n--b--\r\n";
+        let parts = parse("b", body).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(
+            (parts[0].name.as_str(), parts[0].filename.as_deref()),
+            ("role", None)
+        );
+        assert_eq!(parts[0].data, b"Circle Lead");
+        assert_eq!(parts[1].filename.as_deref(), Some("voters.txt"));
+        assert_eq!(parts[1].data, b"Ann\r\nBob");
+    }
+
+    #[test]
+    fn rejects_broken_bodies() {
+        assert!(parse("b", b"no delimiter").is_none());
+        // not terminated
+        assert!(parse(
+            "b",
+            b"--b\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nx"
+        )
+        .is_none());
+        // without a name
+        assert!(parse("b", b"--b\r\nContent-Type: text/plain\r\n\r\nx\r\n--b--").is_none());
+    }
+}
@@ -0,0 +1,85 @@
+//! The ballot a voter cast from this browser, as remembered by their cookie,
+//! to update or withdraw it until the tally.
+
+use crate::{
+    ballot_summary, base_html, cookies, process_error, round_note, ElectionDB, ElectionUpdate,
+};
+use axum::{
+    extract::{Path, State},
+    http::{header::SET_COOKIE, HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use iep_core::{Ballot, ElectionProcess};
+use maud::{html, Markup};
+
+pub async fn view_my_vote(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    headers: HeaderMap,
+) -> Result<Markup, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let voter = cookies::voted_as(&state, &headers, &id);
+    let ballot = voter.as_ref().and_then(|voter| {
+        election
+            .voting_round_ballots()
+            .ok()
+            .and_then(|round| round.get(voter))
+    });
+    Ok(base_html(
+        &format!("{} - My Vote", election.elected_role),
+        html! { (election.elected_role) br; "My Vote" },
+        html! {
+            @match (&voter, ballot) {
+                (Some(voter), Some(ballot)) => (my_vote(election, voter, ballot)),
+                _ => p {
+                    "There is no vote from this browser in the current round. "
+                    a href={"/election/" (id) "/voting"} { "Go to the vote" }
+                },
+            }
+        },
+        html! {},
+    ))
+}
+
+/// The ballot of `voter` with the actions to update or withdraw it.
+pub fn my_vote(election: &ElectionProcess, voter: &str, ballot: &Ballot) -> Markup {
+    html! {
+        h2 { (election.phase_title()) }
+        (round_note(election))
+        p { "You already voted (for " strong { (ballot_summary(election, ballot)) } ") — update your vote?" }
+        @if let Some(reason) = &ballot.reason {
+            p { small { "Your reason: " (reason) } }
+        }
+        div ."grid" {
+            a role="button" ."secondary" href={"/election/" (election.id) "/voting?update=true"} {
+                "Update the vote of " (voter)
+            }
+            button ."secondary outline"
+              hx-post={"/election/" (election.id) "/voting/withdraw"} hx-swap="none"
+              hx-confirm="Withdraw your vote? It will no longer be counted." {
+                "Withdraw"
+            }
+        }
+    }
+}
+
+/// Removes the ballot of the voter of this browser and forgets them.
+pub async fn post_withdraw(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let voter = cookies::voted_as(&state, &headers, &id)
+        .ok_or((StatusCode::FORBIDDEN, "No vote was cast from this browser"))?;
+    state.modify_election(&id, |election| {
+        election
+            .withdraw_vote(&voter, state.clock.now())
+            .map_err(process_error)
+    })?;
+    state.notify(&id, ElectionUpdate::VotesChanged)?;
+    Ok((
+        StatusCode::ACCEPTED,
+        [(SET_COOKIE, cookies::forget_voter(&id))],
+        [("HX-Redirect", format!("/election/{}/voting", id))],
+    ))
+}
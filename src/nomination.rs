@@ -0,0 +1,230 @@
+//! The nomination phase, in which participants propose nominees before the
+//! first vote and the facilitator tidies up the list.
+
+use crate::{process_error, ElectionDB, ElectionUpdate, PhaseDescription};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Form,
+};
+use iep_core::{ElectionProcess, NominationStatus, ProcessError};
+use itertools::*;
+use maud::{html, Markup};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct NominationForm {
+    name: String,
+    /// Who nominates; nominees naming themselves need not accept.
+    nominated_by: Option<String>,
+}
+
+pub async fn post_nomination(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    Form(form): Form<NominationForm>,
+) -> Result<Markup, (StatusCode, &'static str)> {
+    let (added, pending) = state.modify_election(&id, |election| {
+        let (nominee, added) = election
+            .nominate(&form.name, form.nominated_by.as_deref(), state.clock.now())
+            .map_err(process_error)?;
+        Ok((added, !election.on_ballot(nominee)))
+    })?;
+    if added {
+        state.notify(&id, ElectionUpdate::NomineesChanged)?;
+    }
+    let name = form.name.trim();
+    Ok(html! {
+        @if !added {
+            p { strong { (name) } " was already nominated." }
+        } @else if pending {
+            p { "Thank you, " strong { (name) } " is nominated and needs to accept on this page." }
+        } @else {
+            p { "Thank you, " strong { (name) } " is nominated." }
+        }
+    })
+}
+
+/// Accepts or declines a nomination, for the nominee.
+pub async fn post_nomination_answer(
+    Path((id, nominee, answer)): Path<(String, u64, String)>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let accepted = match &answer[..] {
+        "accept" => true,
+        "decline" => false,
+        _ => return Err((StatusCode::BAD_REQUEST, "Invalid answer")),
+    };
+    state.modify_election(&id, |election| {
+        election
+            .answer_nomination(nominee, accepted, state.clock.now())
+            .map_err(process_error)
+    })?;
+    state.notify(&id, ElectionUpdate::NomineesChanged)?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[derive(Deserialize)]
+pub struct MergeForm {
+    duplicate: u64,
+    /// Id of the nominee the duplicate is the same as; empty to just remove
+    /// the duplicate.
+    #[serde(default)]
+    into: String,
+}
+
+/// Removes a duplicate nomination, for the facilitator.
+pub async fn post_nominee_merge(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    Form(form): Form<MergeForm>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    state.modify_election(&id, |election| {
+        if !form.into.is_empty() {
+            let into = form.into.parse::<u64>().ok();
+            if into == Some(form.duplicate)
+                || !into.is_some_and(|i| election.nominees.contains_key(&i))
+            {
+                return Err(process_error(ProcessError::UnknownNominee));
+            }
+        }
+        election
+            .remove_nominee(form.duplicate, state.clock.now())
+            .map_err(process_error)
+    })?;
+    state.notify(&id, ElectionUpdate::NomineesChanged)?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// The nominees so far, refreshed for participants while others nominate.
+pub async fn get_nominees(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<Markup, StatusCode> {
+    let election = state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(nominee_list(&election))
+}
+
+pub async fn get_pending_nominations(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<Markup, StatusCode> {
+    let election = state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(pending_nominations(&election))
+}
+
+fn nominee_list(election: &ElectionProcess) -> Markup {
+    html! {
+        @if election.nominees.is_empty() {
+            p { "Nobody was nominated yet." }
+        }
+        ul {
+            @for (nominee_id, nominee) in election.nominees.iter().sorted_by_key(|(_, n)| n.to_lowercase()) {
+                li {
+                    @match election.nomination_status.get(nominee_id) {
+                        Some(NominationStatus::Declined) => { s { (nominee) } " " small { "(declined)" } },
+                        _ => (nominee),
+                    }
+                }
+            }
+        }
+        (pending_nominations(election))
+    }
+}
+
+/// Buttons for nominees to accept or decline being nominated by others.
+pub fn pending_nominations(election: &ElectionProcess) -> Markup {
+    let pending = election
+        .nominees
+        .iter()
+        .filter(|(id, _)| election.nomination_status.get(id) == Some(&NominationStatus::Pending))
+        .sorted_by_key(|(_, n)| n.to_lowercase())
+        .collect::<Vec<_>>();
+    if pending.is_empty() {
+        return html! {};
+    }
+    html! {
+        article {
+            header { "Were you nominated? Please accept or decline before the vote." }
+            @for (nominee_id, nominee) in pending {
+                @let path = format!("/election/{}/nominations/{}", election.id, nominee_id);
+                p {
+                    strong { (nominee) } " "
+                    button ."outline" hx-post={(path) "/accept"} hx-swap="none"
+                      hx-confirm={"Accept the nomination as " (nominee) "?"} { "Accept" }
+                    " "
+                    button ."secondary outline" hx-post={(path) "/decline"} hx-swap="none"
+                      hx-confirm={"Decline the nomination as " (nominee) "?"} { "Decline" }
+                }
+            }
+        }
+    }
+}
+
+pub fn nomination_form(election: &ElectionProcess) -> Markup {
+    html! {
+        h2 { (election.phase_title()) }
+        p { (election.phase.nice_description()) }
+        article #"nomination" {
+            form ."table rows" hx-post={"/election/" (election.id) "/nominations"} hx-target="#nomination" {
+                label for="name" {
+                    "Nominee: ";
+                    input type="text" name="name" required {}
+                }
+                label for="nominated_by" {
+                    "Your name (the same to nominate yourself): ";
+                    input type="text" name="nominated_by" {}
+                }
+                button type="submit" { "Nominate" }
+            }
+        }
+        h3 { "Nominated so far" }
+        div hx-get={"/election/" (election.id) "/nominations"} hx-trigger="sse:nominees-changed" {
+            (nominee_list(election))
+        }
+    }
+}
+
+/// The nominations with actions to merge duplicates, for the facilitator.
+pub fn nomination_review(election: &ElectionProcess) -> Markup {
+    let nominees = election
+        .nominees
+        .iter()
+        .sorted_by_key(|(_, n)| n.to_lowercase())
+        .collect::<Vec<_>>();
+    let path = format!("/election/{}/nominations/merge", election.id);
+    html! {
+        p { "Nominations so far: " (nominees.len()) ". Open the vote with \"Next Phase\" once everyone is nominated." }
+        table ."striped" {
+            tbody {
+                @for (nominee_id, nominee) in &nominees {
+                    tr {
+                        td {
+                            (nominee)
+                            @match election.nomination_status.get(nominee_id) {
+                                Some(NominationStatus::Pending) => small { " (not accepted yet)" },
+                                Some(NominationStatus::Declined) => small { " (declined)" },
+                                _ => {},
+                            }
+                        }
+                        td {
+                            form hx-post=(path) hx-swap="none" style="margin: 0;" {
+                                input type="hidden" name="duplicate" value=(nominee_id) {}
+                                fieldset role="group" style="margin: 0;" {
+                                    select name="into" {
+                                        option value="" { "Remove" }
+                                        @for (other_id, other) in nominees.iter().filter(|(o, _)| o != nominee_id) {
+                                            option value=(other_id) { "Same as " (other) }
+                                        }
+                                    }
+                                    button type="submit" ."secondary outline" { "Apply" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! A read-only view of the evaluation, with the live tally and phase but
+//! without anything that changes the election, to put on a projector or to
+//! share with people who should only watch.
+
+use crate::{base_html, eval_view, live_votes, ElectionDB};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+};
+use maud::{html, Markup};
+
+pub async fn view_observer(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<Markup, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let voting_url = format!("{}/election/{}/voting", &state.base_url, id);
+
+    Ok(base_html(
+        format!("{} - Observer", election.elected_role).as_str(),
+        html! { (election.elected_role) br; "Observer" },
+        html! {
+            @if election.closed_at.is_some() {
+                (eval_view(election, false))
+            } @else {
+                p { "Vote at " a ."contrast" href=(voting_url) { (voting_url) } }
+                div hx-ext="sse" sse-connect={"/election/" (id) "/stream"} {
+                    div #"eval"
+                      hx-get={"/election/" (id) "/observe/content"}
+                      hx-trigger="sse:phase-changed,sse:nominees-changed,sse:resync,reload"
+                      hx-swap="innerHTML" {
+                        (eval_view(election, false))
+                    }
+                    (live_votes())
+                }
+            }
+        },
+        html! {},
+    ))
+}
+
+pub async fn get_observer_content(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<Markup, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(eval_view(election, false))
+}
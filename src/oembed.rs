@@ -0,0 +1,108 @@
+//! Link previews of elections for chat tools: Open Graph tags on the voting
+//! page, and an oEmbed endpoint at `/oembed?url=<election link>` with the
+//! live widget of [`embed`](crate::embed) as rich content.
+
+use crate::{url_encode, ElectionDB};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use iep_core::{ElectionKind, ElectionProcess};
+use itertools::*;
+use maud::{html, Markup};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const WIDTH: u32 = 400;
+const HEIGHT: u32 = 300;
+
+/// e.g. "First Vote · 7 of 10 voted" or "Elected: Ann"
+fn description(election: &ElectionProcess) -> String {
+    let elected = election.elected_seats();
+    if !elected.is_empty() {
+        return format!("Elected: {}", elected.iter().map(|e| &e.name).join(", "));
+    }
+    let phase = match election.closed_at {
+        Some(_) => "Closed",
+        None => election.phase_title(),
+    };
+    match (election.voting_round().is_ok(), election.expected_voters) {
+        (true, Some(expected)) => format!(
+            "{} · {} of {} voted",
+            phase,
+            election.voted_count(),
+            expected
+        ),
+        (true, None) => format!("{} · {} voted", phase, election.voted_count()),
+        (false, _) => phase.to_string(),
+    }
+}
+
+fn title(election: &ElectionProcess) -> String {
+    match election.kind {
+        ElectionKind::Election => format!("Election for {}", election.elected_role),
+        ElectionKind::StrawPoll => format!("Straw poll on {}", election.elected_role),
+    }
+}
+
+/// The tags in the head of the voting page that chat tools show.
+pub fn link_preview(state: &ElectionDB, election: &ElectionProcess) -> Markup {
+    let url = format!("{}/election/{}/voting", state.base_url, election.id);
+    html! {
+        meta property="og:type" content="website" {}
+        meta property="og:site_name" content="IEP Tool" {}
+        meta property="og:title" content=(title(election)) {}
+        meta property="og:description" content=(description(election)) {}
+        meta property="og:url" content=(url) {}
+        meta property="og:image" content={(state.base_url) "/election/" (election.id) "/qr.png"} {}
+        link rel="alternate" type="application/json+oembed" title=(title(election))
+          href={(state.base_url) "/oembed?format=json&url=" (url_encode(&url))} {}
+    }
+}
+
+#[derive(Deserialize)]
+pub struct OEmbedQuery {
+    url: String,
+    format: Option<String>,
+    maxwidth: Option<u32>,
+    maxheight: Option<u32>,
+}
+
+pub async fn get_oembed(
+    Query(query): Query<OEmbedQuery>,
+    State(state): State<ElectionDB>,
+) -> Result<Json<Value>, (StatusCode, &'static str)> {
+    if query.format.as_deref().is_some_and(|f| f != "json") {
+        return Err((StatusCode::NOT_IMPLEMENTED, "Only JSON is supported"));
+    }
+    let id = query
+        .url
+        .strip_prefix(&format!("{}/election/", state.base_url))
+        .and_then(|rest| rest.split(['/', '?', '#']).next())
+        .ok_or((StatusCode::NOT_FOUND, "Not an election link"))?;
+    let election = state
+        .store
+        .get(id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    // the description gives away the votes, and the widget needs the passcode
+    if election.passcode.is_some() {
+        return Err((StatusCode::FORBIDDEN, "This election needs its passcode"));
+    }
+    let width = query.maxwidth.map_or(WIDTH, |w| w.min(WIDTH));
+    let height = query.maxheight.map_or(HEIGHT, |h| h.min(HEIGHT));
+    let iframe = html! {
+        iframe src={(state.base_url) "/election/" (election.id) "/embed"}
+          width=(width) height=(height) style="border: 0;" title=(title(&election)) {}
+    };
+    Ok(Json(json!({
+        "version": "1.0",
+        "type": "rich",
+        "provider_name": "IEP Tool",
+        "provider_url": state.base_url,
+        "title": format!("{}: {}", title(&election), description(&election)),
+        "html": iframe.into_string(),
+        "width": width,
+        "height": height,
+    })))
+}
@@ -0,0 +1,347 @@
+//! Optional single sign-on with OpenID Connect, e.g. with Keycloak or
+//! Authentik, so only logged-in users can create and facilitate elections
+//! and open the admin pages. Voting stays anonymous.
+//!
+//! The provider is configured with `OIDC_ISSUER`, `OIDC_CLIENT_ID` and
+//! `OIDC_CLIENT_SECRET`, and `BASE_URL/auth/callback` has to be allowed as
+//! its redirect URI. The issuer and its endpoints have to be `https://`, as
+//! the client secret and tokens go to them.
+
+use crate::{
+    cookies,
+    crypto::{base64_decode, base64_encode, constant_time_eq},
+    facilitator, http, url_encode, ElectionDB,
+};
+use axum::{
+    extract::{Query, Request, State},
+    http::{
+        header::{LOCATION, SET_COOKIE},
+        HeaderMap, Method, StatusCode, Uri,
+    },
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+const STATE_COOKIE: &str = "iep_oidc";
+/// How long a login at the provider may take.
+const LOGIN_SECS: u64 = 10 * 60;
+
+pub struct Provider {
+    issuer: String,
+    client_id: String,
+    client_secret: String,
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct Tokens {
+    access_token: String,
+}
+
+#[derive(Deserialize)]
+struct UserInfo {
+    sub: String,
+    preferred_username: Option<String>,
+    email: Option<String>,
+}
+
+impl Provider {
+    /// The provider of `OIDC_ISSUER`, if it is set; an error if the rest of
+    /// its configuration is missing or wrong.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Option<Self>, String> {
+        let var = |name| var(name).filter(|v| !v.is_empty());
+        let Some(issuer) = var("OIDC_ISSUER") else {
+            return Ok(None);
+        };
+        if !http::is_https(&issuer) {
+            return Err(format!(
+                "OIDC_ISSUER has to be an https:// URL, got {}",
+                issuer
+            ));
+        }
+        let required = |name| var(name).ok_or(format!("OIDC_ISSUER is set but {} is not", name));
+        Ok(Some(Provider {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id: required("OIDC_CLIENT_ID")?,
+            client_secret: required("OIDC_CLIENT_SECRET")?,
+        }))
+    }
+
+    async fn metadata(&self) -> Result<Metadata, String> {
+        let url = format!("{}/.well-known/openid-configuration", self.issuer);
+        let body = http::request("GET", &url, &[], b"").await?;
+        let metadata: Metadata = serde_json::from_str(&body)
+            .map_err(|e| format!("Invalid metadata at {}: {}", url, e))?;
+        for endpoint in [
+            &metadata.authorization_endpoint,
+            &metadata.token_endpoint,
+            &metadata.userinfo_endpoint,
+        ] {
+            if !http::is_https(endpoint) {
+                return Err(format!("The endpoint {} is not https://", endpoint));
+            }
+        }
+        Ok(metadata)
+    }
+
+    /// The name of the user who logged in with `code`.
+    async fn user(&self, redirect_uri: &str, code: &str) -> Result<String, String> {
+        let metadata = self.metadata().await?;
+        let form = format!(
+            "grant_type=authorization_code&code={}&redirect_uri={}&client_id={}&client_secret={}",
+            url_encode(code),
+            url_encode(redirect_uri),
+            url_encode(&self.client_id),
+            url_encode(&self.client_secret)
+        );
+        let body = http::post(
+            &metadata.token_endpoint,
+            "application/x-www-form-urlencoded",
+            &[],
+            form.as_bytes(),
+        )
+        .await?;
+        let tokens: Tokens =
+            serde_json::from_str(&body).map_err(|e| format!("Invalid token response: {}", e))?;
+        let body = http::request(
+            "GET",
+            &metadata.userinfo_endpoint,
+            &[("Authorization", format!("Bearer {}", tokens.access_token))],
+            b"",
+        )
+        .await?;
+        let info: UserInfo =
+            serde_json::from_str(&body).map_err(|e| format!("Invalid userinfo: {}", e))?;
+        Ok(info.preferred_username.or(info.email).unwrap_or(info.sub))
+    }
+}
+
+fn redirect_uri(state: &ElectionDB) -> String {
+    format!("{}/auth/callback", state.base_url)
+}
+
+/// Only paths on this site, so logging in cannot lead elsewhere. Browsers
+/// read `/\host` like `//host`, so backslashes and control characters are
+/// refused along with anything that is not a plain path and query.
+fn local_path(next: Option<String>) -> String {
+    next.filter(|n| {
+        n.starts_with('/')
+            && !n.starts_with("//")
+            && !n.chars().any(|c| c == '\\' || c.is_control())
+            && n.parse::<Uri>()
+                .is_ok_and(|uri| uri.scheme().is_none() && uri.authority().is_none())
+    })
+    .unwrap_or("/".to_string())
+}
+
+fn redirect(location: String, cookie: String) -> Response {
+    (
+        StatusCode::SEE_OTHER,
+        [(LOCATION, location), (SET_COOKIE, cookie)],
+    )
+        .into_response()
+}
+
+/// Who is logged in, or `None` without single sign-on.
+pub fn user(state: &ElectionDB, headers: &HeaderMap) -> Option<String> {
+    state.oidc.as_ref()?;
+    cookies::logged_in_as(state, headers)
+}
+
+/// Whether single sign-on is configured and nobody is logged in.
+pub fn needs_login(state: &ElectionDB, headers: &HeaderMap) -> bool {
+    state.oidc.is_some() && cookies::logged_in_as(state, headers).is_none()
+}
+
+/// Whether the path is for creating or facilitating elections.
+fn is_restricted_route(method: &Method, path: &str) -> bool {
+    let segments = path.trim_start_matches('/').split('/').collect::<Vec<_>>();
+    match &segments[..] {
+        ["election"] | ["api", "v1", "elections"] => method == Method::POST,
+        ["election", "new"] | ["templates", ..] | ["admin", ..] | ["circles", ..] => true,
+        ["election", _, "follow-up" | "clone" | "template"] => true,
+        ["election", _, rest @ ..] => facilitator::is_facilitator_route(method, &rest.join("/")),
+        _ => false,
+    }
+}
+
+/// Sends users who are not logged in to the provider when they open a
+/// restricted page and refuses their other restricted requests, unless they
+/// give the admin token, e.g. from scripts.
+pub async fn require_login(
+    State(state): State<ElectionDB>,
+    request: Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let admin_token = request
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="));
+    if !is_restricted_route(request.method(), request.uri().path())
+        || !needs_login(&state, request.headers())
+        || state.check_admin(admin_token).is_ok()
+    {
+        return next.run(request).await;
+    }
+    if request.method() != Method::GET || request.headers().contains_key("HX-Request") {
+        return (StatusCode::UNAUTHORIZED, "Log in first").into_response();
+    }
+    let location = format!(
+        "/auth/login?next={}",
+        url_encode(&request.uri().to_string())
+    );
+    (StatusCode::SEE_OTHER, [(LOCATION, location)]).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct LoginQuery {
+    next: Option<String>,
+}
+
+pub async fn get_login(
+    Query(query): Query<LoginQuery>,
+    State(state): State<ElectionDB>,
+) -> Result<Response, (StatusCode, &'static str)> {
+    let provider = state
+        .oidc
+        .as_ref()
+        .ok_or((StatusCode::NOT_FOUND, "Single sign-on is not configured"))?;
+    let metadata = provider.metadata().await.map_err(|error| {
+        tracing::error!("Fetching the OpenID Connect metadata failed: {}", error);
+        (StatusCode::BAD_GATEWAY, "The login provider is unavailable")
+    })?;
+    let nonce = state.random.alphanumeric(32);
+    let location = format!(
+        "{}?response_type=code&scope=openid%20profile%20email&client_id={}&redirect_uri={}&state={}",
+        metadata.authorization_endpoint,
+        url_encode(&provider.client_id),
+        url_encode(&redirect_uri(&state)),
+        nonce
+    );
+    let cookie = format!(
+        "{}={}.{}; Path=/auth; Max-Age={}; HttpOnly; SameSite=Lax",
+        STATE_COOKIE,
+        nonce,
+        base64_encode(local_path(query.next).as_bytes()),
+        LOGIN_SECS
+    );
+    Ok(redirect(location, cookie))
+}
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+}
+
+pub async fn get_callback(
+    Query(query): Query<CallbackQuery>,
+    State(state): State<ElectionDB>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, &'static str)> {
+    let provider = state
+        .oidc
+        .as_ref()
+        .ok_or((StatusCode::NOT_FOUND, "Single sign-on is not configured"))?;
+    let (_, next) = cookies::read(&headers, STATE_COOKIE)
+        .and_then(|value| value.split_once('.'))
+        .filter(|(nonce, _)| {
+            let given = query.state.as_deref();
+            given.is_some_and(|given| constant_time_eq(given.as_bytes(), nonce.as_bytes()))
+        })
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            "The login expired, please try again",
+        ))?;
+    let next = base64_decode(next).and_then(|n| String::from_utf8(n).ok());
+    let code = query
+        .code
+        .ok_or((StatusCode::UNAUTHORIZED, "The login was not completed"))?;
+    let user = provider
+        .user(&redirect_uri(&state), &code)
+        .await
+        .map_err(|error| {
+            tracing::error!("OpenID Connect login failed: {}", error);
+            (StatusCode::BAD_GATEWAY, "The login failed")
+        })?;
+    tracing::info!("{} logged in", user);
+    Ok(redirect(
+        local_path(next),
+        cookies::session_cookie(&state, &user),
+    ))
+}
+
+pub async fn get_logout() -> Response {
+    redirect("/".to_string(), cookies::forget_session())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirects_only_to_local_paths() {
+        let next = |n: &str| local_path(Some(n.to_string()));
+        assert_eq!(next("/election/abc?x=1"), "/election/abc?x=1");
+        for outside in [
+            "//evil.com",
+            "/\\evil.com",
+            "/\\/evil.com",
+            "/\t/evil.com",
+            "https://evil.com",
+            "evil.com",
+        ] {
+            assert_eq!(next(outside), "/", "{}", outside);
+        }
+        assert_eq!(local_path(None), "/");
+    }
+
+    #[test]
+    fn needs_the_whole_configuration() {
+        let provider = |vars: &[(&str, &str)]| {
+            let vars = vars.to_vec();
+            Provider::from_vars(move |name| {
+                vars.iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| v.to_string())
+            })
+            .map(|p| p.map(|p| (p.issuer, p.client_id, p.client_secret)))
+        };
+        assert_eq!(provider(&[]), Ok(None));
+        assert_eq!(provider(&[("OIDC_ISSUER", "")]), Ok(None));
+        let issuer = ("OIDC_ISSUER", "https://id.example.org/");
+        assert_eq!(
+            provider(&[issuer, ("OIDC_CLIENT_ID", "iep")]),
+            Err("OIDC_ISSUER is set but OIDC_CLIENT_SECRET is not".to_string())
+        );
+        assert_eq!(
+            provider(&[issuer, ("OIDC_CLIENT_SECRET", "s")]),
+            Err("OIDC_ISSUER is set but OIDC_CLIENT_ID is not".to_string())
+        );
+        assert!(provider(&[("OIDC_ISSUER", "http://id.example.org")]).is_err());
+        assert_eq!(
+            provider(&[
+                issuer,
+                ("OIDC_CLIENT_ID", "iep"),
+                ("OIDC_CLIENT_SECRET", "s")
+            ]),
+            Ok(Some((
+                "https://id.example.org".to_string(),
+                "iep".to_string(),
+                "s".to_string()
+            )))
+        );
+    }
+}
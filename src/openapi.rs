@@ -0,0 +1,291 @@
+//! The OpenAPI document of the JSON API, served at `/api/openapi.json` and
+//! browsable with Swagger UI at `/api/docs`. It is written by hand next to
+//! the handlers in `api.rs`, so keep the two in step.
+
+use crate::ElectionDB;
+use axum::{extract::State, Json};
+use maud::{html, Markup, DOCTYPE};
+use serde_json::{json, Value};
+
+fn error_response(description: &str) -> Value {
+    json!({
+        "description": description,
+        "content": { "text/plain": { "schema": { "type": "string" } } },
+    })
+}
+
+fn election_id() -> Value {
+    json!({
+        "name": "id",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "string" },
+    })
+}
+
+fn json_body(schema: &str) -> Value {
+    json!({
+        "required": true,
+        "content": { "application/json": { "schema": { "$ref": format!("#/components/schemas/{}", schema) } } },
+    })
+}
+
+fn json_response(description: &str, schema: &str) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": { "$ref": format!("#/components/schemas/{}", schema) } } },
+    })
+}
+
+fn schemas() -> Value {
+    let phase = json!({
+        "type": "string",
+        "enum": ["Nomination", "FirstVote", "FirstTally", "SecondVote", "SecondTally", "SafetyRound", "Completed"],
+    });
+    let kind = json!({ "type": "string", "enum": ["Election", "StrawPoll"] });
+    let method = json!({ "type": "string", "enum": ["Plurality", "Ranked", "Score"] });
+    json!({
+        "ElectionList": {
+            "type": "object",
+            "properties": {
+                "elections": { "type": "array", "items": { "$ref": "#/components/schemas/ElectionSummary" } },
+                "next_cursor": { "type": "string", "nullable": true, "description": "Pass as `cursor` to get the next page; absent on the last page." },
+            },
+        },
+        "ElectionSummary": {
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" },
+                "kind": kind,
+                "elected_role": { "type": "string" },
+                "phase": phase,
+                "meeting": { "type": "string", "nullable": true },
+                "nominees": { "type": "integer" },
+                "first_round_ballots": { "type": "integer" },
+                "second_round_ballots": { "type": "integer" },
+                "created_at": { "type": "integer", "description": "Unix time in seconds." },
+            },
+        },
+        "NewElection": {
+            "type": "object",
+            "required": ["elected_role"],
+            "properties": {
+                "kind": kind,
+                "elected_role": { "type": "string" },
+                "nominees": { "type": "array", "items": { "type": "string" } },
+                "method": method,
+                "seats": { "type": "integer", "minimum": 1 },
+                "rounds": { "type": "integer", "minimum": 1 },
+                "nomination": { "type": "boolean", "description": "Start with a nomination phase." },
+                "post_vote_message": { "type": "string" },
+                "meeting": { "type": "string" },
+                "slug": { "type": "string", "description": "Human-readable id to use instead of a random one." },
+                "keep_days": { "type": "integer", "description": "Days to keep the election after its last activity." },
+                "expected_voters": { "type": "integer", "minimum": 1 },
+                "require_quorum": { "type": "boolean" },
+                "auto_advance": { "type": "boolean" },
+                "roster": { "type": "array", "items": { "type": "string" }, "description": "The eligible voters." },
+                "passcode": { "type": "string", "description": "Needed to vote." },
+                "webhooks": { "type": "array", "items": { "type": "string" }, "description": "`http://` or `https://` URLs told about new votes and phases." },
+                "slack_webhook": { "type": "string", "description": "Slack incoming webhook announcing the phases." },
+                "discord_webhook": { "type": "string", "description": "Discord webhook announcing the phases." },
+                "report_to": { "type": "array", "items": { "type": "string" }, "description": "Addresses emailed the results on closing." },
+                "report_to_roster": { "type": "boolean", "description": "Also email the results to the roster." },
+            },
+        },
+        "CreatedElection": {
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" },
+                "facilitator_key": { "type": "string", "nullable": true, "description": "Needed as bearer token to change the phase." },
+                "webhook_secret": { "type": "string", "nullable": true, "description": "Signs the payloads of the webhooks, if there are any." },
+                "eval_url": { "type": "string" },
+                "voting_url": { "type": "string" },
+            },
+        },
+        "ElectionState": {
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" },
+                "kind": kind,
+                "elected_role": { "type": "string" },
+                "method": method,
+                "phase": phase,
+                "closed": { "type": "boolean" },
+                "nominees": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string" },
+                    "description": "Nominee names by id, the ids to vote with.",
+                },
+                "voted": { "type": "integer", "description": "Ballots in the current voting round." },
+                "expected_voters": { "type": "integer", "nullable": true },
+                "elected": {
+                    "type": "object",
+                    "nullable": true,
+                    "properties": {
+                        "nominee_id": { "type": "integer" },
+                        "name": { "type": "string" },
+                        "at": { "type": "integer" },
+                    },
+                },
+                "results": { "type": "object", "description": "Like `/election/{id}/results.json`." },
+            },
+        },
+        "NewVote": {
+            "type": "object",
+            "properties": {
+                "voter_name": { "type": "string", "description": "Taken from the token instead if there is one." },
+                "token": { "type": "string", "description": "The token of a voting link." },
+                "vote": { "type": "integer", "description": "The nominee id, for plurality elections." },
+                "ranking": { "type": "array", "items": { "type": "integer" }, "description": "Nominee ids in order of preference, for ranked elections." },
+                "scores": {
+                    "type": "object",
+                    "additionalProperties": { "type": "integer", "minimum": 0 },
+                    "description": "Scores by nominee id, for score elections.",
+                },
+                "reason": { "type": "string" },
+                "write_in": { "type": "string", "description": "A new nominee to vote for instead, during the first vote." },
+                "update": { "type": "boolean", "description": "Replace an earlier ballot of the same name." },
+                "passcode": { "type": "string", "description": "Needed if the election has one." },
+            },
+        },
+        "CastVote": {
+            "type": "object",
+            "properties": {
+                "message": { "type": "string" },
+                "receipt": { "type": "string", "description": "To check on the voting page that the vote counted." },
+            },
+        },
+        "PhaseStep": {
+            "type": "object",
+            "required": ["step"],
+            "properties": {
+                "step": { "type": "string", "enum": ["next", "prev", "reset", "undo", "close"] },
+                "from": {
+                    "allOf": [{ "$ref": "#/components/schemas/Phase" }],
+                    "description": "Only take the step if the election is still in this phase.",
+                },
+            },
+        },
+        "Phase": phase,
+    })
+}
+
+fn document(state: &ElectionDB) -> Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "IEP Tool API",
+            "version": "1",
+            "description": "Create elections, cast votes, read their state and move them through their phases.",
+        },
+        "servers": [{ "url": state.base_url }],
+        "paths": {
+            "/api/v1/elections": {
+                "get": {
+                    "summary": "List elections",
+                    "parameters": [
+                        { "name": "token", "in": "query", "required": true, "schema": { "type": "string" }, "description": "The admin token." },
+                        { "name": "phase", "in": "query", "schema": { "$ref": "#/components/schemas/Phase" } },
+                        { "name": "kind", "in": "query", "schema": { "type": "string" } },
+                        { "name": "role", "in": "query", "schema": { "type": "string" }, "description": "Case-insensitive part of the elected role." },
+                        { "name": "meeting", "in": "query", "schema": { "type": "string" } },
+                        { "name": "sort", "in": "query", "schema": { "type": "string", "enum": ["created", "role", "phase"] } },
+                        { "name": "desc", "in": "query", "schema": { "type": "boolean" } },
+                        { "name": "limit", "in": "query", "schema": { "type": "integer", "minimum": 1, "maximum": 100 } },
+                        { "name": "cursor", "in": "query", "schema": { "type": "string" } },
+                    ],
+                    "responses": {
+                        "200": json_response("A page of elections", "ElectionList"),
+                        "400": error_response("Invalid filter or cursor"),
+                        "401": error_response("Invalid admin token"),
+                    },
+                },
+                "post": {
+                    "summary": "Create an election",
+                    "requestBody": json_body("NewElection"),
+                    "responses": {
+                        "201": json_response("The new election", "CreatedElection"),
+                        "400": error_response("Invalid election"),
+                        "401": error_response("Single sign-on is configured and neither a session nor the admin token was given"),
+                        "409": error_response("The id is taken"),
+                    },
+                },
+            },
+            "/api/v1/elections/{id}": {
+                "get": {
+                    "summary": "Get the state of an election",
+                    "description": "Elections with a passcode need it, or the facilitator key, as bearer token.",
+                    "parameters": [election_id()],
+                    "responses": {
+                        "200": json_response("The election", "ElectionState"),
+                        "403": error_response("Needs the passcode or the facilitator key"),
+                        "404": error_response("Election not found"),
+                    },
+                },
+            },
+            "/api/v1/elections/{id}/votes": {
+                "post": {
+                    "summary": "Cast a vote",
+                    "parameters": [election_id()],
+                    "requestBody": json_body("NewVote"),
+                    "responses": {
+                        "201": json_response("The vote was added", "CastVote"),
+                        "400": error_response("Invalid ballot"),
+                        "403": error_response("Wrong passcode or not an eligible voter"),
+                        "404": error_response("Election not found"),
+                        "409": error_response("Not in a voting phase, or already voted without `update`"),
+                    },
+                },
+            },
+            "/api/v1/elections/{id}/phase": {
+                "post": {
+                    "summary": "Take a step through the phases",
+                    "parameters": [election_id()],
+                    "security": [{ "facilitatorKey": [] }],
+                    "requestBody": json_body("PhaseStep"),
+                    "responses": {
+                        "200": json_response("The election after the step", "ElectionState"),
+                        "403": error_response("Needs the facilitator key"),
+                        "404": error_response("Election not found"),
+                        "409": error_response("The step is not possible in the current phase"),
+                    },
+                },
+            },
+        },
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": {
+                "facilitatorKey": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "The facilitator key returned when creating the election, or the key of a co-facilitator.",
+                },
+            },
+        },
+    })
+}
+
+pub async fn get_openapi_json(State(state): State<ElectionDB>) -> Json<Value> {
+    Json(document(&state))
+}
+
+pub async fn view_api_docs() -> Markup {
+    html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="utf-8";
+                title { "IEP Tool API" }
+                link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist/swagger-ui.css";
+            }
+            body {
+                div #"swagger-ui" {}
+                script src="https://unpkg.com/swagger-ui-dist/swagger-ui-bundle.js" {}
+                script {
+                    (maud::PreEscaped("SwaggerUIBundle({ url: '/api/openapi.json', dom_id: '#swagger-ui' });"))
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! The optional passcode of an election, so not everyone who comes across
+//! its short id can vote or follow it. The pages of an election ask for it
+//! once and a cookie remembers it was given; voting links and the facilitator
+//! need none.
+
+use crate::{base_html, cookies, crypto::constant_time_eq, facilitator, ElectionDB};
+use axum::{
+    extract::{Path, Request, State},
+    http::{
+        header::{ACCEPT, SET_COOKIE},
+        Method, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Form,
+};
+use iep_core::ElectionProcess;
+use maud::{html, Markup};
+use serde::Deserialize;
+
+/// Whether the path after `/election/:id/` needs the passcode, which is all
+/// of them but a few: the facilitator routes need the key instead, voting
+/// links are a secret of their own, and the QR code only holds the link to
+/// the voting page.
+fn needs_passcode(method: &Method, rest: &str) -> bool {
+    let segments = rest.split('/').collect::<Vec<_>>();
+    !facilitator::is_facilitator_route(method, rest)
+        && !matches!(&segments[..], ["passcode"] | ["vote", ..] | ["qr.png"])
+}
+
+/// Whether a script giving `key` as bearer token may read the election,
+/// which needs the passcode or a facilitator key if it has a passcode.
+pub fn allows_key(election: &ElectionProcess, key: Option<&str>) -> bool {
+    let Some(passcode) = &election.passcode else {
+        return true;
+    };
+    key.is_some_and(|key| {
+        constant_time_eq(key.as_bytes(), passcode.as_bytes())
+            || facilitator::facilitator_name(election, key).is_some()
+    })
+}
+
+/// Whether `given` is the passcode of `election`.
+pub fn is_passcode(election: &ElectionProcess, given: &str) -> bool {
+    election
+        .passcode
+        .as_ref()
+        .is_some_and(|passcode| constant_time_eq(given.as_bytes(), passcode.as_bytes()))
+}
+
+fn passcode_form(election: &ElectionProcess, next: &str) -> Markup {
+    html! {
+        form hx-post={"/election/" (election.id) "/passcode"} hx-target="#passcode-warning" hx-swap="innerHTML" {
+            input type="hidden" name="next" value=(next) {}
+            label for="passcode" {
+                "This election needs a passcode to vote: ";
+                input type="password" name="passcode" autocomplete="off" autofocus required {}
+            }
+            div #"passcode-warning" {}
+            button type="submit" { "Continue" }
+        }
+    }
+}
+
+/// Asks for the passcode on the routes of an election that has one, unless
+/// this browser gave it already or belongs to the facilitator.
+pub async fn require_passcode(
+    State(state): State<ElectionDB>,
+    request: Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let Some((id, _)) = request
+        .uri()
+        .path()
+        .strip_prefix("/election/")
+        .and_then(|rest| rest.split_once('/'))
+        .filter(|(_, rest)| needs_passcode(request.method(), rest))
+    else {
+        return next.run(request).await;
+    };
+    let Some(election) = state.store.get(id) else {
+        return next.run(request).await;
+    };
+    let Some(passcode) = election.passcode.as_deref() else {
+        return next.run(request).await;
+    };
+    let headers = request.headers();
+    let facilitator = facilitator::facilitator_of(&election, headers).is_some();
+    if facilitator || cookies::knows_passcode(&state, headers, id, passcode) {
+        return next.run(request).await;
+    }
+    // only pages opened in the browser can show the form, not scripts,
+    // streams or downloads
+    let page = headers
+        .get(ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/html"));
+    if request.method() != Method::GET || headers.contains_key("HX-Request") || !page {
+        return (StatusCode::FORBIDDEN, "This election needs its passcode").into_response();
+    }
+    base_html(
+        election.elected_role.as_str(),
+        html! { (election.elected_role) },
+        passcode_form(&election, &request.uri().to_string()),
+        html! {},
+    )
+    .into_response()
+}
+
+#[derive(Deserialize)]
+pub struct PasscodeForm {
+    passcode: String,
+    /// The page that asked for the passcode.
+    next: Option<String>,
+}
+
+/// Remembers the passcode if it is right and goes on to the page that asked
+/// for it.
+pub async fn post_passcode(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    Form(form): Form<PasscodeForm>,
+) -> Result<Response, (StatusCode, &'static str)> {
+    let election = state
+        .store
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    let passcode = election
+        .passcode
+        .as_deref()
+        .ok_or((StatusCode::BAD_REQUEST, "This election has no passcode"))?;
+    if !constant_time_eq(form.passcode.trim().as_bytes(), passcode.as_bytes()) {
+        return Ok(html! { p { mark { "Wrong passcode, please try again." } } }.into_response());
+    }
+    let next = form
+        .next
+        .filter(|next| next.starts_with(&format!("/election/{}/", id)))
+        .unwrap_or_else(|| format!("/election/{}/voting", id));
+    Ok((
+        StatusCode::OK,
+        [(SET_COOKIE, cookies::passcode_cookie(&state, &id, passcode))],
+        [("HX-Redirect", next)],
+    )
+        .into_response())
+}
@@ -0,0 +1,382 @@
+//! A printable A4 report of a finished election at `/election/:id/report.pdf`
+//! with the outcome, the phases, the tally and reasons of each round and
+//! lines to sign, for the documentation of the circle.
+//!
+//! The PDF is written by hand with the standard Helvetica fonts, so text is
+//! limited to Latin-1 and typographic punctuation; other characters are
+//! printed as `?`.
+
+use crate::{report::tally, ElectionDB};
+use axum::{
+    extract::{Path, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        StatusCode,
+    },
+    response::IntoResponse,
+};
+use iep_core::{ElectionKind, ElectionPhase, ElectionProcess, UtcDateTime};
+use itertools::*;
+
+const WIDTH: f32 = 595.0;
+const HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+/// Wider than the average Helvetica character, so wrapped lines fit.
+const CHAR_WIDTH: f32 = 0.55;
+const BAR_WIDTH: f32 = 200.0;
+
+/// Pages of drawing operators, laid out from the top down.
+struct Document {
+    pages: Vec<String>,
+    y: f32,
+}
+
+impl Document {
+    fn new() -> Self {
+        Document {
+            pages: vec![String::new()],
+            y: HEIGHT - MARGIN,
+        }
+    }
+
+    /// Starts a new page unless `height` still fits on this one.
+    fn room(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            self.pages.push(String::new());
+            self.y = HEIGHT - MARGIN;
+        }
+    }
+
+    fn ops(&mut self) -> &mut String {
+        self.pages.last_mut().expect("a document has a page")
+    }
+
+    fn text_at(&mut self, x: f32, size: f32, bold: bool, text: &str) {
+        let font = if bold { "F2" } else { "F1" };
+        let y = self.y;
+        *self.ops() += &format!(
+            "BT /{} {} Tf {:.1} {:.1} Td ({}) Tj ET\n",
+            font,
+            size,
+            x,
+            y,
+            escape(text)
+        );
+    }
+
+    /// Writes `text` wrapped at the margins.
+    fn paragraph(&mut self, size: f32, bold: bool, text: &str) {
+        self.indented(0.0, size, bold, text);
+    }
+
+    fn indented(&mut self, indent: f32, size: f32, bold: bool, text: &str) {
+        let per_line = ((WIDTH - 2.0 * MARGIN - indent) / (size * CHAR_WIDTH)) as usize;
+        for line in wrap(text, per_line) {
+            self.room(size * 1.4);
+            self.y -= size * 1.4;
+            self.text_at(MARGIN + indent, size, bold, &line);
+        }
+    }
+
+    fn gap(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    /// A nominee with a bar of `value` relative to `max`.
+    fn bar(&mut self, label: &str, value: u64, max: u64) {
+        self.room(16.0);
+        self.y -= 16.0;
+        let label = wrap(label, 28).into_iter().next().unwrap_or_default();
+        self.text_at(MARGIN + 10.0, 10.0, false, &label);
+        let width = BAR_WIDTH * value as f32 / max.max(1) as f32;
+        let x = MARGIN + 180.0;
+        let y = self.y;
+        *self.ops() += &format!(
+            "0.3 0.5 0.8 rg {:.1} {:.1} {:.1} 10 re f 0 g\n",
+            x,
+            y - 1.0,
+            width.max(0.5)
+        );
+        self.text_at(x + width + 6.0, 10.0, false, &value.to_string());
+    }
+
+    /// A line to sign on with its caption below.
+    fn signature(&mut self, x: f32, caption: &str) {
+        let y = self.y;
+        *self.ops() += &format!("0.5 w {:.1} {:.1} m {:.1} {:.1} l S\n", x, y, x + 200.0, y);
+        self.y -= 12.0;
+        self.text_at(x, 9.0, false, caption);
+        self.y = y;
+    }
+
+    /// The whole file with the pages numbered in the footer.
+    fn finish(mut self, footer: &str) -> Vec<u8> {
+        let count = self.pages.len();
+        for (i, page) in self.pages.iter_mut().enumerate() {
+            *page += &format!(
+                "BT /F1 8 Tf {:.1} 30 Td ({} - page {} of {}) Tj ET\n",
+                MARGIN,
+                escape(footer),
+                i + 1,
+                count
+            );
+        }
+
+        // 1 catalog, 2 pages, 3 and 4 fonts, then a page and its content
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                (0..count).map(|i| format!("{} 0 R", 5 + 2 * i)).join(" "),
+                count
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>"
+                .to_string(),
+        ];
+        for (i, page) in self.pages.iter().enumerate() {
+            objects.push(format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                WIDTH,
+                HEIGHT,
+                6 + 2 * i
+            ));
+            // the length in bytes once written as Latin-1
+            objects.push(format!(
+                "<< /Length {} >>\nstream\n{}endstream",
+                page.chars().count(),
+                page
+            ));
+        }
+
+        let mut pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = vec![];
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+            pdf.extend(latin1(object));
+            pdf.extend_from_slice(b"\nendobj\n");
+        }
+        let xref = pdf.len();
+        pdf.extend_from_slice(
+            format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+        );
+        for offset in offsets {
+            pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+        pdf.extend_from_slice(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+                objects.len() + 1,
+                xref
+            )
+            .as_bytes(),
+        );
+        pdf
+    }
+}
+
+/// Escapes a string literal of a content stream.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('(', "\\(")
+        .replace(')', "\\)")
+}
+
+/// The bytes of `text` in WinAnsi, which is Latin-1 with typographic
+/// punctuation in between.
+fn latin1(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            '€' => 0x80,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            c if (c as u32) < 0x80 || (0xa0..=0xff).contains(&(c as u32)) => c as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Breaks `text` into lines of at most `width` characters at spaces.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+fn report(election: &ElectionProcess, now: u64) -> Vec<u8> {
+    let date_time = |at: u64| format!("{} UTC", UtcDateTime::from_unix(at).date_time());
+    let mut doc = Document::new();
+
+    doc.paragraph(20.0, true, &election.elected_role);
+    let kind = match election.kind {
+        ElectionKind::Election => "Election",
+        ElectionKind::StrawPoll => "Straw poll",
+    };
+    let mut about = format!(
+        "{} with {} voting",
+        kind,
+        election.method.to_string().to_lowercase()
+    );
+    if election.seats > 1 {
+        about += &format!(" for {} seats", election.seats);
+    }
+    doc.paragraph(11.0, false, &about);
+    if let Some(meeting) = &election.meeting {
+        doc.paragraph(11.0, false, meeting);
+    }
+
+    doc.gap(10.0);
+    doc.paragraph(14.0, true, "Outcome");
+    let elected = election.elected_seats();
+    if elected.is_empty() && election.kind == ElectionKind::Election {
+        doc.paragraph(11.0, false, "Nobody was elected.");
+    }
+    for seat in &elected {
+        doc.paragraph(
+            11.0,
+            false,
+            &format!("{}, confirmed {}", seat.name, date_time(seat.at)),
+        );
+    }
+    if let Some(closed_at) = election.closed_at {
+        doc.paragraph(11.0, false, &format!("Closed {}", date_time(closed_at)));
+    }
+
+    for (number, round) in election.numbered_rounds() {
+        doc.gap(10.0);
+        doc.paragraph(14.0, true, &format!("Round {}", number));
+        doc.paragraph(
+            11.0,
+            false,
+            &format!(
+                "{} ballot{}",
+                round.len(),
+                if round.len() == 1 { "" } else { "s" }
+            ),
+        );
+        let tally = tally(election, round);
+        let most = tally.first().map_or(0, |(_, v)| *v);
+        for (nominee, votes) in &tally {
+            doc.bar(nominee, *votes, most);
+        }
+        for (nominee, reasons) in election.reasons(round) {
+            doc.gap(4.0);
+            doc.paragraph(11.0, true, &format!("Reasons for {}", nominee));
+            for (voter, reason) in reasons {
+                doc.indented(10.0, 10.0, false, &format!("{}: {}", voter, reason));
+            }
+        }
+    }
+
+    doc.gap(10.0);
+    doc.paragraph(14.0, true, "Phases");
+    for transition in &election.phase_history {
+        doc.paragraph(
+            10.0,
+            false,
+            &format!(
+                "{}  {}",
+                date_time(transition.at),
+                transition.phase.nice_title()
+            ),
+        );
+    }
+
+    doc.room(90.0);
+    doc.gap(70.0);
+    doc.signature(MARGIN, "Facilitator, date");
+    doc.signature(WIDTH - MARGIN - 200.0, "Secretary, date");
+
+    doc.finish(&format!(
+        "Election {}, printed {} with IEP Tool v{}",
+        election.id,
+        date_time(now),
+        env!("CARGO_PKG_VERSION")
+    ))
+}
+
+pub async fn get_election_report_pdf(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let election = state
+        .store
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    if election.phase != ElectionPhase::Completed && election.closed_at.is_none() {
+        return Err((StatusCode::CONFLICT, "The election is not finished yet"));
+    }
+    Ok((
+        [
+            (CONTENT_TYPE, "application/pdf".to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("inline; filename=\"election-{}.pdf\"", id),
+            ),
+        ],
+        report(&election, state.clock.now()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_text_for_the_standard_fonts() {
+        assert_eq!(escape(r"a (b) \ c"), r"a \(b\) \\ c");
+        assert_eq!(latin1("Zoë – 5 €, 東"), b"Zo\xeb \x96 5 \x80, ?");
+        assert_eq!(
+            wrap("one two three four\n\nfive", 9),
+            ["one two", "three", "four", "", "five"]
+        );
+    }
+
+    #[test]
+    fn points_the_cross_reference_table_at_the_objects() {
+        let election = ElectionProcess::new_and_cleaned(
+            "e".to_string(),
+            "Circle (Lead)".to_string(),
+            "Ann\nBob",
+            1709642096,
+        );
+        let pdf = report(&election, 1709642096);
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        let position = |needle: &[u8]| pdf.windows(needle.len()).rposition(|w| w == needle);
+        assert!(position(br"(Circle \(Lead\)) Tj").is_some());
+
+        let startxref = position(b"startxref\n").unwrap() + "startxref\n".len();
+        let tail = std::str::from_utf8(&pdf[startxref..]).unwrap();
+        let xref = tail.lines().next().unwrap().parse::<usize>().unwrap();
+        let mut table = std::str::from_utf8(&pdf[xref..]).unwrap().lines();
+        assert_eq!(table.next(), Some("xref"));
+        let count = table.next().unwrap()[2..].parse::<usize>().unwrap();
+        assert_eq!(table.next(), Some("0000000000 65535 f "));
+        for object in 1..count {
+            let offset = table.next().unwrap()[..10].parse::<usize>().unwrap();
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj\n", object).as_bytes()));
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! Small photos of nominees, stored as files in the data directory.
+
+use crate::{multipart, ElectionDB, ElectionUpdate};
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, Path, Request, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE},
+        StatusCode,
+    },
+    response::IntoResponse,
+};
+use iep_core::ElectionProcess;
+use maud::{html, Markup};
+use std::path::PathBuf;
+
+pub const MAX_PHOTO_BYTES: usize = 512 * 1024;
+
+/// Detects the supported image formats from their magic bytes, so the
+/// uploaded file name or content type does not have to be trusted.
+fn image_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if data.len() > 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+fn photo_path(state: &ElectionDB, id: &str, nominee: u64) -> PathBuf {
+    state
+        .data_dir
+        .join("photos")
+        .join(format!("{}-{}", id, nominee))
+}
+
+pub fn remove_photos(state: &ElectionDB, election: &ElectionProcess) {
+    for nominee in election.nominee_photos.keys() {
+        let path = photo_path(state, &election.id, *nominee);
+        if let Err(error) = std::fs::remove_file(&path) {
+            tracing::warn!("Removing {} failed: {}", path.display(), error);
+        }
+    }
+}
+
+pub async fn post_nominee_photo(
+    Path((id, nominee)): Path<(String, u64)>,
+    State(state): State<ElectionDB>,
+    request: Request,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let content_type = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let boundary = multipart::boundary(&content_type)
+        .ok_or((StatusCode::BAD_REQUEST, "Expected multipart form data"))?;
+    let body = Bytes::from_request(request, &state)
+        .await
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid request body"))?;
+    let photo = multipart::parse(boundary, &body)
+        .ok_or((StatusCode::BAD_REQUEST, "Invalid multipart data"))?
+        .into_iter()
+        .find(|p| p.name == "photo" && !p.data.is_empty())
+        .ok_or((StatusCode::BAD_REQUEST, "No photo given"))?;
+    if photo.data.len() > MAX_PHOTO_BYTES {
+        return Err((StatusCode::PAYLOAD_TOO_LARGE, "Photo is too large"));
+    }
+    let image_type = image_type(&photo.data).ok_or((
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        "Photo must be PNG, JPEG or WebP",
+    ))?;
+
+    let election = state
+        .store
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    if !election.nominees.contains_key(&nominee) {
+        return Err((StatusCode::NOT_FOUND, "Nominee not found"));
+    }
+
+    let path = photo_path(&state, &id, nominee);
+    let written = match path.parent() {
+        Some(dir) => tokio::fs::create_dir_all(dir).await,
+        None => Ok(()),
+    };
+    written
+        .and(tokio::fs::write(&path, &photo.data).await)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Could not store photo"))?;
+
+    state.modify_election(&id, |election| {
+        election
+            .nominee_photos
+            .insert(nominee, image_type.to_string());
+        Ok(())
+    })?;
+    state.notify(&id, ElectionUpdate::VotesChanged)?;
+
+    Ok((StatusCode::CREATED, [("HX-Refresh", "true")]))
+}
+
+pub async fn get_nominee_photo(
+    Path((id, nominee)): Path<(String, u64)>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let image_type = state
+        .store
+        .get(&id)
+        .and_then(|e| e.nominee_photos.get(&nominee).cloned())
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let data = tokio::fs::read(photo_path(&state, &id, nominee))
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    Ok((
+        [
+            (CONTENT_TYPE, image_type),
+            (CACHE_CONTROL, "no-cache".to_string()),
+        ],
+        data,
+    ))
+}
+
+/// A small photo of the nominee, if one was uploaded.
+pub fn nominee_photo(election: &ElectionProcess, nominee: u64) -> Markup {
+    html! {
+        @if election.nominee_photos.contains_key(&nominee) {
+            img ."nominee-photo" alt=""
+              src={"/election/" (election.id) "/nominees/" (nominee) "/photo"} {}
+        }
+    }
+}
+
+pub fn nominee_photo_named(election: &ElectionProcess, name: &str) -> Markup {
+    match election.nominees.iter().find(|(_, n)| *n == name) {
+        Some((id, _)) => nominee_photo(election, *id),
+        None => html! {},
+    }
+}
+
+/// All nominees with photos, shown above the voting form.
+pub fn photo_gallery(election: &ElectionProcess) -> Markup {
+    if election.nominee_photos.is_empty() {
+        return html! {};
+    }
+    html! {
+        div ."nominee-gallery" {
+            @for (id, nominee) in election.grouped_nominees().into_iter().flat_map(|(_, n)| n) {
+                figure {
+                    (nominee_photo(election, id))
+                    figcaption { (nominee) }
+                }
+            }
+        }
+    }
+}
+
+pub fn photos_form(election: &ElectionProcess) -> Markup {
+    html! {
+        details {
+            summary { "Nominee photos" }
+            small { "PNG, JPEG or WebP, up to " (MAX_PHOTO_BYTES / 1024) " KiB." }
+            table {
+                tbody {
+                    @for (id, nominee) in election.grouped_nominees().into_iter().flat_map(|(_, n)| n) {
+                        tr {
+                            td { (nominee_photo(election, id)) " " (nominee) }
+                            td {
+                                form hx-post={"/election/" (election.id) "/nominees/" (id) "/photo"}
+                                  hx-encoding="multipart/form-data" hx-swap="none" {
+                                    input type="file" name="photo" accept="image/png,image/jpeg,image/webp" required {}
+                                    button type="submit" ."secondary" { "Upload" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! How many participants follow an election over its live update stream, for
+//! the facilitator to see when everyone has the page open. Every connection
+//! holds a [`Presence`] while it is open, and joining or leaving publishes a
+//! `presence-changed` update with the new count.
+
+use crate::{ElectionDB, ElectionUpdate};
+use maud::{html, Markup};
+
+/// Counts a connection to the stream of an election until it is dropped.
+pub struct Presence {
+    state: ElectionDB,
+    id: String,
+}
+
+impl Presence {
+    pub fn join(state: &ElectionDB, id: &str) -> Presence {
+        change(state, id, true);
+        Presence {
+            state: state.clone(),
+            id: id.to_string(),
+        }
+    }
+}
+
+impl Drop for Presence {
+    fn drop(&mut self) {
+        change(&self.state, &self.id, false);
+    }
+}
+
+fn change(state: &ElectionDB, id: &str, joined: bool) {
+    let Ok(mut streams) = state.streams.lock() else {
+        return;
+    };
+    // the stream is gone once the election is removed or the server stops
+    let Some(stream) = streams.get_mut(id) else {
+        return;
+    };
+    stream.connected = match joined {
+        true => stream.connected + 1,
+        false => stream.connected.saturating_sub(1),
+    };
+    let data = serde_json::json!({ "connected": stream.connected });
+    stream.publish(ElectionUpdate::PresenceChanged, data);
+}
+
+/// The number of connections following the election on this instance.
+pub fn connected(state: &ElectionDB, id: &str) -> usize {
+    state
+        .streams
+        .lock()
+        .ok()
+        .and_then(|streams| streams.get(id).map(|s| s.connected))
+        .unwrap_or(0)
+}
+
+/// The live count of participants, kept up to date by live.js.
+pub fn presence(connected: usize) -> Markup {
+    html! {
+        p #"presence" hx-trigger="sse:presence-changed" {
+            small { (connected) " participant" @if connected != 1 { "s" } " connected" }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! The voting link of an election as a PNG QR code at `/election/:id/qr.png`,
+//! for chat embeds which cannot show the SVG of the share dialog.
+//!
+//! Like the ZIP export the PNG is written uncompressed, which keeps it free of
+//! dependencies; a QR code of a link is only a few kilobytes anyway.
+
+use crate::{zip::crc32, ElectionDB};
+use axum::{
+    extract::{Path, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::IntoResponse,
+};
+use qrcode::{Color, QrCode};
+
+/// Pixels per module of the code.
+const SCALE: usize = 8;
+/// Light modules around the code, as scanners expect.
+const QUIET_ZONE: usize = 4;
+/// The most a stored deflate block can hold.
+const MAX_BLOCK: usize = 0xffff;
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// `data` as a zlib stream of stored blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        stream.push(blocks.peek().is_none() as u8);
+        stream.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        stream.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    stream.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    stream
+}
+
+/// A black and white PNG of `code`.
+pub fn png(code: &QrCode) -> Vec<u8> {
+    let modules = code.width();
+    let colors = code.to_colors();
+    let size = (modules + 2 * QUIET_ZONE) * SCALE;
+    let row_bytes = size.div_ceil(8);
+
+    let mut pixels = Vec::with_capacity(size * (row_bytes + 1));
+    for y in 0..size {
+        // no filter
+        pixels.push(0);
+        let mut row = vec![0xffu8; row_bytes];
+        for x in 0..size {
+            let (mx, my) = (x / SCALE, y / SCALE);
+            let dark = (QUIET_ZONE..QUIET_ZONE + modules).contains(&mx)
+                && (QUIET_ZONE..QUIET_ZONE + modules).contains(&my)
+                && colors[(my - QUIET_ZONE) * modules + mx - QUIET_ZONE] == Color::Dark;
+            if dark {
+                row[x / 8] &= !(0x80 >> (x % 8));
+            }
+        }
+        pixels.extend_from_slice(&row);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(size as u32).to_be_bytes());
+    header.extend_from_slice(&(size as u32).to_be_bytes());
+    // 1 bit grayscale, no interlacing
+    header.extend_from_slice(&[1, 0, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut png, b"IHDR", &header);
+    chunk(&mut png, b"IDAT", &zlib_stored(&pixels));
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+pub async fn get_election_qr_png(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if !state.store.contains(&id) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let voting_url = format!("{}/election/{}/voting", state.base_url, id);
+    let code = QrCode::new(voting_url.as_bytes()).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(([(CONTENT_TYPE, "image/png")], png(&code)))
+}
@@ -0,0 +1,402 @@
+//! Keeps elections and circles in Redis and relays updates between instances through
+//! Redis pub/sub, so several instances can serve the same elections behind a
+//! load balancer.
+//!
+//! Speaks just enough of the Redis protocol (RESP) over plain TCP for that.
+
+use crate::{
+    store::{ElectionStore, SharedConnection},
+    ElectionDB, ElectionStream, ElectionUpdate,
+};
+use iep_core::{Circle, ElectionProcess, Random, ThreadRandom};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Error, ErrorKind, Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+const PREFIX: &str = "ieptool";
+const UPDATES_CHANNEL: &str = "ieptool:updates";
+/// Locks of crashed instances expire after this.
+const LOCK_MILLIS: u64 = 5000;
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// How long to wait before asking for a taken lock again.
+const LOCK_RETRY: Duration = Duration::from_millis(10);
+/// Deletes the lock only if it is still ours.
+const UNLOCK_SCRIPT: &str =
+    "if redis.call('get', KEYS[1]) == ARGV[1] then return redis.call('del', KEYS[1]) end return 0";
+
+#[derive(Debug)]
+pub enum Reply {
+    Status,
+    Integer,
+    Bulk(Option<Vec<u8>>),
+    Array(Option<Vec<Reply>>),
+}
+
+pub struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    /// Connects to a `redis://host:port` URL.
+    pub fn connect(url: &str, timeout: Option<Duration>) -> std::io::Result<Self> {
+        let address = url
+            .strip_prefix("redis://")
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Only redis:// URLs are supported"))?
+            .trim_end_matches('/');
+        let address = match address.contains(':') {
+            true => address.to_string(),
+            false => format!("{}:6379", address),
+        };
+        let stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        Ok(Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+
+    pub fn query(&mut self, args: &[&[u8]]) -> std::io::Result<Reply> {
+        let mut command = format!("*{}\r\n", args.len()).into_bytes();
+        for arg in args {
+            command.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            command.extend_from_slice(arg);
+            command.extend_from_slice(b"\r\n");
+        }
+        self.writer.write_all(&command)?;
+        self.read()
+    }
+
+    pub fn read(&mut self) -> std::io::Result<Reply> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "Redis closed the connection",
+            ));
+        }
+        let line = line.trim_end_matches("\r\n");
+        let invalid = || Error::new(ErrorKind::InvalidData, format!("Invalid reply {:?}", line));
+        let (kind, rest) = line.split_at_checked(1).ok_or_else(invalid)?;
+        let length = || rest.parse::<i64>().map_err(|_| invalid());
+        match kind {
+            "+" => Ok(Reply::Status),
+            "-" => Err(Error::other(format!("Redis error: {}", rest))),
+            ":" => length().map(|_| Reply::Integer),
+            "$" => match usize::try_from(length()?) {
+                Err(_) => Ok(Reply::Bulk(None)),
+                Ok(length) => {
+                    let mut data = vec![0; length + 2];
+                    self.reader.read_exact(&mut data)?;
+                    data.truncate(length);
+                    Ok(Reply::Bulk(Some(data)))
+                }
+            },
+            "*" => match usize::try_from(length()?) {
+                Err(_) => Ok(Reply::Array(None)),
+                Ok(length) => Ok(Reply::Array(Some(
+                    (0..length).map(|_| self.read()).collect::<Result<_, _>>()?,
+                ))),
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Reply {
+    fn into_bulk(self) -> Option<Vec<u8>> {
+        match self {
+            Reply::Bulk(data) => data,
+            _ => None,
+        }
+    }
+
+    fn into_array(self) -> Vec<Reply> {
+        match self {
+            Reply::Array(Some(items)) => items,
+            _ => vec![],
+        }
+    }
+}
+
+/// An update published by one instance for the others.
+#[derive(Serialize, Deserialize)]
+struct RelayedUpdate {
+    instance: String,
+    election: String,
+    update: ElectionUpdate,
+}
+
+pub struct RedisStore {
+    url: String,
+    /// Tells our own relayed updates apart from those of other instances.
+    instance: String,
+    connection: SharedConnection<Connection>,
+}
+
+impl RedisStore {
+    pub fn open(url: &str, instance: String) -> std::io::Result<Self> {
+        let connection = Connection::connect(url, Some(TIMEOUT))?;
+        Ok(RedisStore {
+            url: url.to_string(),
+            instance,
+            connection: SharedConnection::new("Redis", connection),
+        })
+    }
+
+    /// Runs `queries` on the shared connection, reconnecting once if it broke.
+    fn with_connection<T>(
+        &self,
+        queries: impl FnMut(&mut Connection) -> std::io::Result<T>,
+    ) -> Option<T> {
+        self.connection
+            .run(|| Connection::connect(&self.url, Some(TIMEOUT)), queries)
+    }
+
+    /// The stored circles, loaded once on startup.
+    pub fn circles(&self) -> HashMap<String, Circle> {
+        self.with_connection(|c| c.query(&[b"GET", circles_key().as_bytes()]))
+            .and_then(|reply| reply.into_bulk())
+            .and_then(|data| {
+                serde_json::from_slice(&data)
+                    .inspect_err(|e| tracing::warn!("Skipping unreadable circles in Redis: {}", e))
+                    .ok()
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn election_key(id: &str) -> String {
+    format!("{}:election:{}", PREFIX, id)
+}
+
+fn index_key() -> String {
+    format!("{}:elections", PREFIX)
+}
+
+fn circles_key() -> String {
+    format!("{}:circles", PREFIX)
+}
+
+fn parse(data: Option<Vec<u8>>) -> Option<ElectionProcess> {
+    serde_json::from_slice(&data?)
+        .inspect_err(|e| tracing::warn!("Skipping unreadable election in Redis: {}", e))
+        .ok()
+}
+
+fn save(connection: &mut Connection, election: &ElectionProcess) -> std::io::Result<()> {
+    let json = serde_json::to_vec(election)?;
+    connection.query(&[b"SET", election_key(&election.id).as_bytes(), &json])?;
+    connection.query(&[b"SADD", index_key().as_bytes(), election.id.as_bytes()])?;
+    Ok(())
+}
+
+impl ElectionStore for RedisStore {
+    fn get(&self, id: &str) -> Option<ElectionProcess> {
+        self.with_connection(|c| c.query(&[b"GET", election_key(id).as_bytes()]))
+            .and_then(|reply| parse(reply.into_bulk()))
+    }
+
+    fn list(&self) -> Vec<ElectionProcess> {
+        self.with_connection(|c| {
+            let ids = c
+                .query(&[b"SMEMBERS", index_key().as_bytes()])?
+                .into_array();
+            if ids.is_empty() {
+                return Ok(vec![]);
+            }
+            let keys = ids
+                .into_iter()
+                .filter_map(|id| Some(election_key(&String::from_utf8(id.into_bulk()?).ok()?)))
+                .collect::<Vec<_>>();
+            let mut args = vec![&b"MGET"[..]];
+            args.extend(keys.iter().map(|k| k.as_bytes()));
+            Ok(c.query(&args)?.into_array())
+        })
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|e| parse(e.into_bulk()))
+        .collect()
+    }
+
+    fn insert(&self, election: ElectionProcess) {
+        self.with_connection(|c| save(c, &election));
+    }
+
+    fn modify(&self, id: &str, change: &mut dyn FnMut(&mut ElectionProcess)) -> bool {
+        let lock = format!("{}:lock:{}", PREFIX, id);
+        // tells this acquisition apart from any other, even on the same thread
+        let token = format!("{}:{}", self.instance, ThreadRandom.alphanumeric(20));
+        // other instances may change the election at the same time
+        loop {
+            let locked = self.with_connection(|c| {
+                c.query(&[
+                    b"SET",
+                    lock.as_bytes(),
+                    token.as_bytes(),
+                    b"NX",
+                    b"PX",
+                    LOCK_MILLIS.to_string().as_bytes(),
+                ])
+            });
+            match locked {
+                Some(Reply::Status) => break,
+                // waiting must not stall the other requests either
+                Some(_) => tokio::task::block_in_place(|| std::thread::sleep(LOCK_RETRY)),
+                None => return false,
+            }
+        }
+        let mut changed = None;
+        let saved = self.with_connection(|c| {
+            let Some(mut election) =
+                parse(c.query(&[b"GET", election_key(id).as_bytes()])?.into_bulk())
+            else {
+                return Ok(());
+            };
+            // a retry after a broken connection must not apply the change twice
+            if changed.is_none() {
+                change(&mut election);
+                changed = Some(election);
+            }
+            save(c, changed.as_ref().unwrap())
+        });
+        self.with_connection(|c| {
+            c.query(&[
+                b"EVAL",
+                UNLOCK_SCRIPT.as_bytes(),
+                b"1",
+                lock.as_bytes(),
+                token.as_bytes(),
+            ])
+        });
+        saved.is_some() && changed.is_some()
+    }
+
+    fn delete(&self, id: &str) -> Option<ElectionProcess> {
+        self.with_connection(|c| {
+            let election = c.query(&[b"GET", election_key(id).as_bytes()])?;
+            c.query(&[b"DEL", election_key(id).as_bytes()])?;
+            c.query(&[b"SREM", index_key().as_bytes(), id.as_bytes()])?;
+            Ok(election)
+        })
+        .and_then(|reply| parse(reply.into_bulk()))
+    }
+
+    fn publish(&self, id: &str, update: ElectionUpdate) {
+        let message = RelayedUpdate {
+            instance: self.instance.clone(),
+            election: id.to_string(),
+            update,
+        };
+        let Ok(message) = serde_json::to_vec(&message) else {
+            return;
+        };
+        self.with_connection(|c| c.query(&[b"PUBLISH", UPDATES_CHANNEL.as_bytes(), &message]));
+    }
+
+    fn save_circles(&self, circles: &HashMap<String, Circle>) {
+        let Ok(data) = serde_json::to_vec(circles) else {
+            return;
+        };
+        self.with_connection(|c| c.query(&[b"SET", circles_key().as_bytes(), &data]));
+    }
+}
+
+/// Passes updates published by other instances on to our own subscribers.
+pub fn relay_updates(state: ElectionDB, url: String, instance: String) {
+    loop {
+        if let Err(error) = subscribe(&state, &url, &instance) {
+            tracing::warn!("Redis subscription failed: {}", error);
+        }
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
+
+fn subscribe(state: &ElectionDB, url: &str, instance: &str) -> std::io::Result<()> {
+    let mut connection = Connection::connect(url, None)?;
+    connection.query(&[b"SUBSCRIBE", UPDATES_CHANNEL.as_bytes()])?;
+    loop {
+        let message = connection.read()?.into_array();
+        let Some(Reply::Bulk(Some(payload))) = message.into_iter().nth(2) else {
+            continue;
+        };
+        let Ok(relayed) = serde_json::from_slice::<RelayedUpdate>(&payload) else {
+            continue;
+        };
+        if relayed.instance == instance {
+            continue;
+        }
+        let election = state.store.get(&relayed.election);
+        state
+            .streams
+            .lock()
+            .unwrap()
+            .entry(relayed.election)
+            .or_insert_with(ElectionStream::new)
+            .publish_about(relayed.update, election.as_ref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// A connection to a server that expects `request` and answers `replies`.
+    fn connection(request: &'static [u8], replies: &'static [u8]) -> Connection {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("redis://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut received = vec![0; request.len()];
+            socket.read_exact(&mut received).unwrap();
+            assert_eq!(received, request);
+            socket.write_all(replies).unwrap();
+        });
+        Connection::connect(&url, Some(TIMEOUT)).unwrap()
+    }
+
+    #[test]
+    fn encodes_commands_as_arrays_of_bulk_strings() {
+        let mut connection = connection(
+            b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$4\r\na\r\nb\r\n",
+            b"+OK\r\n",
+        );
+        let reply = connection.query(&[b"SET", b"k", b"a\r\nb"]).unwrap();
+        assert!(matches!(reply, Reply::Status));
+    }
+
+    #[test]
+    fn reads_replies() {
+        let mut connection = connection(
+            b"*1\r\n$4\r\nPING\r\n",
+            b"$5\r\nhello\r\n$-1\r\n:3\r\n*3\r\n$1\r\na\r\n$-1\r\n*1\r\n:1\r\n*-1\r\n-ERR boom\r\n!\r\n",
+        );
+        let reply = connection.query(&[b"PING"]).unwrap();
+        assert_eq!(reply.into_bulk(), Some(b"hello".to_vec()));
+        assert_eq!(connection.read().unwrap().into_bulk(), None);
+        assert!(matches!(connection.read().unwrap(), Reply::Integer));
+        let items = connection.read().unwrap().into_array();
+        assert_eq!(items.len(), 3);
+        assert!(matches!(&items[2], Reply::Array(Some(nested)) if nested.len() == 1));
+        assert!(connection.read().unwrap().into_array().is_empty());
+        let error = connection.read().unwrap_err();
+        assert_eq!(
+            (error.kind(), error.to_string()),
+            (ErrorKind::Other, "Redis error: ERR boom".to_string())
+        );
+        assert_eq!(
+            connection.read().unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        assert_eq!(
+            connection.read().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! The results emailed on closing an election to the addresses given on its
+//! creation and, if asked for, to the voters of the roster.
+
+use crate::{mail::Mail, ElectionDB};
+use iep_core::{Ballot, ElectionKind, ElectionProcess, UtcDateTime, VotingMethod};
+use itertools::*;
+use std::collections::HashMap;
+
+/// The nominees of `round` with their votes or score totals, most first.
+pub fn tally(election: &ElectionProcess, round: &HashMap<String, Ballot>) -> Vec<(String, u64)> {
+    match election.method {
+        VotingMethod::Score => election
+            .score_totals(round)
+            .iter()
+            .map(|s| (election.nominee_name(s.nominee).to_string(), s.total as u64))
+            .collect(),
+        _ => election
+            .accumulated_votes(round)
+            .votes
+            .iter()
+            .map(|(nominee, votes)| (nominee.to_string(), *votes as u64))
+            .sorted_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+            .collect(),
+    }
+}
+
+fn body(election: &ElectionProcess, url: &str) -> String {
+    let mut text = vec![];
+    if let Some(meeting) = &election.meeting {
+        text.push(meeting.clone());
+    }
+    let elected = election.elected_seats();
+    match elected.is_empty() {
+        true if election.kind == ElectionKind::StrawPoll => {}
+        true => text.push("Nobody was elected.".to_string()),
+        false => text.push(format!(
+            "Elected: {}",
+            elected.iter().map(|e| &e.name).join(", ")
+        )),
+    }
+    if let Some(closed_at) = election.closed_at {
+        text.push(format!(
+            "Closed {} UTC",
+            UtcDateTime::from_unix(closed_at).date_time()
+        ));
+    }
+    for (number, round) in election.numbered_rounds() {
+        text.push(String::new());
+        text.push(format!(
+            "Round {}: {} ballot{}",
+            number,
+            round.len(),
+            if round.len() == 1 { "" } else { "s" }
+        ));
+        let tally = tally(election, round);
+        let width = tally.iter().map(|(n, _)| n.chars().count()).max();
+        let most = tally.first().map_or(1, |(_, v)| *v).max(1);
+        for (nominee, votes) in &tally {
+            text.push(format!(
+                "  {:width$}  {} {}",
+                nominee,
+                "█".repeat((20 * votes / most) as usize),
+                votes,
+                width = width.unwrap_or(0)
+            ));
+        }
+        for (nominee, reasons) in election.reasons(round) {
+            text.push(format!("  Reasons for {}:", nominee));
+            for (_, reason) in reasons {
+                text.push(format!("  - {}", reason));
+            }
+        }
+    }
+    text.push(String::new());
+    text.push(format!("All results: {}", url));
+    text.join("\n")
+}
+
+/// Emails the results of the just closed `election` in the background.
+pub fn send(state: &ElectionDB, election: &ElectionProcess) {
+    let Some(smtp) = state.mail.clone() else {
+        return;
+    };
+    let roster = election
+        .roster
+        .iter()
+        .filter(|_| election.report_to_roster)
+        .filter_map(|voter_name| election.voter_email(voter_name));
+    let recipients = election
+        .report_recipients
+        .iter()
+        .map(|a| &a[..])
+        .chain(roster)
+        .unique_by(|a| a.to_lowercase())
+        .collect::<Vec<_>>();
+    if recipients.is_empty() {
+        return;
+    }
+    let subject = match election.kind {
+        ElectionKind::Election => format!("Results of the election for {}", election.elected_role),
+        ElectionKind::StrawPoll => {
+            format!("Results of the straw poll on {}", election.elected_role)
+        }
+    };
+    let body = body(
+        election,
+        &format!("{}/election/{}/voting", state.base_url, election.id),
+    );
+    let mails = recipients
+        .into_iter()
+        .map(|to| Mail {
+            to: to.to_string(),
+            subject: subject.clone(),
+            body: body.clone(),
+            attachment: None,
+        })
+        .collect::<Vec<_>>();
+    tokio::spawn(async move {
+        match smtp.send(&mails).await {
+            Ok(failed) => {
+                for (to, error) in failed {
+                    tracing::warn!("Emailing the results to {} failed: {}", to, error);
+                }
+            }
+            Err(error) => tracing::warn!("Emailing the results failed: {}", error),
+        }
+    });
+}
@@ -0,0 +1,223 @@
+//! Backs all elections up to an S3-compatible bucket now and then, and
+//! restores the latest backup when starting with no elections.
+//!
+//! Requests are signed with AWS Signature Version 4 and use path-style URLs,
+//! which S3 itself and minio both accept.
+
+use crate::{
+    crypto::{hex, hmac_sha256, sha256},
+    http,
+    snapshot::Snapshot,
+    ElectionDB,
+};
+use iep_core::UtcDateTime;
+use std::time::Duration;
+
+const PREFIX: &str = "backups/";
+
+pub struct Bucket {
+    /// e.g. `http://localhost:9000`
+    endpoint: String,
+    name: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl Bucket {
+    /// Reads the bucket from `S3_ENDPOINT`, `S3_BUCKET`, `S3_ACCESS_KEY`,
+    /// `S3_SECRET_KEY` and `S3_REGION`, if the first four are set.
+    pub fn from_env() -> Option<Self> {
+        let var = |name| std::env::var(name).ok().filter(|v| !v.is_empty());
+        Some(Bucket {
+            endpoint: var("S3_ENDPOINT")?.trim_end_matches('/').to_string(),
+            name: var("S3_BUCKET")?,
+            region: var("S3_REGION").unwrap_or("us-east-1".to_string()),
+            access_key: var("S3_ACCESS_KEY")?,
+            secret_key: var("S3_SECRET_KEY")?,
+        })
+    }
+
+    async fn send(
+        &self,
+        method: &str,
+        key: &str,
+        query: &[(&str, &str)],
+        body: &[u8],
+        now: u64,
+    ) -> Result<String, String> {
+        let path = format!("/{}/{}", self.name, key);
+        let mut query = query
+            .iter()
+            .map(|(k, v)| format!("{}={}", encode(k), encode(v)))
+            .collect::<Vec<_>>();
+        query.sort();
+        let query = query.join("&");
+        let url = match query.is_empty() {
+            true => format!("{}{}", self.endpoint, encode_path(&path)),
+            false => format!("{}{}?{}", self.endpoint, encode_path(&path), query),
+        };
+        let (host, _) = http::split_url(&url)?;
+
+        let time = UtcDateTime::from_unix(now).basic_format();
+        let date = &time[..8];
+        let payload_hash = hex(&sha256(body));
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method,
+            encode_path(&path),
+            query,
+            host,
+            payload_hash,
+            time,
+            signed_headers,
+            payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            time,
+            scope,
+            hex(&sha256(canonical_request.as_bytes()))
+        );
+        let key = [date, &self.region, "s3", "aws4_request"].iter().fold(
+            format!("AWS4{}", self.secret_key).into_bytes(),
+            |key, part| hmac_sha256(&key, part.as_bytes()).to_vec(),
+        );
+        let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        );
+
+        let headers = [
+            ("x-amz-content-sha256", payload_hash),
+            ("x-amz-date", time.clone()),
+            ("Authorization", authorization),
+        ];
+        http::request(method, &url, &headers, body).await
+    }
+
+    async fn put(&self, key: &str, body: &[u8], now: u64) -> Result<(), String> {
+        self.send("PUT", key, &[], body, now).await.map(|_| ())
+    }
+
+    async fn get(&self, key: &str, now: u64) -> Result<String, String> {
+        self.send("GET", key, &[], &[], now).await
+    }
+
+    /// The keys starting with `prefix`, in the bucket's (lexical) order.
+    async fn list(&self, prefix: &str, now: u64) -> Result<Vec<String>, String> {
+        let mut keys = Vec::new();
+        let mut token = None::<String>;
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix)];
+            if let Some(token) = &token {
+                query.push(("continuation-token", token));
+            }
+            let listing = self.send("GET", "", &query, &[], now).await?;
+            keys.extend(elements(&listing, "Key"));
+            match elements(&listing, "IsTruncated")
+                .first()
+                .map(String::as_str)
+            {
+                Some("true") => {
+                    token = elements(&listing, "NextContinuationToken").pop();
+                    if token.is_none() {
+                        return Err("Truncated listing without a continuation token".to_string());
+                    }
+                }
+                _ => return Ok(keys),
+            }
+        }
+    }
+}
+
+/// Percent-encodes everything but the unreserved characters, as SigV4 wants.
+fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn encode_path(path: &str) -> String {
+    path.split('/').map(encode).collect::<Vec<_>>().join("/")
+}
+
+/// The text of all `<tag>` elements; enough XML for a bucket listing.
+fn elements(xml: &str, tag: &str) -> Vec<String> {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+    xml.split(&open)
+        .skip(1)
+        .filter_map(|rest| rest.split_once(&close))
+        .map(|(text, _)| {
+            text.replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&")
+        })
+        .collect()
+}
+
+async fn back_up(state: &ElectionDB, bucket: &Bucket) -> Result<String, String> {
+    let now = state.clock.now();
+    let snapshot = Snapshot {
+        taken_at: now,
+        elections: state.store.list(),
+        circles: state.circles.lock().unwrap().clone(),
+    };
+    let body = serde_json::to_vec(&snapshot).map_err(|e| e.to_string())?;
+    let key = format!(
+        "{}ieptool-{}.json",
+        PREFIX,
+        UtcDateTime::from_unix(now).basic_format()
+    );
+    bucket.put(&key, &body, now).await?;
+    Ok(key)
+}
+
+pub async fn back_up_periodically(state: ElectionDB, bucket: Bucket, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    // the first tick completes immediately, and there is nothing new yet
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        match back_up(&state, &bucket).await {
+            Ok(key) => tracing::info!("Backed up elections to {}", key),
+            Err(error) => tracing::error!("Backing up elections failed: {}", error),
+        }
+    }
+}
+
+/// Restores the elections, and the circles if there are none, from the latest
+/// backup. Does nothing if elections were already stored, so a restart never
+/// overwrites newer changes.
+pub async fn restore_latest(state: &ElectionDB, bucket: &Bucket) -> Result<(), String> {
+    if !state.store.list().is_empty() {
+        return Ok(());
+    }
+    let now = state.clock.now();
+    let Some(latest) = bucket.list(PREFIX, now).await?.into_iter().max() else {
+        return Ok(());
+    };
+    let snapshot: Snapshot = serde_json::from_str(&bucket.get(&latest, now).await?)
+        .map_err(|e| format!("Unreadable backup {}: {}", latest, e))?;
+    let count = snapshot.elections.len();
+    for election in snapshot.elections {
+        state.add_election(election);
+    }
+    let mut circles = state.circles.lock().unwrap();
+    if circles.is_empty() && !snapshot.circles.is_empty() {
+        *circles = snapshot.circles;
+        state.circles_changed(&circles);
+    }
+    tracing::info!("Restored {} elections from {}", count, latest);
+    Ok(())
+}
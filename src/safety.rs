@@ -0,0 +1,251 @@
+//! Consents and objections of the participants in the safety round.
+
+use crate::{process_error, ElectionDB, ElectionUpdate};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Form,
+};
+use iep_core::{ElectionProcess, ObjectionStatus};
+use itertools::*;
+use maud::{html, Markup};
+use serde::Deserialize;
+use std::str::FromStr;
+
+#[derive(Deserialize)]
+pub struct SafetyForm {
+    participant: String,
+    /// `consent` or `object`.
+    response: String,
+    reason: Option<String>,
+}
+
+pub async fn post_safety(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    Form(form): Form<SafetyForm>,
+) -> Result<Markup, (StatusCode, &'static str)> {
+    let consent = match &form.response[..] {
+        "consent" => true,
+        "object" => false,
+        _ => return Err((StatusCode::BAD_REQUEST, "Invalid response")),
+    };
+    if form.participant.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Name is required"));
+    }
+    state.modify_election(&id, |election| {
+        election
+            .respond_to_safety(form.participant, consent, form.reason, state.clock.now())
+            .map_err(process_error)
+    })?;
+    state.notify(&id, ElectionUpdate::VotesChanged)?;
+
+    Ok(html! {
+        @if consent {
+            p { "Thank you, your consent was recorded." }
+        } @else {
+            p { "Thank you, your objection was recorded and will be discussed." }
+        }
+    })
+}
+
+#[derive(Deserialize)]
+pub struct ProposalForm {
+    nominee: u64,
+}
+
+/// Chooses who the safety round asks about, for the facilitator.
+pub async fn post_proposal(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    Form(form): Form<ProposalForm>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    state.modify_election(&id, |election| {
+        election
+            .propose(form.nominee, state.clock.now())
+            .map_err(process_error)
+    })?;
+    // the question on the voting page changes with it
+    state.notify(&id, ElectionUpdate::PhaseChanged)?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[derive(Deserialize)]
+pub struct ObjectionForm {
+    by: Option<String>,
+    text: String,
+}
+
+/// Records an objection raised aloud, for the facilitator.
+pub async fn post_objection(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    Form(form): Form<ObjectionForm>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    state.modify_election(&id, |election| {
+        election
+            .raise_objection(form.by, form.text, state.clock.now())
+            .map_err(process_error)
+    })?;
+    state.notify(&id, ElectionUpdate::VotesChanged)?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+pub async fn post_objection_status(
+    Path((id, index, status)): Path<(String, usize, String)>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let status = ObjectionStatus::from_str(&status)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid objection status"))?;
+    state.modify_election(&id, |election| {
+        election
+            .resolve_objection(index, status, state.clock.now())
+            .map_err(process_error)
+    })?;
+    state.notify(&id, ElectionUpdate::VotesChanged)?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Who the safety round asks about, or the most voted while the facilitator
+/// has not chosen yet.
+pub fn proposal(election: &ElectionProcess) -> Markup {
+    match election.proposed {
+        Some(nominee) => html! {
+            h3 #"proposal" {
+                @if election.seats > 1 { "Proposed for seat " (election.current_seat()) ": " }
+                @else { "Proposed: " }
+                mark { (election.nominee_name(nominee)) }
+            }
+        },
+        None => {
+            let most_votes = election
+                .accumulated_votes(election.final_round())
+                .top(election.seats);
+            html! {
+                p { "The most votes were for: " (most_votes.join(", ")) }
+            }
+        }
+    }
+}
+
+/// Who was confirmed for the earlier seats, for multi-seat elections.
+pub fn seats_overview(election: &ElectionProcess) -> Markup {
+    html! {
+        p {
+            "Seat " strong { (election.current_seat()) } " of " (election.seats)
+            @if !election.seated.is_empty() {
+                " · already elected: " (election.seated.iter().map(|e| &e.name).join(", "))
+            }
+        }
+    }
+}
+
+/// Choice of the proposal among the nominees with votes, for the facilitator.
+pub fn proposal_form(election: &ElectionProcess) -> Markup {
+    let proposed = election.proposed.or(election.next_seat_nominee());
+    html! {
+        form ."table rows" hx-post={"/election/" (election.id) "/proposal"} hx-swap="none" {
+            label for="nominee" {
+                "Propose: ";
+                select name="nominee" required {
+                    @for nominee in election.top_voted_nominees() {
+                        option value=(nominee) selected[proposed == Some(nominee)] {
+                            (election.nominee_name(nominee))
+                        }
+                    }
+                }
+            }
+            button type="submit" ."secondary" {
+                @if election.proposed.is_some() { "Change Proposal" } @else { "Propose" }
+            }
+        }
+        @if !election.safety_responses.is_empty() {
+            p { small { "Changing the proposal discards the consents and objections so far." } }
+        }
+    }
+}
+
+pub fn safety_form(election: &ElectionProcess) -> Markup {
+    html! {
+        article #"safety" {
+            header { "Is this outcome safe enough to try? If not, tell us why." }
+            form ."table rows" hx-post={"/election/" (election.id) "/safety"} hx-target="#safety" {
+                label for="participant" {
+                    "Your Name: ";
+                    input type="text" name="participant" required {}
+                }
+                fieldset {
+                    legend { "Response" }
+                    label {
+                        input type="radio" name="response" value="consent" required checked {}
+                        "I consent"
+                    }
+                    label {
+                        input type="radio" name="response" value="object" {}
+                        "I object"
+                    }
+                }
+                label for="reason" {
+                    "Reason (required for objections): ";
+                    textarea name="reason" rows="2" {}
+                }
+                button type="submit" { "Send" }
+            }
+        }
+    }
+}
+
+/// Live counts and the objections to integrate, for the facilitator.
+pub fn safety_summary(election: &ElectionProcess) -> Markup {
+    let (consents, objections) = election.safety_counts();
+    let path = format!("/election/{}/objections", election.id);
+    html! {
+        p #"safety-counts" {
+            strong { (consents) } " consent · " strong { (objections) } " object"
+        }
+        h3 { "Objections" }
+        @if election.objections.is_empty() {
+            p { "No objections so far." }
+        }
+        ul {
+            @for (index, objection) in election.objections.iter().enumerate() {
+                li {
+                    @if let Some(by) = &objection.by { strong { (by) } ": " }
+                    (objection.text)
+                    @match objection.status {
+                        ObjectionStatus::Open => {
+                            " "
+                            a href="#" hx-post={(path) "/" (index) "/integrated"} hx-swap="none" {
+                                small { "Integrated" }
+                            }
+                            " · "
+                            a ."secondary" href="#" hx-post={(path) "/" (index) "/withdrawn"} hx-swap="none" {
+                                small { "Withdrawn" }
+                            }
+                        },
+                        ObjectionStatus::Integrated => " " mark { "integrated" },
+                        ObjectionStatus::Withdrawn => " " small { s { "withdrawn" } },
+                    }
+                }
+            }
+        }
+        details {
+            summary { "Record an objection" }
+            form ."table rows" hx-post=(path) hx-swap="none" {
+                label for="by" {
+                    "Raised by (optional): ";
+                    input type="text" name="by" {}
+                }
+                label for="text" {
+                    "Objection: ";
+                    textarea name="text" rows="2" required {}
+                }
+                button type="submit" ."secondary" { "Record" }
+            }
+        }
+        @if election.open_objections() > 0 {
+            p { small { "Integrate or withdraw all objections to complete the election." } }
+        }
+    }
+}
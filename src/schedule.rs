@@ -0,0 +1,134 @@
+//! Scheduled voting windows: the election moves into a voting phase when it
+//! opens and on to the tally when it closes, without the facilitator.
+
+use crate::{process_error, step_election, ElectionDB, ElectionUpdate};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Form,
+};
+use iep_core::{ElectionProcess, UtcDateTime, VotingWindow};
+use maud::{html, Markup};
+use std::{collections::HashMap, time::Duration};
+
+/// Sets the windows of all voting phases from fields like `FirstVote_opens`,
+/// in UTC; empty fields remove the time.
+pub async fn post_schedule(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let time = |field: String| match form.get(&field).map(|t| t.trim()) {
+        None | Some("") => Ok(None),
+        Some(text) => UtcDateTime::parse(text)
+            .and_then(|t| t.to_unix())
+            .map(Some)
+            .ok_or((StatusCode::BAD_REQUEST, "Invalid time")),
+    };
+    state.modify_election(&id, |election| {
+        for phase in election.voting_phases() {
+            let window = VotingWindow {
+                phase,
+                opens_at: time(format!("{}_opens", phase))?,
+                closes_at: time(format!("{}_closes", phase))?,
+            };
+            election
+                .set_voting_window(window, state.clock.now())
+                .map_err(process_error)?;
+        }
+        Ok(())
+    })?;
+    state.notify(&id, ElectionUpdate::PhaseChanged)?;
+    Ok((StatusCode::ACCEPTED, [("HX-Refresh", "true")]))
+}
+
+/// Form for the voting windows on the evaluation page.
+pub fn schedule_form(election: &ElectionProcess) -> Markup {
+    let value = |time: Option<u64>| time.map(|t| UtcDateTime::from_unix(t).input_value());
+    html! {
+        details open[!election.voting_windows.is_empty()] {
+            summary { "Schedule (UTC)" }
+            form hx-post={"/election/" (election.id) "/schedule"} hx-swap="none" {
+                @for phase in election.voting_phases() {
+                    @let window = election.voting_window(phase);
+                    fieldset ."grid" {
+                        legend { (phase.nice_title()) }
+                        label {
+                            "Opens"
+                            input type="datetime-local" name={(phase) "_opens"}
+                              value=[value(window.and_then(|w| w.opens_at))] {}
+                        }
+                        label {
+                            "Closes"
+                            input type="datetime-local" name={(phase) "_closes"}
+                              value=[value(window.and_then(|w| w.closes_at))] {}
+                        }
+                    }
+                }
+                button ."secondary outline" type="submit" { "Save schedule" }
+            }
+            @if election.voting_windows.iter().any(|w| w.opens_at.is_some()) {
+                p {
+                    small { a href={"/election/" (election.id) "/calendar.ics"} { "Calendar file" } " of the voting windows" }
+                    @if !election.voter_emails.is_empty() {
+                        " "
+                        button ."secondary outline" hx-post={"/election/" (election.id) "/schedule/invite"} hx-swap="none" {
+                            "Email it to the voters"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// When voting in the current phase opens or closes, for voters.
+pub fn schedule_note(election: &ElectionProcess, now: u64) -> Markup {
+    let format = |time: u64| UtcDateTime::from_unix(time).date_time();
+    html! {
+        @if let Some(opens_at) = election.opens_at(now) {
+            p {
+                mark { "Voting opens at " (format(opens_at)) " (UTC)." }
+                " " small { a href={"/election/" (election.id) "/calendar.ics"} { "Add to calendar" } }
+            }
+        } @else if let Some(closes_at) = election.voting_window(election.phase).and_then(|w| w.closes_at) {
+            p { small { "Voting closes at " (format(closes_at)) " (UTC)." } }
+        }
+    }
+}
+
+/// Moves elections on whose voting window opened or closed, like the
+/// facilitator pressing "Next Phase".
+pub async fn advance_scheduled_phases(state: ElectionDB, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let now = state.clock.now();
+        for election in state.store.list() {
+            if !election.scheduled_step_due(now) {
+                continue;
+            }
+            let advanced = state.modify_election(&election.id, |election| {
+                match election.scheduled_step_due(now) {
+                    true => step_election(&state, election, "next").map(|_| true),
+                    false => Ok(false),
+                }
+            });
+            match advanced {
+                Ok(true) => {
+                    tracing::info!("Scheduled phase change of {}", election.id);
+                    let _ = state.notify(&election.id, ElectionUpdate::PhaseChanged);
+                }
+                Ok(false) => {}
+                Err((_, error)) => {
+                    tracing::warn!(
+                        "Scheduled phase change of {} failed: {}",
+                        election.id,
+                        error
+                    )
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Elections inserted on startup: a demo election to try things out, and
+//! elections read from a seed file, e.g. to prepare the elections of a
+//! meeting before it starts.
+
+use crate::ElectionDB;
+use iep_core::{Ballot, ElectionKind, ElectionProcess};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+pub const DEMO_ID: &str = "1337";
+
+/// An election with three nominees and three votes, unless it exists.
+pub fn insert_demo_election(state: &ElectionDB) {
+    if state.store.contains(DEMO_ID) {
+        return;
+    }
+    let mut test_nominee_map = HashMap::new();
+    test_nominee_map.insert(13589, "Test Nominee 1".to_string());
+    test_nominee_map.insert(29852, "Test2".to_string());
+    test_nominee_map.insert(96109, "Test Nominee 3".to_string());
+    let now = state.clock.now();
+    let mut election = ElectionProcess {
+        nominees: test_nominee_map,
+        ..ElectionProcess::new_and_cleaned(DEMO_ID.to_string(), "Test Role".to_string(), "", now)
+    };
+    let random = state.random.as_ref();
+    for (voter, vote) in [
+        ("Test Voter 1", 13589),
+        ("Test Voter 2", 29852),
+        ("Test Voter 3", 13589),
+    ] {
+        election
+            .add_vote(voter.to_string(), Ballot::new(vote, random), None, now)
+            .unwrap();
+    }
+    state.add_election(election);
+}
+
+/// An entry of a seed file with just what the creation form asks for, unless
+/// it is a whole election, e.g. from an export.
+#[derive(Deserialize)]
+struct NewSeed {
+    id: String,
+    #[serde(default)]
+    kind: ElectionKind,
+    elected_role: String,
+    nominees: Vec<String>,
+    post_vote_message: Option<String>,
+    meeting: Option<String>,
+}
+
+/// Inserts the elections of a JSON array in `path` whose ids are not taken
+/// yet, so restarting with the same file changes nothing. Returns how many
+/// were inserted.
+pub fn insert_seed_file(state: &ElectionDB, path: &str) -> Result<usize, String> {
+    let content = std::fs::read(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let seeds: Vec<serde_json::Value> =
+        serde_json::from_slice(&content).map_err(|e| format!("Invalid {}: {}", path, e))?;
+    let mut inserted = 0;
+    for seed in seeds {
+        // not an untagged enum, which cannot read the numeric keys of the nominees
+        let election = match serde_json::from_value::<ElectionProcess>(seed.clone()) {
+            Ok(election) => election,
+            Err(_) => {
+                let NewSeed {
+                    id,
+                    kind,
+                    elected_role,
+                    nominees,
+                    post_vote_message,
+                    meeting,
+                } = serde_json::from_value(seed).map_err(|e| format!("Invalid {}: {}", path, e))?;
+                ElectionProcess {
+                    kind,
+                    post_vote_message,
+                    meeting,
+                    ..ElectionProcess::new_and_cleaned(
+                        id,
+                        elected_role,
+                        &nominees.join("\n"),
+                        state.clock.now(),
+                    )
+                }
+            }
+        };
+        if election.nominees.is_empty() {
+            return Err(format!(
+                "Election {} in {} has no nominees",
+                election.id, path
+            ));
+        }
+        if state.store.contains(&election.id) {
+            continue;
+        }
+        state.add_election(election);
+        inserted += 1;
+    }
+    Ok(inserted)
+}
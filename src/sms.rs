@@ -0,0 +1,268 @@
+//! Optional voting by SMS through a Twilio-compatible gateway, for voters
+//! without a phone on the network.
+//!
+//! The facilitator texts the ballot to the voters of the roster with a phone
+//! number from the voting links page. Voters reply with the number or name
+//! of a nominee, or text an election id to get its ballot and `<id> <number>`
+//! to vote right away. The gateway has to POST incoming messages to `/sms`,
+//! signed like Twilio does in `X-Twilio-Signature`.
+//!
+//! Set `SMS_API_URL` (e.g. `https://api.twilio.com`),
+//! `SMS_ACCOUNT_SID`, `SMS_AUTH_TOKEN` and `SMS_FROM`, the sending number.
+
+use crate::{
+    cast_vote,
+    crypto::{base64_encode, constant_time_eq, hmac_sha1},
+    http, url_encode, ElectionDB, Vote,
+};
+use axum::{
+    extract::{Path, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Form,
+};
+use iep_core::ElectionProcess;
+use itertools::*;
+use maud::{html, PreEscaped};
+use std::{collections::HashMap, sync::Mutex};
+
+pub struct Gateway {
+    api: String,
+    account: String,
+    token: String,
+    from: String,
+    /// The election of the last ballot texted to each number, for replies.
+    ballots: Mutex<HashMap<String, String>>,
+}
+
+impl Gateway {
+    pub fn from_env() -> Option<Self> {
+        let var = |name| std::env::var(name).ok().filter(|v| !v.is_empty());
+        Some(Gateway {
+            api: var("SMS_API_URL")?.trim_end_matches('/').to_string(),
+            account: var("SMS_ACCOUNT_SID")?,
+            token: var("SMS_AUTH_TOKEN")?,
+            from: var("SMS_FROM")?,
+            ballots: Mutex::new(HashMap::new()),
+        })
+    }
+
+    async fn send(&self, to: &str, text: &str) -> Result<String, String> {
+        let url = format!(
+            "{}/2010-04-01/Accounts/{}/Messages.json",
+            self.api, self.account
+        );
+        let credentials = base64_encode(format!("{}:{}", self.account, self.token).as_bytes());
+        let body = format!(
+            "To={}&From={}&Body={}",
+            url_encode(to),
+            url_encode(&self.from),
+            url_encode(text)
+        );
+        http::post(
+            &url,
+            "application/x-www-form-urlencoded",
+            &[("Authorization", format!("Basic {}", credentials))],
+            body.as_bytes(),
+        )
+        .await
+    }
+
+    fn remember_ballot(&self, phone: &str, id: &str) {
+        if let Ok(mut ballots) = self.ballots.lock() {
+            ballots.insert(phone.to_string(), id.to_string());
+        }
+    }
+
+    fn last_ballot(&self, phone: &str) -> Option<String> {
+        self.ballots.lock().ok()?.get(phone).cloned()
+    }
+
+    /// Whether `signature` is Twilio's for a request to `url` with `params`:
+    /// the base64 HMAC-SHA1 of the URL followed by the sorted names and
+    /// values.
+    fn signed(&self, url: &str, params: &HashMap<String, String>, signature: &str) -> bool {
+        let data = params
+            .iter()
+            .sorted()
+            .fold(url.to_string(), |data, (name, value)| data + name + value);
+        let expected = base64_encode(&hmac_sha1(self.token.as_bytes(), data.as_bytes()));
+        constant_time_eq(expected.as_bytes(), signature.as_bytes())
+    }
+}
+
+/// The nominees in the order of the ballot.
+fn ballot_nominees(election: &ElectionProcess) -> Vec<(u64, &str)> {
+    election
+        .nominees
+        .iter()
+        .map(|(nominee, name)| (*nominee, &name[..]))
+        .sorted_by_key(|(_, name)| *name)
+        .collect()
+}
+
+fn ballot(election: &ElectionProcess) -> String {
+    format!(
+        "{} for {}: reply with the number of your choice. {}",
+        election.phase_title(),
+        election.elected_role,
+        ballot_nominees(election)
+            .iter()
+            .enumerate()
+            .map(|(i, (_, name))| format!("{} {}", i + 1, name))
+            .join(", ")
+    )
+}
+
+/// The nominee of a reply, by number or name.
+fn choice(election: &ElectionProcess, text: &str) -> Option<u64> {
+    let nominees = ballot_nominees(election);
+    match text.parse::<usize>() {
+        Ok(number) => nominees
+            .get(number.checked_sub(1)?)
+            .map(|(nominee, _)| *nominee),
+        Err(_) => nominees
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(text))
+            .map(|(nominee, _)| *nominee),
+    }
+}
+
+/// The answer to an incoming message.
+fn reply(state: &ElectionDB, gateway: &Gateway, phone: &str, text: &str) -> String {
+    let (first, rest) = text.split_once(' ').unwrap_or((text, ""));
+    let (election, choice_text) = match state.store.get(first) {
+        Some(election) => (election, rest.trim()),
+        None => match gateway
+            .last_ballot(phone)
+            .and_then(|id| state.store.get(&id))
+        {
+            Some(election) => (election, text),
+            None => return "Text the id of an election to get its ballot.".to_string(),
+        },
+    };
+    if !election.phase.is_voting() || election.closed_at.is_some() {
+        return format!(
+            "The election for {} is not open for votes.",
+            election.elected_role
+        );
+    }
+    if choice_text.is_empty() {
+        gateway.remember_ballot(phone, &election.id);
+        return ballot(&election);
+    }
+    let Some(nominee) = choice(&election, choice_text) else {
+        return format!("Unknown choice. {}", ballot(&election));
+    };
+    let vote = Vote {
+        voter_name: election.phone_voter(phone).unwrap_or(phone).to_string(),
+        token: None,
+        vote: Some(nominee),
+        reason: None,
+        update: None,
+        write_in: None,
+        ranking: None,
+        scores: None,
+        protect_earlier: false,
+    };
+    match cast_vote(state, &election.id, vote) {
+        Ok((message, receipt)) => format!("{} Your receipt: {}", message, receipt),
+        Err((_, error)) => error.to_string(),
+    }
+}
+
+/// Messages of voters forwarded by the gateway, answered with TwiML.
+pub async fn post_sms(
+    State(state): State<ElectionDB>,
+    headers: HeaderMap,
+    Form(params): Form<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let gateway = state
+        .sms
+        .clone()
+        .ok_or((StatusCode::NOT_FOUND, "SMS voting is not configured"))?;
+    let signature = headers
+        .get("X-Twilio-Signature")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !gateway.signed(&format!("{}/sms", state.base_url), &params, signature) {
+        return Err((StatusCode::FORBIDDEN, "Invalid signature"));
+    }
+    let phone = params.get("From").map_or("", |p| p.trim());
+    let text = params.get("Body").map_or("", |b| b.trim());
+    let answer = reply(&state, &gateway, phone, text);
+    let twiml = html! {
+        (PreEscaped(r#"<?xml version="1.0" encoding="UTF-8"?>"#))
+        Response { Message { (answer) } }
+    };
+    Ok(([(CONTENT_TYPE, "text/xml")], twiml.into_string()))
+}
+
+/// Texts the ballot to the voters of the roster with a phone number who did
+/// not vote yet, in the background.
+pub async fn post_sms_ballots(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let gateway = state.sms.clone().ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "No SMS gateway is configured",
+    ))?;
+    let election = state
+        .store
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    if !election.phase.is_voting() || election.closed_at.is_some() {
+        return Err((StatusCode::CONFLICT, "Election is not in a voting phase"));
+    }
+    let text = ballot(&election);
+    let phones = election
+        .missing_voters()
+        .into_iter()
+        .filter_map(|voter_name| election.voter_phone(voter_name))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    tokio::spawn(async move {
+        for phone in phones {
+            gateway.remember_ballot(&phone, &id);
+            if let Err(error) = gateway.send(&phone, &text).await {
+                tracing::warn!("Texting the ballot to {} failed: {}", phone, error);
+            }
+        }
+    });
+    Ok((StatusCode::ACCEPTED, [("HX-Refresh", "true")]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_signatures_like_twilio() {
+        let gateway = Gateway {
+            api: String::new(),
+            account: String::new(),
+            token: "12345".to_string(),
+            from: String::new(),
+            ballots: Mutex::new(HashMap::new()),
+        };
+        // the example of Twilio's documentation
+        let url = "https://mycompany.com/myapp.php?foo=1&bar=2";
+        let mut params = [
+            ("CallSid", "CA1234567890ABCDE"),
+            ("Caller", "+12349013030"),
+            ("Digits", "1234"),
+            ("From", "+12349013030"),
+            ("To", "+18005551212"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        let signature = "0/KCTR6DLpKmkAf8muzZqo1nDgQ=";
+        assert!(gateway.signed(url, &params, signature));
+        assert!(!gateway.signed(url, &params, ""));
+        assert!(!gateway.signed("https://mycompany.com/", &params, signature));
+        params.insert("Digits".to_string(), "1235".to_string());
+        assert!(!gateway.signed(url, &params, signature));
+    }
+}
@@ -0,0 +1,102 @@
+//! Keeps elections in memory and saves all of them to a single snapshot file
+//! now and then, for installs where writing every change is not worth it.
+//!
+//! Everything since the last snapshot is lost on a crash.
+
+use crate::{
+    store::{ElectionStore, MemoryStore},
+    ElectionDB,
+};
+use iep_core::{Circle, ElectionProcess};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Snapshot {
+    pub taken_at: u64,
+    pub elections: Vec<ElectionProcess>,
+    pub circles: HashMap<String, Circle>,
+}
+
+pub struct SnapshotStore {
+    file: PathBuf,
+    memory: MemoryStore,
+}
+
+impl SnapshotStore {
+    /// Restores the snapshot in `data_dir`, if there is one.
+    pub fn open(data_dir: &Path) -> std::io::Result<(Self, HashMap<String, Circle>)> {
+        let store = SnapshotStore {
+            file: data_dir.join("snapshot.json"),
+            memory: MemoryStore::default(),
+        };
+        let snapshot = match std::fs::read(&store.file) {
+            Ok(data) => serde_json::from_slice::<Snapshot>(&data)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))?,
+            Err(error) if error.kind() == ErrorKind::NotFound => Snapshot::default(),
+            Err(error) => return Err(error),
+        };
+        for election in snapshot.elections {
+            store.memory.insert(election);
+        }
+        Ok((store, snapshot.circles))
+    }
+
+    pub async fn save(&self, state: &ElectionDB) -> std::io::Result<()> {
+        let snapshot = Snapshot {
+            taken_at: state.clock.now(),
+            elections: self.memory.list(),
+            circles: state.circles.lock().unwrap().clone(),
+        };
+        if let Some(dir) = self.file.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let tmp = self.file.with_extension("json.tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec(&snapshot)?).await?;
+        tokio::fs::rename(&tmp, &self.file).await
+    }
+}
+
+impl ElectionStore for SnapshotStore {
+    fn get(&self, id: &str) -> Option<ElectionProcess> {
+        self.memory.get(id)
+    }
+
+    fn list(&self) -> Vec<ElectionProcess> {
+        self.memory.list()
+    }
+
+    fn insert(&self, election: ElectionProcess) {
+        self.memory.insert(election)
+    }
+
+    fn modify(&self, id: &str, change: &mut dyn FnMut(&mut ElectionProcess)) -> bool {
+        self.memory.modify(id, change)
+    }
+
+    fn delete(&self, id: &str) -> Option<ElectionProcess> {
+        self.memory.delete(id)
+    }
+
+    fn contains(&self, id: &str) -> bool {
+        self.memory.contains(id)
+    }
+}
+
+pub async fn take_snapshots(state: ElectionDB, store: Arc<SnapshotStore>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    // the first tick completes immediately, and there is nothing new yet
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        if let Err(error) = store.save(&state).await {
+            tracing::error!("Writing snapshot failed: {}", error);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Persistence of elections and circles as JSON files in the data directory.
+//!
+//! Elections are kept in a [`MemoryStore`] as the working copy. Changes only
+//! mark the election, and a background task writes the marked ones to disk,
+//! so no request waits for the file system.
+
+use crate::{
+    store::{ElectionStore, MemoryStore},
+    ElectionDB,
+};
+use iep_core::{Circle, ElectionProcess};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+pub struct FileStore {
+    dir: PathBuf,
+    memory: MemoryStore,
+    dirty: Mutex<HashSet<String>>,
+    circles_dirty: AtomicBool,
+    /// Held while flushing, so a final flush waits for one still running.
+    flushing: tokio::sync::Mutex<()>,
+}
+
+impl FileStore {
+    /// Reads all elections stored in `data_dir`.
+    pub fn open(data_dir: &Path) -> std::io::Result<Self> {
+        let store = FileStore {
+            dir: data_dir.to_path_buf(),
+            memory: MemoryStore::default(),
+            dirty: Mutex::new(HashSet::new()),
+            circles_dirty: AtomicBool::new(false),
+            flushing: tokio::sync::Mutex::new(()),
+        };
+        let entries = match std::fs::read_dir(store.elections_dir()) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(store),
+            Err(error) => return Err(error),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|e| e != "json") {
+                continue;
+            }
+            match serde_json::from_slice::<ElectionProcess>(&std::fs::read(&path)?) {
+                Ok(election) => store.memory.insert(election),
+                Err(error) => tracing::warn!("Skipping {}: {}", path.display(), error),
+            }
+        }
+        Ok(store)
+    }
+
+    fn elections_dir(&self) -> PathBuf {
+        self.dir.join("elections")
+    }
+
+    fn circles_file(&self) -> PathBuf {
+        self.dir.join("circles.json")
+    }
+
+    /// Schedules the election for writing, or for deletion if it is gone.
+    fn mark(&self, id: &str) {
+        self.dirty
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id.to_string());
+    }
+
+    pub fn load_circles(&self) -> std::io::Result<HashMap<String, Circle>> {
+        match std::fs::read(self.circles_file()) {
+            Ok(data) => {
+                serde_json::from_slice(&data).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Writes everything marked since the last call.
+    pub async fn flush(&self, state: &ElectionDB) -> std::io::Result<()> {
+        let _flushing = self.flushing.lock().await;
+        let ids = std::mem::take(&mut *self.dirty.lock().unwrap_or_else(|e| e.into_inner()));
+        let circles = match self.circles_dirty.swap(false, Ordering::Relaxed) {
+            true => Some(serde_json::to_vec_pretty(&*state.circles.lock().unwrap())?),
+            false => None,
+        };
+
+        tokio::fs::create_dir_all(self.elections_dir()).await?;
+        for id in ids {
+            let path = self.elections_dir().join(format!("{}.json", id));
+            match self.memory.get(&id) {
+                Some(election) => {
+                    write_atomically(&path, &serde_json::to_vec_pretty(&election)?).await?
+                }
+                None => match tokio::fs::remove_file(&path).await {
+                    Err(error) if error.kind() != ErrorKind::NotFound => return Err(error),
+                    _ => {}
+                },
+            }
+        }
+        if let Some(circles) = circles {
+            write_atomically(&self.circles_file(), &circles).await?;
+        }
+        Ok(())
+    }
+}
+
+impl ElectionStore for FileStore {
+    fn get(&self, id: &str) -> Option<ElectionProcess> {
+        self.memory.get(id)
+    }
+
+    fn list(&self) -> Vec<ElectionProcess> {
+        self.memory.list()
+    }
+
+    fn insert(&self, election: ElectionProcess) {
+        self.mark(&election.id);
+        self.memory.insert(election);
+    }
+
+    fn modify(&self, id: &str, change: &mut dyn FnMut(&mut ElectionProcess)) -> bool {
+        self.mark(id);
+        self.memory.modify(id, change)
+    }
+
+    fn delete(&self, id: &str) -> Option<ElectionProcess> {
+        self.mark(id);
+        self.memory.delete(id)
+    }
+
+    fn contains(&self, id: &str) -> bool {
+        self.memory.contains(id)
+    }
+
+    /// Only marks the circles, they are read again when writing.
+    fn save_circles(&self, _circles: &HashMap<String, Circle>) {
+        self.circles_dirty.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Writes to a temporary file first, so a crash never leaves half a file.
+async fn write_atomically(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, data).await?;
+    tokio::fs::rename(&tmp, path).await
+}
+
+pub async fn write_behind(
+    state: ElectionDB,
+    storage: std::sync::Arc<FileStore>,
+    interval: Duration,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Err(error) = storage.flush(&state).await {
+            tracing::error!("Writing elections to disk failed: {}", error);
+        }
+    }
+}
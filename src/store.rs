@@ -0,0 +1,122 @@
+//! Access to elections behind a trait, so deployments can choose where they
+//! are kept without changing the handlers.
+
+use crate::ElectionUpdate;
+use iep_core::{Circle, ElectionProcess};
+use std::{collections::HashMap, io::ErrorKind, sync::Mutex};
+
+pub trait ElectionStore: Send + Sync {
+    fn get(&self, id: &str) -> Option<ElectionProcess>;
+    fn list(&self) -> Vec<ElectionProcess>;
+    /// Adds the election, replacing a stored one with the same id.
+    fn insert(&self, election: ElectionProcess);
+    /// Applies `change` to the stored election; returns false if there is none.
+    fn modify(&self, id: &str, change: &mut dyn FnMut(&mut ElectionProcess)) -> bool;
+    fn delete(&self, id: &str) -> Option<ElectionProcess>;
+
+    fn contains(&self, id: &str) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Tells other instances sharing the store about an update.
+    fn publish(&self, _id: &str, _update: ElectionUpdate) {}
+
+    /// Called after the circles changed, for stores that keep them as well.
+    fn save_circles(&self, _circles: &HashMap<String, Circle>) {}
+}
+
+/// Keeps elections in memory only; they are lost on restart.
+#[derive(Default)]
+pub struct MemoryStore {
+    elections: Mutex<HashMap<String, ElectionProcess>>,
+}
+
+impl MemoryStore {
+    pub fn len(&self) -> usize {
+        self.elections().len()
+    }
+
+    fn elections(&self) -> std::sync::MutexGuard<'_, HashMap<String, ElectionProcess>> {
+        // a panic in another request must not lock everyone out
+        self.elections.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ElectionStore for MemoryStore {
+    fn get(&self, id: &str) -> Option<ElectionProcess> {
+        self.elections().get(id).cloned()
+    }
+
+    fn list(&self) -> Vec<ElectionProcess> {
+        self.elections().values().cloned().collect()
+    }
+
+    fn insert(&self, election: ElectionProcess) {
+        self.elections().insert(election.id.clone(), election);
+    }
+
+    fn modify(&self, id: &str, change: &mut dyn FnMut(&mut ElectionProcess)) -> bool {
+        match self.elections().get_mut(id) {
+            Some(election) => {
+                change(election);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn delete(&self, id: &str) -> Option<ElectionProcess> {
+        self.elections().remove(id)
+    }
+
+    fn contains(&self, id: &str) -> bool {
+        self.elections().contains_key(id)
+    }
+}
+
+/// The connection of a store backed by a server, shared by all requests.
+/// Errors of kind `Other` are the server refusing a query; any other error
+/// drops the connection, which is opened again once for the same queries.
+pub struct SharedConnection<C> {
+    server: &'static str,
+    connection: Mutex<Option<C>>,
+}
+
+impl<C> SharedConnection<C> {
+    pub fn new(server: &'static str, connection: C) -> Self {
+        SharedConnection {
+            server,
+            connection: Mutex::new(Some(connection)),
+        }
+    }
+
+    /// Runs `queries`, reconnecting with `connect` if the connection broke.
+    pub fn run<T>(
+        &self,
+        connect: impl Fn() -> std::io::Result<C>,
+        mut queries: impl FnMut(&mut C) -> std::io::Result<T>,
+    ) -> Option<T> {
+        // a request waiting for the server must not stall the other requests
+        tokio::task::block_in_place(|| {
+            let mut connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+            for _ in 0..2 {
+                let result = match connection.as_mut() {
+                    Some(connection) => queries(connection),
+                    None => connect().and_then(|c| queries(connection.insert(c))),
+                };
+                match result {
+                    Ok(result) => return Some(result),
+                    Err(error) if error.kind() == ErrorKind::Other => {
+                        tracing::error!("{} query failed: {}", self.server, error);
+                        return None;
+                    }
+                    Err(error) => {
+                        tracing::warn!("{} connection failed: {}", self.server, error);
+                        *connection = None;
+                    }
+                }
+            }
+            None
+        })
+    }
+}
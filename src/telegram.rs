@@ -0,0 +1,264 @@
+//! Optional Telegram bot for voting from a phone.
+//!
+//! Participants send the bot an election id (or open `t.me/<bot>?start=<id>`)
+//! and get the nominees as inline buttons; they can also reply with the
+//! number or name of a nominee. The bot remembers their chat and sends the
+//! ballot again whenever a voting round of the election opens. Ballots are
+//! cast under the participant's Telegram name, just like the web form.
+//!
+//! `TELEGRAM_API_URL` can point at a local Bot API server instead of
+//! Telegram's.
+
+use crate::{cast_vote, http, ElectionDB, ElectionUpdate, Vote};
+use iep_core::ElectionProcess;
+use itertools::*;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// How long a `getUpdates` call waits for new messages, below the HTTP timeout.
+const POLL_SECS: u64 = 5;
+
+pub struct Bot {
+    api: String,
+    token: String,
+    /// The election of the last ballot sent to each chat, for replies.
+    ballots: Mutex<HashMap<i64, String>>,
+}
+
+impl Bot {
+    pub fn from_env() -> Option<Self> {
+        Some(Bot {
+            api: std::env::var("TELEGRAM_API_URL")
+                .unwrap_or("https://api.telegram.org".to_string()),
+            token: std::env::var("TELEGRAM_BOT_TOKEN")
+                .ok()
+                .filter(|t| !t.is_empty())?,
+            ballots: Mutex::new(HashMap::new()),
+        })
+    }
+
+    async fn call(&self, method: &str, body: Value) -> Result<Value, String> {
+        let url = format!("{}/bot{}/{}", self.api, self.token, method);
+        let response: Value = serde_json::from_str(&http::post_json(&url, &body).await?)
+            .map_err(|e| format!("Invalid response to {}: {}", method, e))?;
+        match response["ok"].as_bool() {
+            Some(true) => Ok(response["result"].clone()),
+            _ => Err(format!("{} failed: {}", method, response["description"])),
+        }
+    }
+
+    async fn send_ballot(&self, chat: i64, election: &ElectionProcess) -> Result<(), String> {
+        if let Ok(mut ballots) = self.ballots.lock() {
+            ballots.insert(chat, election.id.clone());
+        }
+        let mut ballot = ballot(election);
+        ballot["chat_id"] = json!(chat);
+        self.call("sendMessage", ballot).await.map(|_| ())
+    }
+
+    fn last_ballot(&self, chat: i64) -> Option<String> {
+        self.ballots.lock().ok()?.get(&chat).cloned()
+    }
+}
+
+pub async fn run(state: ElectionDB, bot: Arc<Bot>) {
+    let mut offset = 0;
+    loop {
+        let updates = match bot
+            .call(
+                "getUpdates",
+                json!({ "offset": offset, "timeout": POLL_SECS }),
+            )
+            .await
+        {
+            Ok(updates) => updates,
+            Err(error) => {
+                tracing::warn!("Telegram polling failed: {}", error);
+                tokio::time::sleep(Duration::from_secs(POLL_SECS)).await;
+                continue;
+            }
+        };
+        for update in updates.as_array().into_iter().flatten() {
+            offset = offset.max(update["update_id"].as_i64().unwrap_or(0) + 1);
+            let result = if let Some(message) = update.get("message") {
+                handle_message(&bot, &state, message).await
+            } else if let Some(query) = update.get("callback_query") {
+                handle_callback(&bot, &state, query).await
+            } else {
+                Ok(())
+            };
+            if let Err(error) = result {
+                tracing::warn!("Telegram update failed: {}", error);
+            }
+        }
+    }
+}
+
+/// The nominees in the order of the ballot.
+fn ballot_nominees(election: &ElectionProcess) -> Vec<(u64, &str)> {
+    election
+        .nominees
+        .iter()
+        .map(|(nominee, name)| (*nominee, &name[..]))
+        .sorted_by_key(|(_, name)| *name)
+        .collect()
+}
+
+/// The numbered nominees of the current voting round with a button each.
+fn ballot(election: &ElectionProcess) -> Value {
+    let nominees = ballot_nominees(election);
+    let buttons = nominees
+        .iter()
+        .map(|(nominee, name)| {
+            [json!({ "text": name, "callback_data": format!("v:{}:{}", election.id, nominee) })]
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "text": format!(
+            "{}: vote for the {}\n\n{}\n\nTap a nominee or reply with their number.",
+            election.phase_title(),
+            election.elected_role,
+            nominees
+                .iter()
+                .enumerate()
+                .map(|(i, (_, name))| format!("{}. {}", i + 1, name))
+                .join("\n")
+        ),
+        "reply_markup": { "inline_keyboard": buttons },
+    })
+}
+
+/// The nominee chosen by a reply to the ballot, by number or name.
+fn choice(election: &ElectionProcess, text: &str) -> Option<u64> {
+    let nominees = ballot_nominees(election);
+    match text.parse::<usize>() {
+        Ok(number) => nominees
+            .get(number.checked_sub(1)?)
+            .map(|(nominee, _)| *nominee),
+        Err(_) => nominees
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(text))
+            .map(|(nominee, _)| *nominee),
+    }
+}
+
+fn voter_name(from: &Value) -> String {
+    [from["first_name"].as_str(), from["last_name"].as_str()]
+        .into_iter()
+        .flatten()
+        .join(" ")
+}
+
+fn vote(state: &ElectionDB, id: &str, nominee: u64, voter_name: String) -> String {
+    let vote = Vote {
+        voter_name,
+        token: None,
+        vote: Some(nominee),
+        reason: None,
+        update: None,
+        write_in: None,
+        ranking: None,
+        scores: None,
+        protect_earlier: false,
+    };
+    match cast_vote(state, id, vote) {
+        Ok((message, receipt)) => format!("{}\nYour receipt: {}", message, receipt),
+        Err((_, error)) => error.to_string(),
+    }
+}
+
+/// Casts the vote of a reply to the last ballot, or answers an election id
+/// with its ballot and remembers the chat for the next rounds.
+async fn handle_message(bot: &Bot, state: &ElectionDB, message: &Value) -> Result<(), String> {
+    let chat = message["chat"]["id"]
+        .as_i64()
+        .ok_or("Message without a chat")?;
+    let text = message["text"].as_str().unwrap_or("").trim();
+
+    let voting = bot
+        .last_ballot(chat)
+        .and_then(|id| state.store.get(&id))
+        .filter(|e| e.phase.is_voting() && e.closed_at.is_none());
+    if let Some((election, nominee)) = voting.and_then(|e| choice(&e, text).map(|n| (e, n))) {
+        let text = vote(state, &election.id, nominee, voter_name(&message["from"]));
+        return bot
+            .call("sendMessage", json!({ "chat_id": chat, "text": text }))
+            .await
+            .map(|_| ());
+    }
+
+    let id = text
+        .strip_prefix("/start")
+        .or_else(|| text.strip_prefix("/join"))
+        .unwrap_or(text)
+        .trim();
+    let text = match state.store.get(id) {
+        None if id.is_empty() => "Send me the id of an election to vote.".to_string(),
+        None => format!("There is no election {}.", id),
+        Some(election) if election.closed_at.is_some() => {
+            format!("The election for {} is closed.", election.elected_role)
+        }
+        Some(election) => {
+            let _ = state.modify_election(id, |election| {
+                election.join_telegram_chat(chat, state.clock.now());
+                Ok(())
+            });
+            if election.phase.is_voting() {
+                return bot.send_ballot(chat, &election).await;
+            }
+            format!(
+                "The election for {} is not open for votes. You will get the ballot once it is.",
+                election.elected_role
+            )
+        }
+    };
+    bot.call("sendMessage", json!({ "chat_id": chat, "text": text }))
+        .await
+        .map(|_| ())
+}
+
+/// Casts the vote behind a nominee button.
+async fn handle_callback(bot: &Bot, state: &ElectionDB, query: &Value) -> Result<(), String> {
+    let data = query["data"].as_str().unwrap_or("");
+    let text = match data
+        .strip_prefix("v:")
+        .and_then(|v| v.rsplit_once(':'))
+        .and_then(|(id, nominee)| Some((id, nominee.parse::<u64>().ok()?)))
+    {
+        None => "Unknown button.".to_string(),
+        Some((id, nominee)) => vote(state, id, nominee, voter_name(&query["from"])),
+    };
+    bot.call(
+        "answerCallbackQuery",
+        json!({ "callback_query_id": query["id"], "text": text, "show_alert": true }),
+    )
+    .await
+    .map(|_| ())
+}
+
+/// Sends the ballot to the chats following election `id` when a voting
+/// round opens, in the background.
+pub fn deliver(state: &ElectionDB, id: &str, update: ElectionUpdate) {
+    let Some(bot) = state.telegram.clone() else {
+        return;
+    };
+    let Some(election) = state.store.get(id).filter(|e| {
+        matches!(update, ElectionUpdate::PhaseChanged)
+            && e.phase.is_voting()
+            && e.closed_at.is_none()
+            && !e.telegram_chats.is_empty()
+    }) else {
+        return;
+    };
+    tokio::spawn(async move {
+        for chat in &election.telegram_chats {
+            if let Err(error) = bot.send_ballot(*chat, &election).await {
+                tracing::warn!("Sending the ballot to Telegram failed: {}", error);
+            }
+        }
+    });
+}
@@ -0,0 +1,210 @@
+//! Saved roles and nominee lists, to start recurring elections with one click.
+//!
+//! Templates are kept in `DATA_DIR/templates.json`, whatever the store.
+
+use crate::{
+    accounts, create_election, facilitator, read_election_creation, ElectionCreation, ElectionDB,
+};
+use axum::{
+    extract::{Path, Request, State},
+    http::{HeaderMap, StatusCode},
+    response::{AppendHeaders, IntoResponse},
+};
+use iep_core::ElectionKind;
+use itertools::*;
+use maud::{html, Markup};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+    path::{Path as FsPath, PathBuf},
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Template {
+    pub id: String,
+    #[serde(default)]
+    pub kind: ElectionKind,
+    pub elected_role: String,
+    /// In the format of the creation form, one nominee per line.
+    pub nominees: String,
+    pub post_vote_message: Option<String>,
+}
+
+fn templates_file(data_dir: &FsPath) -> PathBuf {
+    data_dir.join("templates.json")
+}
+
+pub fn load(data_dir: &FsPath) -> std::io::Result<HashMap<String, Template>> {
+    match std::fs::read(templates_file(data_dir)) {
+        Ok(data) => {
+            serde_json::from_slice(&data).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+        }
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(error) => Err(error),
+    }
+}
+
+fn save(
+    state: &ElectionDB,
+    templates: &HashMap<String, Template>,
+) -> Result<(), (StatusCode, &'static str)> {
+    let file = templates_file(&state.data_dir);
+    let tmp = file.with_extension("json.tmp");
+    std::fs::create_dir_all(&state.data_dir)
+        .and_then(|_| std::fs::write(&tmp, serde_json::to_vec_pretty(templates)?))
+        .and_then(|_| std::fs::rename(&tmp, &file))
+        .map_err(|error| {
+            tracing::error!("Writing {} failed: {}", file.display(), error);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Could not save templates",
+            )
+        })
+}
+
+fn add(state: &ElectionDB, template: Template) -> Result<(), (StatusCode, &'static str)> {
+    if template.elected_role.trim().is_empty() || template.nominees.trim().is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Templates need a role and nominees",
+        ));
+    }
+    let mut templates = state
+        .templates
+        .lock()
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Lock error"))?;
+    templates.insert(template.id.clone(), template);
+    save(state, &templates)
+}
+
+/// Saves the creation form as a template instead of starting the election.
+pub async fn post_template(
+    State(state): State<ElectionDB>,
+    request: Request,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let form = read_election_creation(&state, request).await?;
+    add(
+        &state,
+        Template {
+            id: state.generate_id(),
+            kind: form.kind,
+            elected_role: form.elected_role.trim().to_string(),
+            nominees: form.nominees,
+            post_vote_message: form.post_vote_message.filter(|m| !m.trim().is_empty()),
+        },
+    )?;
+    Ok((StatusCode::CREATED, [("HX-Redirect", "/")]))
+}
+
+/// Saves the role and nominees of an existing election as a template.
+pub async fn post_election_template(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let election = state
+        .store
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    add(
+        &state,
+        Template {
+            id: state.generate_id(),
+            kind: election.kind,
+            elected_role: election.elected_role.clone(),
+            nominees: election.nominees_text(&[]),
+            post_vote_message: election.post_vote_message.clone(),
+        },
+    )?;
+    Ok((StatusCode::CREATED, [("HX-Redirect", "/")]))
+}
+
+pub async fn post_template_action(
+    Path((id, action)): Path<(String, String)>,
+    State(state): State<ElectionDB>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let template = state
+        .templates
+        .lock()
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Lock error"))?
+        .get(&id)
+        .cloned()
+        .ok_or((StatusCode::NOT_FOUND, "Template not found"))?;
+    match &action[..] {
+        "start" => {
+            let (account, cookie) = accounts::account_or_new(&state, &headers);
+            let election = create_election(
+                &state,
+                ElectionCreation {
+                    kind: template.kind,
+                    elected_role: template.elected_role,
+                    nominees: template.nominees,
+                    post_vote_message: template.post_vote_message,
+                    ..Default::default()
+                },
+                Some(account),
+            )?;
+            Ok((
+                StatusCode::CREATED,
+                AppendHeaders(cookie),
+                [("HX-Redirect", facilitator::eval_path(&election))],
+            ))
+        }
+        "delete" => {
+            let mut templates = state
+                .templates
+                .lock()
+                .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Lock error"))?;
+            templates.remove(&id);
+            save(&state, &templates)?;
+            Ok((
+                StatusCode::ACCEPTED,
+                AppendHeaders(None),
+                [("HX-Redirect", "/".to_string())],
+            ))
+        }
+        _ => Err((StatusCode::BAD_REQUEST, "Invalid action")),
+    }
+}
+
+/// The saved templates for the home page.
+pub fn templates_list(state: &ElectionDB) -> Markup {
+    let templates = match state.templates.lock() {
+        Ok(templates) => templates.values().cloned().collect::<Vec<_>>(),
+        Err(_) => vec![],
+    };
+    if templates.is_empty() {
+        return html! {};
+    }
+    html! {
+        h2 { "Start from a Template" }
+        table ."striped" {
+            tbody {
+                @for template in templates.iter().sorted_by_key(|t| t.elected_role.to_lowercase()) {
+                    @let path = format!("/templates/{}", template.id);
+                    tr {
+                        td {
+                            (template.elected_role)
+                            @if template.kind == ElectionKind::StrawPoll { small { " (straw poll)" } }
+                            br;
+                            small { (template.nominees.lines().filter(|l| !l.trim().is_empty()).join(", ")) }
+                        }
+                        td {
+                            button hx-post={(path) "/start"} hx-trigger="click" hx-swap="none" {
+                                "Start"
+                            }
+                        }
+                        td {
+                            a ."secondary" href="#"
+                              hx-post={(path) "/delete"} hx-trigger="click" hx-swap="none"
+                              hx-confirm={"Delete the template for " (template.elected_role) "?"} {
+                                "Delete"
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
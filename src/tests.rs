@@ -0,0 +1,686 @@
+//! Drives whole elections through the router, with a fixed clock and a
+//! seeded random generator so ids, keys, receipts and times are the same on
+//! every run, and checks what its routes give away to whom.
+
+use crate::{
+    cookies, crypto, ids, router,
+    snapshot::SnapshotStore,
+    store::{ElectionStore, MemoryStore},
+    ElectionDB,
+};
+use axum::{
+    body::Body,
+    http::{
+        header::{HeaderName, ACCEPT, AUTHORIZATION, CONTENT_TYPE, COOKIE, SET_COOKIE},
+        Method, Request, StatusCode,
+    },
+};
+use iep_core::{
+    Ballot, Circle, Clock, ElectionPhase, ElectionProcess, FixedClock, ProcessError, SeededRandom,
+};
+use serde_json::{json, Value};
+use std::{sync::Arc, time::Duration};
+use tower::ServiceExt;
+
+const START: u64 = 1_700_000_000;
+
+fn state(clock: Arc<FixedClock>, seed: u64) -> ElectionDB {
+    ElectionDB {
+        store: Arc::new(MemoryStore::default()),
+        circles: Default::default(),
+        templates: Default::default(),
+        streams: Default::default(),
+        redirects: Default::default(),
+        base_url: "http://localhost:3000".to_string(),
+        data_dir: std::env::temp_dir().join("ieptool-tests"),
+        admin_token: None,
+        reminder_weeks: 4,
+        keep_for: None,
+        reminder_webhook: None,
+        slack_webhook: None,
+        matrix: None,
+        mail: None,
+        sms: None,
+        telegram: None,
+        sse_keep_alive: Duration::from_secs(20),
+        sse_retry: Duration::from_millis(3000),
+        id_style: ids::IdStyle::Random(5),
+        clock: clock as Arc<dyn Clock>,
+        random: Arc::new(SeededRandom::new(seed)),
+        cookie_secret: Arc::new([0; 32]),
+        oidc: None,
+    }
+}
+
+/// Sends a JSON request, with `key` as bearer token, and reads the answer.
+async fn call(
+    state: &ElectionDB,
+    method: Method,
+    uri: &str,
+    key: Option<&str>,
+    body: Value,
+) -> (StatusCode, Value) {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(CONTENT_TYPE, "application/json");
+    if let Some(key) = key {
+        request = request.header(AUTHORIZATION, format!("Bearer {}", key));
+    }
+    send(state, request.body(Body::from(body.to_string())).unwrap()).await
+}
+
+async fn send(state: &ElectionDB, request: Request<Body>) -> (StatusCode, Value) {
+    let response = router(state.clone()).oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// Runs a two-round election of Ann, Bob and Cid to its safety round and
+/// returns its state through the API.
+async fn run_election(seed: u64) -> Value {
+    let clock = Arc::new(FixedClock::new(START));
+    let state = state(clock.clone(), seed);
+    let (status, created) = call(
+        &state,
+        Method::POST,
+        "/api/v1/elections",
+        None,
+        json!({
+            "elected_role": "Lead",
+            "nominees": ["Cid", "Ann", "Bob"],
+            "roster": ["v1", "v2", "v3"],
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let id = created["id"].as_str().unwrap();
+    let key = created["facilitator_key"].as_str();
+    let votes = format!("/api/v1/elections/{}/votes", id);
+    let phase = format!("/api/v1/elections/{}/phase", id);
+
+    let mut receipts = vec![];
+    for round in [[1, 0, 2], [1, 1, 0]] {
+        for (voter, vote) in ["v1", "v2", "v3"].iter().zip(round) {
+            clock.advance(10);
+            let ballot = json!({ "voter_name": voter, "vote": vote });
+            let (status, cast) = call(&state, Method::POST, &votes, None, ballot).await;
+            assert_eq!(status, StatusCode::CREATED);
+            receipts.push(cast["receipt"].clone());
+        }
+        for _ in 0..2 {
+            clock.advance(60);
+            let next = json!({ "step": "next" });
+            let (status, _) = call(&state, Method::POST, &phase, None, next.clone()).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            let (status, _) = call(&state, Method::POST, &phase, key, next).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+    }
+    let (status, mut election) = call(
+        &state,
+        Method::GET,
+        &format!("/api/v1/elections/{}", id),
+        None,
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    election["receipts"] = receipts.into();
+    election["facilitator_key"] = created["facilitator_key"].clone();
+    election
+}
+
+#[tokio::test]
+async fn runs_an_election_reproducibly() {
+    let election = run_election(7).await;
+    assert_eq!(election["phase"], "SafetyRound");
+    assert_eq!(
+        election["nominees"],
+        json!({"0": "Ann", "1": "Bob", "2": "Cid"})
+    );
+    let results = &election["results"];
+    assert_eq!(
+        results["first_round"]["votes"],
+        json!({"Ann": 1, "Bob": 1, "Cid": 1})
+    );
+    assert_eq!(
+        results["second_round"]["votes"],
+        json!({"Bob": 2, "Ann": 1})
+    );
+    assert_eq!(results["second_round"]["most_votes"], json!(["Bob"]));
+    let history = results["phase_history"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| (t["phase"].as_str().unwrap(), t["at"].as_u64().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        history,
+        [
+            ("FirstVote", START),
+            ("FirstTally", START + 90),
+            ("SecondVote", START + 150),
+            ("SecondTally", START + 240),
+            ("SafetyRound", START + 300),
+        ]
+    );
+
+    // the same seed gives the same ids, keys and receipts
+    assert_eq!(run_election(7).await, election);
+    let other = run_election(8).await;
+    assert_ne!(other["id"], election["id"]);
+    assert_ne!(other["receipts"], election["receipts"]);
+    assert_eq!(other["results"]["second_round"], results["second_round"]);
+}
+
+#[tokio::test]
+async fn tells_only_facilitators_who_voted() {
+    let state = state(Arc::new(FixedClock::new(START)), 1);
+    let election = json!({ "elected_role": "Lead", "nominees": ["Ann"], "roster": ["v1", "v2"] });
+    let (_, created) = call(&state, Method::POST, "/api/v1/elections", None, election).await;
+    let id = created["id"].as_str().unwrap();
+    let ballot = json!({ "voter_name": "v1", "vote": 0 });
+    let votes = format!("/api/v1/elections/{}/votes", id);
+    call(&state, Method::POST, &votes, None, ballot).await;
+
+    let votes_changed = |cookie: Option<String>| {
+        let mut request = Request::get(format!("/election/{}/state?since=0", id));
+        if let Some(cookie) = cookie {
+            request = request.header(COOKIE, cookie);
+        }
+        let state = state.clone();
+        async move {
+            let (status, body) = send(&state, request.body(Body::empty()).unwrap()).await;
+            assert_eq!(status, StatusCode::OK);
+            body["updates"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|u| u["event"] == "votes-changed")
+                .unwrap()["data"]
+                .clone()
+        }
+    };
+    let public = votes_changed(None).await;
+    assert_eq!(public["votes"], 1);
+    assert!(public.get("voters").is_none() && public.get("missing").is_none());
+    let cookie = format!(
+        "{}={}",
+        cookies::facilitator_cookie_name(id),
+        created["facilitator_key"].as_str().unwrap()
+    );
+    let facilitator = votes_changed(Some(cookie)).await;
+    assert_eq!(facilitator["voters"], json!(["v1"]));
+    assert_eq!(facilitator["missing"], json!(["v2"]));
+    let wrong_key = format!("{}=guess", cookies::facilitator_cookie_name(id));
+    assert!(votes_changed(Some(wrong_key)).await.get("voters").is_none());
+}
+
+/// The status of a GET request with `headers`, and its `Set-Cookie` header.
+async fn get(
+    state: &ElectionDB,
+    uri: &str,
+    headers: &[(HeaderName, &str)],
+) -> (StatusCode, String) {
+    let mut request = Request::get(uri);
+    for (name, value) in headers {
+        request = request.header(name, *value);
+    }
+    let response = router(state.clone())
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let cookie = response
+        .headers()
+        .get(SET_COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .unwrap_or_default();
+    (response.status(), cookie.to_string())
+}
+
+#[tokio::test]
+async fn asks_for_the_passcode_on_every_route() {
+    let state = state(Arc::new(FixedClock::new(START)), 1);
+    let election = json!({
+        "elected_role": "Lead",
+        "nominees": ["Ann", "Bob"],
+        "roster": ["v1", "v2"],
+        "passcode": "4711",
+    });
+    let (_, created) = call(&state, Method::POST, "/api/v1/elections", None, election).await;
+    let id = created["id"].as_str().unwrap();
+    let key = created["facilitator_key"].as_str().unwrap();
+
+    for route in [
+        "voting",
+        "voting/form",
+        "voting/options",
+        "voting/previous?voter_name=v1",
+        "voting/check",
+        "voting/mine",
+        "kiosk",
+        "kiosk/form",
+        "nominations",
+        "nominations/pending",
+        "observe",
+        "observe/content",
+        "embed",
+        "embed/content",
+        "stats",
+        "results.json",
+        "stream",
+        "ws",
+        "updates",
+        "state",
+        "ballots",
+        "follow-up",
+        "clone",
+        "calendar.ics",
+        "nominees/0/photo",
+    ] {
+        let uri = format!("/election/{}/{}", id, route);
+        assert_eq!(
+            get(&state, &uri, &[]).await.0,
+            StatusCode::FORBIDDEN,
+            "{}",
+            uri
+        );
+    }
+    let ballot = json!({ "voter_name": "v1", "vote": 0 });
+    let voting = format!("/election/{}/voting", id);
+    let (status, _) = call(&state, Method::POST, &voting, None, ballot).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let oembed = format!(
+        "/oembed?url={}",
+        crate::url_encode(&format!("{}/election/{}/voting", state.base_url, id))
+    );
+    assert_eq!(get(&state, &oembed, &[]).await.0, StatusCode::FORBIDDEN);
+    // pages opened in the browser ask for it instead
+    let page = get(&state, &voting, &[(ACCEPT, "text/html")]).await;
+    assert_eq!(page.0, StatusCode::OK);
+    let qr = format!("/election/{}/qr.png", id);
+    assert_eq!(get(&state, &qr, &[]).await.0, StatusCode::OK);
+
+    let stats = format!("/election/{}/stats", id);
+    let passcode = cookies::passcode_cookie(&state, id, "4711");
+    let passcode = passcode.split(';').next().unwrap();
+    assert_eq!(
+        get(&state, &stats, &[(COOKIE, passcode)]).await.0,
+        StatusCode::OK
+    );
+    let facilitator = format!("{}={}", cookies::facilitator_cookie_name(id), key);
+    assert_eq!(
+        get(&state, &stats, &[(COOKIE, &facilitator)]).await.0,
+        StatusCode::OK
+    );
+
+    // a voting link stands in for the passcode
+    let tokens = format!("/election/{}/tokens?key={}", id, key);
+    let (status, _) = call(&state, Method::POST, &tokens, None, Value::Null).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let token = state
+        .store
+        .get(id)
+        .unwrap()
+        .voting_tokens
+        .into_keys()
+        .next()
+        .unwrap();
+    let link = format!("/election/{}/vote/{}", id, token);
+    let (status, cookie) = get(&state, &link, &[]).await;
+    assert_eq!((status, cookie.as_str()), (StatusCode::OK, passcode));
+    let wrong_link = format!("/election/{}/vote/guess", id);
+    assert_eq!(get(&state, &wrong_link, &[]).await.1, "");
+}
+
+#[tokio::test]
+async fn needs_the_passcode_for_graphql() {
+    let state = state(Arc::new(FixedClock::new(START)), 1);
+    let election = json!({ "elected_role": "Lead", "nominees": ["Ann"], "passcode": "4711" });
+    let (_, created) = call(&state, Method::POST, "/api/v1/elections", None, election).await;
+    let query = json!({
+        "query": "query E($id: String!) { election(id: $id) { nominees { name } } }",
+        "variables": { "id": created["id"] },
+    });
+    let graphql =
+        |key: Option<&'static str>| call(&state, Method::POST, "/api/graphql", key, query.clone());
+    let (status, refused) = graphql(None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(refused["data"]["election"], Value::Null);
+    assert_eq!(refused["errors"][0]["path"], json!(["election"]));
+    assert_eq!(
+        graphql(Some("4712")).await.1["data"]["election"],
+        Value::Null
+    );
+    let (_, allowed) = graphql(Some("4711")).await;
+    assert_eq!(
+        allowed["data"]["election"]["nominees"],
+        json!([{ "name": "Ann" }])
+    );
+}
+
+#[tokio::test]
+async fn waits_for_the_quorum_and_advances_once_everyone_voted() {
+    let state = state(Arc::new(FixedClock::new(START)), 1);
+    let mut ids = vec![];
+    for auto_advance in [false, true] {
+        let election = json!({
+            "elected_role": "Lead",
+            "nominees": ["Ann"],
+            "expected_voters": 2,
+            "require_quorum": true,
+            "auto_advance": auto_advance,
+        });
+        let (_, created) = call(&state, Method::POST, "/api/v1/elections", None, election).await;
+        ids.push((created["id"].clone(), created["facilitator_key"].clone()));
+    }
+    for (id, key) in &ids {
+        let (id, key) = (id.as_str().unwrap(), key.as_str());
+        let phase = format!("/api/v1/elections/{}/phase", id);
+        let next = json!({ "step": "next" });
+        let (status, _) = call(&state, Method::POST, &phase, key, next).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        for voter in ["v1", "v2"] {
+            let ballot = json!({ "voter_name": voter, "vote": 0 });
+            let votes = format!("/api/v1/elections/{}/votes", id);
+            let (status, _) = call(&state, Method::POST, &votes, None, ballot).await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+    }
+    let phase_of = |id: &Value| state.store.get(id.as_str().unwrap()).unwrap().phase;
+    assert_eq!(phase_of(&ids[0].0), ElectionPhase::FirstVote);
+    assert_eq!(phase_of(&ids[1].0), ElectionPhase::FirstTally);
+}
+
+#[tokio::test]
+async fn lets_only_facilitators_change_the_election() {
+    let state = state(Arc::new(FixedClock::new(START)), 1);
+    let election = json!({ "elected_role": "Lead", "nominees": ["Ann"] });
+    let (_, created) = call(&state, Method::POST, "/api/v1/elections", None, election).await;
+    let id = created["id"].as_str().unwrap();
+    let key = created["facilitator_key"].as_str().unwrap();
+    let post = |uri: String, cookie: String, form: &'static str| {
+        let request = Request::post(uri)
+            .header(COOKIE, cookie)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(form))
+            .unwrap();
+        let state = state.clone();
+        async move { send(&state, request).await.0 }
+    };
+
+    let eval = format!("/election/{}/eval", id);
+    assert_eq!(get(&state, &eval, &[]).await.0, StatusCode::FORBIDDEN);
+    let guessed = format!("{}?key=guess", eval);
+    assert_eq!(get(&state, &guessed, &[]).await.0, StatusCode::FORBIDDEN);
+    let (status, cookie) = get(&state, &format!("{}?key={}", eval, key), &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        cookie,
+        format!("{}={}", cookies::facilitator_cookie_name(id), key)
+    );
+
+    let invite = format!("/election/{}/co-facilitators", id);
+    let status = post(invite.clone(), String::new(), "name=Cid").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let status = post(invite, cookie.clone(), "name=Cid").await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (co_key, _) = state
+        .store
+        .get(id)
+        .unwrap()
+        .co_facilitators
+        .into_iter()
+        .next()
+        .unwrap();
+    let co_cookie = format!("{}={}", cookies::facilitator_cookie_name(id), co_key);
+
+    let template = format!("/election/{}/template", id);
+    assert_eq!(
+        post(template, String::new(), "").await,
+        StatusCode::FORBIDDEN
+    );
+    let step = format!("/election/{}/step/next/FirstVote", id);
+    assert_eq!(
+        post(step.clone(), String::new(), "").await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        post(step, co_cookie.clone(), "").await,
+        StatusCode::ACCEPTED
+    );
+    let election = state.store.get(id).unwrap();
+    assert_eq!(election.phase, ElectionPhase::FirstTally);
+    let by = |i: usize| election.events[i].by.as_deref();
+    assert_eq!((by(0), by(1)), (Some("Facilitator"), Some("Cid")));
+
+    let revoke = format!("/election/{}/co-facilitators/{}/revoke", id, co_key);
+    assert_eq!(post(revoke, cookie, "").await, StatusCode::ACCEPTED);
+    let eval = get(&state, &eval, &[(COOKIE, &co_cookie)]).await;
+    assert_eq!(eval.0, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn remembers_the_right_passcode() {
+    let state = state(Arc::new(FixedClock::new(START)), 1);
+    let election = json!({ "elected_role": "Lead", "nominees": ["Ann"], "passcode": "4711" });
+    let (_, created) = call(&state, Method::POST, "/api/v1/elections", None, election).await;
+    let id = created["id"].as_str().unwrap();
+    let enter = |form: String| {
+        let request = Request::post(format!("/election/{}/passcode", id))
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(form))
+            .unwrap();
+        let router = router(state.clone());
+        async move {
+            let response = router.oneshot(request).await.unwrap();
+            let header = |name: &str| {
+                let value = response.headers().get(name);
+                value.and_then(|v| v.to_str().ok()).map(str::to_string)
+            };
+            (header("set-cookie"), header("hx-redirect"))
+        }
+    };
+
+    assert_eq!(enter("passcode=1234".to_string()).await, (None, None));
+    let next = format!("/election/{}/stats", id);
+    let (cookie, redirect) = enter(format!("passcode=+4711+&next={}", next)).await;
+    assert_eq!(cookie, Some(cookies::passcode_cookie(&state, id, "4711")));
+    assert_eq!(redirect, Some(next));
+    // it does not send anyone elsewhere
+    let (_, redirect) = enter("passcode=4711&next=https://example.org/".to_string()).await;
+    assert_eq!(redirect, Some(format!("/election/{}/voting", id)));
+
+    let votes = format!("/api/v1/elections/{}/votes", id);
+    for (passcode, status) in [
+        ("1234", StatusCode::FORBIDDEN),
+        ("4711", StatusCode::CREATED),
+    ] {
+        let ballot = json!({ "voter_name": "v1", "vote": 0, "passcode": passcode });
+        assert_eq!(
+            call(&state, Method::POST, &votes, None, ballot).await.0,
+            status
+        );
+    }
+}
+
+#[tokio::test]
+async fn signs_the_webhook_payloads() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let hook = format!("http://{}/hook", listener.local_addr().unwrap());
+    let state = state(Arc::new(FixedClock::new(START)), 1);
+    let election = json!({ "elected_role": "Lead", "nominees": ["Ann"], "webhooks": [hook] });
+    let (_, created) = call(&state, Method::POST, "/api/v1/elections", None, election).await;
+    let id = created["id"].as_str().unwrap();
+    let secret = created["webhook_secret"].as_str().unwrap();
+    let ballot = json!({ "voter_name": "v1", "vote": 0 });
+    let votes = format!("/api/v1/elections/{}/votes", id);
+    call(&state, Method::POST, &votes, None, ballot).await;
+
+    let (mut socket, _) = listener.accept().await.unwrap();
+    let mut request = vec![];
+    let (head, body) = loop {
+        let mut chunk = [0; 1024];
+        let read = socket.read(&mut chunk).await.unwrap();
+        request.extend_from_slice(&chunk[..read]);
+        let text = String::from_utf8_lossy(&request).to_string();
+        if let Some((head, body)) = text.split_once("\r\n\r\n") {
+            let length = head
+                .lines()
+                .find_map(|l| {
+                    l.to_lowercase()
+                        .strip_prefix("content-length:")?
+                        .trim()
+                        .parse()
+                        .ok()
+                })
+                .unwrap_or(0);
+            if body.len() >= length || read == 0 {
+                break (head.to_string(), body.to_string());
+            }
+        }
+    };
+    socket
+        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+        .await
+        .unwrap();
+
+    let signature = head
+        .lines()
+        .find_map(|l| l.strip_prefix("X-IEP-Signature: "))
+        .unwrap();
+    let expected = crypto::hex(&crypto::hmac_sha256(secret.as_bytes(), body.as_bytes()));
+    assert_eq!(signature, format!("sha256={}", expected));
+    let payload: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(payload["event"], "votes_changed");
+    assert_eq!(payload["election"]["id"], id);
+    assert_eq!(payload["election"]["voted"], 1);
+}
+
+#[tokio::test]
+async fn restores_the_elections_of_a_snapshot() {
+    let dir = std::env::temp_dir().join(format!("ieptool-snapshot-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let (store, _) = SnapshotStore::open(&dir).unwrap();
+    let store = Arc::new(store);
+    let state = ElectionDB {
+        store: store.clone(),
+        ..state(Arc::new(FixedClock::new(START)), 1)
+    };
+    let election = json!({ "elected_role": "Lead", "nominees": ["Ann", "Bob"] });
+    let (_, created) = call(&state, Method::POST, "/api/v1/elections", None, election).await;
+    let id = created["id"].as_str().unwrap();
+    let ballot = json!({ "voter_name": "v1", "vote": 1 });
+    let votes = format!("/api/v1/elections/{}/votes", id);
+    call(&state, Method::POST, &votes, None, ballot).await;
+    let circle = Circle {
+        id: "c".to_string(),
+        name: "Board".to_string(),
+        roles: vec![],
+    };
+    state
+        .circles
+        .lock()
+        .unwrap()
+        .insert("c".to_string(), circle);
+    store.save(&state).await.unwrap();
+
+    let (restored, circles) = SnapshotStore::open(&dir).unwrap();
+    let election = restored.get(id).unwrap();
+    assert_eq!(Some(&election), state.store.get(id).as_ref());
+    assert_eq!(election.current_round()["v1"].vote, 1);
+    assert_eq!(circles["c"].name, "Board");
+
+    std::fs::write(dir.join("snapshot.json"), "{").unwrap();
+    let error = SnapshotStore::open(&dir).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn skips_ballots_of_removed_nominees_in_the_results() {
+    let random = SeededRandom::new(1);
+    let mut election = ElectionProcess {
+        rounds: 1,
+        ..ElectionProcess::new_and_cleaned("e".to_string(), "Lead".to_string(), "Ann\nBob", 0)
+    };
+    for (voter, vote) in [("v1", 0), ("v2", 1), ("v3", 1)] {
+        let ballot = Ballot::new(vote, &random);
+        election
+            .add_vote(voter.to_string(), ballot, None, 1)
+            .unwrap();
+    }
+    election.step_next(2);
+    election.nominees.remove(&1);
+    let results = crate::election_results_json(&election);
+    assert_eq!(results["first_round"]["ballots"], 3);
+    assert_eq!(results["first_round"]["votes"], json!({ "Ann": 1 }));
+    assert_eq!(results["first_round"]["most_votes"], json!(["Ann"]));
+}
+
+#[test]
+fn answers_process_errors_with_their_message() {
+    let (status, message) = crate::process_error(ProcessError::InvalidScore);
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(message, ProcessError::InvalidScore.to_string());
+    // made static only once
+    let (_, again) = crate::process_error(ProcessError::InvalidScore);
+    assert!(std::ptr::eq(message, again));
+    assert_eq!(
+        crate::process_error(ProcessError::NoBallot).0,
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn lets_only_the_voter_replace_their_ballot() {
+    let state = state(Arc::new(FixedClock::new(START)), 1);
+    let election = json!({ "elected_role": "Lead", "nominees": ["Ann", "Bob"] });
+    let (_, created) = call(&state, Method::POST, "/api/v1/elections", None, election).await;
+    let id = created["id"].as_str().unwrap();
+    let vote = |form: &'static str, cookie: &str| {
+        let request = Request::post(format!("/election/{}/voting", id))
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(COOKIE, cookie)
+            .body(Body::from(form))
+            .unwrap();
+        let router = router(state.clone());
+        async move {
+            let response = router.oneshot(request).await.unwrap();
+            let cookie = response
+                .headers()
+                .get(SET_COOKIE)
+                .and_then(|v| v.to_str().ok());
+            cookie.and_then(|c| c.split(';').next()).map(str::to_string)
+        }
+    };
+    let ballot = || state.store.get(id).unwrap().current_round()["v1"].vote;
+
+    let cookie = vote("voter_name=v1&vote=0", "").await.unwrap();
+    // from another browser, even when confirming
+    assert_eq!(vote("voter_name=v1&vote=1&update=true", "").await, None);
+    assert_eq!(ballot(), 0);
+    let answer = call(
+        &state,
+        Method::POST,
+        &format!("/api/v1/elections/{}/votes", id),
+        None,
+        json!({ "voter_name": "v1", "vote": 1 }),
+    )
+    .await;
+    assert_eq!(answer.0, StatusCode::CONFLICT);
+    assert_eq!(ballot(), 0);
+
+    assert_eq!(vote("voter_name=v1&vote=1", &cookie).await, None);
+    assert_eq!(ballot(), 0);
+    let replaced = vote("voter_name=v1&vote=1&update=true", &cookie).await;
+    assert_eq!(replaced, Some(cookie));
+    assert_eq!(ballot(), 1);
+}
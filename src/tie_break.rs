@@ -0,0 +1,91 @@
+//! Breaking a tie for the most votes of the final round, for the facilitator.
+
+use crate::{process_error, ElectionDB, ElectionUpdate};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Form,
+};
+use iep_core::{ElectionPhase, ElectionProcess, TieBreakMethod};
+use maud::{html, Markup};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct TieBreakForm {
+    nominee: Option<u64>,
+}
+
+/// `method` is `decide` with the chosen nominee, `draw` or `runoff`.
+pub async fn post_tie_break(
+    Path((id, method)): Path<(String, String)>,
+    State(state): State<ElectionDB>,
+    Form(form): Form<TieBreakForm>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let now = state.clock.now();
+    let update = state.modify_election(&id, |election| {
+        match (&method[..], form.nominee) {
+            ("decide", Some(nominee)) => election
+                .decide_tie(nominee, now)
+                .map(|_| ElectionUpdate::VotesChanged),
+            ("decide", None) => return Err((StatusCode::BAD_REQUEST, "Nominee is required")),
+            ("draw", _) => election
+                .draw_tie(state.random.as_ref(), now)
+                .map(|_| ElectionUpdate::VotesChanged),
+            ("runoff", _) => election
+                .start_runoff(now)
+                .map(|_| ElectionUpdate::PhaseChanged),
+            _ => return Err((StatusCode::BAD_REQUEST, "Invalid tie break")),
+        }
+        .map_err(process_error)
+    })?;
+    state.notify(&id, update)?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// The ways to break a tie at the final tally, and how it was broken.
+pub fn tie_break_actions(election: &ElectionProcess) -> Markup {
+    let tied = election.tied_nominees();
+    if !election.is_final_tally() || tied.is_empty() {
+        return html! {};
+    }
+    let path = format!("/election/{}/tie-break", election.id);
+    let names = tied
+        .iter()
+        .map(|id| election.nominee_name(*id))
+        .collect::<Vec<_>>();
+    html! {
+        article #"tie-break" {
+            header { "Tied for the most votes: " (names.join(", ")) }
+            @if let Some(tie_break) = election.tie_break.as_ref().filter(|t| tied.contains(&t.nominee)) {
+                p {
+                    @match tie_break.method {
+                        TieBreakMethod::Facilitator => "Decided by the facilitator: ",
+                        TieBreakMethod::Draw { seed } => { "Drawn by lot with seed " code { (seed) } ": " },
+                    }
+                    mark { (election.nominee_name(tie_break.nominee)) }
+                }
+            }
+            form ."table rows" hx-post={(path) "/decide"} hx-swap="none" {
+                label for="nominee" {
+                    "Decide for: ";
+                    select name="nominee" required {
+                        @for nominee in &tied {
+                            option value=(nominee) { (election.nominee_name(*nominee)) }
+                        }
+                    }
+                }
+                button type="submit" ."secondary" { "Decide" }
+            }
+            div ."grid" {
+                button ."secondary" hx-post={(path) "/draw"} hx-swap="none" { "Draw by Lot" }
+                @if election.phase == ElectionPhase::SecondTally {
+                    button ."secondary" hx-post={(path) "/runoff"} hx-swap="none"
+                      hx-confirm="Start another round between the tied nominees?" {
+                        "Runoff Between Them"
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! Single-use voting links for the voters of the roster, handed out by the
+//! facilitator or emailed to the voters with an address on the roster.
+
+use crate::{
+    base_html, closed_results, cookies,
+    mail::{Mail, Smtp},
+    process_error, voting_form, ElectionDB, FormVoter,
+};
+use axum::{
+    extract::{Path, State},
+    http::{header::SET_COOKIE, StatusCode},
+    response::{IntoResponse, Response},
+};
+use iep_core::{ElectionKind, ElectionProcess};
+use maud::{html, Markup};
+use std::sync::Arc;
+
+/// The voting page of a voting link. For an election with a passcode, a valid
+/// link stands in for it, so the page can follow the election and vote.
+pub async fn view_token_voting(
+    Path((id, token)): Path<(String, String)>,
+    State(state): State<ElectionDB>,
+) -> Result<Response, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    let passcode = election
+        .passcode
+        .as_deref()
+        .filter(|_| election.token_voter(&token).is_some())
+        .map(|passcode| [(SET_COOKIE, cookies::passcode_cookie(&state, &id, passcode))]);
+    let page = base_html(
+        election.elected_role.as_str(),
+        html! { (election.elected_role) },
+        html! {
+            @if election.closed_at.is_some() {
+                (closed_results(election))
+            } @else {
+                div hx-ext="sse" sse-connect={"/election/" (id) "/stream"} {
+                  div #"vote-content"
+                    hx-get={"/election/" (id) "/vote/" (token) "/form"}
+                    hx-trigger="sse:phase-changed,sse:resync"
+                    hx-swap="innerHTML" {
+                      (token_voting_form(election, &token, state.clock.now()))
+                  }
+                }
+            }
+        },
+        html!(strong { (id) }),
+    );
+    Ok((passcode, page).into_response())
+}
+
+pub async fn get_token_voting_form(
+    Path((id, token)): Path<(String, String)>,
+    State(state): State<ElectionDB>,
+) -> Result<Markup, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(token_voting_form(election, &token, state.clock.now()))
+}
+
+fn token_voting_form(election: &ElectionProcess, token: &str, now: u64) -> Markup {
+    match election.token_voter(token) {
+        Some(voter_name) => voting_form(election, now, FormVoter::Token { token, voter_name }),
+        None if election.phase.is_voting() => html! {
+            h2 { (election.phase_title()) }
+            p { "This voting link was already used or replaced by a new one." }
+        },
+        None => voting_form(election, now, FormVoter::Anyone),
+    }
+}
+
+/// The voting link of every voter of the roster, for the facilitator to
+/// hand out.
+pub async fn view_voting_links(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<Markup, StatusCode> {
+    let election = &state.store.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(base_html(
+        &format!("{} - Voting Links", election.elected_role),
+        html! { (election.elected_role) br; "Voting Links" },
+        html! {
+            p {
+                "Each link lets its voter vote once without entering a name. "
+                "Links are used up by their vote and when the votes of the round are reset."
+            }
+            @if election.roster.is_empty() {
+                p { "The election has no list of eligible voters." }
+            } @else {
+                table ."striped" {
+                    tbody {
+                        @for voter_name in &election.roster {
+                            tr {
+                                td { (voter_name) }
+                                td { small { (election.voter_email(voter_name).or(election.voter_phone(voter_name)).unwrap_or_default()) } }
+                                td {
+                                    @match election.voter_token(voter_name) {
+                                        Some(token) => {
+                                            @let link = format!("{}/election/{}/vote/{}", state.base_url, id, token);
+                                            a href=(link) { code { (link) } }
+                                        },
+                                        None => small { "No unused link" },
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                button hx-post={"/election/" (id) "/tokens"} hx-swap="none"
+                  hx-confirm=[(!election.voting_tokens.is_empty()).then_some("Replace all unused links with new ones?")] {
+                    @if election.voting_tokens.is_empty() { "Create Links" } @else { "Create New Links" }
+                }
+                @if state.mail.is_some() && !election.voter_emails.is_empty() {
+                    " "
+                    button ."secondary" hx-post={"/election/" (id) "/tokens/invite"} hx-swap="none"
+                      hx-confirm=[(!election.voting_tokens.is_empty()).then_some("Replace all unused links with new ones and email them?")] {
+                        "Email New Links"
+                    }
+                    " "
+                    button ."secondary outline" hx-post={"/election/" (id) "/tokens/remind"} hx-swap="none" {
+                        "Remind Who Did Not Vote"
+                    }
+                }
+                @if state.sms.is_some() && !election.voter_phones.is_empty() && election.voting_round().is_ok() {
+                    " "
+                    button ."secondary outline" hx-post={"/election/" (id) "/tokens/sms"} hx-swap="none" {
+                        "Text the Ballot to Who Did Not Vote"
+                    }
+                }
+            }
+            a href={"/election/" (id) "/eval"} { "Back to the evaluation" }
+        },
+        html! {},
+    ))
+}
+
+pub async fn post_voting_links(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    state.modify_election(&id, |election| {
+        election
+            .issue_tokens(state.random.as_ref(), state.clock.now())
+            .map_err(process_error)
+    })?;
+    Ok((StatusCode::ACCEPTED, [("HX-Refresh", "true")]))
+}
+
+fn mail_server(state: &ElectionDB) -> Result<Arc<Smtp>, (StatusCode, &'static str)> {
+    state.mail.clone().ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "No mail server is configured",
+    ))
+}
+
+/// Emails the voters who did not vote yet and have an address and an unused
+/// voting link, in the background.
+fn send_links(state: &ElectionDB, smtp: Arc<Smtp>, election: &ElectionProcess, reminder: bool) {
+    let topic = match election.kind {
+        ElectionKind::Election => format!("the election for {}", election.elected_role),
+        ElectionKind::StrawPoll => format!("the straw poll on {}", election.elected_role),
+    };
+    let mails = election
+        .missing_voters()
+        .into_iter()
+        .filter_map(|voter_name| {
+            let to = election.voter_email(voter_name)?.to_string();
+            let link = format!(
+                "{}/election/{}/vote/{}",
+                state.base_url,
+                election.id,
+                election.voter_token(voter_name)?
+            );
+            let (subject, opening) = match reminder {
+                false => (
+                    format!("Your vote: {}", election.elected_role),
+                    format!("you are invited to vote in {}.", topic),
+                ),
+                true => (
+                    format!("Reminder: {}", election.elected_role),
+                    format!("you have not voted in {} yet.", topic),
+                ),
+            };
+            let body = format!(
+                "Hello {},\n\n{} Your personal voting link works once:\n\n{}\n\nPlease do not pass it on.\n",
+                voter_name, opening, link
+            );
+            Some(Mail {
+                to,
+                subject,
+                body,
+                attachment: None,
+            })
+        })
+        .collect::<Vec<_>>();
+    tokio::spawn(async move {
+        match smtp.send(&mails).await {
+            Ok(failed) => {
+                for (to, error) in failed {
+                    tracing::warn!("Emailing the voting link to {} failed: {}", to, error);
+                }
+            }
+            Err(error) => tracing::warn!("Emailing voting links failed: {}", error),
+        }
+    });
+}
+
+/// Issues new voting links and emails them.
+pub async fn post_invitations(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let smtp = mail_server(&state)?;
+    state.modify_election(&id, |election| {
+        election
+            .issue_tokens(state.random.as_ref(), state.clock.now())
+            .map_err(process_error)
+    })?;
+    let election = state
+        .store
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    send_links(&state, smtp, &election, false);
+    Ok((StatusCode::ACCEPTED, [("HX-Refresh", "true")]))
+}
+
+/// Emails the unused voting links again to who did not vote yet.
+pub async fn post_reminders(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    let smtp = mail_server(&state)?;
+    let election = state
+        .store
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Election not found"))?;
+    if election.closed_at.is_some() {
+        return Err(process_error(iep_core::ProcessError::Closed));
+    }
+    send_links(&state, smtp, &election, true);
+    Ok((StatusCode::ACCEPTED, [("HX-Refresh", "true")]))
+}
@@ -0,0 +1,151 @@
+//! Webhooks registered per election, e.g. for chat bots or dashboards. New
+//! votes, phase changes and the completion are POSTed to them as JSON,
+//! signed with the secret of the election in the `X-IEP-Signature` header
+//! as `sha256=` and the hex HMAC-SHA256 of the body.
+
+use crate::{
+    crypto::{hex, hmac_sha256},
+    http, ElectionDB, ElectionUpdate,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Form,
+};
+use iep_core::{ElectionPhase, ElectionProcess};
+use maud::{html, Markup};
+use serde::Deserialize;
+
+const SECRET_LENGTH: usize = 32;
+
+/// Registers `url`, creating the secret with the first webhook.
+pub fn add(
+    state: &ElectionDB,
+    election: &mut ElectionProcess,
+    url: &str,
+) -> Result<(), (StatusCode, &'static str)> {
+    if http::split_url(url).is_err() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Webhooks need http:// or https:// URLs",
+        ));
+    }
+    election.add_webhook(
+        url,
+        || state.random.alphanumeric(SECRET_LENGTH),
+        state.clock.now(),
+    );
+    Ok(())
+}
+
+fn payload(election: &ElectionProcess, update: ElectionUpdate, at: u64) -> Option<String> {
+    let event = match update {
+        ElectionUpdate::VotesChanged => "votes_changed",
+        ElectionUpdate::PhaseChanged if election.phase == ElectionPhase::Completed => "completed",
+        ElectionUpdate::PhaseChanged => "phase_changed",
+        ElectionUpdate::NomineesChanged
+        | ElectionUpdate::Resync
+        | ElectionUpdate::PresenceChanged
+        | ElectionUpdate::VoteRemoved => return None,
+    };
+    let payload = serde_json::json!({
+        "event": event,
+        "at": at,
+        "election": {
+            "id": election.id,
+            "elected_role": election.elected_role,
+            "phase": election.phase,
+            "closed": election.closed_at.is_some(),
+            "voted": election.voted_count(),
+            "expected_voters": election.expected_voters,
+            "elected": election.elected,
+        },
+    });
+    Some(payload.to_string())
+}
+
+/// Sends `update` of election `id` to its webhooks in the background.
+pub fn deliver(state: &ElectionDB, id: &str, update: ElectionUpdate) {
+    let Some(election) = state.store.get(id).filter(|e| !e.webhooks.is_empty()) else {
+        return;
+    };
+    let Some(body) = payload(&election, update, state.clock.now()) else {
+        return;
+    };
+    let secret = election.webhook_secret.unwrap_or_default();
+    let signature = format!(
+        "sha256={}",
+        hex(&hmac_sha256(secret.as_bytes(), body.as_bytes()))
+    );
+    let urls = election.webhooks;
+    tokio::spawn(async move {
+        for url in urls {
+            let headers = [("X-IEP-Signature", signature.clone())];
+            if let Err(error) =
+                http::post(&url, "application/json", &headers, body.as_bytes()).await
+            {
+                tracing::warn!("Sending webhook to {} failed: {}", url, error);
+            }
+        }
+    });
+}
+
+#[derive(Deserialize)]
+pub struct WebhookForm {
+    url: String,
+}
+
+pub async fn post_webhook(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    Form(form): Form<WebhookForm>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    state.modify_election(&id, |election| add(&state, election, form.url.trim()))?;
+    Ok((StatusCode::CREATED, [("HX-Refresh", "true")]))
+}
+
+pub async fn post_remove_webhook(
+    Path(id): Path<String>,
+    State(state): State<ElectionDB>,
+    Form(form): Form<WebhookForm>,
+) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    state.modify_election(&id, |election| {
+        election.remove_webhook(&form.url, state.clock.now());
+        Ok(())
+    })?;
+    Ok((StatusCode::ACCEPTED, [("HX-Refresh", "true")]))
+}
+
+/// The webhooks with the secret to check their signatures, and the form to
+/// add another.
+pub fn webhooks_form(election: &ElectionProcess) -> Markup {
+    html! {
+        details {
+            summary { "Webhooks" }
+            small { "New votes, phase changes and the completion are POSTed to these URLs as signed JSON." }
+            @if let Some(secret) = &election.webhook_secret {
+                p { small { "Secret for the " code { "X-IEP-Signature" } " header: " code { (secret) } } }
+            }
+            table {
+                tbody {
+                    @for url in &election.webhooks {
+                        tr {
+                            td { code { (url) } }
+                            td {
+                                form hx-post={"/election/" (election.id) "/webhooks/remove"} hx-swap="none" {
+                                    input type="hidden" name="url" value=(url) {}
+                                    button type="submit" ."secondary outline" { "Remove" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            form hx-post={"/election/" (election.id) "/webhooks"} hx-swap="none" {
+                input type="url" name="url" placeholder="https://..." required {}
+                button type="submit" ."secondary" { "Add webhook" }
+            }
+        }
+    }
+}
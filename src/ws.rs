@@ -0,0 +1,208 @@
+//! The updates of the SSE stream over a WebSocket at `/election/:id/ws`, for
+//! networks whose proxies break SSE. Each update is sent as a text message
+//! like `{"id": 12, "event": "votes-changed"}`; `?since=12` replays what was
+//! missed after a reconnect, like `Last-Event-ID` does for SSE.
+//!
+//! Only what this needs of RFC 6455 is implemented: the server sends text and
+//! ping frames, answers pings and closes, and ignores any other message.
+
+use crate::{
+    cookies, crypto, facilitator, presence::Presence, ElectionDB, ElectionUpdate, SequencedUpdate,
+};
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{
+        header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, UPGRADE},
+        StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use hyper_util::rt::TokioIo;
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::broadcast::{error::RecvError, Receiver},
+};
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Clients have nothing to say, so their messages may be small.
+const MAX_PAYLOAD: u64 = 4096;
+
+const TEXT: u8 = 0x1;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xa;
+
+#[derive(Deserialize)]
+pub struct WsQuery {
+    since: Option<u64>,
+}
+
+pub async fn get_election_ws(
+    Path(id): Path<String>,
+    Query(query): Query<WsQuery>,
+    State(state): State<ElectionDB>,
+    mut request: Request,
+) -> Result<Response, (StatusCode, &'static str)> {
+    let is_websocket = request
+        .headers()
+        .get(UPGRADE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("websocket"));
+    let key = request
+        .headers()
+        .get(SEC_WEBSOCKET_KEY)
+        .and_then(|v| v.to_str().ok())
+        .filter(|_| is_websocket)
+        .ok_or((StatusCode::BAD_REQUEST, "Expected a WebSocket handshake"))?;
+    let accept = crypto::base64_encode(&crypto::sha1(format!("{}{}", key, GUID).as_bytes()));
+
+    let voter = cookies::voted_as(&state, request.headers(), &id);
+    let facilitator = facilitator::is_facilitating(&state, request.headers(), &id);
+    let (replay, rx) = {
+        let streams = state.streams_of(&id)?;
+        let stream = streams
+            .get(&id)
+            .ok_or((StatusCode::NOT_FOUND, "Election stream not found"))?;
+        let replay = match query
+            .since
+            .map(|since| stream.since(since, voter.as_deref()))
+        {
+            None => vec![],
+            Some(Some(missed)) => missed,
+            Some(None) => vec![stream.resync()],
+        };
+        (replay, stream.sender.subscribe())
+    };
+
+    let upgrade = hyper::upgrade::on(&mut request);
+    let keep_alive = state.sse_keep_alive;
+    tokio::spawn(async move {
+        match upgrade.await {
+            Ok(upgraded) => {
+                let _presence = Presence::join(&state, &id);
+                let (reader, writer) = tokio::io::split(TokioIo::new(upgraded));
+                if let Err(error) =
+                    serve(reader, writer, replay, rx, voter, facilitator, keep_alive).await
+                {
+                    tracing::debug!("WebSocket of {} ended: {}", id, error);
+                }
+            }
+            Err(error) => tracing::warn!("WebSocket upgrade failed: {}", error),
+        }
+    });
+
+    Ok((
+        StatusCode::SWITCHING_PROTOCOLS,
+        [
+            (UPGRADE, "websocket".to_string()),
+            (CONNECTION, "Upgrade".to_string()),
+            (SEC_WEBSOCKET_ACCEPT, accept),
+        ],
+    )
+        .into_response())
+}
+
+fn message(update: &SequencedUpdate) -> String {
+    serde_json::json!({
+        "id": update.seq,
+        "event": update.update.event_name(),
+        "data": update.data,
+    })
+    .to_string()
+}
+
+async fn serve(
+    mut reader: impl AsyncRead + Unpin,
+    mut writer: impl AsyncWrite + Unpin,
+    replay: Vec<SequencedUpdate>,
+    mut rx: Receiver<SequencedUpdate>,
+    voter: Option<String>,
+    facilitator: bool,
+    keep_alive: std::time::Duration,
+) -> std::io::Result<()> {
+    for update in replay {
+        send(
+            &mut writer,
+            TEXT,
+            message(&update.seen_by(facilitator)).as_bytes(),
+        )
+        .await?;
+    }
+    let mut ping = tokio::time::interval(keep_alive);
+    ping.tick().await;
+    loop {
+        tokio::select! {
+            update = rx.recv() => match update {
+                Ok(update) if update.is_for(voter.as_deref()) => {
+                    send(&mut writer, TEXT, message(&update.seen_by(facilitator)).as_bytes()).await?
+                }
+                Ok(_) => {}
+                // the client reloads everything instead of what it missed
+                Err(RecvError::Lagged(_)) => {
+                    let resync = serde_json::json!({ "event": ElectionUpdate::Resync.event_name() }).to_string();
+                    send(&mut writer, TEXT, resync.as_bytes()).await?
+                }
+                Err(RecvError::Closed) => return send(&mut writer, CLOSE, &[]).await,
+            },
+            frame = receive(&mut reader) => match frame? {
+                (CLOSE, _) => return send(&mut writer, CLOSE, &[]).await,
+                (PING, payload) => send(&mut writer, PONG, &payload).await?,
+                _ => {}
+            },
+            _ = ping.tick() => send(&mut writer, PING, &[]).await?,
+        }
+    }
+}
+
+/// Sends a whole, unmasked frame.
+async fn send(
+    writer: &mut (impl AsyncWrite + Unpin),
+    opcode: u8,
+    payload: &[u8],
+) -> std::io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame).await?;
+    writer.flush().await
+}
+
+/// Reads a frame of the client and returns its opcode and unmasked payload.
+async fn receive(reader: &mut (impl AsyncRead + Unpin)) -> std::io::Result<(u8, Vec<u8>)> {
+    let mut head = [0u8; 2];
+    reader.read_exact(&mut head).await?;
+    let opcode = head[0] & 0x0f;
+    let masked = head[1] & 0x80 != 0;
+    let len = match head[1] & 0x7f {
+        126 => reader.read_u16().await? as u64,
+        127 => reader.read_u64().await?,
+        len => len as u64,
+    };
+    if len > MAX_PAYLOAD {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Message too large",
+        ));
+    }
+    let mut mask = [0u8; 4];
+    if masked {
+        reader.read_exact(&mut mask).await?;
+    }
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload).await?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok((opcode, payload))
+}
@@ -0,0 +1,186 @@
+//! A minimal writer for uncompressed ("stored") ZIP archives.
+//!
+//! Election exports are small text files, so skipping compression keeps this
+//! dependency free while still producing archives every unzip tool can read.
+
+use iep_core::UtcDateTime;
+
+const LOCAL_FILE_HEADER: u32 = 0x04034b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+
+struct Entry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+pub struct ZipWriter {
+    buf: Vec<u8>,
+    entries: Vec<Entry>,
+    dos_time: u16,
+    dos_date: u16,
+}
+
+impl ZipWriter {
+    /// Creates an archive whose files are all stamped with `now` (unix seconds).
+    pub fn new(now: u64) -> Self {
+        let (dos_time, dos_date) = dos_timestamp(now);
+        ZipWriter {
+            buf: Vec::new(),
+            entries: Vec::new(),
+            dos_time,
+            dos_date,
+        }
+    }
+
+    pub fn add_file(&mut self, name: &str, data: &[u8]) {
+        let entry = Entry {
+            name: name.to_string(),
+            crc: crc32(data),
+            size: data.len() as u32,
+            offset: self.buf.len() as u32,
+        };
+
+        self.put_u32(LOCAL_FILE_HEADER);
+        self.put_u16(20); // version needed to extract
+        self.put_u16(0x0800); // flags: utf-8 file names
+        self.put_u16(0); // compression: stored
+        self.put_u16(self.dos_time);
+        self.put_u16(self.dos_date);
+        self.put_u32(entry.crc);
+        self.put_u32(entry.size);
+        self.put_u32(entry.size);
+        self.put_u16(entry.name.len() as u16);
+        self.put_u16(0); // extra field length
+        self.buf.extend_from_slice(entry.name.as_bytes());
+        self.buf.extend_from_slice(data);
+
+        self.entries.push(entry);
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let directory_offset = self.buf.len() as u32;
+        let entries = std::mem::take(&mut self.entries);
+        for entry in &entries {
+            self.put_u32(CENTRAL_DIRECTORY_HEADER);
+            self.put_u16(20); // version made by
+            self.put_u16(20); // version needed to extract
+            self.put_u16(0x0800);
+            self.put_u16(0);
+            self.put_u16(self.dos_time);
+            self.put_u16(self.dos_date);
+            self.put_u32(entry.crc);
+            self.put_u32(entry.size);
+            self.put_u32(entry.size);
+            self.put_u16(entry.name.len() as u16);
+            self.put_u16(0); // extra field length
+            self.put_u16(0); // comment length
+            self.put_u16(0); // disk number
+            self.put_u16(0); // internal attributes
+            self.put_u32(0); // external attributes
+            self.put_u32(entry.offset);
+            self.buf.extend_from_slice(entry.name.as_bytes());
+        }
+        let directory_size = self.buf.len() as u32 - directory_offset;
+
+        self.put_u32(END_OF_CENTRAL_DIRECTORY);
+        self.put_u16(0);
+        self.put_u16(0);
+        self.put_u16(entries.len() as u16);
+        self.put_u16(entries.len() as u16);
+        self.put_u32(directory_size);
+        self.put_u32(directory_offset);
+        self.put_u16(0); // comment length
+        self.buf
+    }
+
+    fn put_u16(&mut self, v: u16) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn put_u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb88320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Converts unix seconds into the MS-DOS (time, date) pair used by ZIP headers.
+fn dos_timestamp(secs: u64) -> (u16, u16) {
+    let t = UtcDateTime::from_unix(secs);
+    if t.year < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let time = (t.hour << 11) | (t.minute << 5) | (t.second / 2);
+    let date = ((t.year as u32 - 1980) << 9) | (t.month << 5) | t.day;
+    (time as u16, date as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(data: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes(data[at..at + 2].try_into().unwrap())
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn computes_checksums_and_timestamps() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(crc32(b""), 0);
+        // 2024-03-05 12:34:56 UTC
+        assert_eq!(
+            dos_timestamp(1709642096),
+            ((12 << 11) | (34 << 5) | 28, (44 << 9) | (3 << 5) | 5)
+        );
+        assert_eq!(dos_timestamp(0), (0, (1 << 5) | 1));
+    }
+
+    #[test]
+    fn writes_stored_files_and_their_directory() {
+        let mut zip = ZipWriter::new(1709642096);
+        zip.add_file("a.txt", b"hello");
+        zip.add_file("b/c.csv", b"");
+        let archive = zip.finish();
+
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(&archive, end), END_OF_CENTRAL_DIRECTORY);
+        assert_eq!(u16_at(&archive, end + 10), 2);
+        let directory = u32_at(&archive, end + 16) as usize;
+        assert_eq!(directory + u32_at(&archive, end + 12) as usize, end);
+
+        let mut entry = directory;
+        for (name, data) in [("a.txt", &b"hello"[..]), ("b/c.csv", b"")] {
+            assert_eq!(u32_at(&archive, entry), CENTRAL_DIRECTORY_HEADER);
+            let name_length = u16_at(&archive, entry + 28) as usize;
+            assert_eq!(
+                &archive[entry + 46..entry + 46 + name_length],
+                name.as_bytes()
+            );
+            assert_eq!(u32_at(&archive, entry + 16), crc32(data));
+
+            let local = u32_at(&archive, entry + 42) as usize;
+            assert_eq!(u32_at(&archive, local), LOCAL_FILE_HEADER);
+            assert_eq!(u16_at(&archive, local + 8), 0);
+            let start = local + 30 + name_length;
+            assert_eq!(&archive[start..start + data.len()], data);
+            entry += 46 + name_length;
+        }
+    }
+}
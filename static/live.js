@@ -0,0 +1,69 @@
+// Patches the evaluation with the data of `votes-changed` events instead of
+// reloading all of it, and reloads it when the change does not fit the page.
+// Also keeps the count of connected participants up to date.
+
+function liveList(list, names) {
+  list.replaceChildren(...names.map((name) => {
+    const item = document.createElement("li");
+    item.textContent = name;
+    return item;
+  }));
+}
+
+function livePatch(live, update) {
+  if (update.phase !== live.dataset.livePhase
+    || String(update.quorum_reached) !== live.dataset.liveQuorum) {
+    return false;
+  }
+  const cells = new Map();
+  live.querySelectorAll("td[data-nominee]").forEach((cell) => cells.set(cell.dataset.nominee, cell));
+  if (update.tally) {
+    const tally = Object.entries(update.tally);
+    if (tally.length !== cells.size || tally.some(([name]) => !cells.has(name))) {
+      return false;
+    }
+    const max = Math.max(1, ...tally.map(([, votes]) => votes));
+    for (const [name, votes] of tally) {
+      const cell = cells.get(name);
+      cell.style.setProperty("--size", votes / max);
+      cell.querySelector(".data").textContent = votes;
+    }
+  }
+
+  live.querySelectorAll(".vote-count").forEach((count) => count.textContent = update.votes);
+  live.querySelectorAll(".turnout").forEach((turnout) => turnout.textContent = update.turnout);
+  // who voted only comes with the updates for facilitators
+  if (update.voters === undefined
+    && live.querySelector("#voter-list, .missing-count, #voter-checklist")) {
+    return false;
+  }
+  const voters = live.querySelector("#voter-list");
+  if (voters) {
+    liveList(voters, update.voters);
+  }
+  live.querySelectorAll(".missing-count").forEach((count) => count.textContent = update.missing.length);
+  live.querySelectorAll("#voter-checklist li[data-voter]").forEach((voter) => {
+    voter.querySelector(".check").textContent = update.missing.includes(voter.dataset.voter) ? "⬜" : "✅";
+  });
+  return true;
+}
+
+document.addEventListener("sse:votes-changed", (event) => {
+  const content = document.getElementById("eval");
+  if (!content) {
+    return;
+  }
+  const live = content.querySelector("[data-live-phase]");
+  if (!live || !livePatch(live, JSON.parse(event.detail.data))) {
+    htmx.trigger(content, "reload");
+  }
+});
+
+document.addEventListener("sse:presence-changed", (event) => {
+  const presence = document.getElementById("presence");
+  if (presence) {
+    const connected = JSON.parse(event.detail.data).connected;
+    presence.querySelector("small").textContent =
+      `${connected} participant${connected === 1 ? "" : "s"} connected`;
+  }
+});
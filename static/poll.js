@@ -0,0 +1,51 @@
+// Polls `/election/:id/state` while the live update stream of a page cannot
+// connect, e.g. behind proxies breaking Server-Sent Events, and triggers the
+// same `sse:*` events on the page as the stream would.
+
+const POLL_INTERVAL_MS = 5000;
+// how long the stream may take to connect before polling starts
+const POLL_FALLBACK_MS = 10000;
+
+function pollDispatch(source, update) {
+  const name = `sse:${update.event}`;
+  source.querySelectorAll("[hx-trigger]").forEach((element) => {
+    if (element.getAttribute("hx-trigger").split(",").some((t) => t.trim() === name)) {
+      htmx.trigger(element, name, { data: JSON.stringify(update.data) });
+    }
+  });
+}
+
+function pollFollow(source) {
+  const url = source.getAttribute("sse-connect").replace(/\/stream$/, "/state");
+  let connected = false;
+  let version = null;
+  let timer = null;
+
+  const poll = async () => {
+    const response = await fetch(version === null ? url : `${url}?since=${version}`);
+    if (response.status === 200) {
+      const state = await response.json();
+      version = state.version;
+      state.updates.forEach((update) => pollDispatch(source, update));
+    }
+  };
+  const start = () => {
+    if (!connected && timer === null) {
+      poll().catch(() => {});
+      timer = setInterval(() => poll().catch(() => {}), POLL_INTERVAL_MS);
+    }
+  };
+
+  source.addEventListener("htmx:sseOpen", () => {
+    connected = true;
+    clearInterval(timer);
+    timer = null;
+  });
+  source.addEventListener("htmx:sseError", () => {
+    connected = false;
+    setTimeout(start, POLL_FALLBACK_MS);
+  });
+  setTimeout(start, POLL_FALLBACK_MS);
+}
+
+document.querySelectorAll("[sse-connect$='/stream'][sse-connect^='/election/']").forEach(pollFollow);